@@ -0,0 +1,362 @@
+//! Structured context carried in the AEAD associated data of encrypted files.
+//!
+//! The AAD is stored in the clear inside the `EncryptedObject` (or the escrow
+//! envelope), but any change to it makes decryption fail, so fields placed
+//! here can be read for policy checks once decryption has succeeded.
+//!
+//! The object's `threshold` and `services` are outside the AEAD. Seal
+//! rebuilds the key from `threshold` shares, so a lowered threshold yields
+//! the wrong key and decryption fails, but a raised one still reconstructs
+//! the right key and goes unnoticed. The CLI therefore records both in the
+//! context (`quorum`) and compares them after decryption.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, NaiveDate, Utc};
+use crypto::{EncryptedObject, ObjectID};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Component, Path};
+use std::time::UNIX_EPOCH;
+
+use crate::container::SealContainer;
+use crate::padding::Padding;
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+pub struct AadContext {
+    /// Monotonic version of the protected document, used to reject rollbacks.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<u64>,
+    /// `{relative_path}|{size}|{mtime}` of the plaintext file, so a
+    /// ciphertext can't be passed off as a different or resized file. See
+    /// `file_binding`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    /// `{threshold}|{server},{server},...` of the fleet the object was
+    /// encrypted to, see `quorum_binding`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quorum: Option<String>,
+    /// How the plaintext was padded into a size class, if it was. See
+    /// `padding`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub padding: Option<Padding>,
+    /// Unix seconds after which `check_expiry` refuses the object.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<i64>,
+}
+
+/// Returned (wrapped in `anyhow::Error`) by `check_expiry` for an object
+/// past its authenticated expiry time.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Expired {
+    pub expires_at: DateTime<Utc>,
+    pub now: DateTime<Utc>,
+}
+
+impl fmt::Display for Expired {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Ciphertext expired at {} ({} ago); pass --ignore-expiry to decrypt it anyway",
+            self.expires_at.to_rfc3339(),
+            humanize(self.now - self.expires_at)
+        )
+    }
+}
+
+impl std::error::Error for Expired {}
+
+fn humanize(elapsed: chrono::TimeDelta) -> String {
+    match (elapsed.num_days(), elapsed.num_hours(), elapsed.num_minutes()) {
+        (days, _, _) if days > 0 => format!("{} day(s)", days),
+        (_, hours, _) if hours > 0 => format!("{} hour(s)", hours),
+        (_, _, minutes) => format!("{} minute(s)", minutes.max(0)),
+    }
+}
+
+/// For `encrypt --expires-at`: an RFC 3339 time, or a date meaning
+/// midnight UTC at its start.
+pub fn parse_expiry(text: &str) -> Result<DateTime<Utc>> {
+    let text = text.trim();
+    if let Ok(time) = DateTime::parse_from_rfc3339(text) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    let date = NaiveDate::parse_from_str(text, "%Y-%m-%d").map_err(|_| {
+        anyhow::anyhow!("Invalid expiry \"{}\", expected a date (2025-12-31) or RFC 3339 time", text)
+    })?;
+    Ok(date.and_hms_opt(0, 0, 0).expect("midnight exists").and_utc())
+}
+
+/// The canonical `{threshold}|{server},{server},...` binding, servers in the
+/// order of the object's `services`.
+pub fn quorum_binding(threshold: u8, key_servers: &[ObjectID]) -> String {
+    let servers: Vec<String> = key_servers.iter().map(ObjectID::to_string).collect();
+    format!("{}|{}", threshold, servers.join(","))
+}
+
+/// The canonical `{relative_path}|{size}|{mtime}` binding for `path`: the
+/// path relative to the working directory (as given if it lies elsewhere),
+/// with `/` separators, its size in bytes and its modification time in
+/// seconds since the Unix epoch.
+pub fn file_binding(path: &Path) -> Result<String> {
+    let metadata = std::fs::metadata(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let modified = metadata.modified()?.duration_since(UNIX_EPOCH)?.as_secs();
+
+    let cwd = std::env::current_dir()?;
+    let relative = path.strip_prefix(&cwd).unwrap_or(path);
+    let components: Vec<_> = relative
+        .components()
+        .filter(|component| *component != Component::CurDir)
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect();
+    Ok(format!("{}|{}|{}", components.join("/"), metadata.len(), modified))
+}
+
+impl AadContext {
+    pub fn is_empty(&self) -> bool {
+        *self == AadContext::default()
+    }
+
+    /// The AAD to pass to `EncryptionInput`, or `None` if there is no context.
+    pub fn to_aad(&self) -> Result<Option<Vec<u8>>> {
+        if self.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(serde_json::to_vec(self)?))
+        }
+    }
+
+    /// Read the context from a container's AAD, including escrow envelopes.
+    /// Files without AAD yield an empty context; AAD that isn't a context is
+    /// an error.
+    pub fn from_container(container: &SealContainer) -> Result<Self> {
+        match container.aad() {
+            None => Ok(AadContext::default()),
+            Some(bytes) => serde_json::from_slice(bytes)
+                .map_err(|e| anyhow::anyhow!("Object AAD is not a seal-demo context: {}", e)),
+        }
+    }
+
+    /// Check a bound file context against the decrypted plaintext and return
+    /// the recorded path and modification time. Only meaningful after
+    /// decryption has authenticated the AAD.
+    pub fn check_file_binding(&self, plaintext_len: usize) -> Result<Option<(&str, u64)>> {
+        let Some(binding) = &self.file else {
+            return Ok(None);
+        };
+        let mut fields = binding.rsplitn(3, '|');
+        let (Some(modified), Some(size), Some(path)) = (fields.next(), fields.next(), fields.next()) else {
+            anyhow::bail!("Malformed file binding {:?}", binding);
+        };
+        let (size, modified): (u64, u64) = (size.parse()?, modified.parse()?);
+        if size != plaintext_len as u64 {
+            anyhow::bail!("File binding records {} bytes but {} were decrypted", size, plaintext_len);
+        }
+        Ok(Some((path, modified)))
+    }
+
+    /// Check a bound quorum against the object's threshold and key servers.
+    /// Only meaningful after decryption has authenticated the AAD.
+    pub fn check_quorum(&self, encrypted_object: &EncryptedObject) -> Result<()> {
+        let Some(recorded) = &self.quorum else {
+            return Ok(());
+        };
+        let servers: Vec<ObjectID> = encrypted_object.services.iter().map(|(server, _)| *server).collect();
+        let stored = quorum_binding(encrypted_object.threshold, &servers);
+        if stored != *recorded {
+            anyhow::bail!(
+                "Threshold or key servers were tampered with: the object says {}, but it was encrypted to {}",
+                stored,
+                recorded
+            );
+        }
+        Ok(())
+    }
+
+    /// Reject objects whose authenticated expiry time is before `now`, with
+    /// an `Expired` error. Only meaningful after decryption has
+    /// authenticated the AAD.
+    pub fn check_expiry(&self, now: DateTime<Utc>) -> Result<()> {
+        let Some(expires_at) = self.expires_at else {
+            return Ok(());
+        };
+        let expires_at = DateTime::from_timestamp(expires_at, 0)
+            .ok_or_else(|| anyhow::anyhow!("Expiry time {} is out of range", expires_at))?;
+        if now > expires_at {
+            return Err(Expired { expires_at, now }.into());
+        }
+        Ok(())
+    }
+
+    /// Reject objects whose authenticated version is below `min_version`.
+    pub fn enforce_min_version(&self, min_version: u64) -> Result<()> {
+        match self.version {
+            Some(version) if version >= min_version => Ok(()),
+            Some(version) => anyhow::bail!(
+                "Stale ciphertext: authenticated version {} is older than the minimum {} (possible rollback)",
+                version,
+                min_version
+            ),
+            None => anyhow::bail!(
+                "Ciphertext carries no authenticated version but --min-version {} was required",
+                min_version
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::SealSession;
+    use crypto::{Ciphertext, EncryptedObject, EncryptionInput};
+
+    fn encrypt_versioned(session: &SealSession, version: u64) -> Result<EncryptedObject> {
+        let context = AadContext { version: Some(version), ..Default::default() };
+        let (encrypted_object, _) = session.encrypt(
+            b"config.toml",
+            EncryptionInput::Aes256Gcm {
+                data: b"setting = true".to_vec(),
+                aad: context.to_aad()?,
+            },
+        )?;
+        Ok(encrypted_object)
+    }
+
+    #[test]
+    fn test_stale_version_rejected() -> Result<()> {
+        let session = SealSession::generate(3, 2)?;
+        let encrypted_object = encrypt_versioned(&session, 3)?;
+
+        session.decrypt(&encrypted_object)?;
+        let container = SealContainer {
+            fingerprint: None,
+            encrypted_object,
+            envelope: None,
+            server_keys: None,
+        };
+        let context = AadContext::from_container(&container)?;
+        context.enforce_min_version(3)?;
+
+        let err = context.enforce_min_version(4).unwrap_err();
+        assert!(err.to_string().contains("Stale ciphertext"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_version_cannot_be_altered() -> Result<()> {
+        let session = SealSession::generate(3, 2)?;
+        let mut encrypted_object = encrypt_versioned(&session, 1)?;
+
+        if let Ciphertext::Aes256Gcm { aad, .. } = &mut encrypted_object.ciphertext {
+            *aad = AadContext { version: Some(99), ..Default::default() }.to_aad()?;
+        }
+        assert!(session.decrypt(&encrypted_object).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_expiry_enforced_by_clock() -> Result<()> {
+        let session = SealSession::generate(3, 2)?;
+        let expires_at = parse_expiry("2030-01-01")?;
+        assert_eq!(expires_at, parse_expiry("2030-01-01T00:00:00Z")?);
+        let context = AadContext { expires_at: Some(expires_at.timestamp()), ..Default::default() };
+        let (encrypted_object, _) = session.encrypt(
+            b"file:offer.pdf",
+            EncryptionInput::Aes256Gcm { data: b"valid until new year".to_vec(), aad: context.to_aad()? },
+        )?;
+
+        // The clock is a parameter, so both sides of the deadline can be tested
+        let decrypted = session.decrypt(&encrypted_object)?;
+        let container = SealContainer {
+            fingerprint: None,
+            encrypted_object: encrypted_object.clone(),
+            envelope: None,
+            server_keys: None,
+        };
+        let recorded = AadContext::from_container(&container)?;
+        let (day, later) = (chrono::TimeDelta::days(1), expires_at + chrono::TimeDelta::days(3));
+        recorded.check_expiry(expires_at - day)?;
+        recorded.check_expiry(expires_at)?;
+        let err = recorded.check_expiry(later).unwrap_err();
+        assert_eq!(err.downcast_ref::<Expired>(), Some(&Expired { expires_at, now: later }));
+        assert!(err.to_string().contains("3 day(s) ago"), "{}", err);
+        assert_eq!(decrypted, b"valid until new year");
+
+        // Pushing the date back breaks authentication
+        let mut extended = encrypted_object;
+        if let Ciphertext::Aes256Gcm { aad, .. } = &mut extended.ciphertext {
+            *aad = AadContext { expires_at: Some(expires_at.timestamp() + 86400 * 365), ..Default::default() }.to_aad()?;
+        }
+        assert!(session.decrypt(&extended).is_err());
+
+        assert!(AadContext::default().check_expiry(later).is_ok());
+        assert!(parse_expiry("next tuesday").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_file_binding() -> Result<()> {
+        let session = SealSession::generate(3, 2)?;
+        let path = std::env::temp_dir().join("aad_binding_test.txt");
+        std::fs::write(&path, "bound to its path")?;
+
+        let context = AadContext { file: Some(file_binding(&path)?), ..Default::default() };
+        let (mut encrypted_object, _) = session.encrypt(
+            b"file:aad_binding_test.txt",
+            EncryptionInput::Aes256Gcm { data: std::fs::read(&path)?, aad: context.to_aad()? },
+        )?;
+        let container = SealContainer {
+            fingerprint: None,
+            encrypted_object: encrypted_object.clone(),
+            envelope: None,
+            server_keys: None,
+        };
+        let decrypted = session.decrypt(&encrypted_object)?;
+        let recorded = AadContext::from_container(&container)?;
+        let (recorded_path, _) = recorded.check_file_binding(decrypted.len())?.unwrap();
+        assert!(recorded_path.ends_with("aad_binding_test.txt"));
+
+        // Renaming the recorded path breaks authentication
+        let renamed = context.file.as_ref().unwrap().replace("aad_binding_test.txt", "renamed.txt");
+        if let Ciphertext::Aes256Gcm { aad, .. } = &mut encrypted_object.ciphertext {
+            *aad = AadContext { file: Some(renamed), ..Default::default() }.to_aad()?;
+        }
+        assert!(session.decrypt(&encrypted_object).is_err());
+
+        // A size that doesn't match the plaintext is caught after decryption
+        let resized = AadContext { file: Some("a.txt|999|0".to_string()), ..Default::default() };
+        assert!(resized.check_file_binding(decrypted.len()).is_err());
+
+        let _ = std::fs::remove_file(&path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_threshold_tampering_detected() -> Result<()> {
+        let session = SealSession::generate(3, 2)?;
+        let context = AadContext { quorum: Some(quorum_binding(session.threshold, &session.key_servers)), ..Default::default() };
+        let (encrypted_object, _) = session.encrypt(
+            b"file:payroll.csv",
+            EncryptionInput::Aes256Gcm { data: b"alice,120000".to_vec(), aad: context.to_aad()? },
+        )?;
+        session.decrypt(&encrypted_object)?;
+        context.check_quorum(&encrypted_object)?;
+
+        // A raised threshold still reconstructs the key; only the binding notices
+        let mut raised = encrypted_object.clone();
+        raised.threshold = 3;
+        assert_eq!(session.decrypt(&raised)?, b"alice,120000");
+        let err = context.check_quorum(&raised).unwrap_err();
+        assert!(err.to_string().contains("tampered"));
+
+        // A lowered one breaks reconstruction outright
+        let mut lowered = encrypted_object.clone();
+        lowered.threshold = 1;
+        assert!(session.decrypt(&lowered).is_err());
+
+        let mut reordered = encrypted_object;
+        reordered.services.swap(0, 1);
+        assert!(context.check_quorum(&reordered).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,98 @@
+//! All-or-nothing file output.
+//!
+//! Encrypted files are written to a sibling temp file which is renamed over
+//! the target only once every byte is on disk. A crash or error mid-write
+//! leaves the target untouched (or absent) instead of a truncated file that
+//! fails to decrypt later.
+
+use anyhow::{Context, Result};
+use rand::{thread_rng, RngCore};
+use std::fs::{self, File, OpenOptions};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+/// `<name>.tmp-<random>` next to `path`, so the rename never crosses a
+/// filesystem boundary.
+fn temp_path_for(path: &Path) -> PathBuf {
+    let mut suffix = [0u8; 8];
+    thread_rng().fill_bytes(&mut suffix);
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".tmp-{}", hex::encode(suffix)));
+    path.with_file_name(name)
+}
+
+/// Produce `path` through `write`, atomically replacing any existing file.
+/// The temp file is opened read-write, so `write` may also memory-map it.
+///
+/// `std::fs::rename` replaces an existing target on Windows as well
+/// (`MoveFileExW` with `MOVEFILE_REPLACE_EXISTING`), so overwriting needs no
+/// separate delete that would open a window without any file at `path`.
+pub fn write_atomically(path: &Path, write: impl FnOnce(&mut BufWriter<File>) -> Result<()>) -> Result<()> {
+    let temp_path = temp_path_for(path);
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create_new(true)
+        .open(&temp_path)
+        .with_context(|| format!("Failed to create {}", temp_path.display()))?;
+
+    let result = (|| -> Result<()> {
+        let mut writer = BufWriter::new(file);
+        write(&mut writer)?;
+        writer.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        fs::rename(&temp_path, path)?;
+        Ok(())
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result.with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// `fs::write`, but atomic.
+pub fn write_file(path: &Path, contents: &[u8]) -> Result<()> {
+    write_atomically(path, |writer| {
+        std::io::Write::write_all(writer, contents)?;
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_failed_write_leaves_no_partial_output() -> Result<()> {
+        let dir = std::env::temp_dir().join("atomic_write_test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir)?;
+        let target = dir.join("report.pdf.seal");
+
+        let failing = |writer: &mut BufWriter<File>| -> Result<()> {
+            writer.write_all(&[0xAB; 10_000])?;
+            anyhow::bail!("disk full")
+        };
+
+        // A fresh target never appears
+        let err = write_atomically(&target, failing).unwrap_err();
+        assert!(format!("{:#}", err).contains("disk full"));
+        assert!(!target.exists());
+
+        // An existing target keeps its old contents
+        write_file(&target, b"previous ciphertext")?;
+        assert!(write_atomically(&target, failing).is_err());
+        assert_eq!(fs::read(&target)?, b"previous ciphertext");
+
+        // Temp files are cleaned up either way
+        assert_eq!(fs::read_dir(&dir)?.count(), 1);
+
+        write_file(&target, b"new ciphertext")?;
+        assert_eq!(fs::read(&target)?, b"new ciphertext");
+        assert_eq!(fs::read_dir(&dir)?.count(), 1);
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -1,13 +1,17 @@
 use anyhow::Result;
 use crypto::{
-    ibe::{generate_key_pair, extract},
-    seal_encrypt, seal_decrypt, 
+    ibe::generate_key_pair,
     EncryptionInput, IBEPublicKeys, IBEUserSecretKeys, ObjectID
 };
 use fastcrypto::groups::bls12381::{G2Element, Scalar};
+use fastcrypto::traits::AllowedRng;
 use rand::thread_rng;
 use std::collections::HashMap;
 
+use crate::output::outln;
+use crate::report::OpCounter;
+use crate::session::{random_object_id, PLAIN_MODE_NOTE};
+
 pub struct BasicDemo {
     pub package_id: ObjectID,
     pub identity: Vec<u8>,
@@ -15,14 +19,21 @@ pub struct BasicDemo {
     pub key_servers: Vec<ObjectID>,
     pub master_keys: Vec<Scalar>,
     pub public_keys: Vec<G2Element>,
+    /// Where this demo tallies its Seal operations.
+    pub counter: OpCounter,
 }
 
 impl BasicDemo {
     pub fn new() -> Result<Self> {
-        println!("🔧 Setting up basic Seal encryption demo...");
+        Self::new_with_rng(&mut thread_rng())
+    }
+    
+    /// `new`, drawing the package ID, server IDs and key pairs from `rng`.
+    pub fn new_with_rng(rng: &mut impl AllowedRng) -> Result<Self> {
+        outln!("🔧 Setting up basic Seal encryption demo...");
         
         // Generate package ID (simulated)
-        let package_id = ObjectID::random();
+        let package_id = random_object_id(rng);
         
         // Create identity for encryption
         let identity = b"user@example.com".to_vec();
@@ -36,14 +47,14 @@ impl BasicDemo {
         let mut public_keys = Vec::new();
         
         for i in 0..3 {
-            let (master_key, public_key) = generate_key_pair(&mut thread_rng());
-            let server_id = ObjectID::random();
+            let (master_key, public_key) = generate_key_pair(rng);
+            let server_id = random_object_id(rng);
             
             key_servers.push(server_id);
             master_keys.push(master_key);
             public_keys.push(public_key);
             
-            println!("   📡 Created key server {}: {}", i + 1, server_id);
+            outln!("   📡 Created key server {}: {}", i + 1, server_id);
         }
         
         Ok(BasicDemo {
@@ -53,17 +64,24 @@ impl BasicDemo {
             key_servers,
             master_keys,
             public_keys,
+            counter: OpCounter::default(),
         })
     }
     
+    /// Count operations on `counter`, e.g. the one `report::measure` passes in.
+    pub fn with_counter(mut self, counter: OpCounter) -> Self {
+        self.counter = counter;
+        self
+    }
+    
     pub fn encrypt_with_aes(&self, message: &[u8]) -> Result<(crypto::EncryptedObject, [u8; 32])> {
-        println!("🔒 Encrypting message with AES-256-GCM...");
-        println!("   📝 Message: \"{}\"", String::from_utf8_lossy(message));
-        println!("   🆔 Identity: \"{}\"", String::from_utf8_lossy(&self.identity));
-        println!("   📦 Package ID: {}", self.package_id);
-        println!("   🔢 Threshold: {}/{}", self.threshold, self.key_servers.len());
+        outln!("🔒 Encrypting message with AES-256-GCM...");
+        outln!("   📝 Message: \"{}\"", String::from_utf8_lossy(message));
+        outln!("   🆔 Identity: \"{}\"", String::from_utf8_lossy(&self.identity));
+        outln!("   📦 Package ID: {}", self.package_id);
+        outln!("   🔢 Threshold: {}/{}", self.threshold, self.key_servers.len());
         
-        let result = seal_encrypt(
+        let result = self.counter.seal_encrypt(
             self.package_id,
             self.identity.clone(),
             self.key_servers.clone(),
@@ -75,15 +93,15 @@ impl BasicDemo {
             },
         )?;
         
-        println!("   ✅ Encryption successful!");
+        outln!("   ✅ Encryption successful!");
         Ok(result)
     }
     
     pub fn encrypt_with_hmac(&self, message: &[u8]) -> Result<(crypto::EncryptedObject, [u8; 32])> {
-        println!("🔒 Encrypting message with HMAC-256-CTR...");
-        println!("   📝 Message: \"{}\"", String::from_utf8_lossy(message));
+        outln!("🔒 Encrypting message with HMAC-256-CTR...");
+        outln!("   📝 Message: \"{}\"", String::from_utf8_lossy(message));
         
-        let result = seal_encrypt(
+        let result = self.counter.seal_encrypt(
             self.package_id,
             self.identity.clone(),
             self.key_servers.clone(),
@@ -95,15 +113,33 @@ impl BasicDemo {
             },
         )?;
         
-        println!("   ✅ Encryption successful!");
+        outln!("   ✅ Encryption successful!");
+        Ok(result)
+    }
+    
+    pub fn encrypt_auto(&self, message: &[u8]) -> Result<(crypto::EncryptedObject, [u8; 32])> {
+        outln!("🔒 Encrypting message with the fastest mode for this CPU...");
+        outln!("   ⚙️  AES hardware acceleration: {}", if aes_accelerated() { "yes" } else { "no" });
+        
+        let result = self.counter.seal_encrypt(
+            self.package_id,
+            self.identity.clone(),
+            self.key_servers.clone(),
+            &IBEPublicKeys::BonehFranklinBLS12381(self.public_keys.clone()),
+            self.threshold,
+            auto_encryption_input(message.to_vec(), None),
+        )?;
+        
+        outln!("   ✅ Encryption successful!");
         Ok(result)
     }
     
     pub fn encrypt_plain(&self) -> Result<(crypto::EncryptedObject, [u8; 32])> {
-        println!("🔒 Generating encryption key (Plain mode)...");
-        println!("   🆔 Identity: \"{}\"", String::from_utf8_lossy(&self.identity));
+        outln!("🔒 Generating encryption key (Plain mode)...");
+        outln!("   ⚠️  {}", PLAIN_MODE_NOTE);
+        outln!("   🆔 Identity: \"{}\"", String::from_utf8_lossy(&self.identity));
         
-        let result = seal_encrypt(
+        let result = self.counter.seal_encrypt(
             self.package_id,
             self.identity.clone(),
             self.key_servers.clone(),
@@ -112,12 +148,12 @@ impl BasicDemo {
             crypto::EncryptionInput::Plain,
         )?;
         
-        println!("   ✅ Key generation successful!");
+        outln!("   ✅ Key generation successful!");
         Ok(result)
     }
     
     pub fn decrypt(&self, encrypted_object: &crypto::EncryptedObject) -> Result<Vec<u8>> {
-        println!("🔓 Decrypting message...");
+        outln!("🔓 Decrypting message...");
         
         // Create full identity (package_id + identity_data)
         let full_id = crypto::create_full_id(&self.package_id, &self.identity);
@@ -125,34 +161,34 @@ impl BasicDemo {
         // Extract user secret keys from first `threshold` key servers
         let mut user_secret_keys = HashMap::new();
         for i in 0..(self.threshold as usize) {
-            let secret_key = extract(&self.master_keys[i], &full_id);
+            let secret_key = self.counter.extract(&self.master_keys[i], &full_id);
             user_secret_keys.insert(self.key_servers[i], secret_key);
-            println!("   🔑 Extracted secret key from server {}", i + 1);
+            outln!("   🔑 Extracted secret key from server {}", i + 1);
         }
         
-        let result = seal_decrypt(
+        let result = self.counter.seal_decrypt(
             encrypted_object,
             &IBEUserSecretKeys::BonehFranklinBLS12381(user_secret_keys),
             Some(&IBEPublicKeys::BonehFranklinBLS12381(self.public_keys.clone())),
         )?;
         
-        println!("   ✅ Decryption successful!");
+        outln!("   ✅ Decryption successful!");
         Ok(result)
     }
     
     pub fn run_aes_demo(&self) -> Result<()> {
-        println!("\n🚀 === AES-256-GCM Demo ===");
+        outln!("\n🚀 === AES-256-GCM Demo ===");
         
         let message = b"Hello, Seal! This is encrypted with AES-256-GCM.";
         
         let (encrypted_object, _symmetric_key) = self.encrypt_with_aes(message)?;
         let decrypted_message = self.decrypt(&encrypted_object)?;
         
-        println!("🎯 Decrypted message: \"{}\"", String::from_utf8_lossy(&decrypted_message));
+        outln!("🎯 Decrypted message: \"{}\"", String::from_utf8_lossy(&decrypted_message));
         
         // Verify the message matches
         if decrypted_message == message {
-            println!("✅ AES demo successful - message matches!");
+            outln!("✅ AES demo successful - message matches!");
         } else {
             anyhow::bail!("❌ AES demo failed - message mismatch!");
         }
@@ -161,18 +197,18 @@ impl BasicDemo {
     }
     
     pub fn run_hmac_demo(&self) -> Result<()> {
-        println!("\n🚀 === HMAC-256-CTR Demo ===");
+        outln!("\n🚀 === HMAC-256-CTR Demo ===");
         
         let message = b"Hello, Seal! This is encrypted with HMAC-256-CTR.";
         
         let (encrypted_object, _symmetric_key) = self.encrypt_with_hmac(message)?;
         let decrypted_message = self.decrypt(&encrypted_object)?;
         
-        println!("🎯 Decrypted message: \"{}\"", String::from_utf8_lossy(&decrypted_message));
+        outln!("🎯 Decrypted message: \"{}\"", String::from_utf8_lossy(&decrypted_message));
         
         // Verify the message matches
         if decrypted_message == message {
-            println!("✅ HMAC demo successful - message matches!");
+            outln!("✅ HMAC demo successful - message matches!");
         } else {
             anyhow::bail!("❌ HMAC demo failed - message mismatch!");
         }
@@ -181,17 +217,17 @@ impl BasicDemo {
     }
     
     pub fn run_plain_demo(&self) -> Result<()> {
-        println!("\n🚀 === Plain Key Derivation Demo ===");
+        outln!("\n🚀 === Plain Key Derivation Demo ===");
         
         let (encrypted_object, original_key) = self.encrypt_plain()?;
         let derived_key = self.decrypt(&encrypted_object)?;
         
-        println!("🎯 Original key: {}", hex::encode(&original_key));
-        println!("🎯 Derived key:  {}", hex::encode(&derived_key));
+        outln!("🎯 Original key: {}", hex::encode(&original_key));
+        outln!("🎯 Derived key:  {}", hex::encode(&derived_key));
         
         // Verify the keys match
         if derived_key == original_key {
-            println!("✅ Plain demo successful - keys match!");
+            outln!("✅ Plain demo successful - keys match!");
         } else {
             anyhow::bail!("❌ Plain demo failed - key mismatch!");
         }
@@ -199,15 +235,145 @@ impl BasicDemo {
         Ok(())
     }
     
+    pub fn run_auto_demo(&self) -> Result<()> {
+        outln!("\n🚀 === Auto Mode Demo ===");
+        
+        let message = b"Hello, Seal! This mode was picked for the CPU it runs on.";
+        
+        let (encrypted_object, _symmetric_key) = self.encrypt_auto(message)?;
+        let mode = match encrypted_object.ciphertext {
+            crypto::Ciphertext::Aes256Gcm { .. } => "AES-256-GCM",
+            crypto::Ciphertext::Hmac256Ctr { .. } => "HMAC-256-CTR",
+            crypto::Ciphertext::Plain => "Plain",
+        };
+        outln!("   🧭 Chosen mode: {}", mode);
+        
+        if self.decrypt(&encrypted_object)? == message {
+            outln!("✅ Auto mode demo successful - message matches!");
+        } else {
+            anyhow::bail!("❌ Auto mode demo failed - message mismatch!");
+        }
+        
+        Ok(())
+    }
+    
+    /// Encrypt one message twice under the same identity, fleet and threshold.
+    /// The objects must differ (fresh data key, shares and IBE randomness each
+    /// time, see "Deterministic Encryption" in the README) yet both decrypt.
+    pub fn demo_nondeterminism(&self) -> Result<()> {
+        outln!("\n🚀 === Randomized Encryption Demo ===");
+        
+        let message = b"Hello, Seal! Same message, same identity, same fleet.";
+        
+        let (first, _) = self.encrypt_with_aes(message)?;
+        let (second, _) = self.encrypt_with_aes(message)?;
+        let first_bytes = bcs::to_bytes(&first)?;
+        let second_bytes = bcs::to_bytes(&second)?;
+        
+        outln!("   🔀 First:  {}…", hex::encode(&first_bytes[first_bytes.len() - 16..]));
+        outln!("   🔀 Second: {}…", hex::encode(&second_bytes[second_bytes.len() - 16..]));
+        if first_bytes == second_bytes {
+            anyhow::bail!("❌ Encrypting twice produced identical objects");
+        }
+        outln!("   ✅ The two encrypted objects differ");
+        
+        if self.decrypt(&first)? == message && self.decrypt(&second)? == message {
+            outln!("✅ Randomized encryption demo successful - both decrypt to the same message!");
+        } else {
+            anyhow::bail!("❌ Randomized encryption demo failed - message mismatch!");
+        }
+        
+        Ok(())
+    }
+    
+    /// Serialized size of the encrypted object each mode produces for a
+    /// `payload_len`-byte payload. Plain mode stores no data, so its size is
+    /// the key material alone and the payload has to be kept elsewhere.
+    pub fn mode_overheads(&self, payload_len: usize) -> Result<Vec<(String, usize)>> {
+        let payload = vec![0u8; payload_len];
+        let modes = [
+            ("AES-256-GCM", EncryptionInput::Aes256Gcm { data: payload.clone(), aad: None }),
+            ("HMAC-256-CTR", EncryptionInput::Hmac256Ctr { data: payload, aad: None }),
+            ("Plain (key only)", EncryptionInput::Plain),
+        ];
+        
+        let mut sizes = Vec::new();
+        for (name, input) in modes {
+            let (encrypted_object, _) = self.counter.seal_encrypt(
+                self.package_id,
+                self.identity.clone(),
+                self.key_servers.clone(),
+                &IBEPublicKeys::BonehFranklinBLS12381(self.public_keys.clone()),
+                self.threshold,
+                input,
+            )?;
+            sizes.push((name.to_string(), bcs::serialized_size(&encrypted_object)?));
+        }
+        Ok(sizes)
+    }
+    
+    pub fn demo_mode_overheads(&self) -> Result<()> {
+        outln!("\n🚀 === Ciphertext Expansion Demo ===");
+        outln!("   {} key servers, threshold {}, {}-byte identity", self.key_servers.len(), self.threshold, self.identity.len());
+        outln!("   {:<18} {:>10} {:>12} {:>10}", "Mode", "Payload", "Ciphertext", "Overhead");
+        
+        for payload_len in [0, 32, 1024, 64 * 1024] {
+            for (mode, size) in self.mode_overheads(payload_len)? {
+                // Plain mode leaves the payload out, so it has no overhead to speak of
+                let overhead = match size.checked_sub(payload_len) {
+                    Some(overhead) if !mode.starts_with("Plain") => format!("+{}", overhead),
+                    _ => "n/a".to_string(),
+                };
+                outln!("   {:<18} {:>10} {:>12} {:>10}", mode, payload_len, size, overhead);
+            }
+        }
+        
+        outln!("   💡 AES and HMAC add a fixed overhead (shares, IBE nonce, AAD, tag/MAC); Plain stores no payload");
+        Ok(())
+    }
+    
     pub fn run_all_demos(&self) -> Result<()> {
         self.run_aes_demo()?;
         self.run_hmac_demo()?;
         self.run_plain_demo()?;
-        println!("\n🎉 All basic demos completed successfully!");
+        self.run_auto_demo()?;
+        self.demo_nondeterminism()?;
+        self.demo_mode_overheads()?;
+        outln!("\n🎉 All basic demos completed successfully!");
         Ok(())
     }
 }
 
+/// Whether this CPU has AES instructions, checked at runtime where the
+/// architecture allows it.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub fn aes_accelerated() -> bool {
+    // AES-GCM also needs carry-less multiplication for GHASH
+    std::arch::is_x86_feature_detected!("aes") && std::arch::is_x86_feature_detected!("pclmulqdq")
+}
+
+#[cfg(target_arch = "aarch64")]
+pub fn aes_accelerated() -> bool {
+    std::arch::is_aarch64_feature_detected!("aes")
+}
+
+#[cfg(not(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64")))]
+pub fn aes_accelerated() -> bool {
+    false
+}
+
+/// AES-256-GCM when the CPU accelerates AES, otherwise HMAC-256-CTR, which
+/// only needs SHA3 and is much faster than software AES on small cores.
+pub fn auto_encryption_input(data: Vec<u8>, aad: Option<Vec<u8>>) -> EncryptionInput {
+    if aes_accelerated() {
+        tracing::info!("AES hardware acceleration available, using AES-256-GCM");
+        EncryptionInput::Aes256Gcm { data, aad }
+    } else {
+        tracing::info!("No AES hardware acceleration, using HMAC-256-CTR");
+        EncryptionInput::Hmac256Ctr { data, aad }
+    }
+}
+
 impl Default for BasicDemo {
     fn default() -> Self {
         Self::new().expect("Failed to create BasicDemo")
@@ -0,0 +1,433 @@
+use anyhow::Result;
+use crypto::{
+    create_full_id,
+    ibe::{extract, generate_key_pair, verify_user_secret_key},
+    EncryptionInput, ObjectID,
+};
+use rand::seq::SliceRandom;
+use rand::{thread_rng, Rng};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::container::SealContainer;
+use crate::file_demo;
+use crate::output::outln;
+use crate::session::SealSession;
+use crate::testutil;
+
+/// Empty-payload encryptions timed to find the fixed per-file cost.
+const OVERHEAD_SAMPLES: usize = 5;
+
+/// Identities `Benchmark::stress` spreads its round trips over, so threads
+/// share some and not others.
+const STRESS_IDENTITIES: usize = 4;
+/// Failures `Benchmark::stress` keeps the details of.
+const STRESS_FAILURES_SHOWN: usize = 10;
+
+/// One sampled file in `Benchmark::estimate`.
+struct SampledFile {
+    plaintext_bytes: u64,
+    output_bytes: u64,
+    elapsed: Duration,
+}
+
+#[derive(Debug, PartialEq)]
+struct Estimate {
+    total: Duration,
+    output_bytes: u64,
+}
+
+/// Scale a sample up to `total_files` files of `total_bytes`: every file pays
+/// `per_file` plus its size at the sample's throughput (net of `per_file`),
+/// and grows by the sample's average container overhead.
+fn extrapolate(sample: &[SampledFile], per_file: Duration, total_files: usize, total_bytes: u64) -> Estimate {
+    let sample_bytes: u64 = sample.iter().map(|file| file.plaintext_bytes).sum();
+    let sample_time: Duration = sample.iter().map(|file| file.elapsed).sum();
+    let variable = sample_time.saturating_sub(per_file * sample.len() as u32);
+    let per_byte = if sample_bytes > 0 { variable.as_secs_f64() / sample_bytes as f64 } else { 0.0 };
+
+    let growth: u64 = sample.iter().map(|file| file.output_bytes.saturating_sub(file.plaintext_bytes)).sum();
+    let growth_per_file = growth / sample.len().max(1) as u64;
+
+    Estimate {
+        total: per_file * total_files as u32 + Duration::from_secs_f64(per_byte * total_bytes as f64),
+        output_bytes: total_bytes + growth_per_file * total_files as u64,
+    }
+}
+
+pub struct Benchmark {
+    pub session: SealSession,
+    pub identity: Vec<u8>,
+    pub payload_bytes: usize,
+    /// Seed for the payload generator; `None` draws fresh randomness.
+    pub seed: Option<u64>,
+}
+
+impl Benchmark {
+    pub fn new(servers: usize, threshold: u8, payload_bytes: usize) -> Result<Self> {
+        outln!("⏱️  Setting up benchmark with {} key servers (threshold {})...", servers, threshold);
+
+        Ok(Benchmark {
+            session: SealSession::generate(servers, threshold)?,
+            identity: b"bench@example.com".to_vec(),
+            payload_bytes,
+            seed: None,
+        })
+    }
+
+    /// Generate payloads from a fixed seed so runs are reproducible.
+    pub fn with_seed(mut self, seed: Option<u64>) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Time `iterations` AES-256-GCM encrypt/decrypt round trips, optionally
+    /// recording every timing as a CSV row. Each row is flushed as soon as it is
+    /// written so a failure in a later iteration never loses earlier results.
+    pub fn run(&self, iterations: usize, output_csv: Option<&Path>) -> Result<()> {
+        outln!("\n🚀 === Encryption Benchmark ===");
+        outln!("   📏 Payload: {} bytes", self.payload_bytes);
+        outln!("   🔁 Iterations: {}", iterations);
+        if let Some(seed) = self.seed {
+            outln!("   🌱 Payload seed: {}", seed);
+        }
+
+        let mut csv = match output_csv {
+            Some(path) => {
+                let mut writer = BufWriter::new(File::create(path)?);
+                writeln!(writer, "iteration,direction,payload_bytes,nanos")?;
+                writer.flush()?;
+                outln!("   📝 Writing per-iteration timings to: {}", path.display());
+                Some(writer)
+            }
+            None => None,
+        };
+
+        let mut rng = testutil::seeded_rng(self.seed);
+        let mut encrypt_total = Duration::ZERO;
+        let mut decrypt_total = Duration::ZERO;
+
+        for iteration in 0..iterations {
+            let payload = testutil::random_bytes(self.payload_bytes, &mut rng);
+
+            let start = Instant::now();
+            let (encrypted_object, _) = self.session.encrypt(
+                &self.identity,
+                EncryptionInput::Aes256Gcm {
+                    data: payload.clone(),
+                    aad: None,
+                },
+            )?;
+            let encrypt_elapsed = start.elapsed();
+            encrypt_total += encrypt_elapsed;
+            record(&mut csv, iteration, "encrypt", self.payload_bytes, encrypt_elapsed)?;
+
+            let start = Instant::now();
+            let decrypted = self.session.decrypt(&encrypted_object)?;
+            let decrypt_elapsed = start.elapsed();
+            decrypt_total += decrypt_elapsed;
+            record(&mut csv, iteration, "decrypt", self.payload_bytes, decrypt_elapsed)?;
+
+            if decrypted != payload {
+                anyhow::bail!("❌ Round trip mismatch in iteration {}", iteration);
+            }
+
+            outln!(
+                "   ⏱️  [{}/{}] encrypt {:?}, decrypt {:?}",
+                iteration + 1,
+                iterations,
+                encrypt_elapsed,
+                decrypt_elapsed
+            );
+        }
+
+        outln!("\n📊 Throughput:");
+        print_throughput("Encrypt", iterations, self.payload_bytes, encrypt_total);
+        print_throughput("Decrypt", iterations, self.payload_bytes, decrypt_total);
+
+        Ok(())
+    }
+}
+
+impl Benchmark {
+    /// Run encrypt/decrypt round trips of random payloads (up to
+    /// `payload_bytes`, with random AAD) on `threads` threads sharing this
+    /// session until `duration` has passed, checking every result. Fails if
+    /// any round trip errored or came back different; otherwise returns the
+    /// number of round trips.
+    pub fn stress(&self, threads: usize, duration: Duration) -> Result<usize> {
+        outln!("\n🚀 === Concurrency Stress Test ===");
+        outln!("   🧵 Threads: {}", threads);
+        outln!("   ⏱️  Duration: {:?}", duration);
+        outln!("   📏 Payloads: up to {} bytes", self.payload_bytes);
+
+        let round_trips = AtomicUsize::new(0);
+        let failed = AtomicUsize::new(0);
+        let failures = Mutex::new(Vec::new());
+        let start = Instant::now();
+        let deadline = start + duration;
+
+        std::thread::scope(|scope| {
+            for thread in 0..threads {
+                let (round_trips, failed, failures) = (&round_trips, &failed, &failures);
+                scope.spawn(move || {
+                    let mut rng = thread_rng();
+                    while Instant::now() < deadline {
+                        let identity = format!("stress-{}@example.com", rng.gen_range(0..STRESS_IDENTITIES));
+                        let payload = testutil::random_bytes(rng.gen_range(0..=self.payload_bytes), &mut rng);
+                        let aad = testutil::random_bytes(rng.gen_range(0..=32), &mut rng);
+                        let outcome = self
+                            .session
+                            .encrypt(identity.as_bytes(), EncryptionInput::Aes256Gcm { data: payload.clone(), aad: Some(aad) })
+                            .and_then(|(encrypted_object, _)| self.session.decrypt(&encrypted_object));
+
+                        let n = round_trips.fetch_add(1, Ordering::Relaxed);
+                        let failure = match outcome {
+                            Ok(decrypted) if decrypted == payload => continue,
+                            Ok(_) => "decrypted data differs from the payload".to_string(),
+                            Err(e) => format!("{:#}", e),
+                        };
+                        failed.fetch_add(1, Ordering::Relaxed);
+                        let mut failures = failures.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+                        if failures.len() < STRESS_FAILURES_SHOWN {
+                            failures.push(format!("thread {}, round trip {}, {}: {}", thread, n, identity, failure));
+                        }
+                    }
+                });
+            }
+        });
+
+        let elapsed = start.elapsed();
+        let (round_trips, failed) = (round_trips.into_inner(), failed.into_inner());
+        outln!("\n📊 Results:");
+        outln!(
+            "   🔁 {} round trips in {:.1?}: {:.1} ops/s",
+            round_trips,
+            elapsed,
+            round_trips as f64 / elapsed.as_secs_f64()
+        );
+        if failed == 0 {
+            outln!("   ✅ Every round trip matched");
+            return Ok(round_trips);
+        }
+        for failure in failures.into_inner().unwrap_or_else(|poisoned| poisoned.into_inner()) {
+            outln!("   ❌ {}", failure);
+        }
+        anyhow::bail!("❌ {} of {} round trips failed", failed, round_trips)
+    }
+
+    /// Encrypt a random sample of the files under `dir` (in memory, nothing
+    /// is written) and extrapolate the time and output size for all of them.
+    pub fn estimate(&self, dir: &Path, sample_size: usize) -> Result<()> {
+        outln!("\n🚀 === Encryption Estimate ===");
+
+        let files = file_demo::files_under(dir)?;
+        if files.is_empty() {
+            anyhow::bail!("No files found under {}", dir.display());
+        }
+        let total_bytes: u64 = files.iter().map(|(_, size)| size).sum();
+        outln!("   📂 {}: {} files, {} bytes", dir.display(), files.len(), total_bytes);
+
+        let mut overheads = Vec::with_capacity(OVERHEAD_SAMPLES);
+        for _ in 0..OVERHEAD_SAMPLES {
+            let start = Instant::now();
+            self.session.encrypt(&self.identity, EncryptionInput::Aes256Gcm { data: Vec::new(), aad: None })?;
+            overheads.push(start.elapsed());
+        }
+        overheads.sort();
+        let per_file = percentile(&overheads, 50);
+        outln!("   ⏱️  Fixed cost per file: {:?}", per_file);
+
+        let mut rng = testutil::seeded_rng(self.seed);
+        let chosen: Vec<&(PathBuf, u64)> = files.choose_multiple(&mut rng, sample_size.max(1)).collect();
+        let mut sample = Vec::with_capacity(chosen.len());
+        for (path, _) in chosen {
+            let data = std::fs::read(path)?;
+            let plaintext_bytes = data.len() as u64;
+            let identity = format!("file:{}", path.file_name().unwrap_or_default().to_string_lossy());
+
+            let start = Instant::now();
+            let (encrypted_object, _) =
+                self.session.encrypt(identity.as_bytes(), EncryptionInput::Aes256Gcm { data, aad: None })?;
+            let elapsed = start.elapsed();
+
+            let container =
+                SealContainer::for_fleet(encrypted_object, &self.session.key_servers, &self.session.public_keys);
+            sample.push(SampledFile {
+                plaintext_bytes,
+                output_bytes: container.to_bytes()?.len() as u64,
+                elapsed,
+            });
+        }
+        let sampled_bytes: u64 = sample.iter().map(|file| file.plaintext_bytes).sum();
+        let sampled_time: Duration = sample.iter().map(|file| file.elapsed).sum();
+        outln!("   🎲 Sampled {} files, {} bytes", sample.len(), sampled_bytes);
+        print_throughput("Sample", sample.len(), (sampled_bytes / sample.len() as u64) as usize, sampled_time);
+
+        let estimate = extrapolate(&sample, per_file, files.len(), total_bytes);
+        outln!("\n📊 Estimate for all {} files:", files.len());
+        outln!("   ⏳ Encryption time: {:.1?}", estimate.total);
+        outln!("   📏 Output size: {} bytes", estimate.output_bytes);
+        outln!(
+            "   ⚠️  This is an estimate from {} of {} files, single-threaded and excluding disk I/O; \
+             unrepresentative samples, other load or a cold cache can move the real figure either way",
+            sample.len(),
+            files.len()
+        );
+
+        Ok(())
+    }
+}
+
+/// Time extracting user secret keys for `identities` distinct identities
+/// from one master key, as a key server would, and optionally verifying each
+/// extracted key against the public key, timed separately.
+pub fn run_extract(identities: usize, verify: bool) -> Result<()> {
+    outln!("\n🚀 === Key Extraction Benchmark ===");
+    outln!("   🆔 Identities: {}", identities);
+
+    let (master_key, public_key) = generate_key_pair(&mut thread_rng());
+    let package_id = ObjectID::random();
+    let mut extract_times = Vec::with_capacity(identities);
+    let mut verify_times = Vec::with_capacity(if verify { identities } else { 0 });
+
+    for i in 0..identities {
+        let full_id = create_full_id(&package_id, format!("user-{}@example.com", i).as_bytes());
+
+        let start = Instant::now();
+        let user_secret_key = extract(&master_key, &full_id);
+        extract_times.push(start.elapsed());
+
+        if verify {
+            let start = Instant::now();
+            verify_user_secret_key(&user_secret_key, &full_id, &public_key)
+                .map_err(|e| anyhow::anyhow!("❌ Extracted key {} failed verification: {}", i, e))?;
+            verify_times.push(start.elapsed());
+        }
+    }
+
+    outln!("\n📊 Latency:");
+    print_latency("Extract", &mut extract_times);
+    if verify {
+        print_latency("Verify", &mut verify_times);
+    }
+
+    Ok(())
+}
+
+/// `30s`, `2m`, `500ms` or plain seconds, for `stress --duration`.
+pub fn parse_duration(text: &str) -> Result<Duration> {
+    let text = text.trim();
+    let split = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: u64 = number.parse().map_err(|_| anyhow::anyhow!("Invalid duration \"{}\"", text))?;
+    match unit {
+        "ms" => Ok(Duration::from_millis(number)),
+        "" | "s" => Ok(Duration::from_secs(number)),
+        "m" => Ok(Duration::from_secs(number * 60)),
+        "h" => Ok(Duration::from_secs(number * 3600)),
+        _ => anyhow::bail!("Invalid duration unit \"{}\", use ms, s, m or h", unit),
+    }
+}
+
+/// Print ops/s and p50/p99 for `times`, sorting it in place.
+fn print_latency(label: &str, times: &mut [Duration]) {
+    if times.is_empty() {
+        return;
+    }
+    times.sort();
+    let total: Duration = times.iter().sum();
+    let secs = total.as_secs_f64();
+    let ops_per_sec = if secs > 0.0 { times.len() as f64 / secs } else { f64::INFINITY };
+    outln!(
+        "   {}: {:.1} ops/s, p50 {:?}, p99 {:?}",
+        label,
+        ops_per_sec,
+        percentile(times, 50),
+        percentile(times, 99)
+    );
+}
+
+/// Nearest-rank percentile of an ascending, non-empty slice.
+fn percentile(sorted: &[Duration], p: usize) -> Duration {
+    let rank = (p * sorted.len()).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
+
+fn record(
+    csv: &mut Option<BufWriter<File>>,
+    iteration: usize,
+    direction: &str,
+    payload_bytes: usize,
+    elapsed: Duration,
+) -> Result<()> {
+    if let Some(writer) = csv {
+        writeln!(writer, "{},{},{},{}", iteration, direction, payload_bytes, elapsed.as_nanos())?;
+        writer.flush()?;
+    }
+    Ok(())
+}
+
+fn print_throughput(label: &str, iterations: usize, payload_bytes: usize, total: Duration) {
+    if iterations == 0 {
+        return;
+    }
+    let secs = total.as_secs_f64();
+    let ops_per_sec = if secs > 0.0 { iterations as f64 / secs } else { f64::INFINITY };
+    let mib_per_sec = ops_per_sec * payload_bytes as f64 / (1024.0 * 1024.0);
+    outln!(
+        "   {}: {:.1} ops/s, {:.2} MiB/s (avg {:?})",
+        label,
+        ops_per_sec,
+        mib_per_sec,
+        total / iterations as u32
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extrapolate() {
+        // 1ms fixed per file plus 1ms per KiB, each output 100 bytes larger
+        let sample: Vec<SampledFile> = [1024u64, 4096]
+            .iter()
+            .map(|&size| SampledFile {
+                plaintext_bytes: size,
+                output_bytes: size + 100,
+                elapsed: Duration::from_millis(1 + size / 1024),
+            })
+            .collect();
+        let estimate = extrapolate(&sample, Duration::from_millis(1), 10, 100 * 1024);
+        assert_eq!(estimate.output_bytes, 100 * 1024 + 10 * 100);
+        let millis = estimate.total.as_secs_f64() * 1000.0;
+        assert!((millis - 110.0).abs() < 0.001, "{}", millis);
+    }
+
+    #[test]
+    fn test_stress_round_trips() -> Result<()> {
+        let bench = Benchmark::new(3, 2, 512)?;
+        assert!(bench.stress(4, Duration::from_millis(300))? > 0);
+
+        assert_eq!(parse_duration("30s")?, Duration::from_secs(30));
+        assert_eq!(parse_duration("2m")?, Duration::from_secs(120));
+        assert_eq!(parse_duration("250ms")?, Duration::from_millis(250));
+        assert_eq!(parse_duration("5")?, Duration::from_secs(5));
+        assert!(parse_duration("5 days").is_err());
+        assert!(parse_duration("s").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_percentile() {
+        let times: Vec<Duration> = (1..=100).map(Duration::from_millis).collect();
+        assert_eq!(percentile(&times, 50), Duration::from_millis(50));
+        assert_eq!(percentile(&times, 99), Duration::from_millis(99));
+        assert_eq!(percentile(&times[..1], 99), Duration::from_millis(1));
+    }
+}
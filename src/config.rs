@@ -0,0 +1,148 @@
+//! Session config files: `seal.toml` for hand-maintained fleets, or the JSON
+//! written by `seal-demo session`. The format is picked by file extension.
+
+use anyhow::{Context, Result};
+use crypto::ObjectID;
+use std::path::Path;
+
+use crate::session::{encode_public_key, SealSession};
+
+fn is_toml(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("toml"))
+}
+
+pub fn load_session(path: &Path) -> Result<SealSession> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read config {}", path.display()))?;
+
+    if is_toml(path) {
+        SealSession::from_toml(&contents)
+    } else {
+        SealSession::from_json(&contents)
+    }
+    .with_context(|| format!("Failed to load config {}", path.display()))
+}
+
+pub fn save_session(session: &SealSession, path: &Path, include_secrets: bool) -> Result<()> {
+    let contents = if is_toml(path) {
+        session.to_toml(include_secrets)?
+    } else {
+        session.to_json(include_secrets)?
+    };
+    std::fs::write(path, contents)?;
+    Ok(())
+}
+
+/// Problems that loading alone doesn't catch: the same server or public key
+/// listed twice makes a quorum look bigger than it really is.
+pub fn consistency_issues(session: &SealSession) -> Vec<String> {
+    let mut issues = Vec::new();
+    for (i, server_id) in session.key_servers.iter().enumerate() {
+        if let Some(first) = session.key_servers[..i].iter().position(|other| other == server_id) {
+            issues.push(format!("Servers {} and {} share the object ID {}", first + 1, i + 1, server_id));
+        }
+    }
+    for (i, public_key) in session.public_keys.iter().enumerate() {
+        if let Some(first) = session.public_keys[..i].iter().position(|other| other == public_key) {
+            issues.push(format!(
+                "Servers {} and {} share the public key {}…",
+                first + 1,
+                i + 1,
+                &encode_public_key(public_key)[..16]
+            ));
+        }
+    }
+    issues
+}
+
+/// How two fleets relate: which servers they share and whether a ciphertext
+/// made for one could be opened with the other's keys.
+pub struct FleetComparison {
+    /// Servers in both fleets with the same public key
+    pub matching: Vec<ObjectID>,
+    /// Servers in both fleets whose public keys differ
+    pub mismatched: Vec<ObjectID>,
+    pub only_first: Vec<ObjectID>,
+    pub only_second: Vec<ObjectID>,
+    /// Ciphertexts encrypted to the first fleet open with the second's keys
+    pub first_to_second: bool,
+    /// Ciphertexts encrypted to the second fleet open with the first's keys
+    pub second_to_first: bool,
+}
+
+/// Compare two fleets by server object ID. A ciphertext lists the servers it
+/// was encrypted to and a threshold, so the other fleet can decrypt it when at
+/// least that many of those servers appear there with the same public key.
+pub fn compare_fleets(first: &SealSession, second: &SealSession) -> FleetComparison {
+    let mut comparison = FleetComparison {
+        matching: Vec::new(),
+        mismatched: Vec::new(),
+        only_first: Vec::new(),
+        only_second: Vec::new(),
+        first_to_second: false,
+        second_to_first: false,
+    };
+
+    for (server_id, public_key) in first.key_servers.iter().zip(&first.public_keys) {
+        match second.key_servers.iter().position(|other| other == server_id) {
+            Some(j) if second.public_keys[j] == *public_key => comparison.matching.push(*server_id),
+            Some(_) => comparison.mismatched.push(*server_id),
+            None => comparison.only_first.push(*server_id),
+        }
+    }
+    comparison.only_second = second
+        .key_servers
+        .iter()
+        .filter(|server_id| !first.key_servers.contains(server_id))
+        .copied()
+        .collect();
+
+    comparison.first_to_second = comparison.matching.len() >= first.threshold as usize;
+    comparison.second_to_first = comparison.matching.len() >= second.threshold as usize;
+    comparison
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_duplicate_servers_reported() -> Result<()> {
+        let mut session = SealSession::generate(3, 2)?;
+        assert!(consistency_issues(&session).is_empty());
+
+        session.key_servers[2] = session.key_servers[0];
+        session.public_keys[2] = session.public_keys[1];
+        let issues = consistency_issues(&session);
+        assert_eq!(issues.len(), 2);
+        assert!(issues[0].starts_with("Servers 1 and 3 share the object ID"));
+        assert!(issues[1].starts_with("Servers 2 and 3 share the public key"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_compare_fleets() -> Result<()> {
+        let staging = SealSession::generate(3, 2)?;
+        let mut prod = SealSession::generate(3, 3)?;
+
+        let comparison = compare_fleets(&staging, &prod);
+        assert!(comparison.matching.is_empty());
+        assert_eq!(comparison.only_first.len(), 3);
+        assert!(!comparison.first_to_second && !comparison.second_to_first);
+
+        // Two servers shared as-is, one shared with a rotated key
+        for i in 0..3 {
+            prod.key_servers[i] = staging.key_servers[i];
+        }
+        prod.public_keys[0] = staging.public_keys[0];
+        prod.public_keys[1] = staging.public_keys[1];
+
+        let comparison = compare_fleets(&staging, &prod);
+        assert_eq!(comparison.matching, staging.key_servers[..2].to_vec());
+        assert_eq!(comparison.mismatched, vec![staging.key_servers[2]]);
+        assert!(comparison.only_first.is_empty() && comparison.only_second.is_empty());
+        assert!(comparison.first_to_second);
+        assert!(!comparison.second_to_first);
+        Ok(())
+    }
+}
@@ -0,0 +1,351 @@
+//! On-disk container for files encrypted from the command line.
+//!
+//! Layout: `"SEAL" | format version (u8) | bcs(SealContainer)`. Files written
+//! before the container existed are a bare `bcs(EncryptedObject)`; they are
+//! still accepted and come back with an empty header.
+//!
+//! Format versions:
+//! - 1: fingerprint and encrypted object
+//! - 2: adds the optional escrow envelope
+//! - 3: adds the key servers' public keys, so a decryptor needs nothing but
+//!   the file (and master keys or key server access) to verify key shares
+
+use anyhow::{Context, Result};
+use crypto::{Ciphertext, EncryptedObject, ObjectID};
+use fastcrypto::groups::bls12381::G2Element;
+use fastcrypto::hash::{HashFunction, Sha3_256};
+use fastcrypto::serde_helpers::ToFromByteArray;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::escrow::Envelope;
+use crate::session::SealSession;
+
+pub const CONTAINER_MAGIC: &[u8; 4] = b"SEAL";
+pub const CONTAINER_VERSION: u8 = 3;
+
+/// Short identifier of a key-server fleet, see `fleet_fingerprint`.
+pub type Fingerprint = [u8; 8];
+
+#[derive(Serialize, Deserialize)]
+pub struct SealContainer {
+    /// Fingerprint of the fleet the object was encrypted to, if recorded.
+    pub fingerprint: Option<Fingerprint>,
+    pub encrypted_object: EncryptedObject,
+    /// Present for escrowed files, whose `encrypted_object` only wraps the
+    /// data key (see `escrow`).
+    pub envelope: Option<Envelope>,
+    /// Public key of each server the object was encrypted to, if recorded.
+    /// Like the fingerprint, they are whatever the file's writer put there;
+    /// see `public_keys`.
+    pub server_keys: Option<Vec<(ObjectID, G2Element)>>,
+}
+
+/// A container written by a newer build of this tool. Returned (wrapped in
+/// `anyhow::Error`) before the body is parsed, so a layout change never
+/// surfaces as a raw bcs error.
+#[derive(Debug, PartialEq, Eq)]
+pub struct UnsupportedVersion {
+    pub found: u8,
+    pub max_supported: u8,
+}
+
+impl fmt::Display for UnsupportedVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Seal container version {} is newer than the latest supported version {}; upgrade seal-demo to read this file",
+            self.found, self.max_supported
+        )
+    }
+}
+
+impl std::error::Error for UnsupportedVersion {}
+
+#[derive(Deserialize)]
+struct ContainerV1 {
+    fingerprint: Option<Fingerprint>,
+    encrypted_object: EncryptedObject,
+}
+
+#[derive(Deserialize)]
+struct ContainerV2 {
+    fingerprint: Option<Fingerprint>,
+    encrypted_object: EncryptedObject,
+    envelope: Option<Envelope>,
+}
+
+impl SealContainer {
+    /// A container recording the fleet `encrypted_object` was encrypted to:
+    /// its fingerprint and every server's public key.
+    pub fn for_fleet(encrypted_object: EncryptedObject, key_servers: &[ObjectID], public_keys: &[G2Element]) -> Self {
+        SealContainer {
+            fingerprint: Some(fleet_fingerprint(key_servers, public_keys)),
+            encrypted_object,
+            envelope: None,
+            server_keys: Some(key_servers.iter().copied().zip(public_keys.iter().copied()).collect()),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut bytes = CONTAINER_MAGIC.to_vec();
+        bytes.push(CONTAINER_VERSION);
+        bytes.extend_from_slice(&bcs::to_bytes(self)?);
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let Some(body) = bytes.strip_prefix(CONTAINER_MAGIC.as_slice()) else {
+            // Without the magic this is either a legacy file or not ours at
+            // all; don't blame corruption for the latter
+            let encrypted_object =
+                bcs::from_bytes(bytes).context("This does not appear to be a Seal-encrypted file")?;
+            return Ok(SealContainer {
+                fingerprint: None,
+                encrypted_object,
+                envelope: None,
+                server_keys: None,
+            });
+        };
+
+        match body.split_first() {
+            Some((&version, _)) if version > CONTAINER_VERSION => Err(UnsupportedVersion {
+                found: version,
+                max_supported: CONTAINER_VERSION,
+            }
+            .into()),
+            Some((&CONTAINER_VERSION, rest)) => bcs::from_bytes(rest).context("Corrupt Seal container"),
+            Some((2, rest)) => {
+                let v2: ContainerV2 = bcs::from_bytes(rest).context("Corrupt Seal container")?;
+                Ok(SealContainer {
+                    fingerprint: v2.fingerprint,
+                    encrypted_object: v2.encrypted_object,
+                    envelope: v2.envelope,
+                    server_keys: None,
+                })
+            }
+            Some((1, rest)) => {
+                let v1: ContainerV1 = bcs::from_bytes(rest).context("Corrupt Seal container")?;
+                Ok(SealContainer {
+                    fingerprint: v1.fingerprint,
+                    encrypted_object: v1.encrypted_object,
+                    envelope: None,
+                    server_keys: None,
+                })
+            }
+            Some((version, _)) => anyhow::bail!("Unsupported Seal container version {}", version),
+            None => anyhow::bail!("Truncated Seal container"),
+        }
+    }
+
+    /// Recover the plaintext. `unwrap` decrypts a Seal object, and is given
+    /// the main object: for escrowed files that yields the data key.
+    pub fn decrypt(&self, unwrap: impl FnOnce(&EncryptedObject) -> Result<Vec<u8>>) -> Result<Vec<u8>> {
+        let unwrapped = unwrap(&self.encrypted_object)?;
+        match &self.envelope {
+            Some(envelope) => envelope.open(&unwrapped),
+            None => Ok(unwrapped),
+        }
+    }
+
+    /// The recorded public keys in the order of the object's servers, as
+    /// `seal_decrypt` expects them, or `None` for containers that predate
+    /// them. They are not authenticated: checking that they hash to the
+    /// recorded fingerprint only shows the two are consistent, and whoever
+    /// wrote the file chose both. Compare that fingerprint with the one of a
+    /// fleet you already trust before relying on these keys.
+    pub fn public_keys(&self) -> Result<Option<Vec<G2Element>>> {
+        let Some(server_keys) = &self.server_keys else {
+            return Ok(None);
+        };
+        let (servers, keys): (Vec<ObjectID>, Vec<G2Element>) = server_keys.iter().copied().unzip();
+        if self.fingerprint != Some(fleet_fingerprint(&servers, &keys)) {
+            anyhow::bail!("Recorded public keys don't match the container's fleet fingerprint");
+        }
+        self.encrypted_object
+            .services
+            .iter()
+            .map(|(server, _)| {
+                server_keys
+                    .iter()
+                    .find(|(id, _)| id == server)
+                    .map(|(_, key)| *key)
+                    .ok_or_else(|| anyhow::anyhow!("No public key recorded for server {}", server))
+            })
+            .collect::<Result<_>>()
+            .map(Some)
+    }
+
+    /// The authenticated data bound to the plaintext, wherever it is stored.
+    pub fn aad(&self) -> Option<&[u8]> {
+        match &self.envelope {
+            Some(envelope) => envelope.aad.as_deref(),
+            None => match &self.encrypted_object.ciphertext {
+                Ciphertext::Aes256Gcm { aad, .. } | Ciphertext::Hmac256Ctr { aad, .. } => aad.as_deref(),
+                Ciphertext::Plain => None,
+            },
+        }
+    }
+}
+
+/// Rewrite a legacy headerless file, or a container of an older version, in
+/// the current format, or `None` if it already is. No keys are needed: the
+/// encrypted object is carried over untouched. A missing fingerprint and the
+/// servers' public keys are filled in from `fleet` only when the object was
+/// encrypted to exactly that fleet's servers (and any recorded fingerprint is
+/// the fleet's); otherwise they stay unrecorded.
+pub fn upgrade(bytes: &[u8], fleet: Option<&SealSession>) -> Result<Option<Vec<u8>>> {
+    let mut container = SealContainer::from_bytes(bytes)?;
+    let current = bytes.strip_prefix(CONTAINER_MAGIC.as_slice()).and_then(|body| body.first()) == Some(&CONTAINER_VERSION);
+    if current {
+        return Ok(None);
+    }
+
+    if let Some(fleet) = fleet {
+        let servers = &container.encrypted_object.services;
+        let same_fleet = servers.len() == fleet.key_servers.len()
+            && servers.iter().all(|(server, _)| fleet.key_servers.contains(server))
+            && container.fingerprint.is_none_or(|fingerprint| fingerprint == fleet.fingerprint());
+        if same_fleet {
+            let recorded = SealContainer::for_fleet(container.encrypted_object, &fleet.key_servers, &fleet.public_keys);
+            container = SealContainer { envelope: container.envelope, ..recorded };
+        }
+    }
+    Ok(Some(container.to_bytes()?))
+}
+
+/// First 8 bytes of a SHA3-256 over the fleet's (server ID, public key) pairs,
+/// sorted by server ID so that listing order doesn't matter.
+pub fn fleet_fingerprint(key_servers: &[ObjectID], public_keys: &[G2Element]) -> Fingerprint {
+    let mut servers: Vec<(Vec<u8>, [u8; 96])> = key_servers
+        .iter()
+        .zip(public_keys)
+        .map(|(server_id, public_key)| (server_id.to_vec(), public_key.to_byte_array()))
+        .collect();
+    servers.sort();
+
+    let mut hasher = Sha3_256::default();
+    for (server_id, public_key) in &servers {
+        hasher.update(server_id);
+        hasher.update(public_key);
+    }
+
+    let digest = hasher.finalize().digest;
+    let mut fingerprint = [0u8; 8];
+    fingerprint.copy_from_slice(&digest[..8]);
+    fingerprint
+}
+
+pub fn format_fingerprint(fingerprint: &Fingerprint) -> String {
+    hex::encode(fingerprint)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crypto::EncryptionInput;
+
+    #[test]
+    fn test_fingerprint_round_trip() -> Result<()> {
+        let session = SealSession::generate(3, 2)?;
+        let (encrypted_object, _) = session.encrypt(
+            b"report.pdf",
+            EncryptionInput::Aes256Gcm {
+                data: b"quarterly numbers".to_vec(),
+                aad: None,
+            },
+        )?;
+        let legacy_bytes = bcs::to_bytes(&encrypted_object)?;
+
+        let container = SealContainer::for_fleet(encrypted_object, &session.key_servers, &session.public_keys);
+        let parsed = SealContainer::from_bytes(&container.to_bytes()?)?;
+        assert_eq!(parsed.fingerprint, Some(session.fingerprint()));
+        assert_eq!(session.decrypt(&parsed.encrypted_object)?, b"quarterly numbers");
+
+        // Headerless files from before the container still parse
+        assert!(SealContainer::from_bytes(&legacy_bytes)?.fingerprint.is_none());
+
+        // Server order doesn't matter, but a different fleet does
+        let mut reordered = session.clone();
+        reordered.key_servers.reverse();
+        reordered.public_keys.reverse();
+        assert_eq!(reordered.fingerprint(), session.fingerprint());
+        assert_ne!(SealSession::generate(3, 2)?.fingerprint(), session.fingerprint());
+        Ok(())
+    }
+
+    #[test]
+    fn test_public_keys_recorded() -> Result<()> {
+        let session = SealSession::generate(4, 2)?;
+        let (encrypted_object, _) = session.encrypt(b"self-describing", EncryptionInput::Plain)?;
+        let container = SealContainer::for_fleet(encrypted_object.clone(), &session.key_servers, &session.public_keys);
+        let parsed = SealContainer::from_bytes(&container.to_bytes()?)?;
+        assert_eq!(parsed.public_keys()?, Some(session.public_keys.clone()));
+
+        // A substituted key no longer hashes to the fingerprint
+        let mut swapped = SealContainer::from_bytes(&container.to_bytes()?)?;
+        swapped.server_keys.as_mut().unwrap()[1].1 = SealSession::generate(1, 1)?.public_keys[0];
+        assert!(swapped.public_keys().is_err());
+
+        // Version 2 containers parse without keys
+        let mut v2 = SealContainer { server_keys: None, ..parsed }.to_bytes()?;
+        assert_eq!(v2.pop(), Some(0));
+        v2[CONTAINER_MAGIC.len()] = 2;
+        let parsed = SealContainer::from_bytes(&v2)?;
+        assert_eq!(parsed.fingerprint, Some(session.fingerprint()));
+        assert_eq!(parsed.public_keys()?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_newer_version_is_rejected_before_parsing() -> Result<()> {
+        let session = SealSession::generate(3, 2)?;
+        let (encrypted_object, _) = session.encrypt(b"report.pdf", EncryptionInput::Plain)?;
+        let container = SealContainer {
+            fingerprint: None,
+            encrypted_object,
+            envelope: None,
+            server_keys: None,
+        };
+
+        let mut bytes = container.to_bytes()?;
+        bytes[CONTAINER_MAGIC.len()] = CONTAINER_VERSION + 1;
+        let error = SealContainer::from_bytes(&bytes).err().expect("newer version must not parse");
+        assert_eq!(
+            error.downcast_ref::<UnsupportedVersion>(),
+            Some(&UnsupportedVersion {
+                found: CONTAINER_VERSION + 1,
+                max_supported: CONTAINER_VERSION,
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_upgrade_legacy_without_keys() -> Result<()> {
+        let session = SealSession::generate(3, 2)?;
+        let (encrypted_object, _) = session.encrypt(
+            b"old.txt",
+            EncryptionInput::Aes256Gcm { data: b"from before the container".to_vec(), aad: None },
+        )?;
+        let legacy_bytes = bcs::to_bytes(&encrypted_object)?;
+
+        // Public parameters only: no master keys involved
+        let public_fleet = SealSession { master_keys: None, ..session.clone() };
+        let upgraded = upgrade(&legacy_bytes, Some(&public_fleet))?.expect("legacy file must be upgraded");
+        assert_eq!(&upgraded[..5], b"SEAL\x03");
+        let container = SealContainer::from_bytes(&upgraded)?;
+        assert_eq!(container.fingerprint, Some(session.fingerprint()));
+        assert_eq!(container.public_keys()?, Some(session.public_keys.clone()));
+        assert_eq!(session.decrypt(&container.encrypted_object)?, b"from before the container");
+        assert!(upgrade(&upgraded, None)?.is_none());
+
+        // Another fleet's servers leave the fingerprint unrecorded
+        let other = SealSession::generate(3, 2)?;
+        let container = SealContainer::from_bytes(&upgrade(&legacy_bytes, Some(&other))?.unwrap())?;
+        assert!(container.fingerprint.is_none());
+
+        assert!(upgrade(b"not a seal file", None).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,174 @@
+//! The manifest of a tree sealed by `FileDemo::encrypt_directory`.
+//!
+//! `manifest.seal` is a sequence of frames (`streaming::write_frame`):
+//!
+//! 1. `bcs(EncryptedObject)` of the encoded manifest
+//! 2. a one-byte flags frame; trees written before it existed end after
+//!    frame 1 and are read as flags `0`
+//!
+//! With `FLAG_ZSTD` the manifest is zstd-compressed before it is sealed.
+//! Paths in large trees share long prefixes, so this shrinks manifests of
+//! thousands of entries several times over.
+//!
+//! With `FLAG_SKIPPED` the manifest also lists the files `encrypt_directory`
+//! left out and why. Without it, the encoding is the entries alone, as
+//! written before skipping existed, so trees with nothing skipped stay
+//! readable by older builds.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::Read;
+
+/// The sealed manifest is zstd-compressed.
+pub const FLAG_ZSTD: u8 = 0x01;
+/// The manifest lists skipped files after the entries.
+pub const FLAG_SKIPPED: u8 = 0x02;
+const KNOWN_FLAGS: u8 = FLAG_ZSTD | FLAG_SKIPPED;
+
+const ZSTD_LEVEL: i32 = 3;
+/// Largest manifest `decode` decompresses, so a hostile one can't exhaust
+/// memory.
+const MAX_DECOMPRESSED_LEN: u64 = 256 * 1024 * 1024;
+
+/// Structure of a tree sealed by `encrypt_directory`, itself encrypted.
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct DirectoryManifest {
+    pub entries: Vec<DirectoryEntry>,
+    /// Files under the tree that were not encrypted.
+    pub skipped: Vec<SkippedEntry>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct DirectoryEntry {
+    /// Relative to the tree root, `/`-separated.
+    pub path: String,
+    pub size: u64,
+    /// Seconds since the Unix epoch.
+    pub modified: u64,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct SkippedEntry {
+    /// Relative to the tree root, `/`-separated.
+    pub path: String,
+    pub size: u64,
+    pub reason: SkipReason,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub enum SkipReason {
+    /// Larger than the limit, in bytes.
+    TooLarge { limit: u64 },
+    /// Has this excluded extension.
+    ExcludedExtension(String),
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SkipReason::TooLarge { limit } => write!(f, "over the {} byte limit", limit),
+            SkipReason::ExcludedExtension(ext) => write!(f, "excluded extension .{}", ext),
+        }
+    }
+}
+
+impl DirectoryManifest {
+    /// The plaintext to seal and the flags to store next to it.
+    pub fn encode(&self) -> Result<(Vec<u8>, u8)> {
+        let (bytes, flags) = if self.skipped.is_empty() {
+            (bcs::to_bytes(&self.entries)?, FLAG_ZSTD)
+        } else {
+            (bcs::to_bytes(self)?, FLAG_ZSTD | FLAG_SKIPPED)
+        };
+        Ok((zstd::bulk::compress(&bytes, ZSTD_LEVEL)?, flags))
+    }
+
+    /// Inverse of `encode`, given the flags read from the header.
+    pub fn decode(bytes: &[u8], flags: u8) -> Result<Self> {
+        if flags & !KNOWN_FLAGS != 0 {
+            anyhow::bail!("Unsupported directory manifest flags {:#04x}", flags);
+        }
+        let mut decompressed = Vec::new();
+        let bytes = if flags & FLAG_ZSTD == 0 {
+            bytes
+        } else {
+            zstd::stream::read::Decoder::new(bytes)?
+                .take(MAX_DECOMPRESSED_LEN + 1)
+                .read_to_end(&mut decompressed)
+                .context("Corrupt compressed directory manifest")?;
+            if decompressed.len() as u64 > MAX_DECOMPRESSED_LEN {
+                anyhow::bail!("Directory manifest decompresses to over {} bytes", MAX_DECOMPRESSED_LEN);
+            }
+            &decompressed
+        };
+        if flags & FLAG_SKIPPED == 0 {
+            let entries = bcs::from_bytes(bytes).context("Corrupt directory manifest")?;
+            return Ok(DirectoryManifest { entries, skipped: Vec::new() });
+        }
+        bcs::from_bytes(bytes).context("Corrupt directory manifest")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_large_manifest_compresses() -> Result<()> {
+        let manifest = DirectoryManifest {
+            entries: (0..10_000)
+                .map(|i| DirectoryEntry {
+                    path: format!("projects/apollo/assets/textures/batch-{:03}/tile-{:05}.png", i / 100, i),
+                    size: 4096 + (i as u64 % 7) * 512,
+                    modified: 1_700_000_000 + i as u64,
+                })
+                .collect(),
+            skipped: Vec::new(),
+        };
+
+        let (encoded, flags) = manifest.encode()?;
+        assert_eq!(flags, FLAG_ZSTD);
+        assert_eq!(DirectoryManifest::decode(&encoded, flags)?, manifest);
+
+        let uncompressed = bcs::to_bytes(&manifest.entries)?;
+        assert!(encoded.len() * 4 < uncompressed.len(), "{} vs {} bytes", encoded.len(), uncompressed.len());
+
+        // Trees written before compression carry no flags
+        assert_eq!(DirectoryManifest::decode(&uncompressed, 0)?, manifest);
+        assert!(DirectoryManifest::decode(&uncompressed, FLAG_ZSTD).is_err());
+        assert!(DirectoryManifest::decode(&encoded, 0x80).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_skipped_files_recorded() -> Result<()> {
+        let mut manifest = DirectoryManifest {
+            entries: vec![DirectoryEntry { path: "notes.txt".to_string(), size: 12, modified: 1_700_000_000 }],
+            skipped: vec![
+                SkippedEntry {
+                    path: "media/talk.mp4".to_string(),
+                    size: 900_000_000,
+                    reason: SkipReason::TooLarge { limit: 100_000_000 },
+                },
+                SkippedEntry {
+                    path: "photos/cat.jpg".to_string(),
+                    size: 40_000,
+                    reason: SkipReason::ExcludedExtension("jpg".to_string()),
+                },
+            ],
+        };
+
+        let (encoded, flags) = manifest.encode()?;
+        assert_eq!(flags, FLAG_ZSTD | FLAG_SKIPPED);
+        assert_eq!(DirectoryManifest::decode(&encoded, flags)?, manifest);
+        assert!(DirectoryManifest::decode(&encoded, FLAG_ZSTD).is_err());
+
+        // Nothing skipped: the layout older builds read
+        manifest.skipped.clear();
+        let (encoded, flags) = manifest.encode()?;
+        assert_eq!(flags, FLAG_ZSTD);
+        assert_eq!(DirectoryManifest::decode(&encoded, flags)?, manifest);
+        Ok(())
+    }
+}
@@ -0,0 +1,100 @@
+//! Break-glass key escrow for CLI-encrypted files.
+//!
+//! An escrowed file is an envelope: the main Seal object is Plain mode and
+//! only wraps a data key, the data itself is sealed with AES-256-GCM under
+//! that key, and a second Seal object wraps the same key for the escrow
+//! identity. Either identity's keys are then enough to open the data, and
+//! the escrow identity never needs access to the main one.
+
+use anyhow::Result;
+use crypto::{EncryptedObject, EncryptionInput};
+use fastcrypto::aes::{Aes256Gcm, AesKey, AuthenticatedCipher, InitializationVector};
+use fastcrypto::hash::{HashFunction, Sha3_256};
+use fastcrypto::traits::ToFromBytes;
+use serde::{Deserialize, Serialize};
+use typenum::U12;
+
+use crate::session::SealSession;
+
+const ENVELOPE_KEY_DOMAIN: &[u8] = b"seal-demo/escrow-envelope-key/v1";
+
+#[derive(Serialize, Deserialize)]
+pub struct Envelope {
+    /// AES-256-GCM of the data under the key wrapped by the main object.
+    pub ciphertext: Vec<u8>,
+    pub aad: Option<Vec<u8>>,
+    /// The same data key, sealed to the escrow identity.
+    pub escrow: EncryptedObject,
+}
+
+fn data_cipher(data_key: &[u8]) -> Result<Aes256Gcm<U12>> {
+    let mut input = ENVELOPE_KEY_DOMAIN.to_vec();
+    input.extend_from_slice(data_key);
+    let key = AesKey::from_bytes(&Sha3_256::digest(&input).digest)
+        .map_err(|e| anyhow::anyhow!("Invalid envelope key: {}", e))?;
+    Ok(Aes256Gcm::new(key))
+}
+
+// Every data key seals exactly one message, so a fixed nonce is safe.
+fn envelope_nonce() -> Result<InitializationVector<U12>> {
+    InitializationVector::from_bytes(&[0u8; 12]).map_err(|e| anyhow::anyhow!("Invalid nonce: {}", e))
+}
+
+/// Encrypt `data` to `identity`, escrowing its key to `escrow_identity` under
+/// the same fleet. Returns the Plain-mode main object and the envelope.
+pub fn encrypt_with_escrow(
+    session: &SealSession,
+    identity: &[u8],
+    escrow_identity: &[u8],
+    data: &[u8],
+    aad: Option<Vec<u8>>,
+) -> Result<(EncryptedObject, Envelope)> {
+    let (encrypted_object, data_key) = session.encrypt(identity, EncryptionInput::Plain)?;
+    let (escrow, _) = session.encrypt(
+        escrow_identity,
+        EncryptionInput::Aes256Gcm {
+            data: data_key.to_vec(),
+            aad: None,
+        },
+    )?;
+
+    let ciphertext = data_cipher(&data_key)?.encrypt_authenticated(
+        &envelope_nonce()?,
+        aad.as_deref().unwrap_or_default(),
+        data,
+    );
+
+    Ok((encrypted_object, Envelope { ciphertext, aad, escrow }))
+}
+
+impl Envelope {
+    /// Decrypt the data with a key recovered from either Seal object.
+    pub fn open(&self, data_key: &[u8]) -> Result<Vec<u8>> {
+        data_cipher(data_key)?
+            .decrypt_authenticated(&envelope_nonce()?, self.aad.as_deref().unwrap_or_default(), &self.ciphertext)
+            .map_err(|_| anyhow::anyhow!("Envelope failed authentication (wrong key or tampered data)"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escrow_and_normal_paths() -> Result<()> {
+        let session = SealSession::generate(3, 2)?;
+        let (encrypted_object, envelope) =
+            encrypt_with_escrow(&session, b"file:payroll.csv", b"escrow:compliance", b"salaries", None)?;
+
+        // Normal path: the main identity's keys unwrap the data key
+        let data_key = session.decrypt(&encrypted_object)?;
+        assert_eq!(envelope.open(&data_key)?, b"salaries");
+
+        // Break-glass path: only the escrow identity's keys are used
+        let escrowed_key = session.decrypt(&envelope.escrow)?;
+        assert_eq!(envelope.open(&escrowed_key)?, b"salaries");
+
+        assert!(envelope.open(&[0u8; 32]).is_err());
+        Ok(())
+    }
+}
@@ -1,14 +1,135 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use base64::{prelude::BASE64_STANDARD, Engine};
 use crypto::{
-    ibe::{generate_key_pair, extract},
-    seal_encrypt, seal_decrypt,
-    create_full_id, EncryptionInput, IBEPublicKeys, IBEUserSecretKeys, ObjectID
+    ibe::{generate_key_pair, verify_user_secret_key},
+    create_full_id, Ciphertext, EncryptionInput, IBEPublicKeys, IBEUserSecretKeys, ObjectID
 };
-use fastcrypto::groups::bls12381::{G2Element, Scalar};
-use rand::thread_rng;
+use fastcrypto::groups::bls12381::{G1Element, G2Element, Scalar};
+use fastcrypto::hash::{HashFunction, Sha3_256};
+use fastcrypto::serde_helpers::ToFromByteArray;
+use fastcrypto::traits::AllowedRng;
+use rand::{thread_rng, RngCore};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::fs;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::atomic;
+use crate::container::{self, SealContainer};
+use crate::directory_manifest::{DirectoryEntry, DirectoryManifest, SkippedEntry};
+use crate::file_modes::{FileFilter, FileModes, FileTreatment};
+use crate::key_cache::KeyCache;
+use crate::lines;
+use crate::output::outln;
+use crate::report::OpCounter;
+use crate::session;
+use crate::shred;
+use crate::streaming::{self, Checkpoint, ChunkCipher, ChunkingStrategy, StreamHeader};
+use crate::testutil;
+
+/// Largest encrypted record `append_record` writes, and `read_log` expects.
+pub const MAX_LOG_RECORD: u32 = 16 * 1024 * 1024;
+
+/// Largest encrypted manifest `decrypt_directory` reads.
+const MAX_DIRECTORY_MANIFEST: u32 = 16 * 1024 * 1024;
+/// Name of the manifest file in a tree written by `encrypt_directory`.
+pub const DIRECTORY_MANIFEST: &str = "manifest.seal";
+
+/// Tries `cleanup` makes at removing the temp directory, and the pause
+/// between them.
+const CLEANUP_ATTEMPTS: usize = 5;
+const CLEANUP_RETRY_DELAY: Duration = Duration::from_millis(200);
+
+/// Demos created so far, numbering their temp directories so no two
+/// `FileDemo`s in one process share one, and one's cleanup can't delete
+/// another's files.
+static TEMP_DIRS: AtomicUsize = AtomicUsize::new(0);
+
+/// Default naming for encrypted outputs, see `output_path_for`.
+pub const DEFAULT_NAME_TEMPLATE: &str = "{stem}.{ext}.seal";
+/// Naming for decrypted copies written next to their originals.
+const DECRYPTED_NAME_TEMPLATE: &str = "{stem}.decrypted.{ext}";
+
+/// What a batch or directory operation did with each file, for
+/// `decrypt_batch_with_servers`, `decrypt_directory_parallel` and the
+/// directory and tree functions, which carry on past a file that fails.
+#[derive(Default)]
+pub struct BatchManifest {
+    pub entries: Vec<ManifestEntry>,
+}
+
+pub struct ManifestEntry {
+    pub input: PathBuf,
+    /// Plaintext bytes written, or why the file was skipped.
+    pub outcome: Result<usize, String>,
+}
+
+impl BatchManifest {
+    pub fn succeeded(&self) -> usize {
+        self.entries.len() - self.failed()
+    }
+    
+    pub fn failed(&self) -> usize {
+        self.entries.iter().filter(|entry| entry.outcome.is_err()).count()
+    }
+    
+    /// Plaintext bytes of the jobs that succeeded.
+    pub fn bytes(&self) -> usize {
+        self.entries.iter().filter_map(|entry| entry.outcome.as_ref().ok()).sum()
+    }
+}
+
+/// Where a batch operation is, for callers drawing their own progress. A
+/// file's start and finish are both reported; `files_done` counts the finish.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileProgress {
+    pub files_done: usize,
+    pub files_total: usize,
+    /// The file about to start or just finished.
+    pub current_path: PathBuf,
+    /// Plaintext bytes of the files finished so far.
+    pub bytes_done: u64,
+}
+
+/// The counts behind the `FileProgress` of one batch operation.
+struct ProgressTracker<'a> {
+    hook: Option<&'a dyn Fn(FileProgress)>,
+    files_done: usize,
+    files_total: usize,
+    bytes_done: u64,
+}
+
+impl<'a> ProgressTracker<'a> {
+    fn new(hook: Option<&'a dyn Fn(FileProgress)>, files_total: usize) -> Self {
+        ProgressTracker { hook, files_done: 0, files_total, bytes_done: 0 }
+    }
+    
+    fn started(&self, path: &Path) {
+        self.report(path);
+    }
+    
+    /// `bytes` is 0 for a file that failed or was skipped.
+    fn finished(&mut self, path: &Path, bytes: u64) {
+        self.files_done += 1;
+        self.bytes_done += bytes;
+        self.report(path);
+    }
+    
+    fn report(&self, path: &Path) {
+        if let Some(hook) = self.hook {
+            hook(FileProgress {
+                files_done: self.files_done,
+                files_total: self.files_total,
+                current_path: path.to_path_buf(),
+                bytes_done: self.bytes_done,
+            });
+        }
+    }
+}
 
 pub struct FileDemo {
     pub package_id: ObjectID,
@@ -17,40 +138,114 @@ pub struct FileDemo {
     pub key_servers: Vec<ObjectID>,
     pub threshold: u8,
     pub temp_dir: PathBuf,
+    /// Naming for encrypted outputs of the demos and batch operations.
+    pub name_template: String,
+    /// Rough cap on bytes buffered at once by streaming and batch
+    /// operations; `None` uses every core without limit.
+    pub max_memory: Option<usize>,
+    /// Checkpoint `encrypt_file_streaming` every this many chunks so an
+    /// interrupted run can resume; `None` writes the output in one go.
+    pub checkpoint_every: Option<u64>,
+    /// Mode and compression per file name in `encrypt_directory`.
+    pub file_modes: FileModes,
+    /// Files `encrypt_directory` leaves out.
+    pub file_filter: FileFilter,
+    /// Tallies operations across batch worker threads as well.
+    pub counter: OpCounter,
+}
+
+/// Progress of a checkpointed `encrypt_file_streaming`, kept in
+/// `<output>.checkpoint` while the frames go to `<output>.partial`.
+#[derive(Serialize, Deserialize)]
+struct ResumeState {
+    checkpoint: Checkpoint,
+    chunking: ChunkingStrategy,
+    /// Length and modification time (Unix seconds) of the input, so a
+    /// changed file is never resumed.
+    source_len: u64,
+    source_modified: u64,
 }
 
 impl FileDemo {
     pub fn new() -> Result<Self> {
-        println!("📁 Setting up file encryption demo...");
-        
-        // Create temporary directory for demo files
-        let temp_dir = std::env::temp_dir().join("seal-demo");
-        fs::create_dir_all(&temp_dir)?;
-        println!("   📂 Created temp directory: {}", temp_dir.display());
-        
         // Set up 3 key servers with threshold 2
+        Self::with_servers(3, 2)
+    }
+    
+    /// Generate a fresh fleet of `num_servers` key servers.
+    pub fn with_servers(num_servers: usize, threshold: u8) -> Result<Self> {
+        Self::with_servers_and_rng(num_servers, threshold, &mut thread_rng())
+    }
+    
+    /// `with_servers`, drawing the package ID, server IDs and key pairs from
+    /// `rng`.
+    pub fn with_servers_and_rng(num_servers: usize, threshold: u8, rng: &mut impl AllowedRng) -> Result<Self> {
+        outln!("📁 Setting up file encryption demo...");
+        
         let mut master_keys = Vec::new();
         let mut public_keys = Vec::new();
         let mut key_servers = Vec::new();
         
-        for i in 0..3 {
-            let (master_key, public_key) = generate_key_pair(&mut thread_rng());
-            let server_id = ObjectID::random();
+        for i in 0..num_servers {
+            let (master_key, public_key) = generate_key_pair(rng);
+            let server_id = session::random_object_id(rng);
             
             master_keys.push(master_key);
             public_keys.push(public_key);
             key_servers.push(server_id);
             
-            println!("   🔑 Created key server {}: {}", i + 1, server_id);
+            outln!("   🔑 Created key server {}: {}", i + 1, server_id);
         }
         
+        let mut demo = Self::with_fleet(master_keys, public_keys, key_servers, threshold)?;
+        demo.package_id = session::random_object_id(rng);
+        Ok(demo)
+    }
+    
+    /// Use an existing fleet instead of generating one. `master_keys[i]` and
+    /// `public_keys[i]` belong to server `key_servers[i]`. The package ID is
+    /// random; set `package_id` afterwards to decrypt files from earlier runs.
+    pub fn with_fleet(
+        master_keys: Vec<Scalar>,
+        public_keys: Vec<G2Element>,
+        key_servers: Vec<ObjectID>,
+        threshold: u8,
+    ) -> Result<Self> {
+        if master_keys.len() != key_servers.len() || public_keys.len() != key_servers.len() {
+            anyhow::bail!(
+                "Fleet needs one master key and public key per server, got {} master keys, {} public keys and {} servers",
+                master_keys.len(),
+                public_keys.len(),
+                key_servers.len()
+            );
+        }
+        if threshold == 0 || threshold as usize > key_servers.len() {
+            anyhow::bail!("Threshold {} is invalid for {} key servers", threshold, key_servers.len());
+        }
+        
+        // Create temporary directory for demo files, in RAM when shredding
+        let temp_base = if shred::enabled() { shred::temp_base() } else { std::env::temp_dir() };
+        let temp_dir = temp_base.join(format!(
+            "seal-demo-{}-{}",
+            std::process::id(),
+            TEMP_DIRS.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&temp_dir)?;
+        outln!("   📂 Created temp directory: {}", temp_dir.display());
+        
         Ok(FileDemo {
             package_id: ObjectID::random(),
             master_keys,
             public_keys,
             key_servers,
-            threshold: 2,
+            threshold,
             temp_dir,
+            name_template: DEFAULT_NAME_TEMPLATE.to_string(),
+            max_memory: None,
+            checkpoint_every: None,
+            file_modes: FileModes::default(),
+            file_filter: FileFilter::default(),
+            counter: OpCounter::default(),
         })
     }
     
@@ -63,128 +258,1423 @@ impl FileDemo {
             ("large_file.txt", &large_file_content),
         ];
         
-        let mut created_files = Vec::new();
+        let mut created_files = Vec::new();
+        
+        for (filename, content) in files {
+            let file_path = self.temp_dir.join(filename);
+            fs::write(&file_path, content)?;
+            created_files.push((file_path, filename.to_string()));
+            outln!("   📄 Created: {} ({} bytes)", filename, content.len());
+        }
+        
+        // Plus a binary file of random bytes
+        let random_path = testutil::random_file(&self.temp_dir, 2048, &mut thread_rng())?;
+        let random_name = random_path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        outln!("   📄 Created: {} (2048 random bytes)", random_name);
+        created_files.push((random_path, random_name));
+        
+        Ok(created_files)
+    }
+    
+    /// Name encrypted outputs with `template` instead of `DEFAULT_NAME_TEMPLATE`.
+    ///
+    /// The template must name each input differently, so it needs `{stem}`
+    /// or `{n}`, and it can't contain path separators. `{stem}` alone still
+    /// maps `a.txt` and `a.md` to the same name; batches refuse such a
+    /// collision rather than let one output overwrite the other.
+    pub fn with_name_template(mut self, template: &str) -> Result<Self> {
+        if !template.contains("{stem}") && !template.contains("{n}") {
+            anyhow::bail!("Name template {:?} needs {{stem}} or {{n}} to keep outputs apart", template);
+        }
+        if template.contains('/') || template.contains('\\') {
+            anyhow::bail!("Name template {:?} must be a file name, not a path", template);
+        }
+        self.name_template = template.to_string();
+        Ok(self)
+    }
+    
+    /// Keep streaming and batch operations under roughly `max_memory` bytes.
+    pub fn with_max_memory(mut self, max_memory: Option<usize>) -> Self {
+        self.max_memory = max_memory;
+        self
+    }
+    
+    /// Make `encrypt_file_streaming` resumable, checkpointing every `every`
+    /// chunks (see `encrypt_file_resumable`).
+    pub fn with_checkpoints(mut self, every: Option<u64>) -> Self {
+        self.checkpoint_every = every;
+        self
+    }
+    
+    /// Pick each file's mode and compression in `encrypt_directory` by
+    /// `file_modes` instead of the defaults.
+    pub fn with_file_modes(mut self, file_modes: FileModes) -> Self {
+        self.file_modes = file_modes;
+        self
+    }
+    
+    /// Leave the files `file_filter` rejects out of `encrypt_directory`.
+    pub fn with_file_filter(mut self, file_filter: FileFilter) -> Self {
+        self.file_filter = file_filter;
+        self
+    }
+    
+    /// Tally operations on `counter` (see `report::measure`).
+    pub fn with_counter(mut self, counter: OpCounter) -> Self {
+        self.counter = counter;
+        self
+    }
+    
+    pub fn encrypt_file(&self, file_path: &Path, output_path: &Path) -> Result<()> {
+        outln!("🔒 Encrypting file: {}", file_path.display());
+        
+        // Read file content
+        let file_content = fs::read(file_path)?;
+        outln!("   📊 File size: {} bytes", file_content.len());
+        
+        // Use filename as identity
+        let identity = file_identity(file_path);
+        
+        outln!("   🆔 Identity: \"{}\"", String::from_utf8_lossy(&identity));
+        
+        // Encrypt the file and save the encrypted object
+        let mut encrypted_size = 0;
+        let mut symmetric_key = [0u8; 32];
+        atomic::write_atomically(output_path, |writer| {
+            (encrypted_size, symmetric_key) = self.encrypt_to_sink(file_content, identity, writer)?;
+            Ok(())
+        })?;
+        
+        // Also save symmetric key for reference
+        let key_path = output_path.with_extension("key");
+        atomic::write_file(&key_path, hex::encode(symmetric_key).as_bytes())?;
+        
+        outln!("   ✅ Encrypted to: {}", output_path.display());
+        outln!("   🔑 Symmetric key saved to: {}", key_path.display());
+        outln!("   📏 Encrypted size: {} bytes", encrypted_size);
+        
+        Ok(())
+    }
+    
+    /// Encrypt `data` to `identity` and write it to `sink`, which can be
+    /// anything from a file to an upload stream, as a `SealContainer`
+    /// recording this fleet's servers and public keys. Returns the bytes
+    /// written and the symmetric key.
+    pub fn encrypt_to_sink(&self, data: Vec<u8>, identity: Vec<u8>, sink: &mut dyn Write) -> Result<(usize, [u8; 32])> {
+        let (encrypted_object, symmetric_key) = self.counter.seal_encrypt(
+            self.package_id,
+            identity,
+            self.key_servers.clone(),
+            &IBEPublicKeys::BonehFranklinBLS12381(self.public_keys.clone()),
+            self.threshold,
+            EncryptionInput::Aes256Gcm { data, aad: None },
+        )?;
+        
+        let encrypted_data = self.container_for(encrypted_object).to_bytes()?;
+        sink.write_all(&encrypted_data)?;
+        sink.flush()?;
+        Ok((encrypted_data.len(), symmetric_key))
+    }
+    
+    pub fn decrypt_file(&self, encrypted_path: &Path, output_path: &Path) -> Result<()> {
+        outln!("🔓 Decrypting file: {}", encrypted_path.display());
+        
+        // Read encrypted object
+        let encrypted_data = fs::read(encrypted_path)?;
+        let container = SealContainer::from_bytes(&encrypted_data)?;
+        let encrypted_object = &container.encrypted_object;
+        
+        outln!("   📊 Encrypted size: {} bytes", encrypted_data.len());
+        outln!("   🔢 Threshold: {}", encrypted_object.threshold);
+        
+        // Extract the filename from the identity
+        let identity_str = String::from_utf8_lossy(&encrypted_object.id);
+        outln!("   🆔 Identity: \"{}\"", identity_str);
+        
+        // The recorded keys and fingerprint are whatever the file's writer put
+        // there, so they count only if the fingerprint is this fleet's
+        let fleet = container::fleet_fingerprint(&self.key_servers, &self.public_keys);
+        if let Some(recorded) = container.fingerprint.filter(|recorded| *recorded != fleet) {
+            anyhow::bail!(
+                "❌ File was encrypted to fleet {} but this is fleet {}; refusing to trust its recorded public keys",
+                container::format_fingerprint(&recorded),
+                container::format_fingerprint(&fleet)
+            );
+        }
+        let public_keys = match container.public_keys()? {
+            Some(public_keys) => {
+                let fingerprint = container.fingerprint.as_ref().map(container::format_fingerprint).unwrap_or_default();
+                outln!("   📇 Public keys: from the file (fleet {}, this one)", fingerprint);
+                public_keys
+            }
+            None => {
+                outln!("   📇 Public keys: not in the file, using this fleet's");
+                self.public_keys.clone()
+            }
+        };
+        
+        // Decrypt with keys from the object's own threshold of servers
+        let decrypted_data = container.decrypt(|object| {
+            let servers: Vec<usize> = (0..object.threshold as usize).collect();
+            for i in &servers {
+                outln!("   🔑 Using key from server {}", i + 1);
+            }
+            self.unwrap_with_keys(object, &servers, &public_keys, |i, full_id| {
+                self.counter.extract(&self.master_keys[i], full_id)
+            })
+        })?;
+        
+        // Write decrypted file
+        atomic::write_file(output_path, &decrypted_data)?;
+        
+        outln!("   ✅ Decrypted to: {}", output_path.display());
+        outln!("   📏 Original size: {} bytes", decrypted_data.len());
+        
+        Ok(())
+    }
+    
+    /// Encrypt many `(input, output)` pairs, several files at a time.
+    ///
+    /// A file in flight costs about twice its size (plaintext plus
+    /// ciphertext), so files are sealed in consecutive groups that fit
+    /// `max_memory` and the core count; a file larger than the budget on its
+    /// own still goes through, alone. Outputs are written atomically and
+    /// reported in input order. Unlike `encrypt_file`, no `.key` sidecar is
+    /// written. `progress`, if given, hears of each file as its group starts
+    /// and again as it is written.
+    pub fn encrypt_batch(&self, jobs: &[(PathBuf, PathBuf)], progress: Option<&dyn Fn(FileProgress)>) -> Result<()> {
+        check_distinct_outputs(jobs)?;
+        let sizes = jobs
+            .iter()
+            .map(|(input, _)| Ok(fs::metadata(input)?.len()))
+            .collect::<Result<Vec<u64>>>()?;
+        let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+        let mut progress = ProgressTracker::new(progress, jobs.len());
+        
+        for group in batch_groups(&sizes, self.max_memory, cores) {
+            outln!("   🧵 Sealing {} file(s) concurrently", group.len());
+            for (input, _) in &jobs[group.clone()] {
+                progress.started(input);
+            }
+            let sealed: Vec<Result<Vec<u8>>> = std::thread::scope(|scope| {
+                let handles: Vec<_> = jobs[group.clone()]
+                    .iter()
+                    .map(|(input, _)| scope.spawn(move || self.seal_file(input)))
+                    .collect();
+                handles
+                    .into_iter()
+                    .map(|handle| handle.join().unwrap_or_else(|_| Err(anyhow::anyhow!("Batch worker panicked"))))
+                    .collect()
+            });
+            
+            for (((input, output), sealed), size) in jobs[group.clone()].iter().zip(sealed).zip(&sizes[group]) {
+                let sealed = sealed.with_context(|| format!("Failed to encrypt {}", input.display()))?;
+                atomic::write_file(output, &sealed)?;
+                outln!("   ✅ {} -> {} ({} bytes)", input.display(), output.display(), sealed.len());
+                progress.finished(input, *size);
+            }
+        }
+        
+        Ok(())
+    }
+    
+    /// Decrypt many `(input, output)` pairs, extracting each server's key for
+    /// an identity only once across the whole batch (see `KeyCache`).
+    pub fn decrypt_batch(&self, jobs: &[(PathBuf, PathBuf)]) -> Result<()> {
+        self.decrypt_batch_with_cache(jobs, &mut KeyCache::new())
+    }
+    
+    /// `decrypt_batch` with a caller-owned cache, whose counts then tell how
+    /// many keys the batch extracted and reused.
+    pub fn decrypt_batch_with_cache(&self, jobs: &[(PathBuf, PathBuf)], cache: &mut KeyCache) -> Result<()> {
+        check_distinct_outputs(jobs)?;
+        
+        for (input, output) in jobs {
+            let container = SealContainer::from_bytes(&fs::read(input)?)
+                .with_context(|| format!("Failed to read {}", input.display()))?;
+            let decrypted = container.decrypt(|object| {
+                let servers: Vec<usize> = (0..object.threshold as usize).collect();
+                self.unwrap_with_cache(object, &servers, cache)
+            })?;
+            atomic::write_file(output, &decrypted)?;
+            outln!("   ✅ {} -> {} ({} bytes)", input.display(), output.display(), decrypted.len());
+        }
+        
+        outln!("   🗝️  Key cache: {} keys extracted, {} reused", cache.extracted(), cache.hits());
+        Ok(())
+    }
+    
+    /// Decrypt a batch using only the key servers at the given fleet indices,
+    /// e.g. because the rest are down. Files the subset can't open (too few
+    /// of its servers, or any other error) are recorded in the manifest and
+    /// skipped, so the job finishes everything it can.
+    pub fn decrypt_batch_with_servers(&self, jobs: &[(PathBuf, PathBuf)], servers: &[usize]) -> Result<BatchManifest> {
+        check_distinct_outputs(jobs)?;
+        if let Some(&index) = servers.iter().find(|&&index| index >= self.key_servers.len()) {
+            anyhow::bail!("Server index {} out of range, the fleet has {} servers", index, self.key_servers.len());
+        }
+        let mut servers = servers.to_vec();
+        servers.sort_unstable();
+        servers.dedup();
+        
+        let mut cache = KeyCache::new();
+        let mut manifest = BatchManifest::default();
+        
+        for (input, output) in jobs {
+            let outcome = (|| -> Result<usize> {
+                let container = SealContainer::from_bytes(&fs::read(input)?)?;
+                let decrypted = container.decrypt(|object| {
+                    let threshold = object.threshold as usize;
+                    let available: Vec<usize> = servers
+                        .iter()
+                        .copied()
+                        .filter(|&i| object.services.iter().any(|(id, _)| *id == self.key_servers[i]))
+                        .take(threshold)
+                        .collect();
+                    if available.len() < threshold {
+                        anyhow::bail!("Needs {} servers, only {} of the available ones can serve it", threshold, available.len());
+                    }
+                    self.unwrap_with_cache(object, &available, &mut cache)
+                })?;
+                atomic::write_file(output, &decrypted)?;
+                Ok(decrypted.len())
+            })();
+            
+            match &outcome {
+                Ok(size) => outln!("   ✅ {} -> {} ({} bytes)", input.display(), output.display(), size),
+                Err(e) => outln!("   ⏭️  {}: skipped, {}", input.display(), e),
+            }
+            manifest.entries.push(ManifestEntry {
+                input: input.clone(),
+                outcome: outcome.map_err(|e| format!("{:#}", e)),
+            });
+        }
+        
+        outln!("   📋 {} decrypted, {} skipped", manifest.entries.len() - manifest.failed(), manifest.failed());
+        Ok(manifest)
+    }
+    
+    /// Decrypt every file directly inside `src` into `dst`, several at a time
+    /// like `encrypt_batch` (same memory and core budget). `name.seal`
+    /// becomes `name`, other names are kept. Workers share one key cache.
+    /// A file that fails is recorded in the manifest and the rest carry on.
+    pub fn decrypt_directory_parallel(&self, src: &Path, dst: &Path) -> Result<BatchManifest> {
+        fs::create_dir_all(dst)?;
+        if fs::canonicalize(src)? == fs::canonicalize(dst)? {
+            anyhow::bail!("Source and destination must be different directories");
+        }
+        
+        let mut inputs = Vec::new();
+        for entry in fs::read_dir(src)? {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                inputs.push((entry.path(), entry.metadata()?.len()));
+            }
+        }
+        inputs.sort();
+        
+        let sizes: Vec<u64> = inputs.iter().map(|(_, size)| *size).collect();
+        let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+        let cache = Mutex::new(KeyCache::new());
+        let mut manifest = BatchManifest::default();
+        
+        for group in batch_groups(&sizes, self.max_memory, cores) {
+            outln!("   🧵 Opening {} file(s) concurrently", group.len());
+            let outcomes: Vec<Result<usize>> = std::thread::scope(|scope| {
+                let handles: Vec<_> = inputs[group.clone()]
+                    .iter()
+                    .map(|(input, _)| {
+                        let cache = &cache;
+                        scope.spawn(move || self.open_into(input, &dst.join(decrypted_name(input)), cache))
+                    })
+                    .collect();
+                handles
+                    .into_iter()
+                    .map(|handle| handle.join().unwrap_or_else(|_| Err(anyhow::anyhow!("Decrypt worker panicked"))))
+                    .collect()
+            });
+            
+            for ((input, _), outcome) in inputs[group].iter().zip(outcomes) {
+                match &outcome {
+                    Ok(size) => outln!("   ✅ {} ({} bytes)", input.display(), size),
+                    Err(e) => outln!("   ❌ {}: {:#}", input.display(), e),
+                }
+                manifest.entries.push(ManifestEntry {
+                    input: input.clone(),
+                    outcome: outcome.map_err(|e| format!("{:#}", e)),
+                });
+            }
+        }
+        
+        let cache = cache.into_inner().unwrap_or_else(|poisoned| poisoned.into_inner());
+        outln!(
+            "   📋 {} decrypted, {} failed; {} keys extracted, {} reused",
+            manifest.entries.len() - manifest.failed(),
+            manifest.failed(),
+            cache.extracted(),
+            cache.hits()
+        );
+        Ok(manifest)
+    }
+    
+    /// One `decrypt_directory_parallel` job. The cache lock is only held for
+    /// lookups, never while extracting.
+    fn open_into(&self, input: &Path, output: &Path, cache: &Mutex<KeyCache>) -> Result<usize> {
+        let container = SealContainer::from_bytes(&fs::read(input)?)?;
+        let decrypted = container.decrypt(|object| {
+            let servers: Vec<usize> = (0..object.threshold as usize).collect();
+            self.unwrap_with_keys(object, &servers, &self.public_keys, |i, full_id| {
+                let server = self.key_servers[i];
+                let cached = cache.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).get(server, full_id);
+                cached.unwrap_or_else(|| {
+                    let key = self.counter.extract(&self.master_keys[i], full_id);
+                    cache.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).insert(server, full_id, key);
+                    key
+                })
+            })
+        })?;
+        atomic::write_file(output, &decrypted)?;
+        Ok(decrypted.len())
+    }
+    
+    /// Encrypt every file under `src` (recursively) into `dst` without
+    /// revealing file names or nesting. Files become `<n>.seal` in one flat
+    /// directory, sealed to `dir:<tree id>:<n>` with a random tree ID, so
+    /// neither names nor identities say where they came from. The number of
+    /// files and each one's ciphertext size still show. Paths, sizes
+    /// and modification times go into a compressed `DirectoryManifest`,
+    /// sealed to `dir:<tree id>` and stored as the header of `manifest.seal`.
+    /// Each file is sealed and compressed as `file_modes` says for its name.
+    /// Files `file_filter` rejects are not encrypted; the manifest lists them
+    /// with the reason. A file that can't be read or sealed is recorded as
+    /// failed in the returned manifest, left out of the tree, and the rest
+    /// carry on; filtered files are not listed there. `progress` is called as
+    /// each file starts and finishes, failed ones included.
+    pub fn encrypt_directory(
+        &self,
+        src: &Path,
+        dst: &Path,
+        progress: Option<&dyn Fn(FileProgress)>,
+    ) -> Result<BatchManifest> {
+        fs::create_dir_all(dst)?;
+        if fs::canonicalize(src)? == fs::canonicalize(dst)? {
+            anyhow::bail!("Source and destination must be different directories");
+        }
+        
+        let mut tree_id = [0u8; 16];
+        thread_rng().fill_bytes(&mut tree_id);
+        let tree_id = hex::encode(tree_id);
+        
+        let mut manifest = DirectoryManifest { entries: Vec::new(), skipped: Vec::new() };
+        let mut summary = BatchManifest::default();
+        let files = files_under(src)?;
+        let mut progress = ProgressTracker::new(progress, files.len());
+        for (input, size) in &files {
+            progress.started(input);
+            let path = input
+                .strip_prefix(src)?
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            if let Some(reason) = self.file_filter.skip_reason(input, *size) {
+                outln!("   ⏭️  Skipping {}: {}", path, reason);
+                manifest.skipped.push(SkippedEntry { path, size: *size, reason });
+                progress.finished(input, 0);
+                continue;
+            }
+            
+            // The next number is only taken once the file is written, so a
+            // failure leaves no gap for `decrypt_directory` to trip over
+            let index = manifest.entries.len();
+            let outcome = (|| -> Result<DirectoryEntry> {
+                let modified = fs::metadata(input)?.modified()?.duration_since(UNIX_EPOCH).map_or(0, |age| age.as_secs());
+                let data = fs::read(input)?;
+                let size = data.len() as u64;
+                let treatment = self.file_modes.treatment_for(input);
+                let sealed = self.seal_input(directory_identity(&tree_id, Some(index)), treatment.input(data)?)?;
+                atomic::write_file(&dst.join(directory_file_name(index)), &sealed)?;
+                Ok(DirectoryEntry { path: path.clone(), size, modified })
+            })();
+            let outcome = match outcome {
+                Ok(entry) => {
+                    let size = entry.size as usize;
+                    manifest.entries.push(entry);
+                    Ok(size)
+                }
+                Err(e) => {
+                    outln!("   ❌ {}: {:#}", path, e);
+                    Err(format!("{:#}", e))
+                }
+            };
+            progress.finished(input, *outcome.as_ref().unwrap_or(&0) as u64);
+            summary.entries.push(ManifestEntry { input: input.clone(), outcome });
+        }
+        
+        let (encoded, flags) = manifest.encode()?;
+        let sealed = self.seal_bytes(directory_identity(&tree_id, None), encoded)?;
+        let mut header = Vec::new();
+        streaming::write_frame(&mut header, &sealed)?;
+        streaming::write_frame(&mut header, &[flags])?;
+        atomic::write_file(&dst.join(DIRECTORY_MANIFEST), &header)?;
+        
+        outln!("   ✅ {} file(s) sealed into {} behind an encrypted manifest", manifest.entries.len(), dst.display());
+        if !manifest.skipped.is_empty() {
+            outln!("   ⏭️  {} file(s) skipped and listed in the manifest", manifest.skipped.len());
+        }
+        if summary.failed() > 0 {
+            outln!("   ❌ {} file(s) failed and were left out", summary.failed());
+        }
+        Ok(summary)
+    }
+    
+    /// Restore a tree written by `encrypt_directory` into `dst`. The manifest
+    /// is opened first and drives everything else: which `<n>.seal` files to
+    /// read, where each goes, its expected size and its modification time.
+    /// An unreadable manifest fails the whole call; a file that can't be
+    /// restored is recorded as failed in the returned manifest and the rest
+    /// carry on.
+    pub fn decrypt_directory(&self, src: &Path, dst: &Path) -> Result<BatchManifest> {
+        let manifest_path = src.join(DIRECTORY_MANIFEST);
+        let mut reader = BufReader::new(
+            File::open(&manifest_path).with_context(|| format!("Failed to open {}", manifest_path.display()))?,
+        );
+        let header = streaming::read_frame(&mut reader, MAX_DIRECTORY_MANIFEST)?
+            .ok_or_else(|| anyhow::anyhow!("Empty directory manifest"))?;
+        let manifest_object: crypto::EncryptedObject = bcs::from_bytes(&header).context("Corrupt directory manifest")?;
+        let flags = match streaming::read_frame(&mut reader, 1)? {
+            Some(flags) => match flags[..] {
+                [flags] => flags,
+                _ => anyhow::bail!("Corrupt directory manifest header"),
+            },
+            None => 0,
+        };
+        let tree_id = String::from_utf8_lossy(&manifest_object.id)
+            .strip_prefix("dir:")
+            .filter(|tree_id| !tree_id.contains(':'))
+            .map(str::to_string)
+            .ok_or_else(|| anyhow::anyhow!("Not a directory manifest"))?;
+        let manifest = DirectoryManifest::decode(
+            &self
+                .unwrap_with_servers(&manifest_object, manifest_object.threshold as usize)
+                .context("Failed to decrypt the directory manifest")?,
+            flags,
+        )?;
+        
+        // A hostile path stops the restore before anything is written
+        for entry in &manifest.entries {
+            if !Path::new(&entry.path).components().all(|component| matches!(component, std::path::Component::Normal(_))) {
+                anyhow::bail!("Manifest path \"{}\" escapes the output directory", entry.path);
+            }
+        }
+        
+        let mut summary = BatchManifest::default();
+        for (index, entry) in manifest.entries.iter().enumerate() {
+            let input = src.join(directory_file_name(index));
+            let outcome = (|| -> Result<usize> {
+                let encrypted_object: crypto::EncryptedObject = bcs::from_bytes(&fs::read(&input)?)
+                    .with_context(|| format!("Corrupt {}", input.display()))?;
+                if encrypted_object.id != directory_identity(&tree_id, Some(index)) {
+                    anyhow::bail!("{} does not belong at position {} of this tree", input.display(), index);
+                }
+                let plaintext = self.unwrap_with_servers(&encrypted_object, encrypted_object.threshold as usize)?;
+                let plaintext = FileTreatment::of(&encrypted_object)?.restore(plaintext, entry.size)?;
+                if plaintext.len() as u64 != entry.size {
+                    anyhow::bail!("{} is {} bytes, the manifest says {}", entry.path, plaintext.len(), entry.size);
+                }
+                
+                let output = dst.join(&entry.path);
+                if let Some(parent) = output.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                atomic::write_file(&output, &plaintext)?;
+                File::options()
+                    .write(true)
+                    .open(&output)?
+                    .set_modified(UNIX_EPOCH + Duration::from_secs(entry.modified))?;
+                Ok(plaintext.len())
+            })();
+            match &outcome {
+                Ok(size) => outln!("   ✅ {} ({} bytes)", entry.path, size),
+                Err(e) => outln!("   ❌ {}: {:#}", entry.path, e),
+            }
+            summary.entries.push(ManifestEntry { input, outcome: outcome.map_err(|e| format!("{:#}", e)) });
+        }
+        for skipped in &manifest.skipped {
+            outln!("   ⏭️  {} was not encrypted ({} bytes, {})", skipped.path, skipped.size, skipped.reason);
+        }
+        
+        Ok(summary)
+    }
+    
+    /// Encrypt every regular file under `src` (recursively) to the same
+    /// relative path under `dst` plus `.seal`, sealed to `file:<path>` with
+    /// the `/`-separated path relative to `src`. Unlike `encrypt_directory`,
+    /// names and layout stay visible; in exchange each file can be decrypted
+    /// on its own. Symlinks are not followed. A file that fails is recorded in
+    /// the manifest and the rest carry on. `progress` works as for
+    /// `encrypt_directory`.
+    pub fn encrypt_tree(
+        &self,
+        src: &Path,
+        dst: &Path,
+        progress: Option<&dyn Fn(FileProgress)>,
+    ) -> Result<BatchManifest> {
+        fs::create_dir_all(dst)?;
+        if fs::canonicalize(src)? == fs::canonicalize(dst)? {
+            anyhow::bail!("Source and destination must be different directories");
+        }
+        
+        let mut manifest = BatchManifest::default();
+        let files = files_under(src)?;
+        let mut progress = ProgressTracker::new(progress, files.len());
+        for (input, _) in files {
+            progress.started(&input);
+            let outcome = (|| {
+                let relative = input.strip_prefix(src)?;
+                let output = sidecar_path(&dst.join(relative), "seal");
+                if let Some(parent) = output.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                let data = fs::read(&input)?;
+                let size = data.len();
+                atomic::write_file(&output, &self.seal_container(tree_identity(relative), data)?)?;
+                Ok::<_, anyhow::Error>(size)
+            })();
+            match &outcome {
+                Ok(size) => outln!("   ✅ {} ({} bytes)", input.display(), size),
+                Err(e) => outln!("   ❌ {}: {:#}", input.display(), e),
+            }
+            progress.finished(&input, *outcome.as_ref().unwrap_or(&0) as u64);
+            manifest.entries.push(ManifestEntry { input, outcome: outcome.map_err(|e| format!("{:#}", e)) });
+        }
+        
+        outln!(
+            "   📋 {} encrypted ({} bytes), {} failed",
+            manifest.entries.len() - manifest.failed(),
+            manifest.bytes(),
+            manifest.failed()
+        );
+        Ok(manifest)
+    }
+    
+    /// Restore a tree written by `encrypt_tree`: every `.seal` file under
+    /// `src` is decrypted to its path under `dst` without the suffix. A file
+    /// whose identity doesn't match its path (moved or renamed since) is
+    /// refused. Failures are recorded in the manifest as in `encrypt_tree`.
+    pub fn decrypt_tree(&self, src: &Path, dst: &Path) -> Result<BatchManifest> {
+        let mut manifest = BatchManifest::default();
+        for (input, _) in files_under(src)? {
+            if input.extension().is_none_or(|ext| ext != "seal") {
+                continue;
+            }
+            let outcome = (|| {
+                let relative = input.strip_prefix(src)?.with_extension("");
+                let container = SealContainer::from_bytes(&fs::read(&input)?)?;
+                if container.encrypted_object.id != tree_identity(&relative) {
+                    anyhow::bail!(
+                        "Sealed to \"{}\", not to its path",
+                        String::from_utf8_lossy(&container.encrypted_object.id)
+                    );
+                }
+                let plaintext = container.decrypt(|object| self.unwrap_with_servers(object, object.threshold as usize))?;
+                let output = dst.join(&relative);
+                if let Some(parent) = output.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                atomic::write_file(&output, &plaintext)?;
+                Ok(plaintext.len())
+            })();
+            match &outcome {
+                Ok(size) => outln!("   ✅ {} ({} bytes)", input.display(), size),
+                Err(e) => outln!("   ❌ {}: {:#}", input.display(), e),
+            }
+            manifest.entries.push(ManifestEntry { input, outcome: outcome.map_err(|e| format!("{:#}", e)) });
+        }
+        
+        outln!(
+            "   📋 {} decrypted ({} bytes), {} failed",
+            manifest.entries.len() - manifest.failed(),
+            manifest.bytes(),
+            manifest.failed()
+        );
+        Ok(manifest)
+    }
+    
+    /// The container `encrypt_file` would write, without printing.
+    fn seal_file(&self, file_path: &Path) -> Result<Vec<u8>> {
+        self.seal_container(file_identity(file_path), fs::read(file_path)?)
+    }
+    
+    /// `data` sealed to `identity` with AES-256-GCM, in a container recording
+    /// this fleet.
+    fn seal_container(&self, identity: Vec<u8>, data: Vec<u8>) -> Result<Vec<u8>> {
+        let encrypted_object = self.seal_object(identity, EncryptionInput::Aes256Gcm { data, aad: None })?;
+        self.container_for(encrypted_object).to_bytes()
+    }
+    
+    fn container_for(&self, encrypted_object: crypto::EncryptedObject) -> SealContainer {
+        SealContainer::for_fleet(encrypted_object, &self.key_servers, &self.public_keys)
+    }
+    
+    /// `data` sealed to `identity` with AES-256-GCM and serialized.
+    fn seal_bytes(&self, identity: Vec<u8>, data: Vec<u8>) -> Result<Vec<u8>> {
+        self.seal_input(identity, EncryptionInput::Aes256Gcm { data, aad: None })
+    }
+    
+    /// `input` sealed to `identity` and serialized.
+    fn seal_input(&self, identity: Vec<u8>, input: EncryptionInput) -> Result<Vec<u8>> {
+        Ok(bcs::to_bytes(&self.seal_object(identity, input)?)?)
+    }
+    
+    /// `input` sealed to `identity` across this fleet.
+    fn seal_object(&self, identity: Vec<u8>, input: EncryptionInput) -> Result<crypto::EncryptedObject> {
+        let (encrypted_object, _) = self.counter.seal_encrypt(
+            self.package_id,
+            identity,
+            self.key_servers.clone(),
+            &IBEPublicKeys::BonehFranklinBLS12381(self.public_keys.clone()),
+            self.threshold,
+            input,
+        )?;
+        Ok(encrypted_object)
+    }
+    
+    /// Decrypt an object using user secret keys from the first `num_servers`
+    /// key servers of this demo's fleet.
+    pub fn decrypt_with_servers(&self, encrypted_object: &crypto::EncryptedObject, num_servers: usize) -> Result<Vec<u8>> {
+        if num_servers > self.key_servers.len() {
+            anyhow::bail!("Requested {} servers but the fleet only has {}", num_servers, self.key_servers.len());
+        }
+        
+        for i in 0..num_servers {
+            outln!("   🔑 Using key from server {}", i + 1);
+        }
+        
+        self.unwrap_with_servers(encrypted_object, num_servers)
+    }
+    
+    /// Decrypt with user secret keys as they arrive from remote key servers:
+    /// compressed G1 points (48 bytes, `to_byte_array`) rather than keys
+    /// extracted locally. Every key is checked against its server's public key
+    /// first, so a bad response is blamed on the server that sent it.
+    pub fn decrypt_with_serialized_keys(
+        &self,
+        encrypted_object: &crypto::EncryptedObject,
+        keys: &[(ObjectID, Vec<u8>)],
+    ) -> Result<Vec<u8>> {
+        let full_id = create_full_id(&encrypted_object.package_id, &encrypted_object.id);
+        
+        let mut user_secret_keys = HashMap::new();
+        for (server_id, bytes) in keys {
+            let Some(index) = self.key_servers.iter().position(|id| id == server_id) else {
+                anyhow::bail!("Key from unknown server {}", server_id);
+            };
+            let array = bytes.as_slice().try_into().map_err(|_| {
+                anyhow::anyhow!("Key from server {} must be 48 bytes, got {}", server_id, bytes.len())
+            })?;
+            let user_secret_key = G1Element::from_byte_array(&array)
+                .map_err(|e| anyhow::anyhow!("Key from server {} is malformed: {}", server_id, e))?;
+            verify_user_secret_key(&user_secret_key, &full_id, &self.public_keys[index])
+                .map_err(|_| anyhow::anyhow!("Key from server {} does not verify for this object", server_id))?;
+            user_secret_keys.insert(*server_id, user_secret_key);
+        }
+        
+        Ok(self.counter.seal_decrypt(
+            encrypted_object,
+            &IBEUserSecretKeys::BonehFranklinBLS12381(user_secret_keys),
+            Some(&IBEPublicKeys::BonehFranklinBLS12381(self.public_keys.clone())),
+        )?)
+    }
+    
+    /// Same as `decrypt_with_servers` but silent, for callers whose stdout may
+    /// be carrying the decrypted data itself.
+    fn unwrap_with_servers(&self, encrypted_object: &crypto::EncryptedObject, num_servers: usize) -> Result<Vec<u8>> {
+        if num_servers > self.key_servers.len() {
+            anyhow::bail!("Requested {} servers but the fleet only has {}", num_servers, self.key_servers.len());
+        }
+        
+        let servers: Vec<usize> = (0..num_servers).collect();
+        self.unwrap_with_cache(encrypted_object, &servers, &mut KeyCache::new())
+    }
+    
+    /// Decrypt with keys from the given fleet indices only, taking user
+    /// secret keys from `cache` when an earlier object already needed them.
+    fn unwrap_with_cache(
+        &self,
+        encrypted_object: &crypto::EncryptedObject,
+        servers: &[usize],
+        cache: &mut KeyCache,
+    ) -> Result<Vec<u8>> {
+        self.unwrap_with_keys(encrypted_object, servers, &self.public_keys, |i, full_id| {
+            cache.get_or_extract(self.key_servers[i], full_id, || self.counter.extract(&self.master_keys[i], full_id))
+        })
+    }
+    
+    /// Decrypt with the user secret keys `user_secret_key(i, full_id)` returns
+    /// for each of the given fleet indices, checked against `public_keys`
+    /// (one per server of the object, in its order).
+    fn unwrap_with_keys(
+        &self,
+        encrypted_object: &crypto::EncryptedObject,
+        servers: &[usize],
+        public_keys: &[G2Element],
+        mut user_secret_key: impl FnMut(usize, &[u8]) -> G1Element,
+    ) -> Result<Vec<u8>> {
+        // Create full identity
+        let full_id = create_full_id(&encrypted_object.package_id, &encrypted_object.id);
+        
+        // Get user secret keys from each of the given servers
+        let mut user_secret_keys = HashMap::new();
+        for &i in servers {
+            user_secret_keys.insert(self.key_servers[i], user_secret_key(i, &full_id));
+        }
+        
+        let decrypted_data = self.counter.seal_decrypt(
+            encrypted_object,
+            &IBEUserSecretKeys::BonehFranklinBLS12381(user_secret_keys),
+            Some(&IBEPublicKeys::BonehFranklinBLS12381(public_keys.to_vec())),
+        )?;
+        
+        Ok(decrypted_data)
+    }
+    
+    /// Encrypt a file in chunks cut by `chunking` without loading it into
+    /// memory.
+    ///
+    /// One Seal key is derived in Plain mode and wrapped in the container
+    /// header; each chunk is then sealed with AES-256-GCM under that key.
+    /// Chunks are sealed in parallel on every available core, unless
+    /// `max_memory` is set: each worker holds a chunk and its ciphertext, so
+    /// the largest chunk shrinks to fit the budget and workers are capped at
+    /// however many chunk pairs it allows.
+    ///
+    /// With `checkpoint_every` set, chunks are sealed sequentially through
+    /// `encrypt_file_resumable` instead.
+    pub fn encrypt_file_streaming(&self, file_path: &Path, output_path: &Path, chunking: ChunkingStrategy) -> Result<()> {
+        let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+        let (chunking, workers) = match self.max_memory {
+            Some(budget) => {
+                let chunking = chunking.within(budget / 2);
+                (chunking, cores.min(budget / (2 * chunking.max_chunk())).max(1))
+            }
+            None => (chunking, cores),
+        };
+        match self.checkpoint_every {
+            Some(every) => {
+                let mut source = BufReader::new(File::open(file_path)?);
+                self.encrypt_file_resumable(file_path, &mut source, output_path, chunking, every)
+            }
+            None => self.encrypt_file_streaming_with_workers(file_path, output_path, chunking, workers),
+        }
+    }
+    
+    /// Stream-encrypt `file_path`, read through `source`, so that a crash or
+    /// error part way can be resumed.
+    ///
+    /// Frames go to `<output>.partial`, and every `every` chunks it is synced
+    /// and the progress recorded in `<output>.checkpoint`. Run again with the
+    /// same input, output and chunking, this picks up at the last checkpoint:
+    /// the partial output is cut back to it, the key is unwrapped from its
+    /// header, and only the remaining chunks are read and sealed. The result
+    /// is renamed to `output_path` once complete.
+    ///
+    /// The checkpoint holds no secrets. A checkpoint for a file that has
+    /// changed since is refused; delete it to start over.
+    pub fn encrypt_file_resumable<R: Read + Seek>(
+        &self,
+        file_path: &Path,
+        source: &mut R,
+        output_path: &Path,
+        chunking: ChunkingStrategy,
+        every: u64,
+    ) -> Result<()> {
+        outln!("🌊 Stream-encrypting file with a checkpoint every {} chunks: {}", every, file_path.display());
+        chunking.validate()?;
+        
+        let partial_path = sidecar_path(output_path, "partial");
+        let checkpoint_path = sidecar_path(output_path, "checkpoint");
+        let metadata = fs::metadata(file_path)?;
+        let source_len = metadata.len();
+        let source_modified = metadata.modified()?.duration_since(UNIX_EPOCH).map_or(0, |age| age.as_secs());
+        
+        let state = match fs::read(&checkpoint_path) {
+            Ok(bytes) => Some(serde_json::from_slice::<ResumeState>(&bytes).context("Corrupt checkpoint")?),
+            Err(e) if e.kind() == ErrorKind::NotFound => None,
+            Err(e) => return Err(e.into()),
+        };
+        let (partial, from, stream_key) = match state {
+            Some(state) => {
+                if state.source_len != source_len || state.source_modified != source_modified {
+                    anyhow::bail!(
+                        "{} changed since {} was written; delete it to start over",
+                        file_path.display(),
+                        checkpoint_path.display()
+                    );
+                }
+                if state.chunking != chunking {
+                    anyhow::bail!("{} was written with different chunking ({:?})", checkpoint_path.display(), state.chunking);
+                }
+                let mut partial = OpenOptions::new().read(true).write(true).open(&partial_path)?;
+                let header = streaming::read_header(&mut BufReader::new(&partial))?;
+                let stream_key =
+                    self.unwrap_with_servers(&header.encrypted_object, header.encrypted_object.threshold as usize)?;
+                partial.set_len(state.checkpoint.output_len)?;
+                partial.seek(SeekFrom::End(0))?;
+                source.seek(SeekFrom::Start(state.checkpoint.plaintext_offset))?;
+                outln!(
+                    "   ⏩ Resuming at chunk {} ({} bytes already encrypted)",
+                    state.checkpoint.chunks, state.checkpoint.plaintext_offset
+                );
+                (partial, state.checkpoint, stream_key)
+            }
+            None => {
+                let identity = file_identity(file_path);
+                outln!("   🆔 Identity: \"{}\"", String::from_utf8_lossy(&identity));
+                let (encrypted_object, stream_key) = self.counter.seal_encrypt(
+                    self.package_id,
+                    identity,
+                    self.key_servers.clone(),
+                    &IBEPublicKeys::BonehFranklinBLS12381(self.public_keys.clone()),
+                    self.threshold,
+                    EncryptionInput::Plain,
+                )?;
+                let mut partial = File::create(&partial_path)?;
+                let header = StreamHeader { chunk_size: chunking.max_chunk() as u32, encrypted_object };
+                streaming::write_header(&mut partial, &header)?;
+                let output_len = partial.stream_position()?;
+                (partial, Checkpoint { output_len, ..Default::default() }, stream_key.to_vec())
+            }
+        };
+        
+        let mut writer = BufWriter::new(partial);
+        let cipher = ChunkCipher::new(&stream_key)?;
+        let record = |writer: &mut BufWriter<File>, checkpoint| {
+            writer.flush()?;
+            writer.get_ref().sync_data()?;
+            let state = ResumeState { checkpoint, chunking, source_len, source_modified };
+            atomic::write_file(&checkpoint_path, &serde_json::to_vec(&state)?)
+        };
+        let total = streaming::encrypt_frames_resumable(source, &mut writer, &cipher, chunking, from, every, record)?;
+        writer.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        fs::rename(&partial_path, output_path)?;
+        match fs::remove_file(&checkpoint_path) {
+            Err(e) if e.kind() != ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+        
+        outln!("   ✅ Encrypted {} bytes to: {}", total, output_path.display());
+        
+        Ok(())
+    }
+    
+    /// `encrypt_file_streaming` with an explicit worker count; 1 seals chunks
+    /// sequentially on the calling thread.
+    pub fn encrypt_file_streaming_with_workers(
+        &self,
+        file_path: &Path,
+        output_path: &Path,
+        chunking: ChunkingStrategy,
+        workers: usize,
+    ) -> Result<()> {
+        outln!("🌊 Stream-encrypting file: {}", file_path.display());
+        
+        let identity = file_identity(file_path);
+        
+        outln!("   🆔 Identity: \"{}\"", String::from_utf8_lossy(&identity));
+        match chunking {
+            ChunkingStrategy::Fixed(size) => outln!("   📦 Chunk size: {} bytes", size),
+            ChunkingStrategy::ContentDefined { min, avg, max } => {
+                outln!("   📦 Content-defined chunks: {}..{} bytes, ~{} on average", min, max, avg)
+            }
+        }
+        outln!("   🧵 Workers: {}", workers.max(1));
+        
+        let (encrypted_object, stream_key) = self.counter.seal_encrypt(
+            self.package_id,
+            identity,
+            self.key_servers.clone(),
+            &IBEPublicKeys::BonehFranklinBLS12381(self.public_keys.clone()),
+            self.threshold,
+            EncryptionInput::Plain,
+        )?;
+        
+        let mut reader = BufReader::new(File::open(file_path)?);
+        let mut total = 0;
+        atomic::write_atomically(output_path, |writer| {
+            total = streaming::encrypt_stream(&mut reader, writer, encrypted_object, &stream_key, chunking, workers)?;
+            Ok(())
+        })?;
+        
+        outln!("   ✅ Encrypted {} bytes to: {}", total, output_path.display());
+        
+        Ok(())
+    }
+    
+    /// Decrypt a streaming container chunk by chunk into any `Write` sink and
+    /// return the number of plaintext bytes written.
+    ///
+    /// Each chunk is written as soon as it authenticates, so the whole
+    /// plaintext is never buffered. The flip side is that on any error,
+    /// including a failed write, `w` may already hold a partial prefix which
+    /// the caller must discard. Nothing is printed to stdout, so `w` may be
+    /// stdout itself.
+    pub fn decrypt_to_writer(&self, input: &Path, w: &mut impl Write) -> Result<u64> {
+        let mut reader = BufReader::new(File::open(input)?);
+        streaming::decrypt_stream(&mut reader, w, |encrypted_object| {
+            self.unwrap_with_servers(encrypted_object, encrypted_object.threshold as usize)
+        })
+    }
+    
+    /// Decrypt a streaming container from `encrypted_path` to `output_path`
+    /// chunk by chunk, holding about two chunks in memory whatever the file
+    /// size, and return the number of plaintext bytes.
+    ///
+    /// The output is written atomically: a truncated or tampered stream is an
+    /// error naming the first bad chunk and leaves nothing at `output_path`.
+    pub fn decrypt_file_streaming(&self, encrypted_path: &Path, output_path: &Path) -> Result<u64> {
+        outln!("🔓 Decrypting chunk by chunk: {}", encrypted_path.display());
+        
+        let mut written = 0;
+        atomic::write_atomically(output_path, |writer| {
+            written = self.decrypt_to_writer(encrypted_path, writer)?;
+            Ok(())
+        })?;
+        
+        outln!("   ✅ Decrypted to: {}", output_path.display());
+        outln!("   📏 Wrote {} bytes", written);
+        Ok(written)
+    }
+    
+    /// Decrypt a streaming container into a memory-mapped output file, so
+    /// the OS pages the plaintext out instead of it passing through write
+    /// buffers, and the result can be accessed randomly right away.
+    ///
+    /// The output is pre-sized to the plaintext length, which follows from
+    /// the frame lengths without decrypting anything, and each chunk is
+    /// written into the mapping as it authenticates. As with every file
+    /// output here, nothing appears at `output` unless every chunk does.
+    /// Returns the number of plaintext bytes.
+    pub fn decrypt_file_mmap(&self, input: &Path, output: &Path) -> Result<u64> {
+        outln!("🗺️  Decrypting into a memory map: {}", input.display());
+        
+        let mut reader = BufReader::new(File::open(input)?);
+        let header = streaming::read_header(&mut reader)?;
+        let plaintext_len = streaming::plaintext_len(&mut reader, header.chunk_size)?;
+        outln!("   📏 Plaintext length: {} bytes", plaintext_len);
+        
+        let stream_key = self.unwrap_with_servers(&header.encrypted_object, header.encrypted_object.threshold as usize)?;
+        let cipher = streaming::ChunkCipher::new(&stream_key)?;
+        
+        let mut written = 0;
+        atomic::write_atomically(output, |writer| {
+            let file = writer.get_mut();
+            file.set_len(plaintext_len)?;
+            if plaintext_len == 0 {
+                // Nothing to map, but the (empty) final chunk must still authenticate
+                written = streaming::decrypt_frames(&mut reader, &mut std::io::sink(), &cipher, header.chunk_size)?;
+                return Ok(());
+            }
+            
+            // SAFETY: the file is a temp file this process just created and
+            // nothing else opens it before it is renamed into place.
+            let mut mapping = unsafe { memmap2::MmapMut::map_mut(&*file)? };
+            let mut target: &mut [u8] = &mut mapping;
+            written = streaming::decrypt_frames(&mut reader, &mut target, &cipher, header.chunk_size)?;
+            mapping.flush()?;
+            Ok(())
+        })?;
+        
+        if written != plaintext_len {
+            anyhow::bail!("Decrypted {} bytes but the frames promised {}", written, plaintext_len);
+        }
+        outln!("   ✅ Decrypted to: {}", output.display());
+        Ok(written)
+    }
+    
+    /// The first `n` bytes of an encrypted file's plaintext (fewer if it is
+    /// shorter), e.g. for a preview. Streaming containers only decrypt the
+    /// chunks covering those bytes; later chunks are not authenticated, so a
+    /// prefix says nothing about the rest of the file. Other files are
+    /// decrypted in full and truncated.
+    pub fn decrypt_prefix(&self, input: &Path, n: usize) -> Result<Vec<u8>> {
+        let mut reader = BufReader::new(File::open(input)?);
+        let mut magic = [0u8; 4];
+        let is_stream = reader.read_exact(&mut magic).is_ok() && &magic == streaming::STREAM_MAGIC;
+        
+        if !is_stream {
+            let container = SealContainer::from_bytes(&fs::read(input)?)?;
+            let mut plaintext = container.decrypt(|object| self.unwrap_with_servers(object, object.threshold as usize))?;
+            plaintext.truncate(n);
+            return Ok(plaintext);
+        }
+        
+        let mut reader = BufReader::new(File::open(input)?);
+        let header = streaming::read_header(&mut reader)?;
+        let stream_key = self.unwrap_with_servers(&header.encrypted_object, header.encrypted_object.threshold as usize)?;
+        streaming::decrypt_prefix_frames(&mut reader, &streaming::ChunkCipher::new(&stream_key)?, header.chunk_size, n)
+    }
+    
+    /// Check that every chunk of a streaming container authenticates, without
+    /// writing any plaintext anywhere. Memory stays at about two chunks
+    /// regardless of file size. On failure the error names the first bad
+    /// chunk.
+    ///
+    /// Authenticated chunks only prove the stream is intact, not that it is
+    /// the expected file, so the SHA3-256 of the plaintext is returned and,
+    /// given `expected_sha3`, compared against it.
+    pub fn verify_stream(&self, input: &Path, expected_sha3: Option<&[u8; 32]>) -> Result<[u8; 32]> {
+        outln!("🔍 Verifying stream: {}", input.display());
+        
+        let mut sink = HashingSink(Sha3_256::default());
+        let total = self.decrypt_to_writer(input, &mut sink)?;
+        let digest = sink.0.finalize().digest;
+        outln!("   ✅ All chunks authenticated ({} bytes)", total);
+        
+        match expected_sha3 {
+            Some(expected) if *expected != digest => anyhow::bail!(
+                "Plaintext SHA3-256 {} doesn't match the expected {}",
+                hex::encode(digest),
+                hex::encode(expected)
+            ),
+            Some(_) => outln!("   ✅ Plaintext SHA3-256 matches: {}", hex::encode(digest)),
+            None => outln!("   #️⃣  Plaintext SHA3-256: {}", hex::encode(digest)),
+        }
+        Ok(digest)
+    }
+    
+    /// Raise the threshold of an encrypted file.
+    ///
+    /// The threshold is baked into how the symmetric key was secret-shared
+    /// across servers, so it cannot be changed in place. This is a full
+    /// decrypt-and-re-encrypt: the plaintext is recovered in memory (never
+    /// written to disk) and encrypted again under the same package, identity,
+    /// mode and AAD with a fresh key split at `new_threshold`.
+    pub fn upgrade_threshold(&self, input: &Path, output: &Path, new_threshold: u8) -> Result<()> {
+        outln!("⬆️  Upgrading threshold of: {}", input.display());
+        
+        let encrypted_object = read_unescrowed(input)?;
+        
+        if new_threshold <= encrypted_object.threshold {
+            anyhow::bail!(
+                "New threshold {} must be greater than the current threshold {}",
+                new_threshold,
+                encrypted_object.threshold
+            );
+        }
+        if new_threshold as usize > self.key_servers.len() {
+            anyhow::bail!(
+                "New threshold {} exceeds the number of key servers ({})",
+                new_threshold,
+                self.key_servers.len()
+            );
+        }
+        
+        outln!("   🔢 Threshold: {} -> {}", encrypted_object.threshold, new_threshold);
+        
+        let (upgraded_object, _) = self.reencrypt(&encrypted_object, new_threshold)?;
+        
+        atomic::write_file(output, &self.container_for(upgraded_object).to_bytes()?)?;
+        
+        outln!("   ✅ Re-encrypted to: {}", output.display());
+        
+        Ok(())
+    }
+    
+    /// Decrypt `encrypted_object` and encrypt the plaintext again under the
+    /// same package, identity, mode and AAD, with a fresh key split at
+    /// `threshold` across this fleet.
+    fn reencrypt(&self, encrypted_object: &crypto::EncryptedObject, threshold: u8) -> Result<(crypto::EncryptedObject, [u8; 32])> {
+        let plaintext = self.decrypt_with_servers(encrypted_object, encrypted_object.threshold as usize)?;
+        
+        let encryption_input = match &encrypted_object.ciphertext {
+            Ciphertext::Aes256Gcm { aad, .. } => EncryptionInput::Aes256Gcm {
+                data: plaintext,
+                aad: aad.clone(),
+            },
+            Ciphertext::Hmac256Ctr { aad, .. } => EncryptionInput::Hmac256Ctr {
+                data: plaintext,
+                aad: aad.clone(),
+            },
+            Ciphertext::Plain => {
+                anyhow::bail!("Plain-mode objects carry no data; derive a new key instead of re-encrypting");
+            }
+        };
+        
+        self.counter.seal_encrypt(
+            encrypted_object.package_id,
+            encrypted_object.id.clone(),
+            self.key_servers.clone(),
+            &IBEPublicKeys::BonehFranklinBLS12381(self.public_keys.clone()),
+            threshold,
+            encryption_input,
+        )
+    }
+    
+    /// Re-encrypt a file under a fresh symmetric key, e.g. after its `.key`
+    /// sidecar leaked. Identity, package, threshold, mode and AAD are taken
+    /// from the file itself; only the key (and the shares wrapping it)
+    /// changes, so the old key opens nothing in `output`. If the input had a
+    /// `.key` sidecar, the new key is saved next to `output` the same way.
+    pub fn rekey_symmetric(&self, input: &Path, output: &Path) -> Result<()> {
+        outln!("🔁 Rotating the symmetric key of: {}", input.display());
         
-        for (filename, content) in files {
-            let file_path = self.temp_dir.join(filename);
-            fs::write(&file_path, content)?;
-            created_files.push((file_path, filename.to_string()));
-            println!("   📄 Created: {} ({} bytes)", filename, content.len());
+        let encrypted_object = read_unescrowed(input)?;
+        let fleet_servers: Vec<ObjectID> = encrypted_object.services.iter().map(|(server, _)| *server).collect();
+        if fleet_servers != self.key_servers {
+            anyhow::bail!("File was encrypted to a different set of key servers than this fleet");
         }
+        outln!("   🆔 Identity: \"{}\"", String::from_utf8_lossy(&encrypted_object.id));
         
-        Ok(created_files)
+        let (rekeyed_object, key) = self.reencrypt(&encrypted_object, encrypted_object.threshold)?;
+        atomic::write_file(output, &self.container_for(rekeyed_object).to_bytes()?)?;
+        outln!("   ✅ Re-encrypted under a fresh key to: {}", output.display());
+        
+        if input.with_extension("key").exists() {
+            let key_path = output.with_extension("key");
+            atomic::write_file(&key_path, hex::encode(key).as_bytes())?;
+            outln!("   🔑 New symmetric key saved to: {}", key_path.display());
+        }
+        
+        Ok(())
     }
     
-    pub fn encrypt_file(&self, file_path: &Path, output_path: &Path) -> Result<()> {
-        println!("🔒 Encrypting file: {}", file_path.display());
+    /// Encrypt a text file line by line (see `lines`), so tampering is
+    /// reported per line on decryption.
+    pub fn encrypt_text_lines(&self, input: &Path, output: &Path) -> Result<()> {
+        outln!("📝 Line-sealing: {}", input.display());
         
-        // Read file content
-        let file_content = fs::read(file_path)?;
-        println!("   📊 File size: {} bytes", file_content.len());
+        let (encrypted_object, key) = self.counter.seal_encrypt(
+            self.package_id,
+            file_identity(input),
+            self.key_servers.clone(),
+            &IBEPublicKeys::BonehFranklinBLS12381(self.public_keys.clone()),
+            self.threshold,
+            EncryptionInput::Plain,
+        )?;
+        let sealed = lines::encrypt_lines(&fs::read(input)?, encrypted_object, &key)?;
+        atomic::write_file(output, sealed.as_bytes())?;
         
-        // Use filename as identity
-        let filename = file_path.file_name()
-            .unwrap_or_default()
-            .to_string_lossy()
-            .to_string();
-        let identity = format!("file:{}", filename).into_bytes();
+        outln!("   ✅ {} lines sealed to: {}", sealed.lines().count() - 1, output.display());
+        Ok(())
+    }
+    
+    /// Decrypt a file written by `encrypt_text_lines`, failing on the first tampered line.
+    pub fn decrypt_text_lines(&self, input: &Path, output: &Path) -> Result<()> {
+        let text = lines::decrypt_lines(&fs::read_to_string(input)?, |encrypted_object| {
+            self.unwrap_with_servers(encrypted_object, encrypted_object.threshold as usize)
+        })?;
+        atomic::write_file(output, &text)?;
+        Ok(())
+    }
+    
+    /// Append one encrypted record to an audit log without touching earlier
+    /// ones. Each record is its own frame (u32 LE length + bcs object)
+    /// encrypted to `log:<file name>:<sequence number>`, so records can't
+    /// be reordered or moved between logs unnoticed.
+    ///
+    /// Finding the next sequence number reads the frame lengths, not the
+    /// records. A crash mid-append leaves a truncated last frame, which
+    /// both this and `read_log` report rather than build on.
+    pub fn append_record(&self, file: &Path, record: &[u8]) -> Result<()> {
+        if record.len() > MAX_LOG_RECORD as usize {
+            anyhow::bail!("Log record of {} bytes exceeds the {} byte limit", record.len(), MAX_LOG_RECORD);
+        }
         
-        println!("   🆔 Identity: \"{}\"", String::from_utf8_lossy(&identity));
+        let sequence = match File::open(file) {
+            Ok(existing) => {
+                let mut reader = BufReader::new(existing);
+                let mut count = 0u64;
+                while streaming::read_frame(&mut reader, MAX_LOG_RECORD)?.is_some() {
+                    count += 1;
+                }
+                count
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => 0,
+            Err(e) => return Err(e.into()),
+        };
         
-        // Encrypt the file
-        let (encrypted_object, symmetric_key) = seal_encrypt(
+        let (encrypted_object, _) = self.counter.seal_encrypt(
             self.package_id,
-            identity,
+            log_record_identity(file, sequence),
             self.key_servers.clone(),
             &IBEPublicKeys::BonehFranklinBLS12381(self.public_keys.clone()),
             self.threshold,
             EncryptionInput::Aes256Gcm {
-                data: file_content,
+                data: record.to_vec(),
                 aad: None,
             },
         )?;
         
-        // Save encrypted object and key
-        let encrypted_data = bcs::to_bytes(&encrypted_object)?;
-        fs::write(output_path, &encrypted_data)?;
-        
-        // Also save symmetric key for reference
-        let key_path = output_path.with_extension("key");
-        fs::write(&key_path, hex::encode(symmetric_key))?;
-        
-        println!("   ✅ Encrypted to: {}", output_path.display());
-        println!("   🔑 Symmetric key saved to: {}", key_path.display());
-        println!("   📏 Encrypted size: {} bytes", encrypted_data.len());
+        // The limit applies to the serialized object, which is what `read_log` reads back
+        let serialized = bcs::to_bytes(&encrypted_object)?;
+        if serialized.len() > MAX_LOG_RECORD as usize {
+            anyhow::bail!(
+                "Log record of {} bytes encrypts to {}, over the {} byte limit",
+                record.len(),
+                serialized.len(),
+                MAX_LOG_RECORD
+            );
+        }
         
+        let mut log = fs::OpenOptions::new().create(true).append(true).open(file)?;
+        let mut frame = Vec::new();
+        streaming::write_frame(&mut frame, &serialized)?;
+        log.write_all(&frame)?;
         Ok(())
     }
     
-    pub fn decrypt_file(&self, encrypted_path: &Path, output_path: &Path) -> Result<()> {
-        println!("🔓 Decrypting file: {}", encrypted_path.display());
+    /// Decrypt every record of an audit log written by `append_record`, in order.
+    pub fn read_log(&self, file: &Path) -> Result<Vec<Vec<u8>>> {
+        let mut reader = BufReader::new(File::open(file)?);
+        let mut records = Vec::new();
         
-        // Read encrypted object
-        let encrypted_data = fs::read(encrypted_path)?;
-        let encrypted_object: crypto::EncryptedObject = bcs::from_bytes(&encrypted_data)?;
+        while let Some(frame) = streaming::read_frame(&mut reader, MAX_LOG_RECORD)? {
+            let sequence = records.len() as u64;
+            let encrypted_object: crypto::EncryptedObject =
+                bcs::from_bytes(&frame).with_context(|| format!("Corrupt log record {}", sequence))?;
+            if encrypted_object.id != log_record_identity(file, sequence) {
+                anyhow::bail!(
+                    "Log record {} is out of order or from another log (identity \"{}\")",
+                    sequence,
+                    String::from_utf8_lossy(&encrypted_object.id)
+                );
+            }
+            records.push(self.unwrap_with_servers(&encrypted_object, encrypted_object.threshold as usize)?);
+        }
         
-        println!("   📊 Encrypted size: {} bytes", encrypted_data.len());
-        println!("   🔢 Threshold: {}", encrypted_object.threshold);
+        Ok(records)
+    }
+    
+    /// Encrypt the `sensitive_columns` of a CSV cell by cell, leaving every
+    /// other column in the clear.
+    ///
+    /// Each cell is its own Seal object under the identity
+    /// `csv:<table>:<column>:row:<index>` and is stored as base64 of its BCS
+    /// bytes. `table` names the dataset, so a key extracted for one file's
+    /// cell opens nothing in files sealed under another table name; it must
+    /// not be empty or contain `:`.
+    pub fn encrypt_csv(&self, input: &Path, output: &Path, table: &str, sensitive_columns: &[String]) -> Result<()> {
+        outln!("🧾 Encrypting CSV columns {:?} of table {} in: {}", sensitive_columns, table, input.display());
+        check_csv_table(table)?;
         
-        // Extract the filename from the identity
-        let identity_str = String::from_utf8_lossy(&encrypted_object.id);
-        println!("   🆔 Identity: \"{}\"", identity_str);
+        let mut reader = csv::Reader::from_path(input)?;
+        let headers = reader.headers()?.clone();
+        let columns = csv_column_indices(&headers, sensitive_columns)?;
         
-        // Create full identity
-        let full_id = create_full_id(&encrypted_object.package_id, &encrypted_object.id);
+        let mut writer = csv::Writer::from_path(output)?;
+        writer.write_record(&headers)?;
         
-        // Get user secret keys from first `threshold` servers
-        let mut user_secret_keys = HashMap::new();
-        for i in 0..(self.threshold as usize) {
-            let secret_key = extract(&self.master_keys[i], &full_id);
-            user_secret_keys.insert(self.key_servers[i], secret_key);
-            println!("   🔑 Using key from server {}", i + 1);
+        let mut rows = 0;
+        for (row, record) in reader.records().enumerate() {
+            let mut fields: Vec<String> = record?.iter().map(str::to_string).collect();
+            
+            for &(index, column) in &columns {
+                let (encrypted_object, _) = self.counter.seal_encrypt(
+                    self.package_id,
+                    csv_cell_identity(table, column, row),
+                    self.key_servers.clone(),
+                    &IBEPublicKeys::BonehFranklinBLS12381(self.public_keys.clone()),
+                    self.threshold,
+                    EncryptionInput::Aes256Gcm {
+                        data: fields[index].as_bytes().to_vec(),
+                        aad: None,
+                    },
+                )?;
+                fields[index] = BASE64_STANDARD.encode(bcs::to_bytes(&encrypted_object)?);
+            }
+            
+            writer.write_record(&fields)?;
+            rows += 1;
         }
+        writer.flush()?;
         
-        // Decrypt
-        let decrypted_data = seal_decrypt(
-            &encrypted_object,
-            &IBEUserSecretKeys::BonehFranklinBLS12381(user_secret_keys),
-            Some(&IBEPublicKeys::BonehFranklinBLS12381(self.public_keys.clone())),
-        )?;
+        outln!("   ✅ Encrypted {} cells across {} rows to: {}", rows * columns.len(), rows, output.display());
         
-        // Write decrypted file
-        fs::write(output_path, &decrypted_data)?;
+        Ok(())
+    }
+    
+    /// Restore the `sensitive_columns` of a CSV written by `encrypt_csv`.
+    ///
+    /// A cell only decrypts in the position it was encrypted for: one that
+    /// was copied into another row, column or table is rejected.
+    pub fn decrypt_csv(&self, input: &Path, output: &Path, table: &str, sensitive_columns: &[String]) -> Result<()> {
+        outln!("🧾 Decrypting CSV columns {:?} of table {} in: {}", sensitive_columns, table, input.display());
+        check_csv_table(table)?;
+        
+        let mut reader = csv::Reader::from_path(input)?;
+        let headers = reader.headers()?.clone();
+        let columns = csv_column_indices(&headers, sensitive_columns)?;
         
-        println!("   ✅ Decrypted to: {}", output_path.display());
-        println!("   📏 Original size: {} bytes", decrypted_data.len());
+        let mut writer = csv::Writer::from_path(output)?;
+        writer.write_record(&headers)?;
+        
+        let mut rows = 0;
+        for (row, record) in reader.records().enumerate() {
+            let mut fields: Vec<String> = record?.iter().map(str::to_string).collect();
+            
+            for &(index, column) in &columns {
+                let encrypted_bytes = BASE64_STANDARD
+                    .decode(&fields[index])
+                    .with_context(|| format!("Cell {}[{}] is not base64", column, row))?;
+                let encrypted_object: crypto::EncryptedObject = bcs::from_bytes(&encrypted_bytes)
+                    .with_context(|| format!("Cell {}[{}] is not an encrypted object", column, row))?;
+                
+                if encrypted_object.id != csv_cell_identity(table, column, row) {
+                    anyhow::bail!(
+                        "Cell {}[{}] was encrypted for \"{}\" - it has been moved",
+                        column,
+                        row,
+                        String::from_utf8_lossy(&encrypted_object.id)
+                    );
+                }
+                
+                let plaintext = self.unwrap_with_servers(&encrypted_object, encrypted_object.threshold as usize)?;
+                fields[index] = String::from_utf8(plaintext)?;
+            }
+            
+            writer.write_record(&fields)?;
+            rows += 1;
+        }
+        writer.flush()?;
+        
+        outln!("   ✅ Decrypted {} rows to: {}", rows, output.display());
         
         Ok(())
     }
     
     pub fn demo_file_encryption(&self) -> Result<()> {
-        println!("\n🚀 === File Encryption Demo ===");
+        outln!("\n🚀 === File Encryption Demo ===");
         
         // Create sample files
         let sample_files = self.create_sample_files()?;
+        let outputs: Vec<(PathBuf, PathBuf)> = sample_files
+            .iter()
+            .enumerate()
+            .map(|(counter, (file_path, _))| {
+                (file_path.clone(), output_path_for(file_path, &self.name_template, counter + 1))
+            })
+            .collect();
+        check_distinct_outputs(&outputs)?;
         
-        for (file_path, filename) in &sample_files {
-            println!("\n📄 Processing file: {}", filename);
+        for (counter, ((file_path, filename), (_, encrypted_path))) in sample_files.iter().zip(&outputs).enumerate() {
+            outln!("\n📄 Processing file: {}", filename);
             
             // Define paths
-            let encrypted_path = self.temp_dir.join(format!("{}.encrypted", filename));
-            let decrypted_path = self.temp_dir.join(format!("{}.decrypted", filename));
+            let decrypted_path = output_path_for(file_path, DECRYPTED_NAME_TEMPLATE, counter + 1);
             
             // Encrypt file
-            self.encrypt_file(file_path, &encrypted_path)?;
+            self.encrypt_file(file_path, encrypted_path)?;
             
             // Decrypt file  
-            self.decrypt_file(&encrypted_path, &decrypted_path)?;
+            self.decrypt_file(encrypted_path, &decrypted_path)?;
             
             // Verify integrity
             let original_content = fs::read(file_path)?;
             let decrypted_content = fs::read(&decrypted_path)?;
             
             if original_content == decrypted_content {
-                println!("   ✅ File integrity verified - contents match!");
+                outln!("   ✅ File integrity verified - contents match!");
             } else {
                 anyhow::bail!("❌ File integrity check failed for {}", filename);
             }
@@ -193,8 +1683,10 @@ impl FileDemo {
         Ok(())
     }
     
-    pub fn demo_batch_encryption(&self) -> Result<()> {
-        println!("\n🚀 === Batch File Encryption Demo ===");
+    /// `progress` is handed to `encrypt_batch`; the demo prints its own
+    /// per-file lines either way.
+    pub fn demo_batch_encryption(&self, progress: Option<&dyn Fn(FileProgress)>) -> Result<()> {
+        outln!("\n🚀 === Batch File Encryption Demo ===");
         
         // Create a directory structure
         let batch_dir = self.temp_dir.join("batch_demo");
@@ -207,42 +1699,44 @@ impl FileDemo {
             ("report.md", "# Monthly Report\n\nSales increased by 15%"),
         ];
         
-        println!("📦 Creating batch of files to encrypt...");
+        outln!("📦 Creating batch of files to encrypt...");
         
         for (filename, content) in &files_to_encrypt {
             let file_path = batch_dir.join(filename);
             fs::write(&file_path, content)?;
-            println!("   📄 Created: {}", filename);
+            outln!("   📄 Created: {}", filename);
         }
         
         // Encrypt all files
         let encrypted_dir = self.temp_dir.join("encrypted_batch");
         fs::create_dir_all(&encrypted_dir)?;
         
-        println!("\n🔒 Encrypting all files...");
+        outln!("\n🔒 Encrypting all files...");
         
-        for (filename, _) in &files_to_encrypt {
-            let source_path = batch_dir.join(filename);
-            let encrypted_path = encrypted_dir.join(format!("{}.seal", filename));
-            
-            self.encrypt_file(&source_path, &encrypted_path)?;
-        }
+        let encrypted_name = |counter: usize, filename: &str| output_path_for(Path::new(filename), &self.name_template, counter + 1);
+        
+        let jobs: Vec<(PathBuf, PathBuf)> = files_to_encrypt
+            .iter()
+            .enumerate()
+            .map(|(counter, (filename, _))| (batch_dir.join(filename), encrypted_dir.join(encrypted_name(counter, filename))))
+            .collect();
+        self.encrypt_batch(&jobs, progress)?;
         
         // Decrypt all files  
         let decrypted_dir = self.temp_dir.join("decrypted_batch");
         fs::create_dir_all(&decrypted_dir)?;
         
-        println!("\n🔓 Decrypting all files...");
+        outln!("\n🔓 Decrypting all files...");
         
-        for (filename, _) in &files_to_encrypt {
-            let encrypted_path = encrypted_dir.join(format!("{}.seal", filename));
-            let decrypted_path = decrypted_dir.join(filename);
-            
-            self.decrypt_file(&encrypted_path, &decrypted_path)?;
-        }
+        let jobs: Vec<(PathBuf, PathBuf)> = files_to_encrypt
+            .iter()
+            .enumerate()
+            .map(|(counter, (filename, _))| (encrypted_dir.join(encrypted_name(counter, filename)), decrypted_dir.join(filename)))
+            .collect();
+        self.decrypt_batch(&jobs)?;
         
         // Verify all files
-        println!("\n🔍 Verifying batch integrity...");
+        outln!("\n🔍 Verifying batch integrity...");
         let mut all_verified = true;
         
         for (filename, original_content) in &files_to_encrypt {
@@ -250,24 +1744,50 @@ impl FileDemo {
             let decrypted_content = fs::read_to_string(&decrypted_path)?;
             
             if decrypted_content == *original_content {
-                println!("   ✅ {}: verified", filename);
+                outln!("   ✅ {}: verified", filename);
             } else {
-                println!("   ❌ {}: integrity check failed", filename);
+                outln!("   ❌ {}: integrity check failed", filename);
                 all_verified = false;
             }
         }
         
         if all_verified {
-            println!("🎉 Batch encryption/decryption successful!");
+            outln!("🎉 Batch encryption/decryption successful!");
         } else {
             anyhow::bail!("❌ Some files failed integrity checks");
         }
         
+        outln!("\n📴 Decrypting again with server 1 offline...");
+        let online: Vec<usize> = (1..self.key_servers.len()).collect();
+        let manifest = self.decrypt_batch_with_servers(&jobs, &online)?;
+        if online.len() >= self.threshold as usize && manifest.failed() > 0 {
+            anyhow::bail!("❌ {} files failed with a quorum still online", manifest.failed());
+        }
+        
+        outln!("\n📴 And with only server {} online...", self.key_servers.len());
+        let manifest = self.decrypt_batch_with_servers(&jobs, &[self.key_servers.len() - 1])?;
+        if self.threshold > 1 && manifest.failed() != jobs.len() {
+            anyhow::bail!("❌ Files decrypted below threshold");
+        }
+        let skipped: Vec<String> = manifest
+            .entries
+            .iter()
+            .filter(|entry| entry.outcome.is_err())
+            .map(|entry| entry.input.file_name().unwrap_or_default().to_string_lossy().into_owned())
+            .collect();
+        outln!("   📋 Left for when more servers are back: {}", skipped.join(", "));
+        
+        outln!("\n🧵 Decrypting the whole encrypted directory in parallel...");
+        let manifest = self.decrypt_directory_parallel(&encrypted_dir, &self.temp_dir.join("parallel_decrypted"))?;
+        if manifest.failed() > 0 {
+            anyhow::bail!("❌ {} files failed to decrypt in parallel", manifest.failed());
+        }
+        
         Ok(())
     }
     
     pub fn demo_file_metadata(&self) -> Result<()> {
-        println!("\n🚀 === File Metadata Demo ===");
+        outln!("\n🚀 === File Metadata Demo ===");
         
         let test_file = self.temp_dir.join("metadata_test.txt");
         let content = "File with metadata demonstration";
@@ -287,11 +1807,11 @@ impl FileDemo {
             metadata.to_string()
         ).into_bytes();
         
-        println!("🏷️  Enhanced identity with metadata:");
-        println!("   {}", String::from_utf8_lossy(&identity_with_metadata));
+        outln!("🏷️  Enhanced identity with metadata:");
+        outln!("   {}", String::from_utf8_lossy(&identity_with_metadata));
         
         // Encrypt with metadata-enhanced identity
-        let (encrypted_object, _) = seal_encrypt(
+        let (encrypted_object, _) = self.counter.seal_encrypt(
             self.package_id,
             identity_with_metadata.clone(),
             self.key_servers.clone(),
@@ -303,56 +1823,605 @@ impl FileDemo {
             },
         )?;
         
-        println!("🔒 Encrypted with metadata as Additional Authenticated Data (AAD)");
+        outln!("🔒 Encrypted with metadata as Additional Authenticated Data (AAD)");
         
         // Decrypt
         let full_id = create_full_id(&self.package_id, &identity_with_metadata);
         let mut user_secret_keys = HashMap::new();
         
         for i in 0..(self.threshold as usize) {
-            let secret_key = extract(&self.master_keys[i], &full_id);
+            let secret_key = self.counter.extract(&self.master_keys[i], &full_id);
             user_secret_keys.insert(self.key_servers[i], secret_key);
         }
         
-        let decrypted = seal_decrypt(
+        let decrypted = self.counter.seal_decrypt(
             &encrypted_object,
             &IBEUserSecretKeys::BonehFranklinBLS12381(user_secret_keys),
             Some(&IBEPublicKeys::BonehFranklinBLS12381(self.public_keys.clone())),
         )?;
         
         if decrypted == content.as_bytes() {
-            println!("✅ Metadata-enhanced encryption successful!");
+            outln!("✅ Metadata-enhanced encryption successful!");
         } else {
             anyhow::bail!("❌ Metadata-enhanced encryption failed");
         }
         
-        println!("\n💡 This demonstrates how to include file metadata in the encryption:");
-        println!("   - Metadata becomes part of the identity (access control)");
-        println!("   - AAD ensures metadata integrity without encryption");
-        println!("   - Fine-grained access control based on file attributes");
+        outln!("\n💡 This demonstrates how to include file metadata in the encryption:");
+        outln!("   - Metadata becomes part of the identity (access control)");
+        outln!("   - AAD ensures metadata integrity without encryption");
+        outln!("   - Fine-grained access control based on file attributes");
+        
+        Ok(())
+    }
+    
+    pub fn demo_threshold_upgrade(&self) -> Result<()> {
+        outln!("\n🚀 === Threshold Upgrade Demo ===");
+        
+        let source_path = self.temp_dir.join("upgrade_me.txt");
+        let encrypted_path = output_path_for(&source_path, &self.name_template, 1);
+        let upgraded_path = self.temp_dir.join("upgrade_me.txt.upgraded");
+        fs::write(&source_path, "Document whose threshold is raised after the fact")?;
+        
+        self.encrypt_file(&source_path, &encrypted_path)?;
+        let new_threshold = self.key_servers.len() as u8;
+        self.upgrade_threshold(&encrypted_path, &upgraded_path, new_threshold)?;
+        
+        let upgraded_object = read_unescrowed(&upgraded_path)?;
+        
+        outln!("\n❌ Trying the upgraded file with the old threshold ({}):", self.threshold);
+        match self.decrypt_with_servers(&upgraded_object, self.threshold as usize) {
+            Ok(_) => anyhow::bail!("❌ Upgraded file decrypted with too few servers"),
+            Err(e) => outln!("   ✅ Decryption correctly failed: {}", e),
+        }
+        
+        outln!("\n🔓 Trying the upgraded file with {} servers:", new_threshold);
+        let decrypted = self.decrypt_with_servers(&upgraded_object, new_threshold as usize)?;
+        if decrypted == fs::read(&source_path)? {
+            outln!("   ✅ Upgraded file decrypts with the new threshold");
+        } else {
+            anyhow::bail!("❌ Upgraded file decrypted to the wrong content");
+        }
+        
+        Ok(())
+    }
+    
+    pub fn demo_symmetric_rekey(&self) -> Result<()> {
+        outln!("\n🚀 === Symmetric Key Rotation Demo ===");
+        
+        let source_path = self.temp_dir.join("leaked.txt");
+        let encrypted_path = self.temp_dir.join("leaked.txt.seal");
+        let rekeyed_path = self.temp_dir.join("leaked.rekeyed.txt.seal");
+        fs::write(&source_path, "Document whose symmetric key was exposed")?;
+        
+        self.encrypt_file(&source_path, &encrypted_path)?;
+        self.rekey_symmetric(&encrypted_path, &rekeyed_path)?;
+        
+        let old_key = fs::read_to_string(encrypted_path.with_extension("key"))?;
+        let new_key = fs::read_to_string(rekeyed_path.with_extension("key"))?;
+        if old_key == new_key {
+            anyhow::bail!("❌ Rotation kept the old symmetric key");
+        }
+        outln!("   ✅ Symmetric key changed: {}… -> {}…", &old_key[..16], &new_key[..16]);
+        
+        let rekeyed_object = read_unescrowed(&rekeyed_path)?;
+        if self.decrypt_with_servers(&rekeyed_object, rekeyed_object.threshold as usize)? == fs::read(&source_path)? {
+            outln!("   ✅ The fleet still decrypts the rotated file under the same identity");
+        } else {
+            anyhow::bail!("❌ Rotated file decrypted to the wrong content");
+        }
+        
+        Ok(())
+    }
+    
+    pub fn demo_streaming(&self) -> Result<()> {
+        outln!("\n🚀 === Streaming Encryption Demo ===");
+        
+        // A few chunks plus a partial one at the end
+        let chunk_size = 4096;
+        let source_path = self.temp_dir.join("stream_me.log");
+        let encrypted_path = self.temp_dir.join("stream_me.log.sealstream");
+        let decrypted_path = output_path_for(&source_path, DECRYPTED_NAME_TEMPLATE, 1);
+        let content = "2024-01-15T10:30:00Z INFO request served in 12ms\n".repeat(250);
+        fs::write(&source_path, &content)?;
+        outln!("📄 Created: stream_me.log ({} bytes)", content.len());
+        
+        self.encrypt_file_streaming(&source_path, &encrypted_path, ChunkingStrategy::Fixed(chunk_size))?;
+        
+        self.decrypt_file_streaming(&encrypted_path, &decrypted_path)?;
+        
+        if fs::read(&decrypted_path)? == content.as_bytes() {
+            outln!("   ✅ Streamed file integrity verified - contents match!");
+        } else {
+            anyhow::bail!("❌ Streamed file integrity check failed");
+        }
+        
+        self.verify_stream(&encrypted_path, Some(&Sha3_256::digest(content.as_bytes()).digest))?;
+        
+        let mapped_path = self.temp_dir.join("stream_me.mapped.log");
+        self.decrypt_file_mmap(&encrypted_path, &mapped_path)?;
+        if fs::read(&mapped_path)? != content.as_bytes() {
+            anyhow::bail!("❌ Memory-mapped decryption produced different content");
+        }
+        
+        // A preview needs only the first chunk
+        let preview = self.decrypt_prefix(&encrypted_path, 48)?;
+        outln!("   👀 Preview of the first 48 bytes: {:?}", String::from_utf8_lossy(&preview).trim_end());
+        
+        Ok(())
+    }
+    
+    pub fn demo_content_defined_chunking(&self) -> Result<()> {
+        outln!("\n🚀 === Content-Defined Chunking Demo ===");
+        
+        let original = testutil::random_bytes(512 * 1024, &mut thread_rng());
+        let mut edited = original.clone();
+        edited.insert(100, b'!');
+        outln!("📄 512 KiB file, then the same file with one byte inserted near the start");
+        
+        let strategies = [
+            ("Fixed 8 KiB", ChunkingStrategy::Fixed(8 * 1024)),
+            ("Content-defined ~8 KiB", ChunkingStrategy::ContentDefined { min: 2 * 1024, avg: 8 * 1024, max: 32 * 1024 }),
+        ];
+        for (name, strategy) in strategies {
+            let before = streaming::chunk_digests(&mut original.as_slice(), strategy)?;
+            let after = streaming::chunk_digests(&mut edited.as_slice(), strategy)?;
+            let unchanged = after.iter().filter(|digest| before.contains(digest)).count();
+            outln!("   🧩 {}: {}/{} chunks unchanged after the insert", name, unchanged, after.len());
+        }
+        
+        // Content-defined containers decrypt like any other stream
+        let source_path = self.temp_dir.join("backup.img");
+        let encrypted_path = self.temp_dir.join("backup.img.sealstream");
+        fs::write(&source_path, &edited)?;
+        self.encrypt_file_streaming(&source_path, &encrypted_path, strategies[1].1)?;
+        
+        let mut output = Vec::new();
+        self.decrypt_to_writer(&encrypted_path, &mut output)?;
+        if output == edited {
+            outln!("   ✅ Content-defined stream round-trips");
+        } else {
+            anyhow::bail!("❌ Content-defined stream did not round trip");
+        }
+        
+        Ok(())
+    }
+    
+    pub fn demo_csv_records(&self) -> Result<()> {
+        outln!("\n🚀 === CSV Field Encryption Demo ===");
+        
+        let source_path = self.temp_dir.join("customers.csv");
+        let encrypted_path = self.temp_dir.join("customers.sealed.csv");
+        let decrypted_path = output_path_for(&source_path, DECRYPTED_NAME_TEMPLATE, 1);
+        let content = "name,email,ssn,city\n\
+                       Alice,alice@example.com,123-45-6789,Lisbon\n\
+                       Bob,bob@example.com,987-65-4321,Hanoi\n";
+        fs::write(&source_path, content)?;
+        outln!("📄 Created: customers.csv (name and city stay queryable)");
+        
+        let sensitive = vec!["email".to_string(), "ssn".to_string()];
+        self.encrypt_csv(&source_path, &encrypted_path, "customers", &sensitive)?;
+        
+        // Cleartext columns can still be filtered without any keys
+        let mut reader = csv::Reader::from_path(&encrypted_path)?;
+        for record in reader.records() {
+            let record = record?;
+            outln!("   🔍 {} in {}: email sealed ({} base64 chars)", &record[0], &record[3], record[1].len());
+        }
+        
+        self.decrypt_csv(&encrypted_path, &decrypted_path, "customers", &sensitive)?;
+        if fs::read_to_string(&decrypted_path)? == content {
+            outln!("   ✅ CSV restored exactly");
+        } else {
+            anyhow::bail!("❌ Decrypted CSV does not match the original");
+        }
+        
+        Ok(())
+    }
+    
+    pub fn demo_audit_log(&self) -> Result<()> {
+        outln!("\n🚀 === Encrypted Audit Log Demo ===");
+        
+        let log_path = self.temp_dir.join("audit.log.seal");
+        let events = [
+            "2024-01-15T10:30:00Z alice logged in",
+            "2024-01-15T10:31:12Z alice exported report.pdf",
+            "2024-01-15T10:45:03Z bob changed billing settings",
+        ];
+        
+        for event in &events {
+            self.append_record(&log_path, event.as_bytes())?;
+            outln!("   ➕ Appended: {}", event);
+        }
+        outln!("   📏 Log size: {} bytes", fs::metadata(&log_path)?.len());
+        
+        let records = self.read_log(&log_path)?;
+        if records.len() == events.len() && records.iter().zip(&events).all(|(record, event)| record == event.as_bytes()) {
+            outln!("   ✅ {} records read back in order", records.len());
+        } else {
+            anyhow::bail!("❌ Audit log did not round-trip");
+        }
+        
+        Ok(())
+    }
+    
+    pub fn demo_directory_manifest(&self) -> Result<()> {
+        outln!("\n🚀 === Encrypted Directory Structure Demo ===");
+        
+        let tree = self.temp_dir.join("tree");
+        let sealed = self.temp_dir.join("tree.sealed");
+        let restored = self.temp_dir.join("tree.restored");
+        fs::create_dir_all(tree.join("hr/salaries"))?;
+        fs::write(tree.join("hr/salaries/2024.csv"), "alice,120000\nbob,95000\n")?;
+        fs::write(tree.join("hr/offboarding.txt"), "carol leaves on Friday")?;
+        fs::write(tree.join("readme.txt"), "Quarterly records")?;
+        
+        let sealed_files = self.encrypt_directory(&tree, &sealed, None)?;
+        if sealed_files.failed() > 0 {
+            anyhow::bail!("❌ {} file(s) of the tree failed to encrypt", sealed_files.failed());
+        }
+        let mut names: Vec<String> = fs::read_dir(&sealed)?
+            .map(|entry| Ok(entry?.file_name().to_string_lossy().into_owned()))
+            .collect::<Result<_>>()?;
+        names.sort();
+        outln!("   👀 An observer sees only: {}", names.join(", "));
+        
+        let restored_files = self.decrypt_directory(&sealed, &restored)?;
+        if restored_files.failed() == 0
+            && fs::read(restored.join("hr/salaries/2024.csv"))? == fs::read(tree.join("hr/salaries/2024.csv"))?
+        {
+            outln!("   ✅ {} file(s) restored to their original paths", restored_files.succeeded());
+        } else {
+            anyhow::bail!("❌ Directory did not round-trip");
+        }
+        
+        outln!("\n🌳 The same tree mirrored file by file, each sealed to its path:");
+        let mirrored = self.temp_dir.join("tree.mirrored");
+        let encrypted = self.encrypt_tree(&tree, &mirrored, None)?;
+        let decrypted = self.decrypt_tree(&mirrored, &self.temp_dir.join("tree.mirrored.restored"))?;
+        if encrypted.failed() > 0 || decrypted.failed() > 0 || decrypted.bytes() != encrypted.bytes() {
+            anyhow::bail!("❌ Mirrored tree did not round-trip");
+        }
+        outln!("   👀 Here an observer sees every name, e.g. hr/salaries/2024.csv.seal");
+        
+        Ok(())
+    }
+    
+    pub fn demo_serialized_keys(&self) -> Result<()> {
+        outln!("\n🚀 === Decrypting With Keys From Remote Servers ===");
+        
+        let file_path = self.temp_dir.join("remote.txt");
+        fs::write(&file_path, b"Decrypted with keys that arrived over the wire")?;
+        let encrypted_path = self.temp_dir.join("remote.txt.seal");
+        self.encrypt_file(&file_path, &encrypted_path)?;
+        let encrypted_object = SealContainer::from_bytes(&fs::read(&encrypted_path)?)?.encrypted_object;
+        
+        // What each key server would send back: the user secret key as bytes
+        let full_id = create_full_id(&encrypted_object.package_id, &encrypted_object.id);
+        let responses: Vec<(ObjectID, Vec<u8>)> = (0..self.threshold as usize)
+            .map(|i| {
+                let user_secret_key = self.counter.extract(&self.master_keys[i], &full_id);
+                (self.key_servers[i], user_secret_key.to_byte_array().to_vec())
+            })
+            .collect();
+        for (server_id, bytes) in &responses {
+            outln!("   📨 {} bytes from server {}", bytes.len(), server_id);
+        }
+        
+        let decrypted = self.decrypt_with_serialized_keys(&encrypted_object, &responses)?;
+        outln!("   ✅ Decrypted: {}", String::from_utf8_lossy(&decrypted));
+        
+        let mut truncated = responses.clone();
+        truncated[0].1.pop();
+        match self.decrypt_with_serialized_keys(&encrypted_object, &truncated) {
+            Ok(_) => anyhow::bail!("❌ Truncated key was accepted"),
+            Err(e) => outln!("   ✅ Truncated key rejected: {}", e),
+        }
         
         Ok(())
     }
     
+    pub fn demo_line_integrity(&self) -> Result<()> {
+        outln!("\n🚀 === Per-Line Integrity Demo ===");
+        
+        let source_path = self.temp_dir.join("config.diff");
+        let sealed_path = output_path_for(&source_path, &self.name_template, 1);
+        let decrypted_path = output_path_for(&source_path, DECRYPTED_NAME_TEMPLATE, 1);
+        let content = "--- a/app.toml\n+++ b/app.toml\n@@ -1,2 +1,2 @@\n-port = 80\n+port = 8080\n log_level = \"info\"\n";
+        fs::write(&source_path, content)?;
+        outln!("📄 Created: config.diff ({} lines)", content.lines().count());
+        
+        self.encrypt_text_lines(&source_path, &sealed_path)?;
+        self.decrypt_text_lines(&sealed_path, &decrypted_path)?;
+        if fs::read_to_string(&decrypted_path)? == content {
+            outln!("   ✅ Diff restored exactly");
+        } else {
+            anyhow::bail!("❌ Decrypted diff does not match the original");
+        }
+        
+        // Corrupt one character of line 5's ciphertext (record 5 after the header)
+        let mut records: Vec<String> = fs::read_to_string(&sealed_path)?.lines().map(str::to_string).collect();
+        let flipped = if records[5].starts_with('A') { "B" } else { "A" };
+        records[5].replace_range(..1, flipped);
+        fs::write(&sealed_path, records.join("\n") + "\n")?;
+        
+        outln!("🔨 Tampered with the ciphertext of line 5");
+        match self.decrypt_text_lines(&sealed_path, &decrypted_path) {
+            Ok(_) => anyhow::bail!("❌ Tampered diff decrypted"),
+            Err(e) => outln!("   ✅ Decryption correctly failed: {}", e),
+        }
+        
+        Ok(())
+    }
+    
+    /// Remove the temp directory, retrying a few times since files another
+    /// process still holds open (antivirus, an indexer, or a viewer on
+    /// Windows) usually become removable shortly after. The error names
+    /// every path that is left and why.
+    pub fn cleanup(&self) -> Result<()> {
+        self.remove_temp_dir(CLEANUP_ATTEMPTS)
+    }
+    
+    fn remove_temp_dir(&self, attempts: usize) -> Result<()> {
+        let mut failures = Vec::new();
+        for attempt in 1..=attempts {
+            failures = remove_tree(&self.temp_dir);
+            if failures.is_empty() {
+                return Ok(());
+            }
+            if attempt < attempts {
+                std::thread::sleep(CLEANUP_RETRY_DELAY);
+            }
+        }
+        
+        let details: Vec<String> = failures
+            .iter()
+            .map(|(path, e)| format!("{}: {}", path.display(), e))
+            .collect();
+        anyhow::bail!(
+            "Could not remove {} path(s) under {}: {}",
+            failures.len(),
+            self.temp_dir.display(),
+            details.join("; ")
+        )
+    }
+    
     pub fn run_all_demos(&self) -> Result<()> {
         self.demo_file_encryption()?;
-        self.demo_batch_encryption()?;
+        self.demo_batch_encryption(None)?;
         self.demo_file_metadata()?;
+        self.demo_threshold_upgrade()?;
+        self.demo_symmetric_rekey()?;
+        self.demo_streaming()?;
+        self.demo_content_defined_chunking()?;
+        self.demo_csv_records()?;
+        self.demo_serialized_keys()?;
+        self.demo_line_integrity()?;
+        self.demo_audit_log()?;
+        self.demo_directory_manifest()?;
         
-        println!("\n🎉 All file encryption demos completed successfully!");
+        outln!("\n🎉 All file encryption demos completed successfully!");
         
         // Clean up
-        println!("\n🧹 Cleaning up temporary files...");
-        fs::remove_dir_all(&self.temp_dir)?;
-        println!("   ✅ Cleanup complete");
+        outln!("\n🧹 Cleaning up temporary files...");
+        match self.cleanup() {
+            Ok(()) => outln!("   ✅ Cleanup complete"),
+            Err(e) => outln!("   ⚠️  {:#}", e),
+        }
         
         Ok(())
     }
 }
 
+/// Output name in `decrypt_directory_parallel`: `.seal` stripped if present.
+fn decrypted_name(input: &Path) -> PathBuf {
+    let name = input.file_name().unwrap_or_default().to_string_lossy();
+    PathBuf::from(name.strip_suffix(".seal").filter(|stem| !stem.is_empty()).unwrap_or(&name))
+}
+
+/// `<output>.<suffix>`, next to `output`.
+fn sidecar_path(output: &Path, suffix: &str) -> PathBuf {
+    let mut name = output.as_os_str().to_owned();
+    name.push(".");
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// `file:<name>`, the identity a file is encrypted to.
+fn file_identity(file_path: &Path) -> Vec<u8> {
+    let filename = file_path.file_name().unwrap_or_default().to_string_lossy();
+    format!("file:{}", filename).into_bytes()
+}
+
+/// The object in a container or legacy file, for operations that re-encrypt
+/// it and so can't carry an escrow envelope along.
+fn read_unescrowed(path: &Path) -> Result<crypto::EncryptedObject> {
+    let container = SealContainer::from_bytes(&fs::read(path)?)?;
+    if container.envelope.is_some() {
+        anyhow::bail!("{} is escrowed; decrypt it with the escrow tooling instead", path.display());
+    }
+    Ok(container.encrypted_object)
+}
+
+/// `file:<path>` for a file of `encrypt_tree`, `/`-separated on every platform.
+fn tree_identity(relative: &Path) -> Vec<u8> {
+    let path: Vec<_> = relative.components().map(|component| component.as_os_str().to_string_lossy()).collect();
+    format!("file:{}", path.join("/")).into_bytes()
+}
+
+/// `dir:<tree id>` for the manifest, `dir:<tree id>:<n>` for file `n`.
+fn directory_identity(tree_id: &str, index: Option<usize>) -> Vec<u8> {
+    match index {
+        Some(index) => format!("dir:{}:{}", tree_id, index).into_bytes(),
+        None => format!("dir:{}", tree_id).into_bytes(),
+    }
+}
+
+fn directory_file_name(index: usize) -> String {
+    format!("{:08}.seal", index)
+}
+
+/// Delete `path` and everything under it (shredding files with `--shred`),
+/// carrying on past failures. A directory is only removed once everything
+/// in it was; what couldn't be removed comes back with its error.
+/// Already-missing paths are fine.
+fn remove_tree(path: &Path) -> Vec<(PathBuf, std::io::Error)> {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Vec::new(),
+        Err(e) => return vec![(path.to_path_buf(), e)],
+    };
+    if !metadata.is_dir() {
+        let removed = if shred::enabled() && metadata.is_file() { shred::shred_file(path) } else { fs::remove_file(path) };
+        return match removed {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => vec![(path.to_path_buf(), e)],
+            _ => Vec::new(),
+        };
+    }
+    
+    let entries = match fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(e) => return vec![(path.to_path_buf(), e)],
+    };
+    let mut failures = Vec::new();
+    for entry in entries {
+        match entry {
+            Ok(entry) => failures.extend(remove_tree(&entry.path())),
+            Err(e) => failures.push((path.to_path_buf(), e)),
+        }
+    }
+    if failures.is_empty() {
+        if let Err(e) = fs::remove_dir(path) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                failures.push((path.to_path_buf(), e));
+            }
+        }
+    }
+    failures
+}
+
+/// Every regular file under `dir`, recursively, with its size, sorted by path.
+pub fn files_under(dir: &Path) -> Result<Vec<(PathBuf, u64)>> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                pending.push(entry.path());
+            } else if file_type.is_file() {
+                files.push((entry.path(), entry.metadata()?.len()));
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn log_record_identity(file: &Path, sequence: u64) -> Vec<u8> {
+    let filename = file.file_name().unwrap_or_default().to_string_lossy();
+    format!("log:{}:{}", filename, sequence).into_bytes()
+}
+
+/// Split files of the given sizes into consecutive groups of at most
+/// `max_workers` whose combined cost (twice the size) fits `max_memory`.
+pub fn batch_groups(sizes: &[u64], max_memory: Option<usize>, max_workers: usize) -> Vec<std::ops::Range<usize>> {
+    let budget = max_memory.map_or(u64::MAX, |bytes| bytes as u64);
+    let mut groups = Vec::new();
+    let (mut start, mut in_flight) = (0, 0u64);
+    
+    for (i, &size) in sizes.iter().enumerate() {
+        let cost = size.saturating_mul(2);
+        if i > start && (i - start >= max_workers.max(1) || in_flight.saturating_add(cost) > budget) {
+            groups.push(start..i);
+            (start, in_flight) = (i, 0);
+        }
+        in_flight = in_flight.saturating_add(cost);
+    }
+    if start < sizes.len() {
+        groups.push(start..sizes.len());
+    }
+    groups
+}
+
+/// Render `template` for `input`, in the same directory.
+///
+/// Placeholders: `{stem}` (file name up to the last dot), `{ext}` (after
+/// it) and `{n}` (`counter`, for numbering a batch). For inputs without an
+/// extension `.{ext}` disappears entirely, so `notes` becomes `notes.seal`
+/// rather than `notes..seal`.
+pub fn output_path_for(input: &Path, template: &str, counter: usize) -> PathBuf {
+    let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match input.extension() {
+        Some(ext) => template.replace("{ext}", &ext.to_string_lossy()),
+        None => template.replace(".{ext}", "").replace("{ext}", ""),
+    };
+    let name = name.replace("{stem}", &stem).replace("{n}", &counter.to_string());
+    input.with_file_name(name)
+}
+
+/// Refuse `(input, output)` jobs that would write two inputs to one output,
+/// as a name template without `{ext}` or `{n}` does for `a.txt` and `a.md`.
+pub fn check_distinct_outputs(jobs: &[(PathBuf, PathBuf)]) -> Result<()> {
+    let mut seen: HashMap<&Path, &Path> = HashMap::new();
+    for (input, output) in jobs {
+        if let Some(earlier) = seen.insert(output, input) {
+            anyhow::bail!(
+                "{} and {} would both be written to {}; add {{ext}} or {{n}} to the name template",
+                earlier.display(),
+                input.display(),
+                output.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Discards everything written to it, keeping only a running hash.
+struct HashingSink(Sha3_256);
+
+impl Write for HashingSink {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.update(buf);
+        Ok(buf.len())
+    }
+    
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// `table` comes first and has no `:`, so no column name can make two
+/// tables' identities collide.
+pub fn csv_cell_identity(table: &str, column: &str, row: usize) -> Vec<u8> {
+    format!("csv:{}:{}:row:{}", table, column, row).into_bytes()
+}
+
+fn check_csv_table(table: &str) -> Result<()> {
+    if table.is_empty() || table.contains(':') {
+        anyhow::bail!("CSV table name {:?} must be non-empty and contain no ':'", table);
+    }
+    Ok(())
+}
+
+fn csv_column_indices<'a>(headers: &csv::StringRecord, columns: &'a [String]) -> Result<Vec<(usize, &'a str)>> {
+    columns
+        .iter()
+        .map(|column| {
+            headers
+                .iter()
+                .position(|header| header == column)
+                .map(|index| (index, column.as_str()))
+                .ok_or_else(|| anyhow::anyhow!("Column '{}' not found in CSV header", column))
+        })
+        .collect()
+}
+
 impl Drop for FileDemo {
     fn drop(&mut self) {
-        let _ = fs::remove_dir_all(&self.temp_dir);
+        if let Err(e) = self.remove_temp_dir(1) {
+            tracing::debug!("Temp dir cleanup failed: {:#}", e);
+        }
     }
 }
 
@@ -0,0 +1,245 @@
+//! Per-file encryption mode and compression for `encrypt_directory`.
+//!
+//! Text shrinks a lot under zstd while JPEGs and archives don't, so each file
+//! gets the treatment of the first rule whose pattern matches its name:
+//!
+//! ```toml
+//! [[rule]]
+//! pattern = "*.json"
+//! mode = "aes-256-gcm"
+//! compression = "zstd"
+//!
+//! [[rule]]
+//! pattern = "*.jpg"
+//! mode = "aes-256-gcm"
+//! compression = "none"
+//! ```
+//!
+//! Patterns are `*.<suffix>` or an exact file name, compared without regard
+//! to case. Files matching no rule get AES-256-GCM without compression.
+//!
+//! The treatment is recorded as the AAD of each sealed file, so it is
+//! authenticated along with the data and decryption needs no config. Files
+//! sealed before this carry no AAD and were never compressed.
+//!
+//! A `FileFilter` leaves files out altogether, by size or extension; they
+//! are listed in the directory manifest with the reason instead.
+
+use anyhow::{Context, Result};
+use crypto::{Ciphertext, EncryptedObject, EncryptionInput};
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::path::Path;
+
+use crate::directory_manifest::SkipReason;
+
+const ZSTD_LEVEL: i32 = 3;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    #[serde(rename = "aes-256-gcm")]
+    Aes256Gcm,
+    #[serde(rename = "hmac-256-ctr")]
+    Hmac256Ctr,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Compression {
+    None,
+    Zstd,
+}
+
+/// How one file is sealed.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct FileTreatment {
+    pub mode: Mode,
+    pub compression: Compression,
+}
+
+impl Default for FileTreatment {
+    fn default() -> Self {
+        FileTreatment { mode: Mode::Aes256Gcm, compression: Compression::None }
+    }
+}
+
+impl FileTreatment {
+    /// The encryption input for `data`, compressed as configured and with
+    /// the treatment as AAD.
+    pub fn input(&self, data: Vec<u8>) -> Result<EncryptionInput> {
+        let data = match self.compression {
+            Compression::None => data,
+            Compression::Zstd => zstd::bulk::compress(&data, ZSTD_LEVEL)?,
+        };
+        let aad = Some(serde_json::to_vec(self)?);
+        Ok(match self.mode {
+            Mode::Aes256Gcm => EncryptionInput::Aes256Gcm { data, aad },
+            Mode::Hmac256Ctr => EncryptionInput::Hmac256Ctr { data, aad },
+        })
+    }
+
+    /// The treatment `encrypted_object` records. Only trustworthy once the
+    /// object has decrypted, which authenticates the AAD.
+    pub fn of(encrypted_object: &EncryptedObject) -> Result<Self> {
+        let (mode, aad) = match &encrypted_object.ciphertext {
+            Ciphertext::Aes256Gcm { aad, .. } => (Mode::Aes256Gcm, aad),
+            Ciphertext::Hmac256Ctr { aad, .. } => (Mode::Hmac256Ctr, aad),
+            Ciphertext::Plain => anyhow::bail!("Directory files can't be Plain-mode objects"),
+        };
+        let Some(aad) = aad else {
+            return Ok(FileTreatment { mode, ..Default::default() });
+        };
+        let treatment: FileTreatment = serde_json::from_slice(aad).context("Corrupt file treatment")?;
+        if treatment.mode != mode {
+            anyhow::bail!("File records mode {:?} but was sealed with {:?}", treatment.mode, mode);
+        }
+        Ok(treatment)
+    }
+
+    /// Undo the compression of a decrypted file expected to hold `size`
+    /// bytes, decompressing no more than that.
+    pub fn restore(&self, plaintext: Vec<u8>, size: u64) -> Result<Vec<u8>> {
+        match self.compression {
+            Compression::None => Ok(plaintext),
+            Compression::Zstd => {
+                let mut decompressed = Vec::new();
+                zstd::stream::read::Decoder::new(plaintext.as_slice())?
+                    .take(size + 1)
+                    .read_to_end(&mut decompressed)
+                    .context("Corrupt compressed file")?;
+                Ok(decompressed)
+            }
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Rule {
+    pattern: String,
+    #[serde(flatten)]
+    treatment: FileTreatment,
+}
+
+/// Ordered pattern rules; the first match wins.
+#[derive(Deserialize)]
+pub struct FileModes {
+    #[serde(rename = "rule", default)]
+    rules: Vec<Rule>,
+}
+
+impl Default for FileModes {
+    /// Compress common text formats, leave everything else as is.
+    fn default() -> Self {
+        let text = ["*.txt", "*.json", "*.csv", "*.log", "*.md", "*.xml", "*.html", "*.toml", "*.yaml", "*.yml"];
+        let zstd = FileTreatment { mode: Mode::Aes256Gcm, compression: Compression::Zstd };
+        FileModes {
+            rules: text.iter().map(|pattern| Rule { pattern: pattern.to_string(), treatment: zstd }).collect(),
+        }
+    }
+}
+
+impl FileModes {
+    pub fn from_toml(toml_str: &str) -> Result<Self> {
+        toml::from_str(toml_str).context("Invalid file mode config")
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read file mode config {}", path.display()))?;
+        Self::from_toml(&contents)
+    }
+
+    pub fn treatment_for(&self, path: &Path) -> FileTreatment {
+        let name = path.file_name().unwrap_or_default().to_string_lossy().to_lowercase();
+        self.rules
+            .iter()
+            .find(|rule| {
+                let pattern = rule.pattern.to_lowercase();
+                match pattern.strip_prefix('*') {
+                    Some(suffix) => name.ends_with(suffix),
+                    None => name == pattern,
+                }
+            })
+            .map_or_else(FileTreatment::default, |rule| rule.treatment)
+    }
+}
+
+/// Files `encrypt_directory` leaves out. The default keeps everything.
+#[derive(Default)]
+pub struct FileFilter {
+    pub max_size: Option<u64>,
+    /// Lower case, without the leading dot; may span dots, e.g. `tar.gz`.
+    pub exclude_ext: Vec<String>,
+}
+
+impl FileFilter {
+    /// Extensions may be given with or without the dot, in any case.
+    pub fn new(max_size: Option<u64>, exclude_ext: &[String]) -> Self {
+        FileFilter {
+            max_size,
+            exclude_ext: exclude_ext.iter().map(|ext| ext.trim().trim_start_matches('.').to_lowercase()).collect(),
+        }
+    }
+
+    /// Why the file at `path`, `size` bytes long, is left out, if it is.
+    pub fn skip_reason(&self, path: &Path, size: u64) -> Option<SkipReason> {
+        let name = path.file_name().unwrap_or_default().to_string_lossy().to_lowercase();
+        if let Some(ext) = self.exclude_ext.iter().find(|ext| name.ends_with(&format!(".{}", ext))) {
+            return Some(SkipReason::ExcludedExtension(ext.clone()));
+        }
+        match self.max_size {
+            Some(limit) if size > limit => Some(SkipReason::TooLarge { limit }),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_first_matching_rule_wins() -> Result<()> {
+        let modes = FileModes::from_toml(
+            r#"
+            [[rule]]
+            pattern = "*.tar.gz"
+            mode = "aes-256-gcm"
+            compression = "none"
+
+            [[rule]]
+            pattern = "*.GZ"
+            mode = "hmac-256-ctr"
+            compression = "none"
+
+            [[rule]]
+            pattern = "Makefile"
+            mode = "aes-256-gcm"
+            compression = "zstd"
+            "#,
+        )?;
+        assert_eq!(modes.treatment_for(Path::new("dist/app.tar.gz")).mode, Mode::Aes256Gcm);
+        assert_eq!(modes.treatment_for(Path::new("logs/old.gz")).mode, Mode::Hmac256Ctr);
+        assert_eq!(modes.treatment_for(Path::new("src/makefile")).compression, Compression::Zstd);
+        assert_eq!(modes.treatment_for(Path::new("photo.jpg")), FileTreatment::default());
+
+        assert_eq!(FileModes::default().treatment_for(Path::new("a.JSON")).compression, Compression::Zstd);
+        assert!(FileModes::from_toml("[[rule]]\npattern = \"*.x\"\nmode = \"des\"\ncompression = \"none\"").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_filter_by_size_and_extension() {
+        let filter = FileFilter::new(Some(1000), &[".MP4".to_string(), "tar.gz".to_string()]);
+        assert_eq!(filter.skip_reason(Path::new("notes.txt"), 1000), None);
+        assert_eq!(filter.skip_reason(Path::new("notes.txt"), 1001), Some(SkipReason::TooLarge { limit: 1000 }));
+        assert_eq!(
+            filter.skip_reason(Path::new("media/Talk.mp4"), 10),
+            Some(SkipReason::ExcludedExtension("mp4".to_string()))
+        );
+        assert!(filter.skip_reason(Path::new("backup.tar.gz"), 10).is_some());
+        assert_eq!(filter.skip_reason(Path::new("backup.gz"), 10), None);
+        assert_eq!(filter.skip_reason(Path::new("mp4"), 10), None);
+        assert_eq!(FileFilter::default().skip_reason(Path::new("huge.mp4"), u64::MAX), None);
+    }
+}
@@ -0,0 +1,139 @@
+//! Interop fixtures for other Seal implementations (`gen-fixtures`).
+//!
+//! The output directory holds:
+//!
+//! - `fleet.json`: the session, master keys included
+//! - `<mode>-t<threshold>.bcs`: one `bcs(EncryptedObject)` per mode and
+//!   threshold, without this tool's container header
+//! - `index.json`: a `FixtureIndex` naming each object's identity, AAD and
+//!   expected decryption
+//!
+//! The fleet, identities, plaintexts and AAD all come from the seed, so they
+//! are identical on every run. The ciphertext bytes are not: `seal_encrypt`
+//! samples its key and shares internally (see
+//! `SealSession::generate_with_rng`), so a consumer should decrypt the
+//! objects and compare against the index rather than compare files. For
+//! Plain mode, the expected output is the derived key, which changes with
+//! the object.
+
+use anyhow::Result;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::atomic;
+use crate::session::{self, SealSession};
+use crate::testutil;
+
+pub const FLEET_FILE: &str = "fleet.json";
+pub const INDEX_FILE: &str = "index.json";
+
+const NUM_SERVERS: usize = 5;
+const THRESHOLDS: [u8; 2] = [2, 3];
+const MODES: [&str; 3] = ["aes-256-gcm", "hmac-256-ctr", "plain"];
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FixtureIndex {
+    /// Seed the fixtures were generated from (hex).
+    pub seed: String,
+    pub fleet: String,
+    pub package_id: String,
+    pub objects: Vec<FixtureObject>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FixtureObject {
+    /// File name within the fixture directory.
+    pub file: String,
+    pub mode: String,
+    pub threshold: u8,
+    /// Identity bytes (hex); always UTF-8 text here.
+    pub identity: String,
+    /// AAD (hex), for the modes that take one.
+    pub aad: Option<String>,
+    /// Expected decryption (hex): the plaintext, or the key for Plain mode.
+    pub expected: String,
+}
+
+/// Write the fixtures for `seed` to `out`, creating it if needed.
+pub fn generate(seed: [u8; 32], out: &Path) -> Result<FixtureIndex> {
+    let mut rng = StdRng::from_seed(seed);
+    let fleet = SealSession::generate_with_rng(NUM_SERVERS, THRESHOLDS[0], &mut rng)?;
+    std::fs::create_dir_all(out)?;
+    atomic::write_file(&out.join(FLEET_FILE), fleet.to_json(true)?.as_bytes())?;
+
+    let mut objects = Vec::new();
+    for threshold in THRESHOLDS {
+        let mut session = fleet.clone();
+        session.threshold = threshold;
+        for mode in MODES {
+            let identity = format!("fixture:{}:t{}", mode, threshold).into_bytes();
+            let plaintext = testutil::random_bytes(64, &mut rng);
+            let aad = testutil::random_bytes(16, &mut rng);
+            let plain = mode == "plain";
+            let input =
+                session::encryption_input(mode, (!plain).then(|| plaintext.clone()), (!plain).then(|| aad.clone()))?;
+            let (encrypted_object, key) = session.encrypt(&identity, input)?;
+
+            let file = format!("{}-t{}.bcs", mode, threshold);
+            atomic::write_file(&out.join(&file), &bcs::to_bytes(&encrypted_object)?)?;
+            objects.push(FixtureObject {
+                file,
+                mode: mode.to_string(),
+                threshold,
+                identity: hex::encode(&identity),
+                aad: (!plain).then(|| hex::encode(&aad)),
+                expected: hex::encode(if plain { &key[..] } else { &plaintext }),
+            });
+        }
+    }
+
+    let index = FixtureIndex {
+        seed: hex::encode(seed),
+        fleet: FLEET_FILE.to_string(),
+        package_id: fleet.package_id.to_string(),
+        objects,
+    };
+    atomic::write_file(&out.join(INDEX_FILE), serde_json::to_string_pretty(&index)?.as_bytes())?;
+    Ok(index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crypto::EncryptedObject;
+
+    #[test]
+    fn test_fixtures_reproducible_and_decryptable() -> Result<()> {
+        let first = std::env::temp_dir().join("fixtures_test_a");
+        let second = std::env::temp_dir().join("fixtures_test_b");
+        let index = generate([7; 32], &first)?;
+        generate([7; 32], &second)?;
+
+        assert_eq!(std::fs::read(first.join(FLEET_FILE))?, std::fs::read(second.join(FLEET_FILE))?);
+        let reread: FixtureIndex = serde_json::from_slice(&std::fs::read(second.join(INDEX_FILE))?)?;
+        assert_eq!(reread.objects.len(), THRESHOLDS.len() * MODES.len());
+        for (a, b) in index.objects.iter().zip(&reread.objects) {
+            assert_eq!((&a.file, &a.identity, &a.aad), (&b.file, &b.identity, &b.aad));
+            if a.mode != "plain" {
+                assert_eq!(a.expected, b.expected);
+            }
+        }
+
+        let fleet = SealSession::from_json(&std::fs::read_to_string(first.join(FLEET_FILE))?)?;
+        for object in &index.objects {
+            let encrypted_object: EncryptedObject = bcs::from_bytes(&std::fs::read(first.join(&object.file))?)?;
+            assert_eq!(encrypted_object.threshold, object.threshold);
+            assert_eq!(hex::encode(&encrypted_object.id), object.identity);
+            assert_eq!(hex::encode(fleet.decrypt(&encrypted_object)?), object.expected);
+        }
+
+        let other = generate([8; 32], &second)?;
+        assert_ne!(other.package_id, index.package_id);
+        assert_ne!(other.objects[0].expected, index.objects[0].expected);
+        let _ = std::fs::remove_dir_all(&first);
+        let _ = std::fs::remove_dir_all(&second);
+        Ok(())
+    }
+}
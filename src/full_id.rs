@@ -0,0 +1,106 @@
+//! Earlier layouts of the full ID keys are extracted for.
+//!
+//! Seal derives user secret keys for `create_full_id(package_id, id)`, so a
+//! change to that byte layout strands every object encrypted before it:
+//! the right master keys extract keys for the wrong point. `decrypt
+//! --full-id-version` rebuilds the full ID the old way instead.
+//!
+//! Scheme versions:
+//! - 1: `package_id | id`, as used before the full ID carried a domain
+//!   separation tag
+//! - 2: `len(DST) (u8) | DST | package_id | id` with
+//!   `DST = "SUI-SEAL-IBE-BLS12381-00"`
+//!
+//! Without the option, decryption uses the linked crate's
+//! `crypto::create_full_id`.
+
+use anyhow::Result;
+use crypto::ObjectID;
+use std::fmt;
+
+const DST_V2: &[u8] = b"SUI-SEAL-IBE-BLS12381-00";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FullIdScheme {
+    V1,
+    V2,
+}
+
+impl FullIdScheme {
+    /// For `--full-id-version`.
+    pub fn parse(version: &str) -> Result<Self> {
+        match version.trim() {
+            "1" => Ok(FullIdScheme::V1),
+            "2" => Ok(FullIdScheme::V2),
+            other => anyhow::bail!("Unknown full ID scheme version \"{}\", expected 1 or 2", other),
+        }
+    }
+
+    pub fn full_id(&self, package_id: &ObjectID, id: &[u8]) -> Vec<u8> {
+        let mut full_id = Vec::new();
+        if *self == FullIdScheme::V2 {
+            full_id.push(DST_V2.len() as u8);
+            full_id.extend_from_slice(DST_V2);
+        }
+        full_id.extend_from_slice(package_id.as_ref());
+        full_id.extend_from_slice(id);
+        full_id
+    }
+}
+
+impl fmt::Display for FullIdScheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FullIdScheme::V1 => write!(f, "1"),
+            FullIdScheme::V2 => write!(f, "2"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::SealSession;
+    use crypto::{create_full_id, EncryptionInput};
+
+    #[test]
+    fn test_legacy_full_id_recovered() -> Result<()> {
+        let session = SealSession::generate(3, 2)?;
+
+        // A version 1 object: its shares are bound to `package_id | id`.
+        // Encryption always uses the linked crate's layout, which puts a
+        // fixed prefix before the package ID, so encrypt under the package
+        // and identity that make the current full ID equal the legacy one,
+        // then label the object with the legacy package and identity.
+        let prefix_len = create_full_id(&ObjectID::ZERO, b"").len() - ObjectID::LENGTH;
+        let mut legacy_package = create_full_id(&ObjectID::ZERO, b"")[..prefix_len].to_vec();
+        legacy_package.resize(ObjectID::LENGTH, 0xa1);
+        let legacy_package = ObjectID::from_bytes(&legacy_package)?;
+        let legacy_id = [b"file:ledger-2019.csv".as_slice(), &[0x5a; 32]].concat();
+        let legacy_full_id = FullIdScheme::V1.full_id(&legacy_package, &legacy_id);
+
+        let current_package = ObjectID::from_bytes(&legacy_full_id[prefix_len..prefix_len + ObjectID::LENGTH])?;
+        let current_id = legacy_full_id[prefix_len + ObjectID::LENGTH..].to_vec();
+        assert_eq!(create_full_id(&current_package, &current_id), legacy_full_id);
+
+        let mut encryptor = session.clone();
+        encryptor.package_id = current_package;
+        let (mut encrypted_object, _) = encryptor.encrypt(
+            &current_id,
+            EncryptionInput::Aes256Gcm { data: b"2019 ledger".to_vec(), aad: None },
+        )?;
+        encrypted_object.package_id = legacy_package;
+        encrypted_object.id = legacy_id.clone();
+
+        assert_eq!(session.decrypt_legacy(&encrypted_object, FullIdScheme::V1)?, b"2019 ledger");
+        assert!(session.decrypt_legacy(&encrypted_object, FullIdScheme::V2).is_err());
+        if create_full_id(&legacy_package, &legacy_id) != legacy_full_id {
+            assert!(session.decrypt(&encrypted_object).is_err());
+        }
+
+        let package = ObjectID::random();
+        assert_eq!(FullIdScheme::V2.full_id(&package, b"x")[..25], [&[24u8][..], DST_V2].concat());
+        assert!(FullIdScheme::parse("3").is_err());
+        Ok(())
+    }
+}
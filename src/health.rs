@@ -0,0 +1,74 @@
+//! Pre-flight checks that a fleet can actually serve keys.
+//!
+//! Only locally held master keys can be checked today: each server extracts a
+//! user secret key for a probe identity, which is then verified against the
+//! server's public key. This catches master/public key mix-ups before a long
+//! encryption job discovers them.
+
+use crypto::{create_full_id, ibe::{extract, verify_user_secret_key}, ObjectID};
+use std::time::{Duration, Instant};
+
+use crate::session::SealSession;
+
+const PROBE_IDENTITY: &[u8] = b"seal-demo/health-probe";
+
+pub struct ServerHealth {
+    pub server_id: ObjectID,
+    /// Time taken to extract and verify the probe key, or why it failed.
+    pub result: Result<Duration, String>,
+}
+
+impl ServerHealth {
+    pub fn is_up(&self) -> bool {
+        self.result.is_ok()
+    }
+}
+
+pub fn check_fleet(session: &SealSession) -> Vec<ServerHealth> {
+    let full_id = create_full_id(&session.package_id, PROBE_IDENTITY);
+
+    session
+        .key_servers
+        .iter()
+        .zip(&session.public_keys)
+        .enumerate()
+        .map(|(i, (server_id, public_key))| {
+            let result = match &session.master_keys {
+                Some(master_keys) => {
+                    let start = Instant::now();
+                    let user_secret_key = extract(&master_keys[i], &full_id);
+                    verify_user_secret_key(&user_secret_key, &full_id, public_key)
+                        .map(|_| start.elapsed())
+                        .map_err(|_| "extracted key does not verify against the public key".to_string())
+                }
+                None => Err("no local key material and no remote endpoint to probe".to_string()),
+            };
+            ServerHealth {
+                server_id: *server_id,
+                result,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crypto::ibe::generate_key_pair;
+    use rand::thread_rng;
+
+    #[test]
+    fn test_mismatched_key_reported_down() -> anyhow::Result<()> {
+        let mut session = SealSession::generate(3, 2)?;
+        assert!(check_fleet(&session).iter().all(ServerHealth::is_up));
+
+        let (wrong_master_key, _) = generate_key_pair(&mut thread_rng());
+        session.master_keys.as_mut().unwrap()[1] = wrong_master_key;
+
+        let report = check_fleet(&session);
+        assert!(report[0].is_up());
+        assert!(!report[1].is_up());
+        assert!(report[2].is_up());
+        Ok(())
+    }
+}
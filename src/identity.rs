@@ -0,0 +1,94 @@
+//! Identity length limit.
+//!
+//! Identities are hashed to a curve point on every extract and carried in
+//! every encrypted object, so a multi-kilobyte identity (say, a whole
+//! metadata blob embedded as in `demo_file_metadata`) costs on both sides
+//! and may be refused by a real key server. The encrypt wrappers
+//! (`OpCounter::seal_encrypt` for the demos, `SealSession::encrypt` for the
+//! CLI) reject identities longer than the configured maximum;
+//! `hash_identity` is the escape hatch for callers that need arbitrarily
+//! long ones.
+
+use fastcrypto::hash::{HashFunction, Sha3_256};
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+pub const DEFAULT_MAX_IDENTITY_LEN: usize = 1024;
+
+static MAX_IDENTITY_LEN: AtomicUsize = AtomicUsize::new(DEFAULT_MAX_IDENTITY_LEN);
+
+/// Returned (wrapped in `anyhow::Error`) by the encrypt wrappers.
+#[derive(Debug, PartialEq, Eq)]
+pub struct IdentityTooLong {
+    pub len: usize,
+    pub max: usize,
+}
+
+impl fmt::Display for IdentityTooLong {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Identity is {} bytes, over the {} byte limit; shorten it or hash it with `hash_identity`",
+            self.len, self.max
+        )
+    }
+}
+
+impl std::error::Error for IdentityTooLong {}
+
+/// Change the limit for the rest of the process (`--max-identity-len`).
+pub fn set_max_identity_len(max: usize) {
+    MAX_IDENTITY_LEN.store(max, Ordering::Relaxed);
+}
+
+pub fn max_identity_len() -> usize {
+    MAX_IDENTITY_LEN.load(Ordering::Relaxed)
+}
+
+fn check_against(identity: &[u8], max: usize) -> Result<(), IdentityTooLong> {
+    if identity.len() > max {
+        return Err(IdentityTooLong { len: identity.len(), max });
+    }
+    Ok(())
+}
+
+pub fn check_identity_len(identity: &[u8]) -> Result<(), IdentityTooLong> {
+    check_against(identity, max_identity_len())
+}
+
+/// `sha3:<hex SHA3-256>`, a fixed 69-byte stand-in for an identity of any
+/// length. Decryptors must hash the same way to derive the same identity.
+pub fn hash_identity(identity: &[u8]) -> Vec<u8> {
+    format!("sha3:{}", hex::encode(Sha3_256::digest(identity).digest)).into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identity_length_boundary() {
+        assert_eq!(check_against(&[b'a'; 64], 64), Ok(()));
+        assert_eq!(check_against(&[b'a'; 65], 64), Err(IdentityTooLong { len: 65, max: 64 }));
+        assert_eq!(check_against(b"", 0), Ok(()));
+
+        assert_eq!(check_identity_len(&vec![b'a'; DEFAULT_MAX_IDENTITY_LEN]), Ok(()));
+        assert!(check_identity_len(&vec![b'a'; DEFAULT_MAX_IDENTITY_LEN + 1]).is_err());
+
+        // Enforced by the encrypt wrappers
+        let session = crate::session::SealSession::generate(3, 2).unwrap();
+        assert!(session.encrypt(&vec![b'a'; DEFAULT_MAX_IDENTITY_LEN], crypto::EncryptionInput::Plain).is_ok());
+        let err = session
+            .encrypt(&vec![b'a'; DEFAULT_MAX_IDENTITY_LEN + 1], crypto::EncryptionInput::Plain)
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<IdentityTooLong>(),
+            Some(&IdentityTooLong { len: DEFAULT_MAX_IDENTITY_LEN + 1, max: DEFAULT_MAX_IDENTITY_LEN })
+        );
+
+        let hashed = hash_identity(&vec![b'a'; 100_000]);
+        assert_eq!(hashed.len(), 69);
+        assert_eq!(hashed, hash_identity(&vec![b'a'; 100_000]));
+        assert_ne!(hashed, hash_identity(&vec![b'a'; 99_999]));
+    }
+}
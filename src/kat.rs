@@ -0,0 +1,278 @@
+//! Known-answer tests for the decrypt pipeline (`kat`).
+//!
+//! Each vector in `kat/vectors.json` fixes a seed, an identity and a message,
+//! and records what the pipeline produced from them when the vectors were
+//! generated:
+//!
+//! - the fleet the seed yields: package ID, server IDs and public keys
+//! - the full ID of the identity and every server's user secret key for it
+//! - one sealed object (`bcs(EncryptedObject)`), its metadata, and what it
+//!   decrypts to
+//!
+//! `seal_encrypt` samples its randomness internally, so a fresh encryption
+//! can't be compared byte for byte; the stored object is decrypted instead.
+//! Any difference from the recorded answers, such as after a crypto
+//! dependency update, is reported field by field. `kat --regenerate`
+//! rewrites the file after an intended change.
+//!
+//! The answers are only as good as the build that wrote them: generate and
+//! commit them from a build linked against the `crypto` revision pinned in
+//! `Cargo.toml`, never from a stand-in. A missing file, or one without a
+//! vector for every case, fails `kat` and `test_checked_in_vectors_pass`
+//! rather than passing with nothing checked.
+
+use anyhow::{Context, Result};
+use crypto::{create_full_id, ibe::extract, Ciphertext, EncryptedObject};
+use fastcrypto::serde_helpers::ToFromByteArray;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+
+use crate::session::{self, SealSession};
+
+/// Where the vectors live in the repository.
+pub const DEFAULT_VECTORS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/kat/vectors.json");
+
+#[derive(Serialize, Deserialize)]
+pub struct KatFile {
+    pub vectors: Vec<KatVector>,
+}
+
+/// Byte strings are hex.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct KatVector {
+    pub name: String,
+    pub seed: String,
+    pub servers: usize,
+    pub threshold: u8,
+    pub identity: String,
+    pub package_id: String,
+    pub key_servers: Vec<String>,
+    pub public_keys: Vec<String>,
+    pub full_id: String,
+    pub user_secret_keys: Vec<String>,
+    pub ciphertext: String,
+    pub mode: String,
+    pub aad: Option<String>,
+    /// Length of the encrypted blob, 0 for Plain mode.
+    pub blob_len: usize,
+    /// The plaintext, or the derived key for Plain mode.
+    pub expected: String,
+}
+
+/// One recorded answer the pipeline no longer gives.
+#[derive(Debug, PartialEq)]
+pub struct Mismatch {
+    pub field: String,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}\n  - expected: {}\n  + actual:   {}", self.field, self.expected, self.actual)
+    }
+}
+
+/// Seed, fleet shape, mode, identity and message of each generated vector.
+const CASES: [(&str, u8, usize, u8, &str, &str); 3] = [
+    ("aes-256-gcm-2of3", 1, 3, 2, "kat:aes-256-gcm", "Known answer: AES-256-GCM under a 2-of-3 fleet"),
+    ("hmac-256-ctr-3of5", 2, 5, 3, "kat:hmac-256-ctr", "Known answer: HMAC-256-CTR under a 3-of-5 fleet"),
+    ("plain-1of1", 3, 1, 1, "kat:plain", ""),
+];
+
+const KAT_AAD: &[u8] = b"seal-demo/kat";
+
+pub fn load(path: &Path) -> Result<KatFile> {
+    if !path.exists() {
+        anyhow::bail!(
+            "No known-answer vectors at {}; write them with `kat --regenerate` on a build of the pinned crypto revision",
+            path.display()
+        );
+    }
+    let json = std::fs::read_to_string(path).with_context(|| format!("Failed to read KAT vectors {}", path.display()))?;
+    let file: KatFile = serde_json::from_str(&json).context("Invalid KAT vectors")?;
+    // A file that lost some cases would otherwise pass on the rest
+    for (name, ..) in CASES {
+        if !file.vectors.iter().any(|vector| vector.name == name) {
+            anyhow::bail!("{} has no vector {}; regenerate the vectors to restore it", path.display(), name);
+        }
+    }
+    Ok(file)
+}
+
+/// Produce a fresh set of vectors from the fixed cases.
+pub fn generate() -> Result<KatFile> {
+    let mut vectors = Vec::new();
+    for (name, seed_byte, servers, threshold, identity, message) in CASES {
+        let seed = [seed_byte; 32];
+        let session = fleet(&seed, servers, threshold)?;
+        let mode = name.rsplit_once('-').map_or(name, |(mode, _)| mode);
+        let plain = mode == "plain";
+        let data = (!plain).then(|| message.as_bytes().to_vec());
+        let input = session::encryption_input(mode, data, (!plain).then(|| KAT_AAD.to_vec()))?;
+        let (encrypted_object, key) = session.encrypt(identity.as_bytes(), input)?;
+        let (_, aad, blob_len) = describe(&encrypted_object);
+        let answers = Answers::of(&session, identity.as_bytes());
+        vectors.push(KatVector {
+            name: name.to_string(),
+            seed: hex::encode(seed),
+            servers,
+            threshold,
+            identity: hex::encode(identity),
+            package_id: answers.package_id,
+            key_servers: answers.key_servers,
+            public_keys: answers.public_keys,
+            full_id: answers.full_id,
+            user_secret_keys: answers.user_secret_keys,
+            ciphertext: hex::encode(bcs::to_bytes(&encrypted_object)?),
+            mode: mode.to_string(),
+            aad,
+            blob_len,
+            expected: hex::encode(if plain { &key[..] } else { message.as_bytes() }),
+        });
+    }
+    Ok(KatFile { vectors })
+}
+
+/// Rerun `vector` and list every answer that differs. Errors only when the
+/// vector itself can't be read.
+pub fn check(vector: &KatVector) -> Result<Vec<Mismatch>> {
+    let seed: [u8; 32] = hex::decode(&vector.seed)?
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("Vector {}: seed must be 32 bytes", vector.name))?;
+    let identity = hex::decode(&vector.identity)?;
+    let session = fleet(&seed, vector.servers, vector.threshold)?;
+
+    let mut mismatches = Vec::new();
+    let mut compare = |field: String, expected: &str, actual: &str| {
+        if expected != actual {
+            mismatches.push(Mismatch { field, expected: expected.to_string(), actual: actual.to_string() });
+        }
+    };
+
+    let answers = Answers::of(&session, &identity);
+    compare("package_id".to_string(), &vector.package_id, &answers.package_id);
+    let lists = [
+        ("key_servers", &vector.key_servers, &answers.key_servers),
+        ("public_keys", &vector.public_keys, &answers.public_keys),
+        ("user_secret_keys", &vector.user_secret_keys, &answers.user_secret_keys),
+    ];
+    for (name, expected, actual) in lists {
+        compare(format!("{}.len", name), &expected.len().to_string(), &actual.len().to_string());
+        for (i, (expected, actual)) in expected.iter().zip(actual).enumerate() {
+            compare(format!("{}[{}]", name, i), expected, actual);
+        }
+    }
+    compare("full_id".to_string(), &vector.full_id, &answers.full_id);
+
+    let encrypted_object: EncryptedObject =
+        bcs::from_bytes(&hex::decode(&vector.ciphertext)?).context("Corrupt KAT ciphertext")?;
+    let (mode, aad, blob_len) = describe(&encrypted_object);
+    compare("ciphertext.package_id".to_string(), &vector.package_id, &encrypted_object.package_id.to_string());
+    compare("ciphertext.id".to_string(), &vector.identity, &hex::encode(&encrypted_object.id));
+    compare("ciphertext.threshold".to_string(), &vector.threshold.to_string(), &encrypted_object.threshold.to_string());
+    let services: Vec<String> = encrypted_object.services.iter().map(|(id, _)| id.to_string()).collect();
+    compare("ciphertext.services".to_string(), &vector.key_servers.join(","), &services.join(","));
+    compare("ciphertext.mode".to_string(), &vector.mode, mode);
+    compare("ciphertext.aad".to_string(), vector.aad.as_deref().unwrap_or("none"), aad.as_deref().unwrap_or("none"));
+    compare("ciphertext.blob_len".to_string(), &vector.blob_len.to_string(), &blob_len.to_string());
+
+    let decrypted = match session.decrypt(&encrypted_object) {
+        Ok(decrypted) => hex::encode(decrypted),
+        Err(e) => format!("error: {}", e),
+    };
+    compare("decrypted".to_string(), &vector.expected, &decrypted);
+    Ok(mismatches)
+}
+
+fn fleet(seed: &[u8; 32], servers: usize, threshold: u8) -> Result<SealSession> {
+    SealSession::generate_with_rng(servers, threshold, &mut StdRng::from_seed(*seed))
+}
+
+/// Mode name, AAD (hex) and blob length of a sealed object.
+fn describe(encrypted_object: &EncryptedObject) -> (&'static str, Option<String>, usize) {
+    match &encrypted_object.ciphertext {
+        Ciphertext::Aes256Gcm { blob, aad } => ("aes-256-gcm", aad.as_ref().map(hex::encode), blob.len()),
+        Ciphertext::Hmac256Ctr { blob, aad, .. } => ("hmac-256-ctr", aad.as_ref().map(hex::encode), blob.len()),
+        Ciphertext::Plain => ("plain", None, 0),
+    }
+}
+
+/// The deterministic answers for a seeded fleet and identity.
+struct Answers {
+    package_id: String,
+    key_servers: Vec<String>,
+    public_keys: Vec<String>,
+    full_id: String,
+    user_secret_keys: Vec<String>,
+}
+
+impl Answers {
+    fn of(session: &SealSession, identity: &[u8]) -> Self {
+        let full_id = create_full_id(&session.package_id, identity);
+        Answers {
+            package_id: session.package_id.to_string(),
+            key_servers: session.key_servers.iter().map(ToString::to_string).collect(),
+            public_keys: session.public_keys.iter().map(session::encode_public_key).collect(),
+            user_secret_keys: session
+                .master_keys
+                .iter()
+                .flatten()
+                .map(|master_key| hex::encode(extract(master_key, &full_id).to_byte_array()))
+                .collect(),
+            full_id: hex::encode(full_id),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::full_id::FullIdScheme;
+
+    #[test]
+    fn test_generated_vectors_pass() -> Result<()> {
+        let path = std::env::temp_dir().join("kat_test_vectors.json");
+        std::fs::write(&path, serde_json::to_string_pretty(&generate()?)?)?;
+        let file = load(&path)?;
+        assert_eq!(file.vectors.len(), CASES.len());
+        for vector in &file.vectors {
+            assert_eq!(check(vector)?, [], "{}", vector.name);
+        }
+
+        // A changed answer is reported against the field it belongs to
+        let mut changed = file.vectors[0].clone();
+        changed.public_keys[1] = session::encode_public_key(&fleet(&[9; 32], 1, 1)?.public_keys[0]);
+        changed.expected = hex::encode(b"something else");
+        let fields: Vec<String> = check(&changed)?.into_iter().map(|mismatch| mismatch.field).collect();
+        assert_eq!(fields, ["public_keys[1]", "decrypted"]);
+
+        // Full IDs carry the domain separation tag, as the real crate builds them
+        let vector = &file.vectors[0];
+        let package_id = fleet(&[CASES[0].1; 32], vector.servers, vector.threshold)?.package_id;
+        let expected = FullIdScheme::V2.full_id(&package_id, &hex::decode(&vector.identity)?);
+        assert_eq!(vector.full_id, hex::encode(expected));
+
+        // Missing vectors, or some of them, fail instead of checking nothing
+        std::fs::write(&path, r#"{"vectors":[]}"#)?;
+        let err = load(&path).err().map(|e| e.to_string()).unwrap_or_default();
+        assert!(err.contains("has no vector aes-256-gcm-2of3"), "{}", err);
+        std::fs::remove_file(&path)?;
+        assert!(load(&path).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_checked_in_vectors_pass() -> Result<()> {
+        let file = load(Path::new(DEFAULT_VECTORS))?;
+        for vector in &file.vectors {
+            let mismatches = check(vector)?;
+            let report: Vec<String> = mismatches.iter().map(ToString::to_string).collect();
+            assert!(mismatches.is_empty(), "{} differs from the recorded answers:\n{}", vector.name, report.join("\n"));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,58 @@
+//! Memoized user secret keys for batch decryption.
+//!
+//! Extracting a user secret key is a pairing-group scalar multiplication per
+//! server, and every file encrypted to the same identity needs the same keys.
+//! A batch keeps one `KeyCache` so each (server, full ID) pair is extracted
+//! once, however many files share it.
+
+use crypto::ObjectID;
+use fastcrypto::groups::bls12381::G1Element;
+use std::collections::HashMap;
+
+#[derive(Default)]
+pub struct KeyCache {
+    keys: HashMap<(ObjectID, Vec<u8>), G1Element>,
+    hits: usize,
+}
+
+impl KeyCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The cached key for `server` and `full_id`, calling `extract` only the
+    /// first time the pair is seen.
+    pub fn get_or_extract(&mut self, server: ObjectID, full_id: &[u8], extract: impl FnOnce() -> G1Element) -> G1Element {
+        if let Some(key) = self.get(server, full_id) {
+            return key;
+        }
+        let key = extract();
+        self.insert(server, full_id, key);
+        key
+    }
+
+    /// Cached key lookup, counted as a hit when found. Together with `insert`
+    /// this lets threads sharing a `Mutex<KeyCache>` extract without holding
+    /// the lock; two workers may then extract the same key once each.
+    pub fn get(&mut self, server: ObjectID, full_id: &[u8]) -> Option<G1Element> {
+        let key = self.keys.get(&(server, full_id.to_vec())).copied();
+        if key.is_some() {
+            self.hits += 1;
+        }
+        key
+    }
+
+    pub fn insert(&mut self, server: ObjectID, full_id: &[u8], key: G1Element) {
+        self.keys.insert((server, full_id.to_vec()), key);
+    }
+
+    /// Number of keys actually extracted.
+    pub fn extracted(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Number of lookups answered from the cache.
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+}
@@ -0,0 +1,425 @@
+//! One key file per key server, so the servers' secrets can be handed out
+//! (or lost) independently.
+//!
+//! A key directory holds `<server object ID>.json` files, each in the shape
+//! of a session file's `servers` entry with its master key. Decryption loads
+//! whichever files are present and matches them against the servers listed
+//! in the ciphertext, so no one has to pick the quorum by hand. When more
+//! than a quorum is available, a `ServerPreference` decides which servers
+//! make it up. Their keys are fetched concurrently, and a server whose key
+//! fails is replaced by the next one in line.
+
+use anyhow::{Context, Result};
+use crypto::{
+    create_full_id,
+    ibe::{extract, verify_user_secret_key},
+    seal_decrypt, EncryptedObject, IBEPublicKeys, IBEUserSecretKeys, ObjectID,
+};
+use fastcrypto::groups::bls12381::{G1Element, G2Element, Scalar};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use crate::atomic;
+use crate::output::outln;
+use crate::session::{self, SealSession};
+
+#[derive(Serialize, Deserialize)]
+struct KeyFile {
+    object_id: ObjectID,
+    public_key: String,
+    master_key: String,
+}
+
+/// A key server's secrets, as loaded from its key file.
+pub struct ServerKey {
+    pub object_id: ObjectID,
+    pub public_key: G2Element,
+    pub master_key: Scalar,
+}
+
+/// Too few of the ciphertext's servers have a key file. Returned wrapped in
+/// `anyhow::Error`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct MissingKeyFiles {
+    pub threshold: u8,
+    pub found: usize,
+    /// Servers of the ciphertext without a key file, any `threshold - found`
+    /// of which would do.
+    pub missing: Vec<ObjectID>,
+}
+
+impl fmt::Display for MissingKeyFiles {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Found key files for {} of the {} servers needed; {} more needed from: {}",
+            self.found,
+            self.threshold,
+            self.threshold as usize - self.found,
+            self.missing.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")
+        )
+    }
+}
+
+impl std::error::Error for MissingKeyFiles {}
+
+/// Reports a server's latency, e.g. `probe_latency`.
+pub type LatencyProbe = Box<dyn Fn(&ServerKey) -> Option<Duration>>;
+
+/// Which servers `decrypt` takes its quorum from when more than `threshold`
+/// of the ciphertext's servers have a key.
+pub enum ServerPreference {
+    /// The first ones, in the order the ciphertext lists them.
+    Listed,
+    /// These servers first, in this order, then the rest as listed.
+    Priority(Vec<ObjectID>),
+    /// The lowest latency reported for each server first; servers without a
+    /// measurement go last.
+    Latency(LatencyProbe),
+}
+
+/// Probe identity `probe_latency` extracts a key for.
+const PROBE_IDENTITY: &[u8] = b"seal-demo/latency-probe";
+
+/// How long `key` takes to extract and verify a probe key, the only latency
+/// there is to measure for local key files. `None` if the key doesn't verify.
+pub fn probe_latency(key: &ServerKey) -> Option<Duration> {
+    let full_id = create_full_id(&ObjectID::ZERO, PROBE_IDENTITY);
+    let start = Instant::now();
+    let user_secret_key = extract(&key.master_key, &full_id);
+    verify_user_secret_key(&user_secret_key, &full_id, &key.public_key).ok()?;
+    Some(start.elapsed())
+}
+
+/// The object's servers that have a key, ordered by `preference`. `decrypt`
+/// asks the first `threshold` and falls back on the rest.
+fn rank_candidates<'a>(
+    encrypted_object: &EncryptedObject,
+    keys: &'a [ServerKey],
+    preference: &ServerPreference,
+) -> Result<Vec<&'a ServerKey>> {
+    let (present, missing): (Vec<_>, Vec<_>) = encrypted_object
+        .services
+        .iter()
+        .map(|(server, _)| (*server, keys.iter().find(|key| key.object_id == *server)))
+        .partition(|(_, key)| key.is_some());
+
+    let threshold = encrypted_object.threshold;
+    if present.len() < threshold as usize {
+        return Err(MissingKeyFiles {
+            threshold,
+            found: present.len(),
+            missing: missing.into_iter().map(|(server, _)| server).collect(),
+        }
+        .into());
+    }
+
+    // Sorts are stable, so ties keep the ciphertext's order
+    let mut present: Vec<&ServerKey> = present.into_iter().filter_map(|(_, key)| key).collect();
+    match preference {
+        ServerPreference::Listed => {}
+        ServerPreference::Priority(order) => {
+            present.sort_by_key(|key| order.iter().position(|server| *server == key.object_id).unwrap_or(order.len()));
+        }
+        ServerPreference::Latency(latency) => {
+            present.sort_by_cached_key(|key| latency(key).unwrap_or(Duration::MAX));
+        }
+    }
+    Ok(present)
+}
+
+/// Write one key file per server of `session` into `dir`. The files hold
+/// master keys and must be treated as secret. Returns how many were written.
+pub fn write_key_files(session: &SealSession, dir: &Path) -> Result<usize> {
+    let master_keys = session
+        .master_keys
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("Session has no master keys to write key files from"))?;
+    fs::create_dir_all(dir)?;
+
+    for ((object_id, public_key), master_key) in session.key_servers.iter().zip(&session.public_keys).zip(master_keys) {
+        let key_file = KeyFile {
+            object_id: *object_id,
+            public_key: session::encode_public_key(public_key),
+            master_key: session::encode_master_key(master_key),
+        };
+        atomic::write_file(
+            &dir.join(format!("{}.json", object_id)),
+            serde_json::to_string_pretty(&key_file)?.as_bytes(),
+        )?;
+    }
+    Ok(session.key_servers.len())
+}
+
+/// Load every `*.json` key file in `dir`; other files are ignored.
+pub fn load_key_files(dir: &Path) -> Result<Vec<ServerKey>> {
+    let mut keys: Vec<ServerKey> = Vec::new();
+    let mut paths: Vec<_> = fs::read_dir(dir)
+        .with_context(|| format!("Failed to read key directory {}", dir.display()))?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<Result<_>>()?;
+    paths.sort();
+
+    for path in paths.iter().filter(|path| path.extension().is_some_and(|ext| ext == "json")) {
+        let key_file: KeyFile = serde_json::from_str(&fs::read_to_string(path)?)
+            .with_context(|| format!("Invalid key file {}", path.display()))?;
+        if keys.iter().any(|key| key.object_id == key_file.object_id) {
+            anyhow::bail!("Two key files for server {}", key_file.object_id);
+        }
+        keys.push(ServerKey {
+            object_id: key_file.object_id,
+            public_key: session::decode_public_key(&key_file.public_key)
+                .with_context(|| format!("Bad public key in {}", path.display()))?,
+            master_key: session::decode_master_key(&key_file.master_key)
+                .with_context(|| format!("Bad master key in {}", path.display()))?,
+        });
+    }
+    Ok(keys)
+}
+
+/// Decrypt with `threshold` of the object's servers that have a key, picked
+/// by `preference`, and return the plaintext with the servers used. Each
+/// user secret key is checked against its file's public key before use, so
+/// a key file filed under the wrong server ID is reported as such instead of
+/// as a failed decryption, and the next server in line is asked instead.
+///
+/// The shares in the object are checked for consistency only when there is
+/// a key file for every server it lists, since `seal_decrypt` needs all of
+/// their public keys; otherwise the check is skipped and says so.
+pub fn decrypt(
+    encrypted_object: &EncryptedObject,
+    keys: &[ServerKey],
+    preference: &ServerPreference,
+) -> Result<(Vec<u8>, Vec<ObjectID>)> {
+    let candidates = rank_candidates(encrypted_object, keys, preference)?;
+    let full_id = create_full_id(&encrypted_object.package_id, &encrypted_object.id);
+    let user_secret_keys = fetch_quorum(&candidates, encrypted_object.threshold, &full_id, |key, full_id| {
+        let user_secret_key = extract(&key.master_key, full_id);
+        if verify_user_secret_key(&user_secret_key, full_id, &key.public_key).is_err() {
+            anyhow::bail!("Key file for server {} doesn't match its public key", key.object_id);
+        }
+        Ok(user_secret_key)
+    })?;
+
+    let servers: Vec<ObjectID> = user_secret_keys.iter().map(|(server, _)| *server).collect();
+    for server in &servers {
+        outln!("   🔑 Using key file for server {}", server);
+    }
+    let public_keys = fleet_public_keys(encrypted_object, keys).map(IBEPublicKeys::BonehFranklinBLS12381);
+    match &public_keys {
+        Some(_) => outln!("   🛡️  Key shares verified against the key files' public keys"),
+        None => outln!(
+            "   ⚠️  Key files cover only some of the object's {} servers, so key shares were not verified",
+            encrypted_object.services.len()
+        ),
+    }
+    let decrypted = seal_decrypt(
+        encrypted_object,
+        &IBEUserSecretKeys::BonehFranklinBLS12381(user_secret_keys.into_iter().collect()),
+        public_keys.as_ref(),
+    )?;
+    Ok((decrypted, servers))
+}
+
+/// The public keys of every server in `encrypted_object.services`, in that
+/// order, or `None` if any of them has no key file.
+fn fleet_public_keys(encrypted_object: &EncryptedObject, keys: &[ServerKey]) -> Option<Vec<G2Element>> {
+    encrypted_object
+        .services
+        .iter()
+        .map(|(server, _)| keys.iter().find(|key| key.object_id == *server).map(|key| key.public_key))
+        .collect()
+}
+
+/// Fetch user secret keys for `full_id` from `candidates`, in order, until
+/// `threshold` have succeeded. The first `threshold` fetches run at once on
+/// their own threads; each failure starts the next candidate in line, so no
+/// more servers are asked than needed. A fetch that panics counts as a
+/// failure, so one dead worker can't leave the rest waiting on it. Keys come
+/// back in candidate order.
+fn fetch_quorum(
+    candidates: &[&ServerKey],
+    threshold: u8,
+    full_id: &[u8],
+    fetch: impl Fn(&ServerKey, &[u8]) -> Result<G1Element> + Sync,
+) -> Result<Vec<(ObjectID, G1Element)>> {
+    let threshold = threshold as usize;
+    let (sender, results) = mpsc::channel();
+    let (mut fetched, failures) = std::thread::scope(|scope| {
+        let (mut fetched, mut failures) = (Vec::new(), Vec::new());
+        let (mut next, mut in_flight) = (0, 0);
+        while fetched.len() < threshold {
+            while fetched.len() + in_flight < threshold && next < candidates.len() {
+                let (key, sender, fetch) = (candidates[next], sender.clone(), &fetch);
+                scope.spawn(move || {
+                    let result = panic::catch_unwind(AssertUnwindSafe(|| fetch(key, full_id)))
+                        .unwrap_or_else(|_| Err(anyhow::anyhow!("Key fetch for server {} panicked", key.object_id)));
+                    let _ = sender.send((next, result));
+                });
+                next += 1;
+                in_flight += 1;
+            }
+            if in_flight == 0 {
+                break;
+            }
+            let (index, result) = results.recv().expect("Every key fetch thread sends a result");
+            in_flight -= 1;
+            match result {
+                Ok(user_secret_key) => fetched.push((index, user_secret_key)),
+                Err(e) => failures.push(format!("{:#}", e)),
+            }
+        }
+        (fetched, failures)
+    });
+
+    if fetched.len() < threshold {
+        anyhow::bail!(
+            "Only {} of the {} keys needed could be fetched: {}",
+            fetched.len(),
+            threshold,
+            failures.join("; ")
+        );
+    }
+    fetched.sort_by_key(|(index, _)| *index);
+    Ok(fetched.into_iter().map(|(index, user_secret_key)| (candidates[index].object_id, user_secret_key)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crypto::EncryptionInput;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_quorum_from_present_key_files() -> Result<()> {
+        let session = SealSession::generate(4, 2)?;
+        let dir = std::env::temp_dir().join("key_files_test");
+        let _ = fs::remove_dir_all(&dir);
+        assert_eq!(write_key_files(&session, &dir)?, 4);
+        fs::write(dir.join("README.txt"), "not a key file")?;
+
+        let (encrypted_object, _) = session.encrypt(
+            b"quarterly-report",
+            EncryptionInput::Aes256Gcm { data: b"numbers".to_vec(), aad: None },
+        )?;
+        assert_eq!(fleet_public_keys(&encrypted_object, &load_key_files(&dir)?), Some(session.public_keys.clone()));
+        assert_eq!(decrypt(&encrypted_object, &load_key_files(&dir)?, &ServerPreference::Listed)?.0, b"numbers");
+
+        // Servers 1 and 3 only: still a quorum, but too few to verify the shares
+        for server in [0, 2] {
+            fs::remove_file(dir.join(format!("{}.json", session.key_servers[server])))?;
+        }
+        assert_eq!(fleet_public_keys(&encrypted_object, &load_key_files(&dir)?), None);
+        assert_eq!(decrypt(&encrypted_object, &load_key_files(&dir)?, &ServerPreference::Listed)?.0, b"numbers");
+
+        // One key file left: one more needed, from the servers without one
+        fs::remove_file(dir.join(format!("{}.json", session.key_servers[1])))?;
+        let err = decrypt(&encrypted_object, &load_key_files(&dir)?, &ServerPreference::Listed).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<MissingKeyFiles>(),
+            Some(&MissingKeyFiles {
+                threshold: 2,
+                found: 1,
+                missing: vec![session.key_servers[0], session.key_servers[1], session.key_servers[2]],
+            })
+        );
+        assert!(err.to_string().contains("1 more needed from"));
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_fastest_quorum_preferred() -> Result<()> {
+        let session = SealSession::generate(4, 2)?;
+        let dir = std::env::temp_dir().join("key_files_latency_test");
+        let _ = fs::remove_dir_all(&dir);
+        write_key_files(&session, &dir)?;
+        let keys = load_key_files(&dir)?;
+        let (encrypted_object, _) = session.encrypt(
+            b"latency-report",
+            EncryptionInput::Aes256Gcm { data: b"fast path".to_vec(), aad: None },
+        )?;
+
+        let servers = session.key_servers.clone();
+        let chosen = |preference: &ServerPreference| -> Result<Vec<ObjectID>> {
+            Ok(rank_candidates(&encrypted_object, &keys, preference)?.iter().take(2).map(|key| key.object_id).collect())
+        };
+        assert_eq!(chosen(&ServerPreference::Listed)?, [servers[0], servers[1]]);
+
+        // Servers 2 and 4 answer fastest; server 3 reports nothing
+        let reported: HashMap<ObjectID, Duration> = [(servers[0], 40), (servers[1], 25), (servers[3], 5)]
+            .into_iter()
+            .map(|(server, millis)| (server, Duration::from_millis(millis)))
+            .collect();
+        let fastest = ServerPreference::Latency(Box::new(move |key: &ServerKey| reported.get(&key.object_id).copied()));
+        assert_eq!(chosen(&fastest)?, [servers[3], servers[1]]);
+        assert_eq!(decrypt(&encrypted_object, &keys, &fastest)?, (b"fast path".to_vec(), vec![servers[3], servers[1]]));
+
+        let priority = ServerPreference::Priority(vec![servers[2], ObjectID::random()]);
+        assert_eq!(chosen(&priority)?, [servers[2], servers[0]]);
+
+        assert!(keys.iter().all(|key| probe_latency(key).is_some()));
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_keys_fetched_concurrently() -> Result<()> {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let session = SealSession::generate(5, 3)?;
+        let dir = std::env::temp_dir().join("key_files_parallel_test");
+        let _ = fs::remove_dir_all(&dir);
+        write_key_files(&session, &dir)?;
+        let keys = load_key_files(&dir)?;
+        let candidates: Vec<&ServerKey> = keys.iter().collect();
+        let full_id = create_full_id(&session.package_id, b"remote-report");
+
+        // A mock remote server: slow to answer, and tracking how many answer at once
+        let (in_flight, max_in_flight, calls) = (AtomicUsize::new(0), AtomicUsize::new(0), AtomicUsize::new(0));
+        let remote = |key: &ServerKey, full_id: &[u8]| -> Result<G1Element> {
+            calls.fetch_add(1, Ordering::SeqCst);
+            max_in_flight.fetch_max(in_flight.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+            std::thread::sleep(Duration::from_millis(100));
+            in_flight.fetch_sub(1, Ordering::SeqCst);
+            if key.object_id == keys[1].object_id {
+                anyhow::bail!("server {} timed out", key.object_id);
+            }
+            Ok(extract(&key.master_key, full_id))
+        };
+
+        let start = Instant::now();
+        let fetched = fetch_quorum(&candidates, 3, &full_id, remote)?;
+        // Serially that would be four 100ms round trips
+        assert!(start.elapsed() < Duration::from_millis(350), "{:?}", start.elapsed());
+        assert_eq!(max_in_flight.load(Ordering::SeqCst), 3);
+        // Server 2 failed, so server 4 was asked in its place and server 5 never was
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+        let servers: Vec<ObjectID> = fetched.iter().map(|(server, _)| *server).collect();
+        assert_eq!(servers, [keys[0].object_id, keys[2].object_id, keys[3].object_id]);
+
+        let err = fetch_quorum(&candidates[..3], 3, &full_id, remote).unwrap_err().to_string();
+        assert!(err.starts_with("Only 2 of the 3 keys needed could be fetched: server "), "{}", err);
+
+        // A fetch that panics is a failure like any other, not a hang
+        let panicking = |key: &ServerKey, full_id: &[u8]| -> Result<G1Element> {
+            if key.object_id == keys[0].object_id {
+                panic!("remote client crashed");
+            }
+            Ok(extract(&key.master_key, full_id))
+        };
+        let fetched = fetch_quorum(&candidates, 3, &full_id, panicking)?;
+        let servers: Vec<ObjectID> = fetched.iter().map(|(server, _)| *server).collect();
+        assert_eq!(servers, [keys[1].object_id, keys[2].object_id, keys[3].object_id]);
+        let err = fetch_quorum(&candidates[..3], 3, &full_id, panicking).unwrap_err().to_string();
+        assert!(err.contains("panicked"), "{}", err);
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
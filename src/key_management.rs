@@ -1,14 +1,22 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use crypto::{
     ibe::{generate_key_pair, extract, verify_user_secret_key, generate_seed, derive_master_key, into_key_pair},
-    create_full_id, ObjectID
+    create_full_id, EncryptionInput, IBEPublicKeys, IBEUserSecretKeys, ObjectID
 };
 use fastcrypto::serde_helpers::ToFromByteArray;
 use rand::thread_rng;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::output::outln;
+use crate::report::OpCounter;
+use crate::shamir;
 
 pub struct KeyManagementDemo {
     pub package_id: ObjectID,
     pub identities: Vec<Vec<u8>>,
+    /// Where extractions and round trips are tallied.
+    pub counter: OpCounter,
 }
 
 impl KeyManagementDemo {
@@ -20,80 +28,117 @@ impl KeyManagementDemo {
                 b"bob@example.com".to_vec(),
                 b"charlie@example.com".to_vec(),
             ],
+            counter: OpCounter::default(),
+        }
+    }
+    
+    /// Load identities from a file, one per line: plain UTF-8, or bytes as
+    /// `hex:<hex>`. Blank lines and lines starting with `#` are skipped.
+    pub fn from_identity_file(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read identity file {}", path.display()))?;
+        
+        let mut identities = Vec::new();
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let identity = match line.strip_prefix("hex:") {
+                Some(hex_str) => hex::decode(hex_str)
+                    .with_context(|| format!("{}:{}: invalid hex identity", path.display(), i + 1))?,
+                None => line.as_bytes().to_vec(),
+            };
+            identities.push(identity);
         }
+        
+        if identities.is_empty() {
+            anyhow::bail!("No identities found in {}", path.display());
+        }
+        Ok(KeyManagementDemo {
+            package_id: ObjectID::random(),
+            identities,
+            counter: OpCounter::default(),
+        })
+    }
+    
+    /// Tally on `counter` instead of a private one.
+    pub fn with_counter(mut self, counter: OpCounter) -> Self {
+        self.counter = counter;
+        self
     }
     
     pub fn demo_key_generation(&self) -> Result<()> {
-        println!("\n🚀 === Key Generation Demo ===");
+        outln!("\n🚀 === Key Generation Demo ===");
         
-        println!("🔑 Generating IBE master key pair...");
+        outln!("🔑 Generating IBE master key pair...");
         let (master_key, public_key) = generate_key_pair(&mut thread_rng());
         
-        println!("   Master Key: {}", hex::encode(master_key.to_byte_array()));
-        println!("   Public Key: {}", hex::encode(bcs::to_bytes(&public_key)?));
+        outln!("   Master Key: {}", hex::encode(master_key.to_byte_array()));
+        outln!("   Public Key: {}", hex::encode(bcs::to_bytes(&public_key)?));
         
         // Demonstrate key extraction for different identities
-        println!("\n👥 Extracting user secret keys for different identities:");
+        outln!("\n👥 Extracting user secret keys for different identities:");
         
         for (i, identity) in self.identities.iter().enumerate() {
             let full_id = create_full_id(&self.package_id, identity);
-            let user_secret_key = extract(&master_key, &full_id);
+            let user_secret_key = self.counter.extract(&master_key, &full_id);
             
-            println!("   User {}: {}", i + 1, String::from_utf8_lossy(identity));
-            println!("   Full ID: {}", hex::encode(&full_id));
-            println!("   Secret Key: {}", hex::encode(bcs::to_bytes(&user_secret_key)?));
+            outln!("   User {}: {}", i + 1, String::from_utf8_lossy(identity));
+            outln!("   Full ID: {}", hex::encode(&full_id));
+            outln!("   Secret Key: {}", hex::encode(bcs::to_bytes(&user_secret_key)?));
             
             // Verify the user secret key
             match verify_user_secret_key(&user_secret_key, &full_id, &public_key) {
-                Ok(_) => println!("   ✅ Secret key verification successful"),
-                Err(e) => println!("   ❌ Secret key verification failed: {}", e),
+                Ok(_) => outln!("   ✅ Secret key verification successful"),
+                Err(e) => outln!("   ❌ Secret key verification failed: {}", e),
             }
-            println!();
+            outln!();
         }
         
         Ok(())
     }
     
     pub fn demo_seed_based_keys(&self) -> Result<()> {
-        println!("\n🚀 === Seed-Based Key Derivation Demo ===");
+        outln!("\n🚀 === Seed-Based Key Derivation Demo ===");
         
-        println!("🌱 Generating seed for deterministic key derivation...");
+        outln!("🌱 Generating seed for deterministic key derivation...");
         let seed = generate_seed(&mut thread_rng());
-        println!("   Seed: {}", hex::encode(seed));
+        outln!("   Seed: {}", hex::encode(seed));
         
-        println!("\n🔄 Deriving multiple key pairs from the same seed:");
+        outln!("\n🔄 Deriving multiple key pairs from the same seed:");
         
         for index in 0..3 {
             let derived_master_key = derive_master_key(&seed, index);
             let (master_key, public_key) = into_key_pair(derived_master_key);
             
-            println!("   Index {}: ", index);
-            println!("     Master Key: {}", hex::encode(master_key.to_byte_array()));
-            println!("     Public Key: {}", hex::encode(bcs::to_bytes(&public_key)?));
+            outln!("   Index {}: ", index);
+            outln!("     Master Key: {}", hex::encode(master_key.to_byte_array()));
+            outln!("     Public Key: {}", hex::encode(bcs::to_bytes(&public_key)?));
             
             // Extract a user secret key for the first identity
             let full_id = create_full_id(&self.package_id, &self.identities[0]);
-            let user_secret_key = extract(&master_key, &full_id);
+            let user_secret_key = self.counter.extract(&master_key, &full_id);
             
             // Verify it works
             match verify_user_secret_key(&user_secret_key, &full_id, &public_key) {
-                Ok(_) => println!("     ✅ Derived key verification successful"),
-                Err(e) => println!("     ❌ Derived key verification failed: {}", e),
+                Ok(_) => outln!("     ✅ Derived key verification successful"),
+                Err(e) => outln!("     ❌ Derived key verification failed: {}", e),
             }
         }
         
-        println!("\n💡 Note: Same seed + same index = same key pair (deterministic)");
+        outln!("\n💡 Note: Same seed + same index = same key pair (deterministic)");
         
         Ok(())
     }
     
     pub fn demo_identity_namespacing(&self) -> Result<()> {
-        println!("\n🚀 === Identity Namespacing Demo ===");
+        outln!("\n🚀 === Identity Namespacing Demo ===");
         
         let (master_key, _public_key) = generate_key_pair(&mut thread_rng());
         
-        println!("🏷️  Package ID: {}", self.package_id);
-        println!("🆔 Demonstrating how identities are namespaced by package ID:");
+        outln!("🏷️  Package ID: {}", self.package_id);
+        outln!("🆔 Demonstrating how identities are namespaced by package ID:");
         
         let identity = b"user@example.com";
         
@@ -106,70 +151,172 @@ impl KeyManagementDemo {
         
         for (i, pkg_id) in package_ids.iter().enumerate() {
             let full_id = create_full_id(pkg_id, identity);
-            let user_secret_key = extract(&master_key, &full_id);
+            let user_secret_key = self.counter.extract(&master_key, &full_id);
             
-            println!("\n   Context {}: Package ID = {}", i + 1, pkg_id);
-            println!("   Identity: \"{}\"", String::from_utf8_lossy(identity));
-            println!("   Full ID: {}", hex::encode(&full_id));
-            println!("   Secret Key: {}", hex::encode(bcs::to_bytes(&user_secret_key)?));
+            outln!("\n   Context {}: Package ID = {}", i + 1, pkg_id);
+            outln!("   Identity: \"{}\"", String::from_utf8_lossy(identity));
+            outln!("   Full ID: {}", hex::encode(&full_id));
+            outln!("   Secret Key: {}", hex::encode(bcs::to_bytes(&user_secret_key)?));
             
             if pkg_id == &self.package_id {
-                println!("   👆 This is our main package context");
+                outln!("   👆 This is our main package context");
             }
         }
         
-        println!("\n💡 Note: Same identity + different package = different secret keys");
+        outln!("\n💡 Note: Same identity + different package = different secret keys");
         
         Ok(())
     }
     
     pub fn demo_key_verification(&self) -> Result<()> {
-        println!("\n🚀 === Key Verification Demo ===");
+        outln!("\n🚀 === Key Verification Demo ===");
         
         let (master_key, public_key) = generate_key_pair(&mut thread_rng());
         let identity = &self.identities[0];
         let full_id = create_full_id(&self.package_id, identity);
         
-        println!("🔍 Generating and verifying user secret keys...");
+        outln!("🔍 Generating and verifying user secret keys...");
         
         // Generate correct secret key
-        let correct_secret_key = extract(&master_key, &full_id);
+        let correct_secret_key = self.counter.extract(&master_key, &full_id);
         
-        println!("   Identity: \"{}\"", String::from_utf8_lossy(identity));
-        println!("   Correct Secret Key: {}", hex::encode(bcs::to_bytes(&correct_secret_key)?));
+        outln!("   Identity: \"{}\"", String::from_utf8_lossy(identity));
+        outln!("   Correct Secret Key: {}", hex::encode(bcs::to_bytes(&correct_secret_key)?));
         
         // Test correct verification
         match verify_user_secret_key(&correct_secret_key, &full_id, &public_key) {
-            Ok(_) => println!("   ✅ Correct key verification: PASSED"),
-            Err(e) => println!("   ❌ Correct key verification: FAILED ({})", e),
+            Ok(_) => outln!("   ✅ Correct key verification: PASSED"),
+            Err(e) => outln!("   ❌ Correct key verification: FAILED ({})", e),
         }
         
         // Test with wrong secret key (different identity)
         let wrong_full_id = create_full_id(&self.package_id, &self.identities[1]);
-        let wrong_secret_key = extract(&master_key, &wrong_full_id);
+        let wrong_secret_key = self.counter.extract(&master_key, &wrong_full_id);
         
         match verify_user_secret_key(&wrong_secret_key, &full_id, &public_key) {
-            Ok(_) => println!("   ❌ Wrong key verification: UNEXPECTEDLY PASSED"),
-            Err(_) => println!("   ✅ Wrong key verification: CORRECTLY FAILED"),
+            Ok(_) => outln!("   ❌ Wrong key verification: UNEXPECTEDLY PASSED"),
+            Err(_) => outln!("   ✅ Wrong key verification: CORRECTLY FAILED"),
         }
         
         // Test with wrong public key
         let (_, wrong_public_key) = generate_key_pair(&mut thread_rng());
         
         match verify_user_secret_key(&correct_secret_key, &full_id, &wrong_public_key) {
-            Ok(_) => println!("   ❌ Wrong public key verification: UNEXPECTEDLY PASSED"),
-            Err(_) => println!("   ✅ Wrong public key verification: CORRECTLY FAILED"),
+            Ok(_) => outln!("   ❌ Wrong public key verification: UNEXPECTEDLY PASSED"),
+            Err(_) => outln!("   ✅ Wrong public key verification: CORRECTLY FAILED"),
+        }
+        
+        Ok(())
+    }
+    
+    pub fn demo_shamir_comparison(&self) -> Result<()> {
+        outln!("\n🚀 === Shamir vs IBE Threshold Demo ===");
+        
+        let (total, threshold) = (5u8, 3u8);
+        let servers: Vec<_> = (0..total)
+            .map(|_| {
+                let (master_key, public_key) = generate_key_pair(&mut thread_rng());
+                (ObjectID::random(), master_key, public_key)
+            })
+            .collect();
+        let identity = &self.identities[0];
+        let full_id = create_full_id(&self.package_id, identity);
+        let public_keys = IBEPublicKeys::BonehFranklinBLS12381(servers.iter().map(|(_, _, pk)| *pk).collect());
+        
+        outln!("🔒 Seal Plain mode: a 32-byte key for \"{}\" ({}/{})...", String::from_utf8_lossy(identity), threshold, total);
+        let (encrypted_object, key) = self.counter.seal_encrypt(
+            self.package_id,
+            identity.clone(),
+            servers.iter().map(|(id, _, _)| *id).collect(),
+            &public_keys,
+            threshold,
+            EncryptionInput::Plain,
+        )?;
+        outln!("   Key: {}", hex::encode(key));
+        
+        // Classic Shamir: a dealer who knows the key hands out the shares
+        outln!("\n🧩 Shamir: the dealer splits the key itself into {} shares", total);
+        let shares = shamir::split(&key, threshold, total, &mut thread_rng())?;
+        for share in &shares {
+            outln!("   Share {}: {}", share.index, hex::encode(&share.data));
+        }
+        
+        let recovered = shamir::combine(&shares[..threshold as usize])?;
+        match recovered == key {
+            true => outln!("   ✅ {} shares reconstruct the key", threshold),
+            false => anyhow::bail!("❌ {} shares gave the wrong key", threshold),
         }
+        let too_few = shamir::combine(&shares[..threshold as usize - 1])?;
+        match too_few == key {
+            true => anyhow::bail!("❌ {} shares UNEXPECTEDLY reconstructed the key", threshold - 1),
+            false => outln!("   ✅ {} shares interpolate to unrelated bytes: {}", threshold - 1, hex::encode(&too_few)),
+        }
+        
+        // Seal: nobody is handed a share, each server extracts one for the identity
+        outln!("\n🔑 IBE threshold: each server extracts a user key for the identity on request");
+        let user_secret_keys = |count: usize| {
+            IBEUserSecretKeys::BonehFranklinBLS12381(
+                servers
+                    .iter()
+                    .take(count)
+                    .map(|(id, master_key, _)| (*id, self.counter.extract(master_key, &full_id)))
+                    .collect::<HashMap<_, _>>(),
+            )
+        };
+        
+        match self.counter.seal_decrypt(&encrypted_object, &user_secret_keys(threshold as usize), Some(&public_keys)) {
+            Ok(decrypted) if decrypted == key => outln!("   ✅ {} servers' keys recover the key", threshold),
+            Ok(_) => anyhow::bail!("❌ {} servers' keys recovered the wrong key", threshold),
+            Err(e) => anyhow::bail!("❌ {} servers' keys failed: {}", threshold, e),
+        }
+        let below_threshold = user_secret_keys(threshold as usize - 1);
+        match self.counter.seal_decrypt(&encrypted_object, &below_threshold, Some(&public_keys)) {
+            Ok(_) => anyhow::bail!("❌ {} servers' keys UNEXPECTEDLY decrypted", threshold - 1),
+            Err(_) => outln!("   ✅ {} servers' keys are rejected", threshold - 1),
+        }
+        
+        outln!("\n💡 Shamir needs a dealer holding the secret and shares stored per secret;");
+        outln!("   with IBE the encryptor never talks to the servers, their keys work for every");
+        outln!("   object under the identity, and too few keys fail loudly instead of silently.");
         
         Ok(())
     }
     
+    /// Extract and verify a key for every identity, as a key server would
+    /// when onboarding a user list, and return how many failed.
+    pub fn demo_bulk_issuance(&self) -> Result<usize> {
+        outln!("\n🚀 === Bulk Key Issuance Demo ===");
+        
+        let (master_key, public_key) = generate_key_pair(&mut thread_rng());
+        outln!("👥 Issuing keys for {} identities...", self.identities.len());
+        
+        let mut failures = Vec::new();
+        for identity in &self.identities {
+            let full_id = create_full_id(&self.package_id, identity);
+            let user_secret_key = self.counter.extract(&master_key, &full_id);
+            if let Err(e) = verify_user_secret_key(&user_secret_key, &full_id, &public_key) {
+                failures.push((String::from_utf8_lossy(identity).into_owned(), e.to_string()));
+            }
+        }
+        
+        outln!("   ✅ Issued and verified: {}", self.identities.len() - failures.len());
+        if !failures.is_empty() {
+            outln!("   ❌ Failed: {}", failures.len());
+            for (identity, error) in &failures {
+                outln!("      - \"{}\": {}", identity, error);
+            }
+        }
+        
+        Ok(failures.len())
+    }
+    
     pub fn run_all_demos(&self) -> Result<()> {
         self.demo_key_generation()?;
         self.demo_seed_based_keys()?;
         self.demo_identity_namespacing()?;
         self.demo_key_verification()?;
-        println!("\n🎉 All key management demos completed successfully!");
+        self.demo_shamir_comparison()?;
+        outln!("\n🎉 All key management demos completed successfully!");
         Ok(())
     }
 }
@@ -178,4 +325,27 @@ impl Default for KeyManagementDemo {
     fn default() -> Self {
         Self::new()
     }
-}
\ No newline at end of file
+}
+
+/// Print the key pairs derived from `seed` for indices `0..count` as a tree,
+/// each with a sample user secret key for `identity` under `package_id`.
+///
+/// The output uses the same hex encodings as session files and contains no
+/// randomness, so it can be diffed or parsed.
+pub fn print_derivation_tree(seed: &[u8; 32], count: u64, package_id: &[u8; 32], identity: &[u8]) {
+    let full_id = create_full_id(package_id, identity);
+
+    outln!("seed {}", hex::encode(seed));
+    outln!("│   package_id: 0x{}", hex::encode(package_id));
+    outln!("│   identity: {}", String::from_utf8_lossy(identity));
+    for index in 0..count {
+        let (master_key, public_key) = into_key_pair(derive_master_key(seed, index));
+        let user_secret_key = extract(&master_key, &full_id);
+
+        let (branch, indent) = if index + 1 == count { ("└──", "    ") } else { ("├──", "│   ") };
+        outln!("{} index {}", branch, index);
+        outln!("{}├── master_key: {}", indent, crate::session::encode_master_key(&master_key));
+        outln!("{}├── public_key: {}", indent, crate::session::encode_public_key(&public_key));
+        outln!("{}└── user_secret_key: {}", indent, hex::encode(user_secret_key.to_byte_array()));
+    }
+}
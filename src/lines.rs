@@ -0,0 +1,152 @@
+//! Line-by-line authenticated encryption for text files such as config diffs.
+//!
+//! Layout (text, one record per line, so encrypted diffs stay line-aligned):
+//!
+//! ```text
+//! SEAL-LINES base64(bcs(LinesHeader))
+//! base64(AES-256-GCM(line 1))
+//! base64(AES-256-GCM(line 2))
+//! ...
+//! ```
+//!
+//! As in `streaming`, the header's Plain-mode Seal object wraps the key.
+//! Each line is sealed on its own with its 1-based line number as the AAD
+//! (and in the nonce, with a final-line flag), so a modified, moved or
+//! dropped line is reported by number instead of failing the whole file.
+
+use anyhow::{Context, Result};
+use base64::{prelude::BASE64_STANDARD, Engine};
+use crypto::{Ciphertext, EncryptedObject};
+use fastcrypto::aes::{Aes256Gcm, AesKey, AuthenticatedCipher, InitializationVector};
+use fastcrypto::hash::{HashFunction, Sha3_256};
+use fastcrypto::traits::ToFromBytes;
+use serde::{Deserialize, Serialize};
+use typenum::U12;
+
+pub const LINES_MAGIC: &str = "SEAL-LINES";
+
+const LINE_KEY_DOMAIN: &[u8] = b"seal-demo/line-key/v1";
+
+#[derive(Serialize, Deserialize)]
+struct LinesHeader {
+    line_count: u64,
+    encrypted_object: EncryptedObject,
+}
+
+struct LineCipher {
+    cipher: Aes256Gcm<U12>,
+}
+
+impl LineCipher {
+    fn new(key: &[u8]) -> Result<Self> {
+        let mut input = LINE_KEY_DOMAIN.to_vec();
+        input.extend_from_slice(key);
+        let key = AesKey::from_bytes(&Sha3_256::digest(&input).digest)
+            .map_err(|e| anyhow::anyhow!("Invalid line key: {}", e))?;
+        Ok(LineCipher { cipher: Aes256Gcm::new(key) })
+    }
+
+    fn nonce(line: u64, is_last: bool) -> Result<InitializationVector<U12>> {
+        let mut nonce = [0u8; 12];
+        nonce[..8].copy_from_slice(&line.to_be_bytes());
+        nonce[11] = is_last as u8;
+        InitializationVector::from_bytes(&nonce).map_err(|e| anyhow::anyhow!("Invalid nonce: {}", e))
+    }
+
+    fn seal(&self, line: u64, is_last: bool, plaintext: &[u8]) -> Result<Vec<u8>> {
+        Ok(self
+            .cipher
+            .encrypt_authenticated(&Self::nonce(line, is_last)?, &line.to_be_bytes(), plaintext))
+    }
+
+    fn open(&self, line: u64, is_last: bool, ciphertext: &[u8]) -> Result<Vec<u8>> {
+        self.cipher
+            .decrypt_authenticated(&Self::nonce(line, is_last)?, &line.to_be_bytes(), ciphertext)
+            .map_err(|_| anyhow::anyhow!("Line {} was tampered with or is corrupt", line))
+    }
+}
+
+/// Seal every line of `text` (terminators included, so the file round-trips
+/// byte for byte). `encrypted_object` must be the Plain-mode object that
+/// wraps `key`.
+pub fn encrypt_lines(text: &[u8], encrypted_object: EncryptedObject, key: &[u8]) -> Result<String> {
+    if !matches!(encrypted_object.ciphertext, Ciphertext::Plain) {
+        anyhow::bail!("Line header must hold a Plain-mode object");
+    }
+
+    let lines: Vec<&[u8]> = text.split_inclusive(|&byte| byte == b'\n').collect();
+    let header = LinesHeader {
+        line_count: lines.len() as u64,
+        encrypted_object,
+    };
+    let cipher = LineCipher::new(key)?;
+
+    let mut sealed = format!("{} {}\n", LINES_MAGIC, BASE64_STANDARD.encode(bcs::to_bytes(&header)?));
+    for (i, line) in lines.iter().enumerate() {
+        let number = i as u64 + 1;
+        sealed.push_str(&BASE64_STANDARD.encode(cipher.seal(number, number == header.line_count, line)?));
+        sealed.push('\n');
+    }
+    Ok(sealed)
+}
+
+/// Recover the key with `unwrap_key` and open every line, failing on the
+/// first line that doesn't authenticate.
+pub fn decrypt_lines(sealed: &str, unwrap_key: impl FnOnce(&EncryptedObject) -> Result<Vec<u8>>) -> Result<Vec<u8>> {
+    let mut records = sealed.lines();
+    let header_b64 = records
+        .next()
+        .and_then(|first| first.strip_prefix(LINES_MAGIC))
+        .and_then(|rest| rest.strip_prefix(' '))
+        .ok_or_else(|| anyhow::anyhow!("Not a line-sealed file (missing {} header)", LINES_MAGIC))?;
+    let header: LinesHeader = bcs::from_bytes(&BASE64_STANDARD.decode(header_b64).context("Corrupt line header")?)
+        .context("Corrupt line header")?;
+
+    let records: Vec<&str> = records.collect();
+    if records.len() as u64 != header.line_count {
+        anyhow::bail!("Expected {} lines, found {}: lines were added or removed", header.line_count, records.len());
+    }
+
+    let cipher = LineCipher::new(&unwrap_key(&header.encrypted_object)?)?;
+    let mut text = Vec::new();
+    for (i, record) in records.iter().enumerate() {
+        let number = i as u64 + 1;
+        let ciphertext = BASE64_STANDARD
+            .decode(record)
+            .map_err(|_| anyhow::anyhow!("Line {} was tampered with or is corrupt", number))?;
+        text.extend_from_slice(&cipher.open(number, number == header.line_count, &ciphertext)?);
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::SealSession;
+    use crypto::EncryptionInput;
+
+    #[test]
+    fn test_tampered_line_is_named() -> Result<()> {
+        let session = SealSession::generate(3, 2)?;
+        let diff = b"--- a/app.toml\n+++ b/app.toml\n-port = 80\n+port = 8080\n";
+        let (encrypted_object, key) = session.encrypt(b"patch:app.toml", EncryptionInput::Plain)?;
+        let sealed = encrypt_lines(diff, encrypted_object, &key)?;
+
+        assert_eq!(decrypt_lines(&sealed, |object| session.decrypt(object))?, diff);
+
+        // Flip one bit in the ciphertext of line 3 ("-port = 80")
+        let mut records: Vec<String> = sealed.lines().map(str::to_string).collect();
+        let mut line_3 = BASE64_STANDARD.decode(&records[3])?;
+        line_3[0] ^= 1;
+        records[3] = BASE64_STANDARD.encode(line_3);
+        let err = decrypt_lines(&records.join("\n"), |object| session.decrypt(object)).unwrap_err();
+        assert_eq!(err.to_string(), "Line 3 was tampered with or is corrupt");
+
+        // Swapping two lines breaks both, and the first one is reported
+        let mut swapped: Vec<&str> = sealed.lines().collect();
+        swapped.swap(1, 2);
+        let err = decrypt_lines(&swapped.join("\n"), |object| session.decrypt(object)).unwrap_err();
+        assert_eq!(err.to_string(), "Line 1 was tampered with or is corrupt");
+        Ok(())
+    }
+}
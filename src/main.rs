@@ -1,16 +1,73 @@
 use anyhow::Result;
 use clap::{Parser, Subcommand};
-use tracing_subscriber;
 
+mod aad;
+mod atomic;
 mod basic_demo;
+mod bench;
+mod config;
+mod container;
+mod directory_manifest;
+mod escrow;
+mod key_cache;
+mod key_files;
 mod key_management;
+mod lines;
+mod padding;
+mod params;
+mod policy;
+mod provenance;
+mod receipt;
+mod report;
 mod threshold_demo;
 mod file_demo;
+mod file_modes;
+mod fixtures;
+mod full_id;
+mod health;
+mod id_encoding;
+mod identity;
+mod kat;
+mod metrics;
+mod monitor;
+mod output;
+mod pack;
+#[cfg(feature = "qr")]
+mod qr;
+mod registry;
+mod session;
+mod shamir;
+mod shred;
+mod signing;
+mod streaming;
+mod tenants;
+#[cfg(feature = "sui")]
+mod sui;
+mod testutil;
+#[cfg(feature = "tui")]
+mod tui;
 
+use aad::AadContext;
 use basic_demo::BasicDemo;
+use bench::Benchmark;
+use container::SealContainer;
 use key_management::KeyManagementDemo;
-use threshold_demo::ThresholdDemo;
+use threshold_demo::{KeyFetch, ThresholdDemo};
 use file_demo::FileDemo;
+use full_id::FullIdScheme;
+use id_encoding::IdEncoding;
+use key_files::ServerPreference;
+use output::{out, outln};
+use padding::Padding;
+use session::{SealEvent, SealSession};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+/// Defaults for `--servers`/`--threshold` of `threshold`, `files` and `all`,
+/// e.g. for CI. A flag on the command line wins over the environment, which
+/// wins over the built-in default.
+const SERVERS_ENV: &str = "SEAL_DEMO_SERVERS";
+const THRESHOLD_ENV: &str = "SEAL_DEMO_THRESHOLD";
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -19,6 +76,51 @@ use file_demo::FileDemo;
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+    /// Rough cap in bytes on data buffered by file operations (streaming chunk size and batch concurrency)
+    #[arg(long, global = true)]
+    max_memory: Option<usize>,
+    /// Longest identity, in bytes, that encryption accepts
+    #[arg(long, global = true, default_value_t = identity::DEFAULT_MAX_IDENTITY_LEN)]
+    max_identity_len: usize,
+    /// Overwrite temp plaintext with random bytes before deleting it, and keep it in /dev/shm if available (best effort, see README)
+    #[arg(long, global = true)]
+    shred: bool,
+    /// Serve Prometheus metrics on this port at /metrics while the command runs (`net` feature)
+    #[arg(long, global = true)]
+    metrics_port: Option<u16>,
+    /// Keep serving --metrics-port after the command finishes, until interrupted
+    #[arg(long, global = true, requires = "metrics_port")]
+    linger: bool,
+    /// Plain-ASCII output ([OK], [FAIL], ->) instead of emoji; automatic when the locale isn't UTF-8
+    #[arg(long, global = true)]
+    no_emoji: bool,
+}
+
+#[derive(Subcommand)]
+enum EnvelopeAction {
+    /// Sign an encrypted file together with the current time and tool version
+    Create {
+        /// BLS secret key (hex) to sign the envelope with
+        #[arg(long)]
+        sign_key: String,
+        /// Encrypted file (from `encrypt`)
+        input: PathBuf,
+        /// Where to write the envelope
+        output: PathBuf,
+    },
+    /// Verify an envelope's signature, report its provenance and decrypt it
+    Open {
+        /// Signer's BLS public key (hex)
+        #[arg(long)]
+        pubkey: String,
+        /// Session JSON including master keys (see `session --include-secrets`)
+        #[arg(long)]
+        session: PathBuf,
+        /// Envelope file
+        input: PathBuf,
+        /// Where to write the plaintext
+        output: PathBuf,
+    },
 }
 
 #[derive(Subcommand)]
@@ -34,6 +136,9 @@ enum Commands {
         /// Run only plain key derivation demo
         #[arg(long)]
         plain_only: bool,
+        /// Run only the hardware-dependent AES/HMAC auto mode demo
+        #[arg(long)]
+        auto_only: bool,
     },
     /// Demonstrate key management operations
     Keys {
@@ -49,12 +154,21 @@ enum Commands {
         /// Show only key verification demo
         #[arg(long)]
         verify_only: bool,
+        /// Show only the Shamir vs IBE threshold comparison
+        #[arg(long)]
+        shamir_only: bool,
+        /// Issue and verify keys for every identity in this file (one per line, `hex:` for raw bytes, `#` comments)
+        #[arg(long)]
+        identities_file: Option<PathBuf>,
     },
     /// Demonstrate threshold encryption with multiple key servers
     Threshold {
         /// Number of key servers to create (default: 3)
-        #[arg(short, long, default_value_t = 3)]
+        #[arg(short, long, env = SERVERS_ENV, default_value_t = 3)]
         servers: usize,
+        /// Threshold to demonstrate (default: 2, or both 2 and 3 for the full demo)
+        #[arg(short, long, env = THRESHOLD_ENV)]
+        threshold: Option<u8>,
         /// Show only basic threshold demo
         #[arg(long)]
         basic_only: bool,
@@ -64,6 +178,9 @@ enum Commands {
         /// Show only access patterns demo
         #[arg(long)]
         access_only: bool,
+        /// Show only time-locked identity demo
+        #[arg(long)]
+        time_lock_only: bool,
     },
     /// Demonstrate file encryption and decryption
     Files {
@@ -76,58 +193,792 @@ enum Commands {
         /// Show only metadata demo
         #[arg(long)]
         metadata_only: bool,
+        /// Number of key servers to create (default: 3)
+        #[arg(short, long, env = SERVERS_ENV, default_value_t = 3)]
+        servers: usize,
+        /// Servers needed to decrypt (default: 2)
+        #[arg(short, long, env = THRESHOLD_ENV, default_value_t = 2)]
+        threshold: u8,
+        /// Naming for encrypted outputs; placeholders {stem}, {ext} and {n} (a counter)
+        #[arg(long, default_value = file_demo::DEFAULT_NAME_TEMPLATE)]
+        name_template: String,
+        /// Make streaming encryption resumable, checkpointing every this many chunks
+        #[arg(long)]
+        checkpoint_every: Option<u64>,
+        /// TOML rules picking each file's mode and compression in directory encryption
+        #[arg(long)]
+        file_modes: Option<PathBuf>,
+        /// Leave files over this many bytes out of directory encryption
+        #[arg(long)]
+        max_file_size: Option<u64>,
+        /// Leave files with these extensions out of directory encryption (comma-separated, e.g. mp4,jpg,zip)
+        #[arg(long, value_delimiter = ',')]
+        exclude_ext: Vec<String>,
+        /// Print a running file and byte count as the batch demo encrypts
+        #[arg(long, requires = "batch_only")]
+        progress: bool,
     },
     /// Run all demonstrations (comprehensive overview)
     All {
-        /// Number of key servers for threshold demo (default: 3)
-        #[arg(short, long, default_value_t = 3)]
+        /// Number of key servers for the threshold and file demos (default: 3)
+        #[arg(short, long, env = SERVERS_ENV, default_value_t = 3)]
         servers: usize,
+        /// Threshold for the threshold and file demos (default: 2, and 3 in the threshold demo)
+        #[arg(short, long, env = THRESHOLD_ENV)]
+        threshold: Option<u8>,
     },
     /// Interactive mode - choose demos interactively
     Interactive,
+    /// Run one demo by name, or list the names
+    Run {
+        /// Demo to run, e.g. threshold-rotation (omit to list all demos)
+        name: Option<String>,
+        /// Number of key servers for threshold and file demos (default: 3)
+        #[arg(short, long, env = SERVERS_ENV, default_value_t = 3)]
+        servers: usize,
+    },
+    /// Benchmark encrypt/decrypt round trips
+    Bench {
+        /// Number of round trips to time (default: 20)
+        #[arg(short, long, default_value_t = 20)]
+        iterations: usize,
+        /// Payload size in bytes (default: 65536)
+        #[arg(long, default_value_t = 65536)]
+        payload_bytes: usize,
+        /// Number of key servers (default: 3)
+        #[arg(short, long, default_value_t = 3)]
+        servers: usize,
+        /// Decryption threshold (default: 2)
+        #[arg(short, long, default_value_t = 2)]
+        threshold: u8,
+        /// Write per-iteration timings to this CSV file
+        #[arg(long)]
+        output_csv: Option<PathBuf>,
+        /// Seed the payload generator for reproducible runs
+        #[arg(long)]
+        seed: Option<u64>,
+        /// Write the session's counters here in Prometheus text format (for node_exporter's textfile collector)
+        #[arg(long)]
+        metrics_file: Option<PathBuf>,
+    },
+    /// Estimate how long encrypting every file under a directory will take
+    Estimate {
+        /// Directory to estimate for (searched recursively)
+        #[arg(long)]
+        dir: PathBuf,
+        /// Number of files to sample (default: 50)
+        #[arg(long, default_value_t = 50)]
+        sample: usize,
+        /// Number of key servers (default: 3)
+        #[arg(short, long, default_value_t = 3)]
+        servers: usize,
+        /// Decryption threshold (default: 2)
+        #[arg(short, long, default_value_t = 2)]
+        threshold: u8,
+        /// Seed the sample selection for reproducible runs
+        #[arg(long)]
+        seed: Option<u64>,
+    },
+    /// Hammer one shared session with concurrent encrypt/decrypt round trips, checking each
+    Stress {
+        /// Concurrent threads (default: one per core)
+        #[arg(long)]
+        threads: Option<usize>,
+        /// How long to run: 30s, 2m, 500ms, ... (default: 10s)
+        #[arg(long, default_value = "10s", value_parser = bench::parse_duration)]
+        duration: std::time::Duration,
+        /// Largest random payload in bytes (default: 4096)
+        #[arg(long, default_value_t = 4096)]
+        payload_bytes: usize,
+        /// Number of key servers (default: 3)
+        #[arg(short, long, default_value_t = 3)]
+        servers: usize,
+        /// Decryption threshold (default: 2)
+        #[arg(short, long, default_value_t = 2)]
+        threshold: u8,
+    },
+    /// Benchmark user secret key extraction, as done by a key server
+    BenchExtract {
+        /// Number of distinct identities to extract keys for (default: 10000)
+        #[arg(long, default_value_t = 10000)]
+        identities: usize,
+        /// Also verify every extracted key, timed separately
+        #[arg(long)]
+        verify: bool,
+    },
+    /// Benchmark decrypt latency at every threshold up to a maximum
+    BenchThreshold {
+        /// Number of key servers (default: 5)
+        #[arg(short, long, default_value_t = 5)]
+        servers: usize,
+        /// Highest threshold to time (default: the number of servers)
+        #[arg(long)]
+        max_threshold: Option<u8>,
+    },
+    /// Decrypt latency by threshold with a simulated network delay per key server
+    LatencySim {
+        /// Number of key servers (default: 5)
+        #[arg(short, long, default_value_t = 5)]
+        servers: usize,
+        /// Highest threshold to time (default: the number of servers)
+        #[arg(long)]
+        max_threshold: Option<u8>,
+        /// Delay each key server adds to its answer, in milliseconds (default: 50)
+        #[arg(long, default_value_t = 50)]
+        server_delay_ms: u64,
+        /// Fetch keys one server at a time (serial) or all at once (parallel); default: time both
+        #[arg(long, value_parser = KeyFetch::parse)]
+        fetch: Option<KeyFetch>,
+    },
+    /// Encrypt a file to a session's key-server fleet
+    Encrypt {
+        /// Session JSON describing the fleet (see `session`)
+        #[arg(long)]
+        session: PathBuf,
+        /// Package ID to encrypt under (hex), instead of the session's
+        #[arg(long, value_parser = session::decode_object_id)]
+        package_id: Option<crypto::ObjectID>,
+        /// Identity to encrypt to (default: "file:<input filename>")
+        #[arg(long)]
+        identity: Option<String>,
+        /// How `--identity` is written: utf8, hex, or base32 (Crockford) for binary identities
+        #[arg(long, requires = "identity", default_value = "utf8", value_parser = IdEncoding::parse)]
+        identity_encoding: IdEncoding,
+        /// Encrypt to the fixed-size SHA3-256 hash of `--identity` instead, for identities over the length limit
+        #[arg(long, requires = "identity")]
+        hash_identity: bool,
+        /// Sign the encrypted output with this BLS secret key (hex), writing `<output>.sig`
+        #[arg(long)]
+        sign_key: Option<String>,
+        /// Bind this monotonic version number into the authenticated data
+        #[arg(long)]
+        object_version: Option<u64>,
+        /// Refuse to decrypt after this date (2025-12-31) or RFC 3339 time, bound into the authenticated data
+        #[arg(long, value_parser = aad::parse_expiry)]
+        expires_at: Option<chrono::DateTime<chrono::Utc>>,
+        /// Also wrap the file's key to this identity for break-glass recovery
+        #[arg(long)]
+        escrow_identity: Option<String>,
+        /// Bind the input's `{relative_path}|{size}|{mtime}` into the authenticated data
+        #[arg(long)]
+        bind_context: bool,
+        /// Pad the plaintext to the next power of two (at least 1 KiB) so the ciphertext only reveals a size class
+        #[arg(long)]
+        pad_to_bucket: bool,
+        /// With --pad-to-bucket, pad to a multiple of this many bytes instead
+        #[arg(long, requires = "pad_to_bucket", value_parser = padding::parse_bucket_size)]
+        bucket_size: Option<usize>,
+        /// File to encrypt
+        input: PathBuf,
+        /// Where to write the encrypted object
+        output: PathBuf,
+    },
+    /// Encrypt stdin to stdout as a streaming container, holding about two chunks in memory
+    EncryptStream {
+        /// Session JSON or TOML describing the fleet (see `session`)
+        #[arg(long)]
+        session: PathBuf,
+        /// Identity to encrypt to
+        #[arg(long)]
+        identity: String,
+        /// Chunk size in bytes (default: 64 KiB)
+        #[arg(long, default_value_t = 64 * 1024)]
+        chunk_size: usize,
+    },
+    /// Decrypt a streaming container from stdin to stdout using a session that includes master keys
+    DecryptStream {
+        /// Session JSON or TOML including master keys (see `session --include-secrets`)
+        #[arg(long)]
+        session: PathBuf,
+    },
+    /// Move a streaming container on stdin to another fleet, chunk by chunk, writing it to stdout
+    RewrapStream {
+        /// Session JSON or TOML including master keys for the fleet the stream is sealed to
+        #[arg(long)]
+        session: PathBuf,
+        /// Session JSON or TOML for the fleet to move it to; public parameters are enough
+        #[arg(long)]
+        new_session: PathBuf,
+        /// Threshold under the new fleet (default: the new session's)
+        #[arg(long)]
+        threshold: Option<u8>,
+    },
+    /// Decrypt a file using a session that includes master keys
+    Decrypt {
+        /// Session JSON including master keys (see `session --include-secrets`)
+        #[arg(long, required_unless_present = "key_dir")]
+        session: Option<PathBuf>,
+        /// Directory of per-server key files (see `session --key-dir`); any quorum of them will do, and key shares
+        /// are verified when there is a file for every server
+        #[arg(long, conflicts_with_all = ["session", "no_verify"])]
+        key_dir: Option<PathBuf>,
+        /// With --key-dir, take the quorum from the servers whose keys extract fastest
+        #[arg(long, requires = "key_dir", conflicts_with = "prefer")]
+        prefer_fastest: bool,
+        /// With --key-dir, take the quorum from these servers first, in order (repeatable)
+        #[arg(long, requires = "key_dir", value_parser = session::decode_object_id)]
+        prefer: Vec<crypto::ObjectID>,
+        /// Only decrypt objects encrypted under this package ID (hex)
+        #[arg(long, value_parser = session::decode_object_id)]
+        package_id: Option<crypto::ObjectID>,
+        /// Reject ciphertexts whose authenticated version is lower than this
+        #[arg(long)]
+        min_version: Option<u64>,
+        /// Decrypt even if the ciphertext's authenticated expiry time has passed
+        #[arg(long)]
+        ignore_expiry: bool,
+        /// Skip key-share verification (INSECURE - accepts shares from a wrong or malicious server)
+        #[arg(long)]
+        no_verify: bool,
+        /// Rebuild the full ID with an earlier scheme (1 or 2) for files sealed before a layout change; skips share verification
+        #[arg(long, conflicts_with_all = ["key_dir", "no_verify"], value_parser = FullIdScheme::parse)]
+        full_id_version: Option<FullIdScheme>,
+        /// Also write a signed receipt recording that the file was opened (see `verify-receipt`)
+        #[arg(long, requires = "receipt_key")]
+        receipt: Option<PathBuf>,
+        /// BLS secret key (hex) to sign the receipt with (see `signing-key`)
+        #[arg(long, requires = "receipt")]
+        receipt_key: Option<String>,
+        /// Encrypted file
+        input: PathBuf,
+        /// Where to write the plaintext
+        output: PathBuf,
+    },
+    /// Check a decryption receipt's signature and that it names this encrypted file
+    VerifyReceipt {
+        /// Opener's BLS public key (hex)
+        #[arg(long)]
+        pubkey: String,
+        /// Receipt written by `decrypt --receipt`
+        receipt: PathBuf,
+        /// The encrypted file the receipt should cover
+        input: PathBuf,
+    },
+    /// Check a user secret key returned by a key server against that server's public key
+    VerifyKey {
+        /// Session JSON or TOML describing the fleet (see `session`)
+        #[arg(long)]
+        session: PathBuf,
+        /// Object ID of the server the key came from
+        #[arg(long, value_parser = session::decode_object_id)]
+        server: crypto::ObjectID,
+        /// Identity the key was requested for
+        #[arg(long)]
+        identity: String,
+        /// The key as a compressed G1 point (hex, 48 bytes)
+        key: String,
+    },
+    /// Recover an escrowed file using only the escrow identity's keys
+    EscrowDecrypt {
+        /// Session JSON including master keys
+        #[arg(long)]
+        session: PathBuf,
+        /// Encrypted file written with `encrypt --escrow-identity`
+        input: PathBuf,
+        /// Where to write the plaintext
+        output: PathBuf,
+    },
+    /// Verify a detached signature over an encrypted file before decrypting it
+    Verify {
+        /// Encrypted file
+        input: PathBuf,
+        /// Detached signature file (hex)
+        signature: PathBuf,
+        /// Signer's BLS public key (hex)
+        #[arg(long)]
+        pubkey: String,
+    },
+    /// Wrap an encrypted file in a signed, timestamped provenance envelope, or open one
+    Envelope {
+        #[command(subcommand)]
+        action: EnvelopeAction,
+    },
+    /// Find the smallest number of servers that can decrypt a file
+    MinQuorum {
+        /// Session JSON including master keys for the whole fleet
+        #[arg(long)]
+        session: PathBuf,
+        /// Encrypted file
+        input: PathBuf,
+    },
+    /// Show the container header and encrypted object metadata of a file
+    Inspect {
+        /// Show identities as utf8 (default), hex, or base32 (Crockford)
+        #[arg(long, default_value = "utf8", value_parser = IdEncoding::parse)]
+        id_encoding: IdEncoding,
+        /// Encrypted file
+        input: PathBuf,
+    },
+    /// Check every key server can serve keys and that a quorum is healthy
+    Health {
+        /// Session config (`.toml`, or JSON from `session`)
+        #[arg(long)]
+        config: PathBuf,
+    },
+    /// Check that a random sample of encrypted files still decrypts with the fleet, for cron (one summary line on stdout, exits nonzero on any failure)
+    Monitor {
+        /// Session config including master keys (`.toml`, or JSON from `session --include-secrets`)
+        #[arg(long)]
+        config: PathBuf,
+        /// Directory of `.seal` and `.sealstream` files (searched recursively)
+        #[arg(long)]
+        sample_dir: PathBuf,
+        /// Number of files to decrypt (default: 100)
+        #[arg(long, default_value_t = 100)]
+        sample: usize,
+        /// Seed the sample selection for reproducible runs
+        #[arg(long)]
+        seed: Option<u64>,
+    },
+    /// Validate a config's key material end to end (exits nonzero on any problem)
+    ValidateConfig {
+        /// Session config (`.toml`, or JSON from `session`)
+        #[arg(long)]
+        config: PathBuf,
+    },
+    /// Count how often each identity is used across a directory of `.seal` files (no keys needed)
+    AuditIdentities {
+        /// Directory of encrypted files (searched recursively)
+        #[arg(long)]
+        dir: PathBuf,
+        /// Also list identities used by a single file
+        #[arg(long)]
+        all: bool,
+        /// Least-reused first instead of most-reused first
+        #[arg(long)]
+        ascending: bool,
+    },
+    /// Rewrite legacy headerless (and older container) files in the current container format, without keys
+    MigrateFormat {
+        /// Directory of encrypted files (searched recursively)
+        #[arg(long)]
+        dir: PathBuf,
+        /// Where to write the upgraded files, mirroring `dir`'s layout
+        #[arg(long)]
+        out: PathBuf,
+        /// Public session of the fleet the files were encrypted to, to record its fingerprint
+        #[arg(long)]
+        session: Option<PathBuf>,
+    },
+    /// Bundle every file under a directory into one indexed `.sealpack`, without keys
+    Pack {
+        /// Directory of encrypted files (searched recursively)
+        #[arg(long)]
+        dir: PathBuf,
+        /// Pack file to write
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// Restore the files in a `.sealpack` under a directory, without keys
+    Unpack {
+        /// Pack file from `pack`
+        #[arg(long)]
+        pack: PathBuf,
+        /// Directory to restore the files into
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// Report which servers two fleets share and whether their ciphertexts are interchangeable
+    CompareFleets {
+        /// First session config (`.toml`, or JSON from `session`)
+        first: PathBuf,
+        /// Second session config
+        second: PathBuf,
+    },
+    /// Print the curve, element sizes, encryption modes and library versions in use
+    Params,
+    /// Print the key pairs derived from a seed as a tree
+    DeriveTree {
+        /// 32-byte seed (hex)
+        #[arg(long)]
+        seed_hex: String,
+        /// Number of indices to derive (default: 5)
+        #[arg(long, default_value_t = 5)]
+        count: u64,
+        /// Identity for the sample user secret keys (package ID is all zeroes)
+        #[arg(long, default_value = "user@example.com")]
+        identity: String,
+    },
+    /// Write a deterministic fleet, encrypted objects and an index of expected plaintexts for interop tests
+    GenFixtures {
+        /// Directory to write the fixtures to
+        #[arg(long)]
+        out: PathBuf,
+        /// 32-byte seed (hex)
+        #[arg(long)]
+        seed_hex: String,
+    },
+    /// Check the crypto pipeline against the known-answer vectors (exits nonzero on any deviation)
+    Kat {
+        /// Vectors file (default: the repository's kat/vectors.json)
+        #[arg(long, default_value = kat::DEFAULT_VECTORS)]
+        vectors: PathBuf,
+        /// Rewrite the vectors from the current build instead, after an intended change
+        #[arg(long)]
+        regenerate: bool,
+    },
+    /// Generate a BLS key pair for signing encrypted files
+    SigningKey,
+    /// Show one key server's public parameters as a QR code for mobile clients
+    Qr {
+        /// Session file describing the fleet (JSON or TOML)
+        #[arg(long)]
+        session: PathBuf,
+        /// Which server of the session to export (default: 0)
+        #[arg(long, default_value_t = 0)]
+        server_index: usize,
+        /// Also write the QR code to this PNG file
+        #[arg(long)]
+        png: Option<PathBuf>,
+    },
+    /// Build a public-key session from the key servers registered under a Sui package (needs the `sui` feature)
+    ImportFleet {
+        /// Seal package the key servers were created under (hex)
+        #[arg(long, value_parser = session::decode_object_id)]
+        package: crypto::ObjectID,
+        /// Sui full node JSON-RPC URL
+        #[arg(long, default_value = "https://fullnode.testnet.sui.io:443")]
+        rpc: String,
+        /// Servers needed to decrypt (default: a majority)
+        #[arg(short, long)]
+        threshold: Option<u8>,
+        /// Write to this file instead of stdout (TOML if it ends in `.toml`, else JSON)
+        #[arg(short, long)]
+        out: Option<PathBuf>,
+    },
+    /// Export a session (fleet public parameters) as JSON for handoff
+    Session {
+        /// Number of key servers in a freshly generated fleet (default: 3)
+        #[arg(short, long, default_value_t = 3)]
+        servers: usize,
+        /// Decryption threshold for a freshly generated fleet (default: 2)
+        #[arg(short, long, default_value_t = 2)]
+        threshold: u8,
+        /// Re-export an existing session file instead of generating a new fleet
+        #[arg(long)]
+        from: Option<PathBuf>,
+        /// Seed the generated fleet's IDs and keys for a reproducible fleet (testing only)
+        #[arg(long, conflicts_with = "from")]
+        seed: Option<u64>,
+        /// Package ID (hex) to record instead of a random one
+        #[arg(long, value_parser = session::decode_object_id)]
+        package_id: Option<crypto::ObjectID>,
+        /// Write to this file instead of stdout (TOML if it ends in `.toml`, else JSON)
+        #[arg(short, long)]
+        out: Option<PathBuf>,
+        /// Also export master keys (SECRET - anyone holding them can decrypt)
+        #[arg(long)]
+        include_secrets: bool,
+        /// Also write one key file per server into this directory (SECRET - holds master keys)
+        #[arg(long)]
+        key_dir: Option<PathBuf>,
+    },
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            // What returning the error would print, but through `render` like the rest of the output
+            eprintln!("Error: {}", output::render(&format!("{:?}", e)));
+            ExitCode::FAILURE
+        }
+    }
 }
 
-fn main() -> Result<()> {
+fn run(cli: Cli) -> Result<()> {
+    // Commands piping data (or monitor's summary line) through stdout print
+    // everything else to stderr
+    let data_on_stdout = matches!(
+        cli.command,
+        Commands::EncryptStream { .. }
+            | Commands::DecryptStream { .. }
+            | Commands::RewrapStream { .. }
+            | Commands::Monitor { .. }
+    );
+
     // Initialize tracing
     tracing_subscriber::fmt()
         .with_max_level(tracing::Level::INFO)
+        .with_writer(move || -> Box<dyn std::io::Write> {
+            if data_on_stdout {
+                Box::new(std::io::stderr())
+            } else {
+                Box::new(std::io::stdout())
+            }
+        })
         .init();
 
-    let cli = Cli::parse();
+    identity::set_max_identity_len(cli.max_identity_len);
+    shred::set_enabled(cli.shred);
+    output::set_plain(cli.no_emoji || !output::terminal_supports_emoji());
+    output::set_to_stderr(data_on_stdout);
+    if let Some(port) = cli.metrics_port {
+        metrics::serve(port)?;
+    }
 
-    println!("🚀 Seal Rust Integration Demo");
-    println!("===============================");
-    println!("This demo shows how to integrate Seal encryption into your Rust applications.");
-    println!();
+    outln!("🚀 Seal Rust Integration Demo");
+    outln!("===============================");
+    outln!("This demo shows how to integrate Seal encryption into your Rust applications.");
+    outln!();
 
     match &cli.command {
-        Commands::Basic { aes_only, hmac_only, plain_only } => {
-            run_basic_demo(*aes_only, *hmac_only, *plain_only)?;
+        Commands::Basic { aes_only, hmac_only, plain_only, auto_only } => {
+            run_basic_demo(*aes_only, *hmac_only, *plain_only, *auto_only)?;
         }
-        Commands::Keys { generation_only, seed_only, namespace_only, verify_only } => {
-            run_key_management_demo(*generation_only, *seed_only, *namespace_only, *verify_only)?;
+        Commands::Keys { generation_only, seed_only, namespace_only, verify_only, shamir_only, identities_file } => {
+            match identities_file {
+                Some(path) => {
+                    let failures = KeyManagementDemo::from_identity_file(path)?.demo_bulk_issuance()?;
+                    if failures > 0 {
+                        anyhow::bail!("{} identities failed key issuance", failures);
+                    }
+                }
+                None => run_key_management_demo(*generation_only, *seed_only, *namespace_only, *verify_only, *shamir_only)?,
+            }
         }
-        Commands::Threshold { servers, basic_only, rotation_only, access_only } => {
-            run_threshold_demo(*servers, *basic_only, *rotation_only, *access_only)?;
+        Commands::Threshold { servers, threshold, basic_only, rotation_only, access_only, time_lock_only } => {
+            run_threshold_demo(*servers, *threshold, *basic_only, *rotation_only, *access_only, *time_lock_only)?;
         }
-        Commands::Files { basic_only, batch_only, metadata_only } => {
-            run_file_demo(*basic_only, *batch_only, *metadata_only)?;
+        Commands::Files {
+            basic_only,
+            batch_only,
+            metadata_only,
+            servers,
+            threshold,
+            name_template,
+            checkpoint_every,
+            file_modes,
+            max_file_size,
+            exclude_ext,
+            progress,
+        } => {
+            let filter = file_modes::FileFilter::new(*max_file_size, exclude_ext);
+            let options = (cli.max_memory, *checkpoint_every, file_modes.as_deref(), filter, *progress);
+            run_file_demo((*servers, *threshold), *basic_only, *batch_only, *metadata_only, Some(name_template), options)?;
         }
-        Commands::All { servers } => {
-            run_all_demos(*servers)?;
+        Commands::All { servers, threshold } => {
+            run_all_demos(*servers, *threshold, cli.max_memory)?;
         }
         Commands::Interactive => {
-            run_interactive_mode()?;
+            run_interactive()?;
+        }
+        Commands::Run { name, servers } => {
+            run_named_demo(name.as_deref(), *servers)?;
+        }
+        Commands::Bench { iterations, payload_bytes, servers, threshold, output_csv, seed, metrics_file } => {
+            let bench = Benchmark::new(*servers, *threshold, *payload_bytes)?.with_seed(*seed);
+            bench.run(*iterations, output_csv.as_deref())?;
+            if let Some(path) = metrics_file {
+                atomic::write_file(path, bench.session.metrics_prometheus().as_bytes())?;
+                outln!("📈 Metrics written to: {}", path.display());
+            }
+        }
+        Commands::Estimate { dir, sample, servers, threshold, seed } => {
+            Benchmark::new(*servers, *threshold, 0)?.with_seed(*seed).estimate(dir, *sample)?;
+        }
+        Commands::Stress { threads, duration, payload_bytes, servers, threshold } => {
+            let threads = threads.unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get()));
+            Benchmark::new(*servers, *threshold, *payload_bytes)?.stress(threads, *duration)?;
+        }
+        Commands::BenchExtract { identities, verify } => {
+            bench::run_extract(*identities, *verify)?;
+        }
+        Commands::BenchThreshold { servers, max_threshold } => {
+            let max_threshold = max_threshold.unwrap_or((*servers).min(u8::MAX as usize) as u8);
+            ThresholdDemo::new(*servers).bench_by_threshold(max_threshold)?;
+        }
+        Commands::LatencySim { servers, max_threshold, server_delay_ms, fetch } => {
+            let max_threshold = max_threshold.unwrap_or((*servers).min(u8::MAX as usize) as u8);
+            let fetches = match fetch {
+                Some(fetch) => vec![*fetch],
+                None => vec![KeyFetch::Serial, KeyFetch::Parallel],
+            };
+            let delay = std::time::Duration::from_millis(*server_delay_ms);
+            ThresholdDemo::new(*servers).latency_by_threshold(max_threshold, delay, &fetches)?;
+        }
+        Commands::Encrypt {
+            session,
+            package_id,
+            identity,
+            identity_encoding,
+            hash_identity,
+            sign_key,
+            object_version,
+            expires_at,
+            escrow_identity,
+            bind_context,
+            pad_to_bucket,
+            bucket_size,
+            input,
+            output,
+        } => {
+            let mut session = SealSession::from_json(&std::fs::read_to_string(session)?)?.with_event_hook(print_session_event);
+            if let Some(package_id) = package_id {
+                session.package_id = *package_id;
+            }
+            let context = AadContext {
+                version: *object_version,
+                file: if *bind_context { Some(aad::file_binding(input)?) } else { None },
+                quorum: Some(aad::quorum_binding(session.threshold, &session.key_servers)),
+                padding: pad_to_bucket.then(|| bucket_size.map_or(Padding::PowerOfTwo, Padding::Multiple)),
+                expires_at: expires_at.map(|time| time.timestamp()),
+            };
+            let identity = match identity {
+                Some(identity) => {
+                    let identity = identity_encoding.decode(identity)?;
+                    Some(if *hash_identity { identity::hash_identity(&identity) } else { identity })
+                }
+                None => None,
+            };
+            run_encrypt(
+                &session,
+                identity.as_deref(),
+                sign_key.as_deref(),
+                &context,
+                escrow_identity.as_deref(),
+                input,
+                output,
+            )?;
+        }
+        Commands::EncryptStream { session, identity, chunk_size } => {
+            run_encrypt_stream(session, identity, *chunk_size)?;
+        }
+        Commands::DecryptStream { session } => {
+            run_decrypt_stream(session)?;
+        }
+        Commands::RewrapStream { session, new_session, threshold } => {
+            run_rewrap_stream(session, new_session, *threshold)?;
+        }
+        Commands::Decrypt {
+            session,
+            key_dir,
+            prefer_fastest,
+            prefer,
+            package_id,
+            min_version,
+            ignore_expiry,
+            no_verify,
+            full_id_version,
+            receipt,
+            receipt_key,
+            input,
+            output,
+        } => {
+            let preference = if *prefer_fastest {
+                ServerPreference::Latency(Box::new(key_files::probe_latency))
+            } else if !prefer.is_empty() {
+                ServerPreference::Priority(prefer.clone())
+            } else {
+                ServerPreference::Listed
+            };
+            let key_dir = key_dir.as_deref().map(|dir| (dir, &preference));
+            let mode = match (full_id_version, no_verify) {
+                (Some(scheme), _) => SessionMode::LegacyFullId(*scheme),
+                (None, true) => SessionMode::Unverified,
+                (None, false) => SessionMode::Verified,
+            };
+            let checks = ContextChecks { min_version: *min_version, ignore_expiry: *ignore_expiry };
+            let opened = run_decrypt(session.as_deref(), key_dir, *package_id, checks, mode, input, output)?;
+            if let (Some(path), Some(sign_key)) = (receipt, receipt_key) {
+                run_write_receipt(opened, sign_key, path)?;
+            }
+        }
+        Commands::VerifyReceipt { pubkey, receipt, input } => {
+            run_verify_receipt(pubkey, receipt, input)?;
+        }
+        Commands::VerifyKey { session, server, identity, key } => {
+            run_verify_key(session, *server, identity, key)?;
+        }
+        Commands::EscrowDecrypt { session, input, output } => {
+            run_escrow_decrypt(session, input, output)?;
+        }
+        Commands::Verify { input, signature, pubkey } => {
+            run_verify(input, signature, pubkey)?;
+        }
+        Commands::Envelope { action } => match action {
+            EnvelopeAction::Create { sign_key, input, output } => run_envelope_create(sign_key, input, output)?,
+            EnvelopeAction::Open { pubkey, session, input, output } => run_envelope_open(pubkey, session, input, output)?,
+        },
+        Commands::MinQuorum { session, input } => {
+            run_min_quorum(session, input)?;
+        }
+        Commands::Inspect { id_encoding, input } => {
+            run_inspect(input, *id_encoding)?;
+        }
+        Commands::Health { config } => {
+            run_health(config)?;
+        }
+        Commands::Monitor { config, sample_dir, sample, seed } => {
+            run_monitor(config, sample_dir, *sample, *seed)?;
+        }
+        Commands::ValidateConfig { config } => {
+            run_validate_config(config)?;
+        }
+        Commands::AuditIdentities { dir, all, ascending } => {
+            run_audit_identities(dir, *all, *ascending)?;
+        }
+        Commands::MigrateFormat { dir, out, session } => {
+            run_migrate_format(dir, out, session.as_deref())?;
+        }
+        Commands::Pack { dir, out } => {
+            run_pack(dir, out)?;
+        }
+        Commands::Unpack { pack, out } => {
+            run_unpack(pack, out)?;
+        }
+        Commands::CompareFleets { first, second } => {
+            run_compare_fleets(first, second)?;
+        }
+        Commands::Params => {
+            params::print_params();
+        }
+        Commands::DeriveTree { seed_hex, count, identity } => {
+            key_management::print_derivation_tree(&decode_seed(seed_hex)?, *count, &[0u8; 32], identity.as_bytes());
+        }
+        Commands::GenFixtures { out, seed_hex } => {
+            run_gen_fixtures(decode_seed(seed_hex)?, out)?;
+        }
+        Commands::Kat { vectors, regenerate } => {
+            run_kat(vectors, *regenerate)?;
+        }
+        Commands::SigningKey => {
+            let (secret_key, public_key) = signing::generate_signing_key();
+            outln!("🔏 Generated BLS signing key pair:");
+            outln!("   Secret Key: {}", session::encode_master_key(&secret_key));
+            outln!("   Public Key: {}", session::encode_public_key(&public_key));
+        }
+        Commands::Session { servers, threshold, from, seed, package_id, out, include_secrets, key_dir } => {
+            let session = match (from, seed) {
+                (Some(path), _) => config::load_session(path)?,
+                (None, Some(_)) => SealSession::generate_with_rng(*servers, *threshold, &mut testutil::seeded_rng(*seed))?,
+                (None, None) => SealSession::generate(*servers, *threshold)?,
+            };
+            run_session_export(session, *package_id, out.as_deref(), *include_secrets, key_dir.as_deref())?;
+        }
+        Commands::Qr { session, server_index, png } => {
+            run_qr(session, *server_index, png.as_deref())?;
+        }
+        Commands::ImportFleet { package, rpc, threshold, out } => {
+            run_import_fleet(*package, rpc, *threshold, out.as_deref())?;
         }
     }
 
-    println!("\n✨ Demo completed! Check the source code to see how each feature is implemented.");
-    println!("📚 For more information, visit: https://github.com/MystenLabs/seal");
+    outln!("\n✨ Demo completed! Check the source code to see how each feature is implemented.");
+    outln!("📚 For more information, visit: https://github.com/MystenLabs/seal");
+
+    if let Some(port) = cli.metrics_port.filter(|_| cli.linger) {
+        // Keep the final counts scrapeable
+        outln!("📈 Serving metrics on http://0.0.0.0:{}/metrics until interrupted", port);
+        loop {
+            std::thread::park();
+        }
+    }
 
     Ok(())
 }
 
-fn run_basic_demo(aes_only: bool, hmac_only: bool, plain_only: bool) -> Result<()> {
+fn run_basic_demo(aes_only: bool, hmac_only: bool, plain_only: bool, auto_only: bool) -> Result<()> {
     let demo = BasicDemo::new()?;
 
     if aes_only {
@@ -136,12 +987,20 @@ fn run_basic_demo(aes_only: bool, hmac_only: bool, plain_only: bool) -> Result<(
         demo.run_hmac_demo()
     } else if plain_only {
         demo.run_plain_demo()
+    } else if auto_only {
+        demo.run_auto_demo()
     } else {
         demo.run_all_demos()
     }
 }
 
-fn run_key_management_demo(generation_only: bool, seed_only: bool, namespace_only: bool, verify_only: bool) -> Result<()> {
+fn run_key_management_demo(
+    generation_only: bool,
+    seed_only: bool,
+    namespace_only: bool,
+    verify_only: bool,
+    shamir_only: bool,
+) -> Result<()> {
     let demo = KeyManagementDemo::new();
 
     if generation_only {
@@ -152,36 +1011,83 @@ fn run_key_management_demo(generation_only: bool, seed_only: bool, namespace_onl
         demo.demo_identity_namespacing()
     } else if verify_only {
         demo.demo_key_verification()
+    } else if shamir_only {
+        demo.demo_shamir_comparison()
     } else {
         demo.run_all_demos()
     }
 }
 
-fn run_threshold_demo(servers: usize, basic_only: bool, rotation_only: bool, access_only: bool) -> Result<()> {
+fn run_threshold_demo(
+    servers: usize,
+    threshold: Option<u8>,
+    basic_only: bool,
+    rotation_only: bool,
+    access_only: bool,
+    time_lock_only: bool,
+) -> Result<()> {
     if servers < 2 {
         anyhow::bail!("Need at least 2 key servers for threshold encryption");
     }
+    if let Some(threshold) = threshold {
+        if threshold == 0 || threshold as usize > servers {
+            anyhow::bail!("Threshold {} is invalid for {} key servers", threshold, servers);
+        }
+    }
 
-    let demo = ThresholdDemo::new(servers);
+    let demo = ThresholdDemo::new(servers).with_threshold(threshold);
 
     if basic_only {
-        demo.demo_threshold_encryption(2)
+        demo.demo_threshold_encryption(threshold.unwrap_or(2))
     } else if rotation_only {
         demo.demo_server_rotation()
     } else if access_only {
         demo.demo_access_patterns()
+    } else if time_lock_only {
+        demo.demo_time_lock(chrono::Utc::now() + chrono::TimeDelta::days(7))
     } else {
         demo.run_all_demos()
     }
 }
 
-fn run_file_demo(basic_only: bool, batch_only: bool, metadata_only: bool) -> Result<()> {
-    let demo = FileDemo::new()?;
+/// `fleet` is the number of key servers to generate and the threshold.
+/// `--max-memory`, `--checkpoint-every`, `--file-modes`, the directory
+/// filter and `--progress` for `run_file_demo`.
+type FileOptions<'a> = (Option<usize>, Option<u64>, Option<&'a Path>, file_modes::FileFilter, bool);
+
+fn run_file_demo(
+    fleet: (usize, u8),
+    basic_only: bool,
+    batch_only: bool,
+    metadata_only: bool,
+    name_template: Option<&str>,
+    (max_memory, checkpoint_every, file_modes, file_filter, progress): FileOptions,
+) -> Result<()> {
+    let mut demo = FileDemo::with_servers(fleet.0, fleet.1)?
+        .with_max_memory(max_memory)
+        .with_checkpoints(checkpoint_every)
+        .with_file_filter(file_filter);
+    if let Some(path) = file_modes {
+        demo = demo.with_file_modes(file_modes::FileModes::load(path)?);
+    }
+    if let Some(template) = name_template {
+        demo = demo.with_name_template(template)?;
+    }
 
     if basic_only {
         demo.demo_file_encryption()
     } else if batch_only {
-        demo.demo_batch_encryption()
+        // A file's finish is the event that moves `files_done` on
+        let files_done = std::cell::Cell::new(0);
+        let report = |progress: file_demo::FileProgress| {
+            if progress.files_done > files_done.replace(progress.files_done) {
+                outln!(
+                    "   ⏳ {}/{} files, {} bytes",
+                    progress.files_done, progress.files_total, progress.bytes_done
+                );
+            }
+        };
+        demo.demo_batch_encryption(progress.then_some(&report as &dyn Fn(file_demo::FileProgress)))
     } else if metadata_only {
         demo.demo_file_metadata()
     } else {
@@ -189,146 +1095,2472 @@ fn run_file_demo(basic_only: bool, batch_only: bool, metadata_only: bool) -> Res
     }
 }
 
-fn run_all_demos(servers: usize) -> Result<()> {
-    println!("🎯 Running comprehensive demo of all Seal features...\n");
+fn run_all_demos(servers: usize, threshold: Option<u8>, max_memory: Option<usize>) -> Result<()> {
+    if let Some(threshold) = threshold {
+        if threshold == 0 || threshold as usize > servers {
+            anyhow::bail!("Threshold {} is invalid for {} key servers", threshold, servers);
+        }
+    }
+
+    outln!("🎯 Running comprehensive demo of all Seal features...\n");
+
+    let mut reports = Vec::new();
 
     // Basic encryption/decryption
-    println!("━━━ BASIC ENCRYPTION ━━━");
-    let basic_demo = BasicDemo::new()?;
-    basic_demo.run_all_demos()?;
+    outln!("━━━ BASIC ENCRYPTION ━━━");
+    reports.push(report::measure("Basic Encryption", |counter| {
+        BasicDemo::new()?.with_counter(counter.clone()).run_all_demos()
+    })?);
 
     // Key management
-    println!("\n━━━ KEY MANAGEMENT ━━━");
-    let key_demo = KeyManagementDemo::new();
-    key_demo.run_all_demos()?;
+    outln!("\n━━━ KEY MANAGEMENT ━━━");
+    reports.push(report::measure("Key Management", |counter| {
+        KeyManagementDemo::new().with_counter(counter.clone()).run_all_demos()
+    })?);
 
     // Threshold encryption
-    println!("\n━━━ THRESHOLD ENCRYPTION ━━━");
-    let threshold_demo = ThresholdDemo::new(servers);
-    threshold_demo.run_all_demos()?;
+    outln!("\n━━━ THRESHOLD ENCRYPTION ━━━");
+    reports.push(report::measure("Threshold Encryption", |counter| {
+        ThresholdDemo::new(servers).with_threshold(threshold).with_counter(counter.clone()).run_all_demos()
+    })?);
 
     // File operations
-    println!("\n━━━ FILE OPERATIONS ━━━");
-    let file_demo = FileDemo::new()?;
-    file_demo.run_all_demos()?;
+    outln!("\n━━━ FILE OPERATIONS ━━━");
+    reports.push(report::measure("File Operations", |counter| {
+        FileDemo::with_servers(servers, threshold.unwrap_or(2))?
+            .with_max_memory(max_memory)
+            .with_counter(counter.clone())
+            .run_all_demos()
+    })?);
+
+    report::print_summary(&reports);
 
-    println!("\n🎉 🎉 🎉 ALL DEMONSTRATIONS COMPLETED SUCCESSFULLY! 🎉 🎉 🎉");
+    outln!("\n🎉 🎉 🎉 ALL DEMONSTRATIONS COMPLETED SUCCESSFULLY! 🎉 🎉 🎉");
 
     Ok(())
 }
 
-fn run_interactive_mode() -> Result<()> {
-    use std::io::{self, Write};
+fn run_encrypt(
+    session: &SealSession,
+    identity: Option<&[u8]>,
+    sign_key: Option<&str>,
+    context: &AadContext,
+    escrow_identity: Option<&str>,
+    input: &Path,
+    output: &Path,
+) -> Result<()> {
+    let identity = match identity {
+        Some(identity) => identity.to_vec(),
+        None => format!("file:{}", input.file_name().unwrap_or_default().to_string_lossy()).into_bytes(),
+    };
 
-    loop {
-        println!("\n🎮 Interactive Demo Mode");
-        println!("========================");
-        println!("Choose a demo to run:");
-        println!("  1. Basic Encryption (AES, HMAC, Plain key derivation)");
-        println!("  2. Key Management (Generation, verification, namespacing)");
-        println!("  3. Threshold Encryption (Multi-server, fault tolerance)");
-        println!("  4. File Operations (File encryption, batch processing)");
-        println!("  5. Run All Demos");
-        println!("  6. Exit");
-        println!();
-
-        print!("Enter your choice (1-6): ");
-        io::stdout().flush()?;
+    outln!("🔒 Encrypting file: {}", input.display());
+    outln!("   🆔 Identity: \"{}\"", String::from_utf8_lossy(&identity));
+    outln!("   📦 Package ID: {}", session.package_id);
+    outln!("   🔢 Threshold: {}/{}", session.threshold, session.key_servers.len());
+    outln!("   🏷️  Fleet fingerprint: {}", container::format_fingerprint(&session.fingerprint()));
+    if let Some(version) = context.version {
+        outln!("   🏷️  Authenticated version: {}", version);
+    }
+    if let Some(binding) = &context.file {
+        outln!("   📎 Bound file context: {}", binding);
+    }
+    if let Some(expires_at) = context.expires_at.and_then(|time| chrono::DateTime::from_timestamp(time, 0)) {
+        outln!("   ⏳ Expires at: {} (enforced by this tool only)", expires_at.to_rfc3339());
+    }
 
-        let mut input = String::new();
-        io::stdin().read_line(&mut input)?;
+    let mut data = std::fs::read(input)?;
+    if let Some(padding) = &context.padding {
+        let len = data.len();
+        data = padding.pad(&data)?;
+        outln!("   🧱 Padded {} bytes to a {}-byte size class", len, data.len());
+    }
+    let (encrypted_object, envelope) = match escrow_identity {
+        Some(escrow_identity) => {
+            outln!("   🗝️  Escrow identity: \"{}\"", escrow_identity);
+            let (encrypted_object, envelope) = escrow::encrypt_with_escrow(
+                session,
+                &identity,
+                escrow_identity.as_bytes(),
+                &data,
+                context.to_aad()?,
+            )?;
+            (encrypted_object, Some(envelope))
+        }
+        None => {
+            let (encrypted_object, _) = session.encrypt(
+                &identity,
+                crypto::EncryptionInput::Aes256Gcm {
+                    data,
+                    aad: context.to_aad()?,
+                },
+            )?;
+            (encrypted_object, None)
+        }
+    };
 
-        match input.trim() {
-            "1" => {
-                run_basic_demo(false, false, false)?;
-            }
-            "2" => {
-                run_key_management_demo(false, false, false, false)?;
-            }
-            "3" => {
-                println!("How many key servers? (default: 3): ");
-                let mut servers_input = String::new();
-                io::stdin().read_line(&mut servers_input)?;
-                let servers = servers_input.trim().parse().unwrap_or(3);
-                
-                if servers < 2 {
-                    println!("❌ Need at least 2 servers. Using 3.");
-                    run_threshold_demo(3, false, false, false)?;
-                } else {
-                    run_threshold_demo(servers, false, false, false)?;
+    let container = SealContainer {
+        envelope,
+        ..SealContainer::for_fleet(encrypted_object, &session.key_servers, &session.public_keys)
+    };
+    let encrypted_data = container.to_bytes()?;
+    atomic::write_file(output, &encrypted_data)?;
+    outln!("   ✅ Encrypted to: {}", output.display());
+
+    if let Some(sign_key) = sign_key {
+        let secret_key = session::decode_master_key(sign_key)?;
+        let signature = signing::sign(&secret_key, &encrypted_data);
+        let mut signature_path = output.as_os_str().to_owned();
+        signature_path.push(".sig");
+        atomic::write_file(Path::new(&signature_path), signing::encode_signature(&signature).as_bytes())?;
+        outln!("   🔏 Signature written to: {}", Path::new(&signature_path).display());
+    }
+
+    Ok(())
+}
+
+fn run_encrypt_stream(session_path: &Path, identity: &str, chunk_size: usize) -> Result<()> {
+    let session = config::load_session(session_path)?;
+    outln!("🌊 Stream-encrypting stdin to \"{}\" in {}-byte chunks", identity, chunk_size);
+    let mut stdout = std::io::BufWriter::new(std::io::stdout().lock());
+    let total = session.encrypt_stream(identity.as_bytes(), &mut std::io::stdin().lock(), &mut stdout, chunk_size)?;
+    std::io::Write::flush(&mut stdout)?;
+    outln!("   ✅ Encrypted {} bytes", total);
+    Ok(())
+}
+
+/// Chunks are written as they authenticate, so on failure stdout already
+/// holds a verified prefix; the exit status tells the reader to discard it.
+fn run_decrypt_stream(session_path: &Path) -> Result<()> {
+    let session = config::load_session(session_path)?;
+    outln!("🌊 Stream-decrypting stdin");
+    let mut stdout = std::io::BufWriter::new(std::io::stdout().lock());
+    let total = session.decrypt_stream(&mut std::io::stdin().lock(), &mut stdout)?;
+    std::io::Write::flush(&mut stdout)?;
+    outln!("   ✅ Decrypted {} bytes", total);
+    Ok(())
+}
+
+/// Stdout only ever carries ciphertext, but a failed run leaves a partial
+/// stream there to discard.
+fn run_rewrap_stream(session_path: &Path, new_session_path: &Path, threshold: Option<u8>) -> Result<()> {
+    let session = config::load_session(session_path)?;
+    let new_session = config::load_session(new_session_path)?;
+    let threshold = threshold.unwrap_or(new_session.threshold);
+    outln!(
+        "🔄 Rewrapping stdin from fleet {} to fleet {} at threshold {}",
+        container::format_fingerprint(&session.fingerprint()),
+        container::format_fingerprint(&new_session.fingerprint()),
+        threshold
+    );
+    let mut stdout = std::io::BufWriter::new(std::io::stdout().lock());
+    let total = session.rewrap_streaming(&mut std::io::stdin().lock(), &mut stdout, &new_session, threshold)?;
+    std::io::Write::flush(&mut stdout)?;
+    outln!("   ✅ Rewrapped {} bytes", total);
+    Ok(())
+}
+
+/// Keys come from `session_path`, or from whichever key files in `key_dir`
+/// match the file's servers.
+fn run_decrypt(
+    session_path: Option<&Path>,
+    key_dir: Option<(&Path, &ServerPreference)>,
+    package_id: Option<crypto::ObjectID>,
+    checks: ContextChecks,
+    mode: SessionMode,
+    input: &Path,
+    output: &Path,
+) -> Result<OpenedFile> {
+    outln!("🔓 Decrypting file: {}", input.display());
+    let ciphertext = std::fs::read(input)?;
+    let container = SealContainer::from_bytes(&ciphertext)?;
+    if let Some(expected) = package_id {
+        if container.encrypted_object.package_id != expected {
+            anyhow::bail!(
+                "File was encrypted under package {}, not the expected {}",
+                container.encrypted_object.package_id,
+                expected
+            );
+        }
+    }
+    let mut servers = Vec::new();
+    let mut decrypted = match (session_path, key_dir) {
+        (_, Some((dir, preference))) => {
+            let keys = key_files::load_key_files(dir)?;
+            outln!("   📂 {} key file(s) in {}", keys.len(), dir.display());
+            container.decrypt(|encrypted_object| {
+                let (decrypted, used) = key_files::decrypt(encrypted_object, &keys, preference)?;
+                servers = used;
+                Ok(decrypted)
+            })?
+        }
+        (Some(session_path), None) => {
+            let session = SealSession::from_json(&std::fs::read_to_string(session_path)?)?.with_event_hook(print_session_event);
+            warn_on_fleet_mismatch(&container, &session);
+            // Both decrypt with the first `threshold` servers
+            let threshold = container.encrypted_object.threshold as usize;
+            servers = session.key_servers.iter().take(threshold).copied().collect();
+            match mode {
+                SessionMode::Verified => container.decrypt(|encrypted_object| session.decrypt(encrypted_object))?,
+                SessionMode::Unverified => {
+                    outln!("   ⚠️  Share verification disabled: a wrong or malicious key server can go undetected");
+                    container.decrypt(|encrypted_object| session.decrypt_unverified(encrypted_object))?
+                }
+                SessionMode::LegacyFullId(scheme) => {
+                    outln!("   ⚠️  Using full ID scheme {}; shares can't be verified under it", scheme);
+                    container.decrypt(|encrypted_object| session.decrypt_legacy(encrypted_object, scheme))?
                 }
             }
-            "4" => {
-                run_file_demo(false, false, false)?;
-            }
-            "5" => {
-                println!("How many key servers for threshold demo? (default: 3): ");
-                let mut servers_input = String::new();
-                io::stdin().read_line(&mut servers_input)?;
-                let servers = servers_input.trim().parse().unwrap_or(3);
-                
-                run_all_demos(servers.max(2))?;
-            }
-            "6" => {
-                println!("👋 Thanks for trying the Seal Rust demo!");
-                break;
-            }
-            _ => {
-                println!("❌ Invalid choice. Please enter 1-6.");
+        }
+        (None, None) => anyhow::bail!("Need a session or a key directory to decrypt with"),
+    };
+
+    // The AAD is only trustworthy once decryption has authenticated it
+    if let Some(min_version) = checks.min_version {
+        AadContext::from_container(&container)?.enforce_min_version(min_version)?;
+        outln!("   ✅ Version check passed (minimum {})", min_version);
+    }
+    // AAD from other tools needn't be a context; only check ours
+    if let Ok(context) = AadContext::from_container(&container) {
+        context.check_quorum(&container.encrypted_object)?;
+        if let Err(e) = context.check_expiry(chrono::Utc::now()) {
+            match e.downcast_ref::<aad::Expired>() {
+                Some(expired) if checks.ignore_expiry => {
+                    outln!("   ⚠️  Ignoring expiry: the file expired at {}", expired.expires_at.to_rfc3339())
+                }
+                _ => return Err(e),
             }
         }
+        if context.padding.is_some() {
+            decrypted = padding::unpad(decrypted)?;
+        }
+        if let Some((path, modified)) = context.check_file_binding(decrypted.len())? {
+            outln!("   📎 Bound to {} ({} bytes, modified at {})", path, decrypted.len(), modified);
+        }
     }
 
+    if container.envelope.is_none() && matches!(container.encrypted_object.ciphertext, crypto::Ciphertext::Plain) {
+        outln!("   ⚠️  This file holds a Plain-mode object, so the output is its derived key, not file contents");
+        outln!("   ⚠️  {}", session::PLAIN_MODE_NOTE);
+    }
+    atomic::write_file(output, &decrypted)?;
+    outln!("   ✅ Decrypted to: {}", output.display());
+
+    Ok(OpenedFile {
+        ciphertext,
+        identity: container.encrypted_object.id,
+        servers,
+    })
+}
+
+/// Policy checks `run_decrypt` applies to the authenticated context.
+struct ContextChecks {
+    /// Reject contexts with a lower (or no) version.
+    min_version: Option<u64>,
+    /// Warn about an expired context instead of refusing it.
+    ignore_expiry: bool,
+}
+
+/// How `run_decrypt` decrypts with a session.
+enum SessionMode {
+    Verified,
+    /// `--no-verify`
+    Unverified,
+    /// `--full-id-version`
+    LegacyFullId(FullIdScheme),
+}
+
+/// What `run_decrypt` opened, for a receipt.
+struct OpenedFile {
+    ciphertext: Vec<u8>,
+    identity: Vec<u8>,
+    /// Servers whose keys were used.
+    servers: Vec<crypto::ObjectID>,
+}
+
+fn run_write_receipt(opened: OpenedFile, sign_key: &str, path: &Path) -> Result<()> {
+    let sign_key = session::decode_master_key(sign_key)?;
+    let receipt = receipt::DecryptionReceipt::create(
+        &opened.ciphertext,
+        opened.identity,
+        opened.servers,
+        &sign_key,
+        chrono::Utc::now(),
+    )?;
+    atomic::write_file(path, &receipt.to_bytes()?)?;
+    outln!("   🧾 Receipt written to: {}", path.display());
     Ok(())
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+fn run_verify_receipt(pubkey: &str, receipt_path: &Path, input: &Path) -> Result<()> {
+    let receipt = receipt::DecryptionReceipt::from_bytes(&std::fs::read(receipt_path)?)?;
+    outln!("🧾 Verifying decryption receipt: {}", receipt_path.display());
 
-    #[test]
-    fn test_basic_demo() -> Result<()> {
-        let demo = BasicDemo::new()?;
-        demo.run_aes_demo()?;
-        Ok(())
+    receipt.verify(&session::decode_public_key(pubkey)?)?;
+    outln!("   ✅ Signature valid");
+    receipt.check_ciphertext(&std::fs::read(input)?)?;
+    outln!("   ✅ Covers {} (SHA3-256 {})", input.display(), hex::encode(receipt.ciphertext_hash));
+    outln!("   🆔 Identity: \"{}\"", String::from_utf8_lossy(&receipt.identity));
+    outln!("   🕒 Opened: {}", receipt.opened_at()?.to_rfc3339());
+    for server in &receipt.servers {
+        outln!("   🔑 Key from server {}", server);
     }
+    Ok(())
+}
 
-    #[test]
-    fn test_key_management_demo() -> Result<()> {
-        let demo = KeyManagementDemo::new();
-        demo.demo_key_generation()?;
-        Ok(())
+fn run_verify_key(session_path: &Path, server: crypto::ObjectID, identity: &str, key: &str) -> Result<()> {
+    let session = config::load_session(session_path)?;
+    outln!("🔍 Verifying key from server {} for \"{}\"", server, identity);
+    session.verify_key_from_server(&session::decode_user_secret_key(key)?, identity.as_bytes(), server)?;
+    outln!("   ✅ Key is valid for this identity");
+    Ok(())
+}
+
+fn run_escrow_decrypt(session_path: &Path, input: &Path, output: &Path) -> Result<()> {
+    let session = SealSession::from_json(&std::fs::read_to_string(session_path)?)?;
+    let container = SealContainer::from_bytes(&std::fs::read(input)?)?;
+    let envelope = container
+        .envelope
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("File has no escrow copy of its key"))?;
+
+    outln!("🗝️  Break-glass decrypting: {}", input.display());
+    outln!("   🆔 Escrow identity: \"{}\"", String::from_utf8_lossy(&envelope.escrow.id));
+    warn_on_fleet_mismatch(&container, &session);
+
+    let data_key = session.decrypt(&envelope.escrow)?;
+    let mut decrypted = envelope.open(&data_key)?;
+    if AadContext::from_container(&container).is_ok_and(|context| context.padding.is_some()) {
+        decrypted = padding::unpad(decrypted)?;
     }
 
-    #[test]
-    fn test_threshold_demo() -> Result<()> {
+    atomic::write_file(output, &decrypted)?;
+    outln!("   ✅ Decrypted to: {}", output.display());
+
+    Ok(())
+}
+
+fn run_verify(input: &Path, signature_path: &Path, pubkey: &str) -> Result<()> {
+    outln!("🔍 Verifying signature over: {}", input.display());
+
+    let public_key = session::decode_public_key(pubkey)?;
+    let signature = signing::decode_signature(&std::fs::read_to_string(signature_path)?)?;
+
+    signing::verify(&signature, &std::fs::read(input)?, &public_key)?;
+    outln!("   ✅ Signature valid - ciphertext was produced by the holder of this key");
+
+    Ok(())
+}
+
+fn run_envelope_create(sign_key: &str, input: &Path, output: &Path) -> Result<()> {
+    let container = SealContainer::from_bytes(&std::fs::read(input)?)?;
+    if container.envelope.is_some() {
+        anyhow::bail!("Escrowed files can't be wrapped in a provenance envelope: the escrow copy of the key would be lost");
+    }
+
+    let secret_key = session::decode_master_key(sign_key)?;
+    let envelope = provenance::ProvenanceEnvelope::create(container.encrypted_object, &secret_key, chrono::Utc::now())?;
+    atomic::write_file(output, &envelope.to_bytes()?)?;
+
+    outln!("📜 Provenance envelope written to: {}", output.display());
+    outln!("   🕒 Created: {}", envelope.created_at()?.to_rfc3339());
+    outln!("   🛠️  Tool: {}", envelope.tool_version);
+    Ok(())
+}
+
+fn run_envelope_open(pubkey: &str, session_path: &Path, input: &Path, output: &Path) -> Result<()> {
+    let session = SealSession::from_json(&std::fs::read_to_string(session_path)?)?;
+    let envelope = provenance::ProvenanceEnvelope::from_bytes(&std::fs::read(input)?)?;
+
+    outln!("📜 Opening provenance envelope: {}", input.display());
+    envelope.verify(&session::decode_public_key(pubkey)?)?;
+    outln!("   ✅ Signature valid");
+    outln!("   🕒 Created: {}", envelope.created_at()?.to_rfc3339());
+    outln!("   🛠️  Tool: {}", envelope.tool_version);
+
+    let decrypted = session.decrypt(&envelope.encrypted_object)?;
+    atomic::write_file(output, &decrypted)?;
+    outln!("   ✅ Decrypted to: {}", output.display());
+    Ok(())
+}
+
+fn run_min_quorum(session_path: &Path, input: &Path) -> Result<()> {
+    let session = SealSession::from_json(&std::fs::read_to_string(session_path)?)?;
+    let container = SealContainer::from_bytes(&std::fs::read(input)?)?;
+
+    outln!("🔬 Measuring minimum viable quorum for: {}", input.display());
+    warn_on_fleet_mismatch(&container, &session);
+    let encrypted_object = container.encrypted_object;
+    outln!("   🔢 Stated threshold: {}/{}", encrypted_object.threshold, encrypted_object.services.len());
+
+    let mut minimum = None;
+    for num_servers in 1..=session.key_servers.len() {
+        match session.decrypt_with_servers(&encrypted_object, num_servers) {
+            Ok(_) => {
+                outln!("   ✅ {} server(s): decryption succeeded", num_servers);
+                minimum = Some(num_servers);
+                break;
+            }
+            Err(e) => outln!("   ❌ {} server(s): decryption failed ({})", num_servers, e),
+        }
+    }
+
+    match minimum {
+        Some(n) if n == encrypted_object.threshold as usize => {
+            outln!("🎯 Minimum quorum is {} - matches the stated threshold", n);
+            Ok(())
+        }
+        Some(n) => anyhow::bail!(
+            "❌ Minimum quorum is {} but the object claims threshold {}",
+            n,
+            encrypted_object.threshold
+        ),
+        None => anyhow::bail!("❌ No quorum of this fleet could decrypt the object"),
+    }
+}
+
+/// Decryption against the wrong fleet fails deep inside share recovery with an
+/// unhelpful error, so say up front when the fingerprints already disagree.
+fn warn_on_fleet_mismatch(container: &SealContainer, session: &SealSession) {
+    let Some(stored) = container.fingerprint else {
+        return;
+    };
+    let expected = session.fingerprint();
+    if stored != expected {
+        outln!(
+            "   ⚠️  Fleet fingerprint mismatch: file was encrypted to {} but the session is {} - decryption will likely fail",
+            container::format_fingerprint(&stored),
+            container::format_fingerprint(&expected)
+        );
+    }
+}
+
+/// Progress lines for the CLI commands that drive a `SealSession`.
+fn print_session_event(event: SealEvent) {
+    match event {
+        SealEvent::EncryptStarted { identity_len } => outln!("   ⏳ Sealing to a {}-byte identity", identity_len),
+        SealEvent::EncryptFinished { bytes } => outln!("   📏 Encrypted object: {} bytes", bytes),
+        SealEvent::DecryptStarted { threshold } => outln!("   ⏳ Collecting keys from {} server(s)", threshold),
+        SealEvent::ServerKeyUsed { server } => outln!("   🔑 Using key from server {}", server),
+        SealEvent::IntegrityVerified => outln!("   ✅ Key shares verified against the servers' public keys"),
+        SealEvent::DecryptFinished { bytes } => outln!("   📏 Decrypted: {} bytes", bytes),
+    }
+}
+
+fn run_inspect(input: &Path, id_encoding: IdEncoding) -> Result<()> {
+    let container = SealContainer::from_bytes(&std::fs::read(input)?)?;
+    let encrypted_object = &container.encrypted_object;
+
+    outln!("🔎 Inspecting: {}", input.display());
+    match &container.fingerprint {
+        Some(fingerprint) => outln!("   🏷️  Fleet fingerprint: {}", container::format_fingerprint(fingerprint)),
+        None => outln!("   🏷️  Fleet fingerprint: (not recorded)"),
+    }
+    outln!("   📦 Package ID: {}", encrypted_object.package_id);
+    outln!("   🆔 Identity: {}", id_encoding.encode(&encrypted_object.id));
+    outln!("   🔢 Threshold: {}/{}", encrypted_object.threshold, encrypted_object.services.len());
+    let public_keys = container.public_keys()?;
+    for (i, (server_id, _)) in encrypted_object.services.iter().enumerate() {
+        match &public_keys {
+            Some(public_keys) => {
+                outln!("   🖥️  Server {}: {} (public key {})", i + 1, server_id, session::encode_public_key(&public_keys[i]))
+            }
+            None => outln!("   🖥️  Server {}: {}", i + 1, server_id),
+        }
+    }
+
+    let (mode, ciphertext_len) = match (&container.envelope, &encrypted_object.ciphertext) {
+        (Some(envelope), _) => ("AES-256-GCM escrow envelope", envelope.ciphertext.len()),
+        (None, crypto::Ciphertext::Aes256Gcm { blob, .. }) => ("AES-256-GCM", blob.len()),
+        (None, crypto::Ciphertext::Hmac256Ctr { blob, .. }) => ("HMAC-256-CTR", blob.len()),
+        (None, crypto::Ciphertext::Plain) => ("Plain (key only)", 0),
+    };
+    outln!("   🔐 Mode: {} ({} ciphertext bytes)", mode, ciphertext_len);
+
+    if let Some(envelope) = &container.envelope {
+        outln!("   🗝️  Escrow identity: {}", id_encoding.encode(&envelope.escrow.id));
+    }
+
+    // Unauthenticated until decryption succeeds, so only informational here
+    if let Ok(context) = AadContext::from_container(&container) {
+        if let Some(version) = context.version {
+            outln!("   🏷️  Version (unverified): {}", version);
+        }
+        if let Some(binding) = context.file {
+            outln!("   📎 File context (unverified): {}", binding);
+        }
+    }
+
+    Ok(())
+}
+
+fn run_health(config_path: &Path) -> Result<()> {
+    let session = config::load_session(config_path)?;
+
+    outln!("🩺 Checking {} key servers from: {}", session.key_servers.len(), config_path.display());
+    let report = health::check_fleet(&session);
+    for (i, server) in report.iter().enumerate() {
+        match &server.result {
+            Ok(elapsed) => outln!("   🟢 Server {} ({}): up, key self-verified in {:?}", i + 1, server.server_id, elapsed),
+            Err(reason) => outln!("   🔴 Server {} ({}): down, {}", i + 1, server.server_id, reason),
+        }
+    }
+
+    let healthy = report.iter().filter(|server| server.is_up()).count();
+    if healthy >= session.threshold as usize {
+        outln!("✅ Quorum reachable: {}/{} healthy, threshold {}", healthy, report.len(), session.threshold);
+        Ok(())
+    } else {
+        anyhow::bail!(
+            "❌ Quorum unreachable: {}/{} healthy, threshold {}",
+            healthy,
+            report.len(),
+            session.threshold
+        )
+    }
+}
+
+/// Everything but the summary line goes to stderr, so cron can mail or log
+/// just that line.
+fn run_monitor(config_path: &Path, sample_dir: &Path, sample: usize, seed: Option<u64>) -> Result<()> {
+    let session = config::load_session(config_path)?;
+    outln!("🩺 Decrypting up to {} files sampled from: {}", sample, sample_dir.display());
+    let report = monitor::check_sample(&session, sample_dir, sample, &mut testutil::seeded_rng(seed))?;
+    for (path, reason) in &report.failures {
+        outln!("   ❌ {}: {}", path.display(), reason);
+    }
+    std::io::Write::write_all(&mut std::io::stdout(), format!("{}\n", report.summary()).as_bytes())?;
+    if !report.failures.is_empty() {
+        anyhow::bail!("❌ {} of {} sampled files failed to decrypt", report.failures.len(), report.checked);
+    }
+    Ok(())
+}
+
+fn run_validate_config(config_path: &Path) -> Result<()> {
+    // Loading already rejects unparseable public/master keys and bad thresholds
+    let session = config::load_session(config_path)?;
+
+    outln!("🔎 Validating config: {}", config_path.display());
+    outln!("   ✅ {} public keys parse, threshold {}", session.public_keys.len(), session.threshold);
+
+    let issues = config::consistency_issues(&session);
+    for issue in &issues {
+        outln!("   ❌ {}", issue);
+    }
+
+    if session.master_keys.is_none() {
+        outln!("   ⚠️  No master keys in config: extraction not checked, quorum not proven");
+    } else {
+        let report = health::check_fleet(&session);
+        for (i, server) in report.iter().enumerate() {
+            match &server.result {
+                Ok(_) => outln!("   🟢 Server {} ({}): OK", i + 1, server.server_id),
+                Err(reason) => outln!("   🔴 Server {} ({}): FAIL, {}", i + 1, server.server_id, reason),
+            }
+        }
+
+        let valid = report.iter().filter(|server| server.is_up()).count();
+        if valid < session.threshold as usize {
+            anyhow::bail!(
+                "❌ No valid quorum: {}/{} servers verified, threshold {}",
+                valid,
+                report.len(),
+                session.threshold
+            );
+        }
+        outln!("   ✅ Valid quorum: {}/{} servers verified, threshold {}", valid, report.len(), session.threshold);
+        if valid < report.len() {
+            anyhow::bail!("❌ {} server(s) failed verification", report.len() - valid);
+        }
+    }
+
+    if !issues.is_empty() {
+        anyhow::bail!("❌ Config has {} inconsistenc{}", issues.len(), if issues.len() == 1 { "y" } else { "ies" });
+    }
+    outln!("✅ Config is valid");
+    Ok(())
+}
+
+/// Each identity with the files encrypted to it.
+type IdentityFiles = Vec<(Vec<u8>, Vec<PathBuf>)>;
+
+/// Files per identity among the `.seal` files under `dir`, most-reused first
+/// (ties by identity), plus the files that couldn't be parsed.
+fn identity_usage(dir: &Path) -> Result<(IdentityFiles, Vec<PathBuf>)> {
+    let mut usage: std::collections::BTreeMap<Vec<u8>, Vec<PathBuf>> = Default::default();
+    let mut unreadable = Vec::new();
+    for (path, _) in file_demo::files_under(dir)? {
+        if path.extension().is_none_or(|ext| ext != "seal") {
+            continue;
+        }
+        match std::fs::read(&path).map_err(anyhow::Error::from).and_then(|bytes| SealContainer::from_bytes(&bytes)) {
+            Ok(container) => usage.entry(container.encrypted_object.id).or_default().push(path),
+            Err(_) => unreadable.push(path),
+        }
+    }
+
+    let mut usage: Vec<_> = usage.into_iter().collect();
+    // Stable, so identities with equal counts stay in identity order
+    usage.sort_by_key(|(_, files)| std::cmp::Reverse(files.len()));
+    Ok((usage, unreadable))
+}
+
+fn run_audit_identities(dir: &Path, all: bool, ascending: bool) -> Result<()> {
+    let (mut usage, unreadable) = identity_usage(dir)?;
+    if ascending {
+        usage.reverse();
+    }
+
+    let files: usize = usage.iter().map(|(_, files)| files.len()).sum();
+    let reused = usage.iter().filter(|(_, files)| files.len() > 1).count();
+    outln!("🔎 Auditing identities under: {}", dir.display());
+    outln!("   📄 {} file(s), {} distinct identit{}", files, usage.len(), if usage.len() == 1 { "y" } else { "ies" });
+    outln!("   🔁 {} identit{} used by more than one file", reused, if reused == 1 { "y" } else { "ies" });
+
+    for (identity, files) in usage.iter().filter(|(_, files)| all || files.len() > 1) {
+        outln!("\n   {:>5}× \"{}\"", files.len(), String::from_utf8_lossy(identity));
+        for file in files {
+            outln!("          {}", file.display());
+        }
+    }
+    for path in &unreadable {
+        outln!("   ⚠️  Not a Seal file, skipped: {}", path.display());
+    }
+    Ok(())
+}
+
+fn run_pack(dir: &Path, out: &Path) -> Result<()> {
+    outln!("📦 Packing {} into {}", dir.display(), out.display());
+    let packed = pack::pack(dir, out)?;
+    outln!("✅ Packed {} files ({} bytes)", packed, std::fs::metadata(out)?.len());
+    Ok(())
+}
+
+fn run_unpack(input: &Path, out: &Path) -> Result<()> {
+    outln!("📦 Unpacking {} into {}", input.display(), out.display());
+    let unpacked = pack::unpack(input, out)?;
+    outln!("✅ Restored {} files", unpacked);
+    Ok(())
+}
+
+fn run_migrate_format(dir: &Path, out: &Path, session_path: Option<&Path>) -> Result<()> {
+    let fleet = session_path.map(config::load_session).transpose()?;
+
+    outln!("📦 Migrating encrypted files from {} to {}", dir.display(), out.display());
+    let (mut migrated, mut current, mut failed) = (0, 0, 0);
+    for (path, _) in file_demo::files_under(dir)? {
+        let target = out.join(path.strip_prefix(dir)?);
+        let result = std::fs::read(&path).map_err(anyhow::Error::from).and_then(|bytes| {
+            let upgraded = container::upgrade(&bytes, fleet.as_ref())?;
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            atomic::write_file(&target, upgraded.as_deref().unwrap_or(&bytes))?;
+            Ok(upgraded.is_some())
+        });
+
+        match result {
+            Ok(true) => {
+                migrated += 1;
+                outln!("   ✅ {}", path.display());
+            }
+            Ok(false) => {
+                current += 1;
+                outln!("   ⏭️  {} (already current, copied)", path.display());
+            }
+            Err(e) => {
+                failed += 1;
+                outln!("   ❌ {}: {:#}", path.display(), e);
+            }
+        }
+    }
+
+    outln!("📊 Migrated {}, already current {}, failed {}", migrated, current, failed);
+    if failed > 0 {
+        anyhow::bail!("❌ {} file(s) could not be migrated", failed);
+    }
+    Ok(())
+}
+
+fn run_compare_fleets(first_path: &Path, second_path: &Path) -> Result<()> {
+    let first = config::load_session(first_path)?;
+    let second = config::load_session(second_path)?;
+    let (first_name, second_name) = (first_path.display(), second_path.display());
+
+    outln!("🔀 Comparing fleets:");
+    for (name, session) in [(&first_name, &first), (&second_name, &second)] {
+        outln!(
+            "   🏷️  {}: {} servers, threshold {}, fingerprint {}",
+            name,
+            session.key_servers.len(),
+            session.threshold,
+            container::format_fingerprint(&session.fingerprint())
+        );
+    }
+    if first.fingerprint() == second.fingerprint() {
+        outln!("   ✅ Same servers and public keys");
+    }
+    if first.package_id != second.package_id {
+        outln!(
+            "   ⚠️  Package IDs differ ({} vs {}): on-chain access policies won't carry over",
+            first.package_id, second.package_id
+        );
+    }
+
+    let comparison = config::compare_fleets(&first, &second);
+    for server_id in &comparison.matching {
+        outln!("   🟢 Shared, same public key: {}", server_id);
+    }
+    for server_id in &comparison.mismatched {
+        outln!("   🔴 Shared ID, different public key: {}", server_id);
+    }
+    for server_id in &comparison.only_first {
+        outln!("   ⚪ Only in {}: {}", first_name, server_id);
+    }
+    for server_id in &comparison.only_second {
+        outln!("   ⚪ Only in {}: {}", second_name, server_id);
+    }
+
+    for (from, to, compatible, threshold) in [
+        (&first_name, &second_name, comparison.first_to_second, first.threshold),
+        (&second_name, &first_name, comparison.second_to_first, second.threshold),
+    ] {
+        if compatible {
+            outln!("   ✅ Encrypted to {} → decryptable with {}", from, to);
+        } else {
+            outln!(
+                "   ❌ Encrypted to {} → NOT decryptable with {} ({} matching servers, threshold {})",
+                from,
+                to,
+                comparison.matching.len(),
+                threshold
+            );
+        }
+    }
+    Ok(())
+}
+
+fn decode_seed(seed_hex: &str) -> Result<[u8; 32]> {
+    hex::decode(seed_hex.trim_start_matches("0x"))?
+        .try_into()
+        .map_err(|b: Vec<u8>| anyhow::anyhow!("Seed must be 32 bytes, got {}", b.len()))
+}
+
+fn run_gen_fixtures(seed: [u8; 32], out: &Path) -> Result<()> {
+    outln!("🧪 Generating fixtures from seed {}", hex::encode(seed));
+    let index = fixtures::generate(seed, out)?;
+    outln!("   🏛️  Fleet: {} (package {})", out.join(&index.fleet).display(), index.package_id);
+    for object in &index.objects {
+        outln!("   🔐 {} ({}, threshold {})", object.file, object.mode, object.threshold);
+    }
+    outln!("   📇 Index: {}", out.join(fixtures::INDEX_FILE).display());
+    outln!("   ℹ️  Ciphertext bytes differ between runs; decrypt and compare against the index");
+    Ok(())
+}
+
+fn run_kat(path: &Path, regenerate: bool) -> Result<()> {
+    if regenerate {
+        let file = kat::generate()?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        atomic::write_file(path, format!("{}\n", serde_json::to_string_pretty(&file)?).as_bytes())?;
+        outln!("🧪 Wrote {} known-answer vectors to {}", file.vectors.len(), path.display());
+        return Ok(());
+    }
+
+    let file = kat::load(path)?;
+    outln!("🧪 Running {} known-answer vectors from {}", file.vectors.len(), path.display());
+    let mut failed = 0;
+    for vector in &file.vectors {
+        let mismatches = kat::check(vector)?;
+        if mismatches.is_empty() {
+            outln!("   ✅ {}", vector.name);
+            continue;
+        }
+        failed += 1;
+        outln!("   ❌ {}: {} answer(s) differ", vector.name, mismatches.len());
+        for mismatch in &mismatches {
+            for line in mismatch.to_string().lines() {
+                outln!("      {}", line);
+            }
+        }
+    }
+    if failed > 0 {
+        anyhow::bail!("{} of {} known-answer vectors failed", failed, file.vectors.len());
+    }
+    outln!("   🎯 All known answers match");
+    Ok(())
+}
+
+fn run_session_export(
+    mut session: SealSession,
+    package_id: Option<crypto::ObjectID>,
+    out: Option<&Path>,
+    include_secrets: bool,
+    key_dir: Option<&Path>,
+) -> Result<()> {
+    if let Some(package_id) = package_id {
+        session.package_id = package_id;
+    }
+
+    outln!("📦 Package ID: {}", session.package_id);
+    outln!("🔢 Threshold: {}/{}", session.threshold, session.key_servers.len());
+    outln!("🏷️  Fleet fingerprint: {}", container::format_fingerprint(&session.fingerprint()));
+
+    match out {
+        Some(path) => {
+            config::save_session(&session, path, include_secrets)?;
+            outln!("   ✅ Session written to: {}", path.display());
+        }
+        None => outln!("{}", session.to_json(include_secrets)?),
+    }
+    if let Some(dir) = key_dir {
+        let written = key_files::write_key_files(&session, dir)?;
+        outln!("   🔑 {} key files written to: {}", written, dir.display());
+    }
+
+    Ok(())
+}
+
+/// The package ID doubles as the session's package; pass `--package-id` to
+/// `encrypt` to encrypt for an application package instead.
+#[cfg(feature = "sui")]
+fn run_import_fleet(package: crypto::ObjectID, rpc: &str, threshold: Option<u8>, out: Option<&Path>) -> Result<()> {
+    outln!("🌐 Discovering key servers of package {} via {}", package, rpc);
+    let fleet = sui::fetch_fleet(rpc, package)?;
+    for server in &fleet.servers {
+        outln!("   📡 {} ({}) at {}", server.name, server.object_id, server.url);
+    }
+    outln!("   ✅ {} key servers with valid BLS12-381 public keys", fleet.servers.len());
+
+    let session = fleet.into_session(package, threshold)?;
+    match threshold {
+        Some(_) => outln!("🔢 Threshold: {}/{}", session.threshold, session.key_servers.len()),
+        None => outln!(
+            "🔢 Threshold: {}/{} (majority; Seal publishes no threshold on chain, pass --threshold to choose)",
+            session.threshold,
+            session.key_servers.len()
+        ),
+    }
+
+    match out {
+        Some(path) => {
+            config::save_session(&session, path, false)?;
+            outln!("   ✅ Session written to: {}", path.display());
+        }
+        None => outln!("{}", session.to_json(false)?),
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "sui"))]
+fn run_import_fleet(_package: crypto::ObjectID, _rpc: &str, _threshold: Option<u8>, _out: Option<&Path>) -> Result<()> {
+    anyhow::bail!("Importing a fleet needs the `sui` feature: cargo run --features sui -- import-fleet ...")
+}
+
+/// The payload is the server's entry from the session file, so a scanned
+/// code can be pasted straight into a client's `servers` list.
+fn run_qr(session_path: &Path, server_index: usize, png: Option<&Path>) -> Result<()> {
+    let session = config::load_session(session_path)?;
+    let payload = session.server_json(server_index)?;
+
+    outln!("📱 Key server {} of {}", server_index, session.key_servers.len());
+    outln!("   Payload: {}", payload);
+
+    #[cfg(feature = "qr")]
+    {
+        qr::print_terminal(&payload)?;
+        if let Some(path) = png {
+            qr::save_png(&payload, path)?;
+            outln!("   ✅ PNG written to: {}", path.display());
+        }
+    }
+    #[cfg(not(feature = "qr"))]
+    {
+        if png.is_some() {
+            anyhow::bail!("Writing a PNG needs the `qr` feature: cargo run --features qr -- qr ...");
+        }
+        outln!("   ℹ️  Rebuild with `--features qr` to render the payload as a QR code");
+    }
+
+    Ok(())
+}
+
+/// Use the TUI when built with the `tui` feature and attached to a terminal,
+/// otherwise fall back to the plain text menu.
+fn run_interactive() -> Result<()> {
+    #[cfg(feature = "tui")]
+    {
+        use std::io::IsTerminal;
+        if std::io::stdin().is_terminal() && std::io::stdout().is_terminal() {
+            return tui::run();
+        }
+    }
+
+    run_interactive_mode()
+}
+
+fn run_named_demo(name: Option<&str>, servers: usize) -> Result<()> {
+    let Some(name) = name else {
+        outln!("📋 Available demos:");
+        for demo in registry::demos() {
+            outln!("   {:<22} {}", demo.name, demo.description);
+        }
+        return Ok(());
+    };
+
+    let demo = registry::find(name)?;
+    (demo.run)(&registry::DemoContext { servers })
+}
+
+fn run_interactive_mode() -> Result<()> {
+    use std::io::{self, Write};
+
+    let menu = registry::menu();
+    let exit = menu.len() + 1;
+
+    loop {
+        outln!("\n🎮 Interactive Demo Mode");
+        outln!("========================");
+        outln!("Choose a demo to run:");
+        for (i, demo) in menu.iter().enumerate() {
+            outln!("  {}. {} ({})", i + 1, demo.title.unwrap_or(demo.name), demo.description);
+        }
+        outln!("  {}. Exit", exit);
+        outln!();
+
+        out!("Enter your choice (1-{}): ", exit);
+        io::stdout().flush()?;
+
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+
+        let choice = match input.trim().parse::<usize>() {
+            Ok(choice) if choice == exit => {
+                outln!("👋 Thanks for trying the Seal Rust demo!");
+                break;
+            }
+            Ok(choice) if (1..exit).contains(&choice) => &menu[choice - 1],
+            _ => {
+                outln!("❌ Invalid choice. Please enter 1-{}.", exit);
+                continue;
+            }
+        };
+
+        let mut servers = 3;
+        if choice.uses_servers {
+            outln!("How many key servers? (default: 3): ");
+            let mut servers_input = String::new();
+            io::stdin().read_line(&mut servers_input)?;
+            servers = servers_input.trim().parse().unwrap_or(3);
+
+            if servers < 2 {
+                outln!("❌ Need at least 2 servers. Using 3.");
+                servers = 3;
+            }
+        }
+
+        (choice.run)(&registry::DemoContext { servers })?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fastcrypto::hash::{HashFunction, Sha3_256};
+    use std::sync::Mutex;
+    use key_cache::KeyCache;
+    use streaming::ChunkingStrategy;
+
+    /// Held by tests that set environment variables or parse the CLI, which
+    /// reads them, so parallel tests never see each other's values.
+    static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+    #[test]
+    fn test_basic_demo() -> Result<()> {
+        let demo = BasicDemo::new()?;
+        demo.run_aes_demo()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_demo_fleets_from_seeded_rng() -> Result<()> {
+        let rng = || testutil::seeded_rng(Some(11));
+        let (a, b) = (BasicDemo::new_with_rng(&mut rng())?, BasicDemo::new_with_rng(&mut rng())?);
+        assert_eq!((a.package_id, &a.key_servers, &a.public_keys), (b.package_id, &b.key_servers, &b.public_keys));
+        assert_ne!(a.package_id, BasicDemo::new()?.package_id);
+        
+        let (a, b) = (ThresholdDemo::new_with_rng(4, &mut rng()), ThresholdDemo::new_with_rng(4, &mut rng()));
+        assert_eq!(a.package_id, b.package_id);
+        for (a, b) in a.key_servers.iter().zip(&b.key_servers) {
+            assert_eq!((a.object_id, a.public_key), (b.object_id, b.public_key));
+        }
+        
+        let a = FileDemo::with_servers_and_rng(3, 2, &mut rng())?;
+        let b = FileDemo::with_servers_and_rng(3, 2, &mut rng())?;
+        assert_eq!((a.package_id, &a.key_servers, &a.public_keys), (b.package_id, &b.key_servers, &b.public_keys));
+        
+        // The fleet is fixed, but seal_encrypt still samples its own key
+        let (mut first, mut second) = (Vec::new(), Vec::new());
+        a.encrypt_to_sink(b"same input".to_vec(), b"file:seeded".to_vec(), &mut first)?;
+        b.encrypt_to_sink(b"same input".to_vec(), b"file:seeded".to_vec(), &mut second)?;
+        assert_ne!(first, second);
+        Ok(())
+    }
+
+    #[test]
+    fn test_fleet_size_from_env() {
+        use clap::Parser;
+        
+        let _env = ENV_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let fleet = |args: &[&str]| match Cli::try_parse_from(args).unwrap().command {
+            Commands::Files { servers, threshold, .. } => (servers, Some(threshold)),
+            Commands::Threshold { servers, threshold, .. } | Commands::All { servers, threshold } => (servers, threshold),
+            _ => unreachable!(),
+        };
+        
+        // No flag, no env: built-in defaults
+        std::env::remove_var(SERVERS_ENV);
+        std::env::remove_var(THRESHOLD_ENV);
+        assert_eq!(fleet(&["seal-demo", "files"]), (3, Some(2)));
+        assert_eq!(fleet(&["seal-demo", "all"]), (3, None));
+        
+        // Env overrides the defaults of every demo command
+        std::env::set_var(SERVERS_ENV, "5");
+        std::env::set_var(THRESHOLD_ENV, "4");
+        assert_eq!(fleet(&["seal-demo", "files"]), (5, Some(4)));
+        assert_eq!(fleet(&["seal-demo", "threshold"]), (5, Some(4)));
+        assert_eq!(fleet(&["seal-demo", "all"]), (5, Some(4)));
+        
+        // Flags override env
+        assert_eq!(fleet(&["seal-demo", "threshold", "--servers", "7"]), (7, Some(4)));
+        assert_eq!(fleet(&["seal-demo", "files", "-s", "4", "-t", "3"]), (4, Some(3)));
+        
+        std::env::set_var(THRESHOLD_ENV, "lots");
+        assert!(Cli::try_parse_from(["seal-demo", "files"]).is_err());
+        
+        std::env::remove_var(SERVERS_ENV);
+        std::env::remove_var(THRESHOLD_ENV);
+    }
+
+    #[test]
+    fn test_metrics_port_lingers_only_on_request() {
+        use clap::Parser;
+        
+        let cli = Cli::try_parse_from(["seal-demo", "--metrics-port", "9898", "params"]).unwrap();
+        assert_eq!((cli.metrics_port, cli.linger), (Some(9898), false));
+        let cli = Cli::try_parse_from(["seal-demo", "params", "--metrics-port", "9898", "--linger"]).unwrap();
+        assert!(cli.linger);
+        assert!(Cli::try_parse_from(["seal-demo", "--linger", "params"]).is_err());
+    }
+
+    #[test]
+    fn test_encryption_is_randomized() -> Result<()> {
+        let demo = BasicDemo::new()?;
+        demo.demo_nondeterminism()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_mode_overheads() -> Result<()> {
+        let demo = BasicDemo::new()?;
+        let small = demo.mode_overheads(16)?;
+        let large = demo.mode_overheads(4096)?;
+        assert_eq!(small.len(), 3);
+        
+        for ((mode, small_size), (_, large_size)) in small.iter().zip(&large) {
+            if mode.starts_with("Plain") {
+                // No payload stored, whatever its size
+                assert_eq!(small_size, large_size);
+            } else {
+                // Fixed overhead on top of the payload (BCS length prefix aside)
+                assert!(*small_size > 16, "{} smaller than its payload", mode);
+                let small_overhead = small_size - 16;
+                let large_overhead = large_size - 4096;
+                assert!(large_overhead.abs_diff(small_overhead) <= 2, "{} overhead grows with payload", mode);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_auto_encryption_input() {
+        let input = basic_demo::auto_encryption_input(b"auto".to_vec(), Some(b"aad".to_vec()));
+        match (input, basic_demo::aes_accelerated()) {
+            (crypto::EncryptionInput::Aes256Gcm { data, aad }, true) | (crypto::EncryptionInput::Hmac256Ctr { data, aad }, false) => {
+                assert_eq!(data, b"auto");
+                assert_eq!(aad.as_deref(), Some(&b"aad"[..]));
+            }
+            _ => panic!("auto mode disagrees with the detected hardware"),
+        }
+    }
+
+    #[test]
+    fn test_key_management_demo() -> Result<()> {
+        let demo = KeyManagementDemo::new();
+        demo.demo_key_generation()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_identity_file() -> Result<()> {
+        let path = std::env::temp_dir().join("identity_file_test.txt");
+        std::fs::write(&path, "# onboarding batch 7\nalice@example.com\n\n  bob@example.com  \nhex:00ff10\n")?;
+        
+        let demo = KeyManagementDemo::from_identity_file(&path)?;
+        assert_eq!(demo.identities, vec![b"alice@example.com".to_vec(), b"bob@example.com".to_vec(), vec![0x00, 0xff, 0x10]]);
+        assert_eq!(demo.demo_bulk_issuance()?, 0);
+        
+        std::fs::write(&path, "alice@example.com\nhex:zz\n")?;
+        let err = KeyManagementDemo::from_identity_file(&path).err().unwrap();
+        assert!(err.to_string().ends_with(":2: invalid hex identity"));
+        
+        let _ = std::fs::remove_file(&path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_threshold_demo() -> Result<()> {
+        let demo = ThresholdDemo::new(3);
+        demo.demo_threshold_encryption(2)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_independent_rotation_excludes_rotated_server() -> Result<()> {
+        ThresholdDemo::new(3).demo_independent_rotation()
+    }
+
+    #[test]
+    fn test_unanimous_needs_every_server() -> Result<()> {
+        let demo = ThresholdDemo::new(4);
+        let ids: Vec<_> = demo.key_servers.iter().map(|s| s.object_id).collect();
+        let encrypted_object = demo.encrypt_unanimous(b"all or nothing", b"board:resolution")?;
+        assert_eq!(encrypted_object.threshold, 4);
+        assert_eq!(demo.decrypt_unanimous(&encrypted_object, &ids)?, b"all or nothing");
+        
+        // Missing any single server blocks decryption, and the error names it
+        for (i, server) in demo.key_servers.iter().enumerate() {
+            let mut available = ids.clone();
+            available.remove(i);
+            let err = demo.decrypt_unanimous(&encrypted_object, &available).unwrap_err().to_string();
+            assert!(err.contains(&server.object_id.to_string()), "{}", err);
+            assert!(err.contains(&server.name));
+        }
+        
+        // The quorum is enforced by the threshold itself, not just the check
+        let full_id = crypto::create_full_id(&encrypted_object.package_id, &encrypted_object.id);
+        let keys = demo.key_servers[..3]
+            .iter()
+            .map(|s| (s.object_id, crypto::ibe::extract(&s.master_key, &full_id)))
+            .collect();
+        assert!(crypto::seal_decrypt(&encrypted_object, &crypto::IBEUserSecretKeys::BonehFranklinBLS12381(keys), None).is_err());
+        
+        // Objects with a lower threshold aren't unanimous
+        let mut partial = encrypted_object.clone();
+        partial.threshold = 2;
+        assert!(demo.decrypt_unanimous(&partial, &ids).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_nested_encryption() -> Result<()> {
+        let demo = ThresholdDemo::new(4);
+        let ids: Vec<_> = demo.key_servers.iter().map(|s| s.object_id).collect();
+        let layers = vec![
+            (ids[..2].to_vec(), 2, b"hr".to_vec()),
+            (ids[2..].to_vec(), 2, b"legal".to_vec()),
+        ];
+        let ciphertext = demo.encrypt_nested(b"layered", &layers)?;
+        
+        assert_eq!(demo.decrypt_nested(&ciphertext, 2, &ids)?, b"layered");
+        assert!(demo.decrypt_nested(&ciphertext, 2, &ids[..2]).is_err());
+        assert!(demo.decrypt_nested(&ciphertext, 2, &ids[2..]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_policy_combinators() -> Result<()> {
+        use policy::Policy;
+        
+        let demo = ThresholdDemo::new(3);
+        let (admin, finance, auditor): (&[u8], &[u8], &[u8]) = (b"role:admin", b"dept:finance", b"role:auditor");
+        
+        // Id: only that identity
+        let ciphertext = demo.encrypt_policy(b"id", &Policy::id("role:admin"))?;
+        assert_eq!(demo.decrypt_policy(&ciphertext, &[admin])?, b"id");
+        assert!(demo.decrypt_policy(&ciphertext, &[finance]).is_err());
+        
+        // And: both, in either order of holding
+        let both = Policy::And(vec![Policy::id("role:admin"), Policy::id("dept:finance")]);
+        let ciphertext = demo.encrypt_policy(b"and", &both)?;
+        assert_eq!(demo.decrypt_policy(&ciphertext, &[finance, admin])?, b"and");
+        assert!(demo.decrypt_policy(&ciphertext, &[admin]).is_err());
+        assert!(demo.decrypt_policy(&ciphertext, &[finance]).is_err());
+        
+        // Or: either one
+        let either = Policy::Or(vec![Policy::id("role:admin"), Policy::id("role:auditor")]);
+        let ciphertext = demo.encrypt_policy(b"or", &either)?;
+        assert_eq!(demo.decrypt_policy(&ciphertext, &[admin])?, b"or");
+        assert_eq!(demo.decrypt_policy(&ciphertext, &[auditor])?, b"or");
+        assert!(demo.decrypt_policy(&ciphertext, &[finance]).is_err());
+        
+        // (admin AND finance) OR auditor
+        let nested = Policy::Or(vec![both, Policy::id("role:auditor")]);
+        assert_eq!(nested.to_string(), "((role:admin AND dept:finance) OR role:auditor)");
+        let ciphertext = demo.encrypt_policy(b"nested", &nested)?;
+        assert_eq!(demo.decrypt_policy(&ciphertext, &[admin, finance])?, b"nested");
+        assert_eq!(demo.decrypt_policy(&ciphertext, &[auditor])?, b"nested");
+        assert!(demo.decrypt_policy(&ciphertext, &[admin]).is_err());
+        
+        assert!(demo.encrypt_policy(b"empty", &Policy::And(Vec::new())).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_time_lock() -> Result<()> {
+        use chrono::{TimeZone, Utc};
+        
+        let demo = ThresholdDemo::new(3);
+        let unlock = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+        let identity = ThresholdDemo::time_lock_identity(unlock, "doc");
+        assert_eq!(identity, b"time:2025-01-01:doc");
+        
+        let public_keys = demo.key_servers.iter().map(|s| s.public_key).collect();
+        let (encrypted_object, _) = crypto::seal_encrypt(
+            demo.package_id,
+            identity,
+            demo.key_servers.iter().map(|s| s.object_id).collect(),
+            &crypto::IBEPublicKeys::BonehFranklinBLS12381(public_keys),
+            2,
+            crypto::EncryptionInput::Aes256Gcm { data: b"embargoed".to_vec(), aad: None },
+        )?;
+        
+        let new_years_eve = Utc.with_ymd_and_hms(2024, 12, 31, 23, 59, 59).unwrap();
+        let err = demo.decrypt_time_locked(&encrypted_object, new_years_eve).unwrap_err();
+        assert!(err.to_string().contains("unlocks on 2025-01-01"));
+        assert_eq!(demo.decrypt_time_locked(&encrypted_object, unlock)?, b"embargoed");
+        Ok(())
+    }
+
+    #[test]
+    fn test_bench_extract() -> Result<()> {
+        bench::run_extract(10, true)
+    }
+
+    #[test]
+    fn test_bench_csv() -> Result<()> {
+        let bench = Benchmark::new(3, 2, 128)?;
+        let csv_path = std::env::temp_dir().join("seal-bench-test.csv");
+        bench.run(2, Some(&csv_path))?;
+
+        let csv = std::fs::read_to_string(&csv_path)?;
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "iteration,direction,payload_bytes,nanos");
+        assert_eq!(lines.len(), 1 + 2 * 2);
+        assert!(lines[1].starts_with("0,encrypt,128,"));
+        assert!(lines[2].starts_with("0,decrypt,128,"));
+
+        let _ = std::fs::remove_file(&csv_path);
+        Ok(())
+    }
+
+    #[test]
+    fn test_file_demo() -> Result<()> {
+        let demo = FileDemo::new()?;
+        // Just test basic functionality without cleanup for tests
+        let temp_file = std::env::temp_dir().join("test.txt");
+        std::fs::write(&temp_file, "test content")?;
+        
+        let encrypted_file = std::env::temp_dir().join("test.txt.encrypted");
+        demo.encrypt_file(&temp_file, &encrypted_file)?;
+        
+        let decrypted_file = std::env::temp_dir().join("test.txt.decrypted");
+        demo.decrypt_file(&encrypted_file, &decrypted_file)?;
+        
+        let original = std::fs::read(&temp_file)?;
+        let decrypted = std::fs::read(&decrypted_file)?;
+        assert_eq!(original, decrypted);
+        
+        // Cleanup
+        let _ = std::fs::remove_file(&temp_file);
+        let _ = std::fs::remove_file(&encrypted_file);
+        let _ = std::fs::remove_file(&decrypted_file);
+        
+        Ok(())
+    }
+
+    #[test]
+    fn test_output_path_for() -> Result<()> {
+        use file_demo::{output_path_for, DEFAULT_NAME_TEMPLATE};
+        use std::path::Path;
+        
+        let input = Path::new("/data/in/report.pdf");
+        assert_eq!(output_path_for(input, DEFAULT_NAME_TEMPLATE, 1), Path::new("/data/in/report.pdf.seal"));
+        assert_eq!(output_path_for(input, "{n}-{stem}.sealed.{ext}", 7), Path::new("/data/in/7-report.sealed.pdf"));
+        assert_eq!(output_path_for(Path::new("notes"), DEFAULT_NAME_TEMPLATE, 1), Path::new("notes.seal"));
+        assert_eq!(output_path_for(Path::new("archive.tar.gz"), DEFAULT_NAME_TEMPLATE, 1), Path::new("archive.tar.gz.seal"));
+        
+        assert!(FileDemo::new()?.with_name_template("{ext}.seal").is_err());
+        assert!(FileDemo::new()?.with_name_template("out/{stem}.seal").is_err());
+        assert!(FileDemo::new()?.with_name_template("batch-{n}.seal").is_ok());
+        
+        // `{stem}` alone sends a.txt and a.md to the same file, which a batch refuses
+        let demo = FileDemo::new()?;
+        let dir = std::env::temp_dir().join("name_collision_test");
+        std::fs::create_dir_all(&dir)?;
+        let jobs: Vec<(PathBuf, PathBuf)> = ["a.txt", "a.md"]
+            .iter()
+            .map(|name| {
+                let input = dir.join(name);
+                std::fs::write(&input, name)?;
+                Ok((input.clone(), output_path_for(&input, "{stem}.seal", 1)))
+            })
+            .collect::<Result<_>>()?;
+        let err = demo.encrypt_batch(&jobs, None).unwrap_err();
+        assert!(err.to_string().contains("would both be written to"), "{}", err);
+        assert!(!dir.join("a.seal").exists());
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_batch_respects_max_memory() -> Result<()> {
+        use file_demo::batch_groups;
+        
+        // Files cost twice their size; the oversized one goes alone
+        assert_eq!(batch_groups(&[100, 100, 100, 500, 10], Some(400), 8), vec![0..2, 2..3, 3..4, 4..5]);
+        assert_eq!(batch_groups(&[100, 100, 100], None, 2), vec![0..2, 2..3]);
+        assert_eq!(batch_groups(&[], Some(400), 2), vec![]);
+        
+        let demo = FileDemo::new()?.with_max_memory(Some(3000));
+        let dir = std::env::temp_dir().join("batch_memory_test");
+        std::fs::create_dir_all(&dir)?;
+        let mut jobs = Vec::new();
+        for i in 0..4 {
+            let input = dir.join(format!("part{}.bin", i));
+            std::fs::write(&input, vec![i as u8; 1000])?;
+            jobs.push((input.clone(), dir.join(format!("part{}.bin.seal", i))));
+        }
+        demo.encrypt_batch(&jobs, None)?;
+        
+        for (input, output) in &jobs {
+            let decrypted = dir.join("roundtrip.bin");
+            demo.decrypt_file(output, &decrypted)?;
+            assert_eq!(std::fs::read(&decrypted)?, std::fs::read(input)?);
+        }
+        
+        let _ = std::fs::remove_dir_all(&dir);
+        Ok(())
+    }
+
+    #[test]
+    fn test_batch_decrypt_extracts_once_per_server() -> Result<()> {
+        let demo = FileDemo::new()?;
+        let root = std::env::temp_dir().join("key_cache_test");
+        
+        // Same file name in different folders, so every file has the identity file:shared.txt
+        let mut encrypt_jobs = Vec::new();
+        let mut decrypt_jobs = Vec::new();
+        for i in 0..5 {
+            let dir = root.join(format!("folder{}", i));
+            std::fs::create_dir_all(&dir)?;
+            let input = dir.join("shared.txt");
+            std::fs::write(&input, format!("copy {}", i))?;
+            encrypt_jobs.push((input, dir.join("shared.txt.seal")));
+            decrypt_jobs.push((dir.join("shared.txt.seal"), dir.join("shared.decrypted.txt")));
+        }
+        demo.encrypt_batch(&encrypt_jobs, None)?;
+        
+        // One extract per server of the threshold, reused by every later file
+        let mut cache = KeyCache::new();
+        demo.decrypt_batch_with_cache(&decrypt_jobs, &mut cache)?;
+        assert_eq!(cache.extracted(), demo.threshold as usize);
+        assert_eq!(cache.hits(), demo.threshold as usize * (decrypt_jobs.len() - 1));
+        for (i, (_, output)) in decrypt_jobs.iter().enumerate() {
+            assert_eq!(std::fs::read_to_string(output)?, format!("copy {}", i));
+        }
+        
+        let _ = std::fs::remove_dir_all(&root);
+        Ok(())
+    }
+
+    #[test]
+    fn test_decrypt_directory_parallel() -> Result<()> {
+        let demo = FileDemo::new()?;
+        let root = std::env::temp_dir().join("parallel_decrypt_test");
+        let _ = std::fs::remove_dir_all(&root);
+        let (plain, sealed, restored) = (root.join("plain"), root.join("sealed"), root.join("restored"));
+        std::fs::create_dir_all(&plain)?;
+        std::fs::create_dir_all(&sealed)?;
+        
+        let mut jobs = Vec::new();
+        for i in 0..8 {
+            let input = plain.join(format!("doc{}.txt", i));
+            std::fs::write(&input, format!("document {}", i))?;
+            jobs.push((input, sealed.join(format!("doc{}.txt.seal", i))));
+        }
+        demo.encrypt_batch(&jobs, None)?;
+        std::fs::write(sealed.join("notes.txt"), "not encrypted")?;
+        
+        let manifest = demo.decrypt_directory_parallel(&sealed, &restored)?;
+        assert_eq!(manifest.entries.len(), 9);
+        assert_eq!(manifest.failed(), 1);
+        let failed = manifest.entries.iter().find(|entry| entry.outcome.is_err()).unwrap();
+        assert!(failed.input.ends_with("notes.txt"));
+        for i in 0..8 {
+            assert_eq!(std::fs::read_to_string(restored.join(format!("doc{}.txt", i)))?, format!("document {}", i));
+        }
+        
+        assert!(demo.decrypt_directory_parallel(&sealed, &sealed).is_err());
+        
+        let _ = std::fs::remove_dir_all(&root);
+        Ok(())
+    }
+
+    #[test]
+    fn test_encrypt_tree_round_trip() -> Result<()> {
+        let demo = FileDemo::new()?;
+        let root = std::env::temp_dir().join("encrypt_tree_test");
+        let _ = std::fs::remove_dir_all(&root);
+        let (plain, sealed, restored) = (root.join("plain"), root.join("sealed"), root.join("restored"));
+        std::fs::create_dir_all(plain.join("sub/deeper"))?;
+        std::fs::write(plain.join("top.txt"), "top level")?;
+        std::fs::write(plain.join("sub/a.txt"), "in sub")?;
+        std::fs::write(plain.join("sub/deeper/b.bin"), [0u8, 1, 2, 3])?;
+        std::fs::write(plain.join("sub/empty"), "")?;
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(plain.join("top.txt"), plain.join("sub/link.txt"))?;
+        
+        let manifest = demo.encrypt_tree(&plain, &sealed, None)?;
+        assert_eq!((manifest.entries.len(), manifest.failed(), manifest.bytes()), (4, 0, 9 + 6 + 4));
+        assert!(!sealed.join("sub/link.txt.seal").exists());
+        let object = SealContainer::from_bytes(&std::fs::read(sealed.join("sub/a.txt.seal"))?)?.encrypted_object;
+        assert_eq!(object.id, b"file:sub/a.txt");
+        
+        // A file moved to another path is refused; the rest still decrypt
+        std::fs::copy(sealed.join("top.txt.seal"), sealed.join("sub/moved.txt.seal"))?;
+        let manifest = demo.decrypt_tree(&sealed, &restored)?;
+        assert_eq!((manifest.entries.len(), manifest.failed()), (5, 1));
+        let failed = manifest.entries.iter().find(|entry| entry.outcome.is_err()).unwrap();
+        assert!(failed.input.ends_with("sub/moved.txt.seal"));
+        assert!(!restored.join("sub/moved.txt").exists());
+        for (path, _) in file_demo::files_under(&plain)? {
+            let relative = path.strip_prefix(&plain)?;
+            assert_eq!(std::fs::read(restored.join(relative))?, std::fs::read(&path)?, "{}", relative.display());
+        }
+        
+        let _ = std::fs::remove_dir_all(&root);
+        Ok(())
+    }
+
+    #[test]
+    fn test_progress_hook_per_file() -> Result<()> {
+        let events = std::cell::RefCell::new(Vec::new());
+        let record = |progress| events.borrow_mut().push(progress);
+        let demo = FileDemo::new()?;
+        let root = std::env::temp_dir().join("progress_hook_test");
+        let _ = std::fs::remove_dir_all(&root);
+        let plain = root.join("plain");
+        std::fs::create_dir_all(plain.join("sub"))?;
+        std::fs::write(plain.join("a.txt"), "12345")?;
+        std::fs::write(plain.join("sub/b.txt"), "123")?;
+        
+        demo.encrypt_directory(&plain, &root.join("sealed"), Some(&record))?;
+        let seen: Vec<(usize, usize, u64)> = events
+            .borrow()
+            .iter()
+            .map(|progress| (progress.files_done, progress.files_total, progress.bytes_done))
+            .collect();
+        assert_eq!(seen, [(0, 2, 0), (1, 2, 5), (1, 2, 5), (2, 2, 8)]);
+        assert_eq!(events.borrow()[3].current_path, plain.join("sub/b.txt"));
+        
+        // Batches report every start before the group is sealed
+        events.borrow_mut().clear();
+        let jobs: Vec<_> = ["a.txt", "sub/b.txt"]
+            .iter()
+            .enumerate()
+            .map(|(i, name)| (plain.join(name), root.join(format!("{}.seal", i))))
+            .collect();
+        demo.encrypt_batch(&jobs, Some(&record))?;
+        let events = events.borrow();
+        assert_eq!(events.len(), 4);
+        // A finish is the event that moves `files_done` on
+        let finished: Vec<&PathBuf> = events
+            .windows(2)
+            .filter(|pair| pair[1].files_done > pair[0].files_done)
+            .map(|pair| &pair[1].current_path)
+            .collect();
+        assert_eq!(finished, jobs.iter().map(|(input, _)| input).collect::<Vec<_>>());
+        assert_eq!((events[3].files_done, events[3].bytes_done), (2, 8));
+        
+        let _ = std::fs::remove_dir_all(&root);
+        Ok(())
+    }
+
+    #[test]
+    fn test_directory_modes_by_extension() -> Result<()> {
+        let modes = file_modes::FileModes::from_toml(
+            r#"
+            [[rule]]
+            pattern = "*.json"
+            mode = "aes-256-gcm"
+            compression = "zstd"
+            
+            [[rule]]
+            pattern = "*.jpg"
+            mode = "aes-256-gcm"
+            compression = "none"
+            "#,
+        )?;
+        let demo = FileDemo::new()?.with_file_modes(modes);
+        let root = std::env::temp_dir().join("directory_modes_test");
+        let _ = std::fs::remove_dir_all(&root);
+        let (plain, sealed, restored) = (root.join("plain"), root.join("sealed"), root.join("restored"));
+        std::fs::create_dir_all(&plain)?;
+        let json = r#"{"user": "alice", "role": "admin"}"#.repeat(200);
+        let jpg = testutil::random_bytes(5000, &mut testutil::seeded_rng(Some(3)));
+        std::fs::write(plain.join("accounts.json"), &json)?;
+        std::fs::write(plain.join("photo.jpg"), &jpg)?;
+        
+        assert_eq!(demo.encrypt_directory(&plain, &sealed, None)?.succeeded(), 2);
+        let sealed_file = |index: usize| -> Result<(crypto::EncryptedObject, u64)> {
+            let path = sealed.join(format!("{:08}.seal", index));
+            Ok((bcs::from_bytes(&std::fs::read(&path)?)?, std::fs::metadata(&path)?.len()))
+        };
+        let (json_object, json_sealed_len) = sealed_file(0)?;
+        let (jpg_object, jpg_sealed_len) = sealed_file(1)?;
+        let json_treatment = file_modes::FileTreatment::of(&json_object)?;
+        let jpg_treatment = file_modes::FileTreatment::of(&jpg_object)?;
+        assert_eq!(json_treatment.compression, file_modes::Compression::Zstd);
+        assert_eq!(json_treatment.mode, file_modes::Mode::Aes256Gcm);
+        assert_eq!(jpg_treatment, file_modes::FileTreatment::default());
+        assert!(json_sealed_len < json.len() as u64 / 4);
+        assert!(jpg_sealed_len > jpg.len() as u64);
+        
+        assert_eq!(demo.decrypt_directory(&sealed, &restored)?.succeeded(), 2);
+        assert_eq!(std::fs::read_to_string(restored.join("accounts.json"))?, json);
+        assert_eq!(std::fs::read(restored.join("photo.jpg"))?, jpg);
+        
+        let _ = std::fs::remove_dir_all(&root);
+        Ok(())
+    }
+
+    #[test]
+    fn test_directory_skips_filtered_files() -> Result<()> {
+        let filter = file_modes::FileFilter::new(Some(4096), &["mp4".to_string()]);
+        let demo = FileDemo::new()?.with_file_filter(filter);
+        let root = std::env::temp_dir().join("directory_filter_test");
+        let _ = std::fs::remove_dir_all(&root);
+        let (plain, sealed, restored) = (root.join("plain"), root.join("sealed"), root.join("restored"));
+        std::fs::create_dir_all(plain.join("media"))?;
+        std::fs::write(plain.join("media/talk.mp4"), vec![1u8; 100])?;
+        std::fs::write(plain.join("dump.bin"), vec![2u8; 5000])?;
+        std::fs::write(plain.join("notes.txt"), "keep me")?;
+        
+        assert_eq!(demo.encrypt_directory(&plain, &sealed, None)?.succeeded(), 1);
+        let names: Vec<_> = std::fs::read_dir(&sealed)?.map(|entry| entry.unwrap().file_name()).collect();
+        assert_eq!(names.len(), 2);
+        
+        // The manifest says what was left out and why
+        let mut reader = std::fs::File::open(sealed.join(file_demo::DIRECTORY_MANIFEST))?;
+        let header = streaming::read_frame(&mut reader, 1 << 20)?.unwrap();
+        let flags = streaming::read_frame(&mut reader, 1)?.unwrap()[0];
+        let manifest_object: crypto::EncryptedObject = bcs::from_bytes(&header)?;
+        let manifest = directory_manifest::DirectoryManifest::decode(&demo.decrypt_with_servers(&manifest_object, 2)?, flags)?;
+        assert_eq!(manifest.entries.iter().map(|entry| entry.path.as_str()).collect::<Vec<_>>(), ["notes.txt"]);
+        let skipped: Vec<_> = manifest.skipped.iter().map(|entry| (entry.path.as_str(), entry.size, &entry.reason)).collect();
+        assert_eq!(
+            skipped,
+            [
+                ("dump.bin", 5000, &directory_manifest::SkipReason::TooLarge { limit: 4096 }),
+                ("media/talk.mp4", 100, &directory_manifest::SkipReason::ExcludedExtension("mp4".to_string())),
+            ]
+        );
+        
+        assert_eq!(demo.decrypt_directory(&sealed, &restored)?.succeeded(), 1);
+        assert_eq!(std::fs::read_to_string(restored.join("notes.txt"))?, "keep me");
+        assert!(!restored.join("dump.bin").exists());
+        
+        let _ = std::fs::remove_dir_all(&root);
+        Ok(())
+    }
+
+    #[test]
+    fn test_directory_carries_on_past_failures() -> Result<()> {
+        let root = std::env::temp_dir().join("directory_failures_test");
+        let _ = std::fs::remove_dir_all(&root);
+        let (plain, sealed, restored) = (root.join("plain"), root.join("sealed"), root.join("restored"));
+        std::fs::create_dir_all(&plain)?;
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            std::fs::write(plain.join(name), name)?;
+        }
+        
+        // b.txt vanishes as it starts, so reading it fails
+        let vanishing = plain.join("b.txt");
+        let vanish = |progress: file_demo::FileProgress| {
+            if progress.files_done == 1 && progress.current_path == vanishing {
+                let _ = std::fs::remove_file(&vanishing);
+            }
+        };
+        let demo = FileDemo::new()?;
+        let summary = demo.encrypt_directory(&plain, &sealed, Some(&vanish))?;
+        assert_eq!((summary.succeeded(), summary.failed(), summary.bytes()), (2, 1, 10));
+        assert_eq!(summary.entries[1].input, plain.join("b.txt"));
+        
+        // A damaged file is reported and the others are still restored
+        std::fs::write(sealed.join("00000000.seal"), b"damaged")?;
+        let summary = demo.decrypt_directory(&sealed, &restored)?;
+        assert_eq!((summary.succeeded(), summary.failed()), (1, 1));
+        assert!(summary.entries[0].outcome.as_ref().is_err_and(|e| e.contains("Corrupt")));
+        assert!(!restored.join("a.txt").exists());
+        assert_eq!(std::fs::read_to_string(restored.join("c.txt"))?, "c.txt");
+        
+        let _ = std::fs::remove_dir_all(&root);
+        Ok(())
+    }
+
+    #[test]
+    fn test_directory_manifest_needs_keys() -> Result<()> {
+        let demo = FileDemo::new()?;
+        let root = std::env::temp_dir().join("directory_manifest_test");
+        let _ = std::fs::remove_dir_all(&root);
+        let (plain, sealed, restored) = (root.join("plain"), root.join("sealed"), root.join("restored"));
+        std::fs::create_dir_all(plain.join("legal/merger"))?;
+        std::fs::write(plain.join("legal/merger/term-sheet.txt"), "confidential terms")?;
+        std::fs::write(plain.join("budget.xlsx"), vec![7u8; 3000])?;
+        
+        assert_eq!(demo.encrypt_directory(&plain, &sealed, None)?.succeeded(), 2);
+        let mut names: Vec<_> = std::fs::read_dir(&sealed)?.map(|entry| entry.unwrap().file_name()).collect();
+        names.sort();
+        assert_eq!(names, ["00000000.seal", "00000001.seal", file_demo::DIRECTORY_MANIFEST]);
+        let header = std::fs::read(sealed.join(file_demo::DIRECTORY_MANIFEST))?;
+        assert!(!header.windows(6).any(|window| window == b"merger"));
+        
+        // Another fleet can't read the manifest, so restores nothing
+        let outsider = FileDemo::new()?;
+        let err = outsider.decrypt_directory(&sealed, &restored).err().map(|e| format!("{:#}", e)).unwrap_or_default();
+        assert!(err.contains("Failed to decrypt the directory manifest"), "{}", err);
+        assert!(!restored.exists());
+        
+        assert_eq!(demo.decrypt_directory(&sealed, &restored)?.succeeded(), 2);
+        assert_eq!(std::fs::read(restored.join("legal/merger/term-sheet.txt"))?, b"confidential terms");
+        assert_eq!(std::fs::read(restored.join("budget.xlsx"))?, vec![7u8; 3000]);
+        let modified = |path: &Path| std::fs::metadata(path).unwrap().modified().unwrap();
+        let seconds = |time: std::time::SystemTime| time.duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+        assert_eq!(
+            seconds(modified(&restored.join("budget.xlsx"))),
+            seconds(modified(&plain.join("budget.xlsx")))
+        );
+        
+        let _ = std::fs::remove_dir_all(&root);
+        Ok(())
+    }
+
+    #[test]
+    fn test_batch_decrypt_with_server_subset() -> Result<()> {
+        let mut demo = FileDemo::new()?;
+        let dir = std::env::temp_dir().join("server_subset_test");
+        std::fs::create_dir_all(&dir)?;
+        
+        // Two 2-of-3 files and one 3-of-3 file
+        let mut jobs = Vec::new();
+        for (name, threshold) in [("a.txt", 2), ("strict.txt", 3), ("b.txt", 2)] {
+            let input = dir.join(name);
+            std::fs::write(&input, name)?;
+            demo.threshold = threshold;
+            demo.encrypt_batch(&[(input, dir.join(format!("{}.seal", name)))], None)?;
+            jobs.push((dir.join(format!("{}.seal", name)), dir.join(format!("{}.out", name))));
+        }
+        
+        // Server 1 is down: the 3-of-3 file is skipped, the rest still decrypt
+        let manifest = demo.decrypt_batch_with_servers(&jobs, &[2, 1])?;
+        assert_eq!(manifest.failed(), 1);
+        assert_eq!(manifest.entries[0].outcome, Ok(5));
+        assert!(manifest.entries[1].outcome.as_ref().unwrap_err().contains("Needs 3 servers"));
+        assert!(!jobs[1].1.exists());
+        assert_eq!(std::fs::read_to_string(&jobs[2].1)?, "b.txt");
+        
+        assert!(demo.decrypt_batch_with_servers(&jobs, &[0, 3]).is_err());
+        
+        let _ = std::fs::remove_dir_all(&dir);
+        Ok(())
+    }
+
+    #[test]
+    fn test_cleanup_reports_leftovers() -> Result<()> {
+        let mut demo = FileDemo::new()?;
+        demo.cleanup()?;
+        demo.temp_dir = std::env::temp_dir().join("cleanup_test");
+        std::fs::create_dir_all(demo.temp_dir.join("nested/deeper"))?;
+        std::fs::write(demo.temp_dir.join("nested/deeper/file.seal"), b"ciphertext")?;
+        std::fs::write(demo.temp_dir.join("top.txt"), b"plaintext")?;
+        
+        demo.cleanup()?;
+        assert!(!demo.temp_dir.exists());
+        // Nothing left to remove is not an error
+        demo.cleanup()?;
+        
+        // procfs refuses deletes even for root
+        #[cfg(target_os = "linux")]
+        {
+            demo.temp_dir = PathBuf::from("/proc/self/status");
+            let err = demo.cleanup().unwrap_err().to_string();
+            assert!(err.starts_with("Could not remove 1 path(s) under /proc/self/status: /proc/self/status: "), "{}", err);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_bench_by_threshold() -> Result<()> {
         let demo = ThresholdDemo::new(3);
-        demo.demo_threshold_encryption(2)?;
+        let timings = demo.bench_by_threshold(3)?;
+        assert_eq!(timings.iter().map(|timing| timing.threshold).collect::<Vec<_>>(), [1, 2, 3]);
+        assert!(timings.iter().all(|timing| timing.p50 > std::time::Duration::ZERO));
+        
+        assert!(demo.bench_by_threshold(4).is_err());
+        assert!(demo.bench_by_threshold(0).is_err());
         Ok(())
     }
 
     #[test]
-    fn test_file_demo() -> Result<()> {
+    fn test_latency_by_threshold() -> Result<()> {
+        let demo = ThresholdDemo::new(3);
+        let delay = std::time::Duration::from_millis(20);
+        let timings = demo.latency_by_threshold(3, delay, &[KeyFetch::Serial, KeyFetch::Parallel])?;
+        assert_eq!(timings.len(), 6);
+        
+        // Serial fetching waits out every server's delay in turn
+        let mean = |threshold, fetch| timings.iter().find(|t| t.threshold == threshold && t.fetch == fetch).unwrap().mean;
+        assert!(mean(3, KeyFetch::Serial) >= 3 * delay);
+        assert!(mean(3, KeyFetch::Parallel) >= delay);
+        assert!(mean(3, KeyFetch::Parallel) < mean(3, KeyFetch::Serial));
+        
+        assert!(demo.latency_by_threshold(4, delay, &[KeyFetch::Serial]).is_err());
+        assert_eq!(KeyFetch::parse("Parallel")?, KeyFetch::Parallel);
+        assert!(KeyFetch::parse("batched").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_file_demo_with_fleet() -> Result<()> {
+        let session = SealSession::generate(4, 3)?;
+        let master_keys = session.master_keys.clone().unwrap();
+        
+        let mut demo = FileDemo::with_fleet(master_keys.clone(), session.public_keys.clone(), session.key_servers.clone(), 3)?;
+        demo.package_id = session.package_id;
+        assert_eq!(demo.threshold, 3);
+        
+        let temp_file = std::env::temp_dir().join("with_fleet_test.txt");
+        std::fs::write(&temp_file, "fleet from a session")?;
+        let encrypted_file = std::env::temp_dir().join("with_fleet_test.txt.seal");
+        demo.encrypt_file(&temp_file, &encrypted_file)?;
+        
+        // The session's own keys open what the demo wrote
+        let container = container::SealContainer::from_bytes(&std::fs::read(&encrypted_file)?)?;
+        assert_eq!(session.decrypt(&container.encrypted_object)?, b"fleet from a session");
+        
+        assert!(FileDemo::with_fleet(master_keys[..3].to_vec(), session.public_keys.clone(), session.key_servers.clone(), 2).is_err());
+        assert!(FileDemo::with_fleet(master_keys.clone(), session.public_keys.clone(), session.key_servers.clone(), 5).is_err());
+        assert!(FileDemo::with_fleet(master_keys, session.public_keys.clone(), session.key_servers.clone(), 0).is_err());
+        
+        let _ = std::fs::remove_file(&temp_file);
+        let _ = std::fs::remove_file(&encrypted_file);
+        let _ = std::fs::remove_file(encrypted_file.with_extension("key"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_wrong_fleet_public_keys_rejected() -> Result<()> {
+        let fleet_a = FileDemo::new()?;
+        let fleet_b = FileDemo::new()?;
+        let temp_file = std::env::temp_dir().join("wrong_fleet_test.txt");
+        let encrypted_file = std::env::temp_dir().join("wrong_fleet_test.txt.seal");
+        let decrypted_file = std::env::temp_dir().join("wrong_fleet_test.decrypted.txt");
+        let _ = std::fs::remove_file(&decrypted_file);
+        std::fs::write(&temp_file, "encrypted to fleet A")?;
+        fleet_a.encrypt_file(&temp_file, &encrypted_file)?;
+        
+        // Fleet A's secret keys, but fleet B's public keys to verify shares against
+        let mut mixed = FileDemo::with_fleet(
+            fleet_a.master_keys.clone(),
+            fleet_b.public_keys.clone(),
+            fleet_a.key_servers.clone(),
+            fleet_a.threshold,
+        )?;
+        mixed.package_id = fleet_a.package_id;
+        
+        // Containers record fleet A's public keys, so only a headerless file
+        // makes the decryptor fall back on its own
+        let container = SealContainer::from_bytes(&std::fs::read(&encrypted_file)?)?;
+        std::fs::write(&encrypted_file, bcs::to_bytes(&container.encrypted_object)?)?;
+        
+        // A clean verification failure from seal_decrypt, not garbage plaintext
+        let err = mixed.decrypt_file(&encrypted_file, &decrypted_file).unwrap_err();
+        assert!(
+            err.downcast_ref::<fastcrypto::error::FastCryptoError>().is_some(),
+            "unexpected error: {:#}",
+            err
+        );
+        assert!(!decrypted_file.exists(), "no plaintext may be written on a failed check");
+        
+        // The same file with matching public keys still opens
+        fleet_a.decrypt_file(&encrypted_file, &decrypted_file)?;
+        assert_eq!(std::fs::read(&decrypted_file)?, b"encrypted to fleet A");
+        
+        let _ = std::fs::remove_file(&temp_file);
+        let _ = std::fs::remove_file(&encrypted_file);
+        let _ = std::fs::remove_file(&decrypted_file);
+        Ok(())
+    }
+
+    #[test]
+    fn test_identity_usage() -> Result<()> {
+        let session = SealSession::generate(3, 2)?;
+        let dir = std::env::temp_dir().join("identity_audit_test");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("nested"))?;
+        
+        let files = [("a.seal", "team:finance"), ("nested/b.seal", "team:finance"), ("c.seal", "user:alice"), ("d.seal", "team:finance")];
+        for (name, identity) in files {
+            let (encrypted_object, _) = session.encrypt(identity.as_bytes(), crypto::EncryptionInput::Plain)?;
+            let container = SealContainer { fingerprint: None, encrypted_object, envelope: None, server_keys: None };
+            std::fs::write(dir.join(name), container.to_bytes()?)?;
+        }
+        std::fs::write(dir.join("junk.seal"), b"not sealed")?;
+        std::fs::write(dir.join("notes.txt"), b"ignored")?;
+        
+        let (usage, unreadable) = identity_usage(&dir)?;
+        let counts: Vec<_> = usage.iter().map(|(identity, files)| (identity.as_slice(), files.len())).collect();
+        assert_eq!(counts, [(b"team:finance".as_slice(), 3), (b"user:alice".as_slice(), 1)]);
+        assert_eq!(unreadable, [dir.join("junk.seal")]);
+        
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_encrypt_to_sink() -> Result<()> {
+        struct Upload {
+            parts: Vec<Vec<u8>>,
+            fail: bool,
+        }
+        impl std::io::Write for Upload {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                if self.fail {
+                    return Err(std::io::Error::other("bucket unreachable"));
+                }
+                self.parts.push(buf.to_vec());
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+        
         let demo = FileDemo::new()?;
-        // Just test basic functionality without cleanup for tests
-        let temp_file = std::env::temp_dir().join("test.txt");
-        std::fs::write(&temp_file, "test content")?;
+        let mut upload = Upload { parts: Vec::new(), fail: false };
+        let (written, _) = demo.encrypt_to_sink(b"straight to the bucket".to_vec(), b"file:upload.bin".to_vec(), &mut upload)?;
+        let uploaded = upload.parts.concat();
+        assert_eq!(uploaded.len(), written);
+        let container = SealContainer::from_bytes(&uploaded)?;
+        assert_eq!(container.public_keys()?, Some(demo.public_keys.clone()));
+        let encrypted_object = container.encrypted_object;
+        assert_eq!(encrypted_object.id, b"file:upload.bin");
+        assert_eq!(demo.decrypt_with_servers(&encrypted_object, demo.threshold as usize)?, b"straight to the bucket");
         
-        let encrypted_file = std::env::temp_dir().join("test.txt.encrypted");
+        let mut unreachable = Upload { parts: Vec::new(), fail: true };
+        assert!(demo.encrypt_to_sink(b"lost".to_vec(), b"file:lost.bin".to_vec(), &mut unreachable).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_decrypt_with_serialized_keys() -> Result<()> {
+        use crypto::{create_full_id, ibe::extract, EncryptionInput, IBEPublicKeys};
+        use fastcrypto::serde_helpers::ToFromByteArray;
+
+        let demo = FileDemo::new()?;
+        let (encrypted_object, _) = crypto::seal_encrypt(
+            demo.package_id,
+            b"remote-keys".to_vec(),
+            demo.key_servers.clone(),
+            &IBEPublicKeys::BonehFranklinBLS12381(demo.public_keys.clone()),
+            demo.threshold,
+            EncryptionInput::Aes256Gcm { data: b"over the wire".to_vec(), aad: None },
+        )?;
+        let full_id = create_full_id(&encrypted_object.package_id, &encrypted_object.id);
+        let keys: Vec<(crypto::ObjectID, Vec<u8>)> = (1..3)
+            .map(|i| (demo.key_servers[i], extract(&demo.master_keys[i], &full_id).to_byte_array().to_vec()))
+            .collect();
+
+        assert_eq!(demo.decrypt_with_serialized_keys(&encrypted_object, &keys)?, b"over the wire");
+
+        let mut truncated = keys.clone();
+        truncated[1].1.truncate(40);
+        let err = demo.decrypt_with_serialized_keys(&encrypted_object, &truncated).unwrap_err();
+        assert!(err.to_string().contains("must be 48 bytes, got 40"));
+
+        let mut garbage = keys.clone();
+        garbage[0].1 = vec![0xFF; 48];
+        let err = demo.decrypt_with_serialized_keys(&encrypted_object, &garbage).unwrap_err();
+        assert!(err.to_string().contains("is malformed"));
+
+        // A valid point that belongs to a different identity
+        let mut wrong = keys.clone();
+        wrong[0].1 = extract(&demo.master_keys[1], b"other").to_byte_array().to_vec();
+        let err = demo.decrypt_with_serialized_keys(&encrypted_object, &wrong).unwrap_err();
+        assert!(err.to_string().contains("does not verify"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_append_log() -> Result<()> {
+        let demo = FileDemo::new()?;
+        let log = std::env::temp_dir().join("append_log_test.log.seal");
+        let _ = std::fs::remove_file(&log);
+        
+        for i in 0..100 {
+            demo.append_record(&log, format!("event {}", i).as_bytes())?;
+        }
+        let records = demo.read_log(&log)?;
+        assert_eq!(records.len(), 100);
+        for (i, record) in records.iter().enumerate() {
+            assert_eq!(record, format!("event {}", i).as_bytes());
+        }
+        
+        // A record whose encryption would overrun the frame limit is refused
+        // without making the log unreadable
+        let limit = file_demo::MAX_LOG_RECORD as usize;
+        let err = demo.append_record(&log, &vec![1; limit]).unwrap_err();
+        assert!(err.to_string().contains("over the"), "{}", err);
+        demo.append_record(&log, &vec![2; limit - 4096])?;
+        let records = demo.read_log(&log)?;
+        assert_eq!(records.len(), 101);
+        assert_eq!(records[100].len(), limit - 4096);
+        
+        let _ = std::fs::remove_file(&log);
+        Ok(())
+    }
+
+    #[test]
+    fn test_decrypt_file_checks_recorded_fleet() -> Result<()> {
+        let mut demo = FileDemo::new()?;
+        let temp_file = std::env::temp_dir().join("recorded_keys_test.txt");
+        let encrypted_file = std::env::temp_dir().join("recorded_keys_test.txt.seal");
+        let decrypted_file = std::env::temp_dir().join("recorded_keys_test.decrypted.txt");
+        std::fs::write(&temp_file, "self-describing")?;
         demo.encrypt_file(&temp_file, &encrypted_file)?;
         
-        let decrypted_file = std::env::temp_dir().join("test.txt.decrypted");
+        let bytes = std::fs::read(&encrypted_file)?;
+        let container = SealContainer::from_bytes(&bytes)?;
+        assert_eq!(&bytes[..5], b"SEAL\x03");
+        assert_eq!(container.fingerprint, Some(container::fleet_fingerprint(&demo.key_servers, &demo.public_keys)));
         demo.decrypt_file(&encrypted_file, &decrypted_file)?;
+        assert_eq!(std::fs::read_to_string(&decrypted_file)?, "self-describing");
         
-        let original = std::fs::read(&temp_file)?;
-        let decrypted = std::fs::read(&decrypted_file)?;
-        assert_eq!(original, decrypted);
+        // A file recording another fleet's keys, consistent with its own
+        // fingerprint, is refused before those keys are used
+        let other = FileDemo::new()?;
+        other.encrypt_file(&temp_file, &encrypted_file)?;
+        assert!(SealContainer::from_bytes(&std::fs::read(&encrypted_file)?)?.public_keys()?.is_some());
+        let err = demo.decrypt_file(&encrypted_file, &decrypted_file).err().map(|e| e.to_string()).unwrap_or_default();
+        assert!(err.contains("refusing to trust"), "{}", err);
+        
+        // A headerless file records nothing and uses this fleet's keys
+        demo.public_keys.reverse();
+        std::fs::write(&encrypted_file, bcs::to_bytes(&container.encrypted_object)?)?;
+        assert!(demo.decrypt_file(&encrypted_file, &decrypted_file).is_err());
+        
+        for path in [&temp_file, &encrypted_file, &decrypted_file] {
+            let _ = std::fs::remove_file(path);
+        }
+        let _ = std::fs::remove_file(encrypted_file.with_extension("key"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_failed_decrypt_file_keeps_previous_output() -> Result<()> {
+        let demo = FileDemo::new()?;
+        let dir = std::env::temp_dir().join("atomic_file_test");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir)?;
+        let (source, encrypted, decrypted) = (dir.join("notes.txt"), dir.join("notes.seal"), dir.join("notes.out"));
+        std::fs::write(&source, "written all at once")?;
+        demo.encrypt_file(&source, &encrypted)?;
+        demo.decrypt_file(&encrypted, &decrypted)?;
+        assert_eq!(std::fs::read_to_string(encrypted.with_extension("key"))?.len(), 64);
+
+        // A tampered file fails without touching the earlier output or leaving temp files
+        let mut bytes = std::fs::read(&encrypted)?;
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        std::fs::write(&encrypted, &bytes)?;
+        assert!(demo.decrypt_file(&encrypted, &decrypted).is_err());
+        assert_eq!(std::fs::read_to_string(&decrypted)?, "written all at once");
+        let mut names: Vec<String> = std::fs::read_dir(&dir)?
+            .map(|entry| Ok(entry?.file_name().to_string_lossy().into_owned()))
+            .collect::<Result<_>>()?;
+        names.sort();
+        assert_eq!(names, ["notes.key", "notes.out", "notes.seal", "notes.txt"]);
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_decrypt_plaintext_file() -> Result<()> {
+        let demo = FileDemo::new()?;
+        let plain_file = std::env::temp_dir().join("never_encrypted.txt");
+        std::fs::write(&plain_file, "just some notes, never encrypted")?;
+        
+        let decrypted_file = std::env::temp_dir().join("never_encrypted.txt.decrypted");
+        let err = demo.decrypt_file(&plain_file, &decrypted_file).unwrap_err();
+        assert_eq!(err.to_string(), "This does not appear to be a Seal-encrypted file");
+        assert!(!decrypted_file.exists());
+        
+        // A damaged file that does carry the header is reported as corrupt
+        std::fs::write(&plain_file, b"SEAL\x02not bcs")?;
+        let err = demo.decrypt_file(&plain_file, &decrypted_file).unwrap_err();
+        assert_eq!(err.to_string(), "Corrupt Seal container");
+        
+        let _ = std::fs::remove_file(&plain_file);
+        
+        Ok(())
+    }
+
+    #[test]
+    fn test_upgrade_threshold() -> Result<()> {
+        let demo = FileDemo::new()?;
+        let temp_file = std::env::temp_dir().join("upgrade_test.txt");
+        std::fs::write(&temp_file, "threshold upgrade")?;
+        
+        let encrypted_file = std::env::temp_dir().join("upgrade_test.txt.encrypted");
+        let upgraded_file = std::env::temp_dir().join("upgrade_test.txt.upgraded");
+        demo.encrypt_file(&temp_file, &encrypted_file)?;
+        demo.upgrade_threshold(&encrypted_file, &upgraded_file, 3)?;
+        
+        let upgraded = SealContainer::from_bytes(&std::fs::read(&upgraded_file)?)?.encrypted_object;
+        assert_eq!(upgraded.threshold, 3);
+        assert!(demo.decrypt_with_servers(&upgraded, 2).is_err());
+        assert_eq!(demo.decrypt_with_servers(&upgraded, 3)?, b"threshold upgrade");
+        
+        let _ = std::fs::remove_file(&temp_file);
+        let _ = std::fs::remove_file(&encrypted_file);
+        let _ = std::fs::remove_file(&upgraded_file);
+        let _ = std::fs::remove_file(encrypted_file.with_extension("key"));
+        
+        Ok(())
+    }
+
+    #[test]
+    fn test_rekey_symmetric() -> Result<()> {
+        let demo = FileDemo::new()?;
+        let temp_file = std::env::temp_dir().join("rekey_test.txt");
+        std::fs::write(&temp_file, "key may have leaked")?;
+        
+        let encrypted_file = std::env::temp_dir().join("rekey_test.txt.encrypted");
+        let rekeyed_file = std::env::temp_dir().join("rekey_test.rekeyed.encrypted");
+        demo.encrypt_file(&temp_file, &encrypted_file)?;
+        demo.rekey_symmetric(&encrypted_file, &rekeyed_file)?;
+        
+        let original = SealContainer::from_bytes(&std::fs::read(&encrypted_file)?)?.encrypted_object;
+        let rekeyed = SealContainer::from_bytes(&std::fs::read(&rekeyed_file)?)?.encrypted_object;
+        assert_eq!(rekeyed.id, original.id);
+        assert_eq!(rekeyed.package_id, original.package_id);
+        assert_eq!(rekeyed.threshold, original.threshold);
+        
+        // The saved old key is not the key of the new file, which the fleet still opens
+        let old_key = std::fs::read_to_string(encrypted_file.with_extension("key"))?;
+        let new_key = std::fs::read_to_string(rekeyed_file.with_extension("key"))?;
+        assert_ne!(old_key, new_key);
+        assert_eq!(demo.decrypt_with_servers(&rekeyed, 2)?, b"key may have leaked");
+        
+        // The old key, recovered from the old shares, fails on the new ciphertext
+        let mut old_key_on_new_file = original.clone();
+        old_key_on_new_file.ciphertext = rekeyed.ciphertext.clone();
+        assert!(demo.decrypt_with_servers(&old_key_on_new_file, 2).is_err());
+        
+        // Another fleet's file is refused rather than re-encrypted to this fleet
+        assert!(FileDemo::new()?.rekey_symmetric(&encrypted_file, &rekeyed_file).is_err());
+        
+        for path in [&temp_file, &encrypted_file, &rekeyed_file] {
+            let _ = std::fs::remove_file(path);
+            let _ = std::fs::remove_file(path.with_extension("key"));
+        }
+        
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_stream_reports_bad_chunk() -> Result<()> {
+        let demo = FileDemo::new()?;
+        let temp_file = std::env::temp_dir().join("verify_stream_test.bin");
+        let content = testutil::random_bytes(5 * 4096 + 100, &mut testutil::seeded_rng(Some(3)));
+        std::fs::write(&temp_file, &content)?;
+        
+        let encrypted_file = std::env::temp_dir().join("verify_stream_test.bin.sealstream");
+        demo.encrypt_file_streaming(&temp_file, &encrypted_file, ChunkingStrategy::Fixed(4096))?;
+        let digest = Sha3_256::digest(&content).digest;
+        assert_eq!(demo.verify_stream(&encrypted_file, None)?, digest);
+        demo.verify_stream(&encrypted_file, Some(&digest))?;
+        
+        // An intact stream of some other file doesn't pass for this one
+        let err = demo.verify_stream(&encrypted_file, Some(&Sha3_256::digest(b"other").digest)).unwrap_err();
+        assert!(err.to_string().contains("doesn't match the expected"), "{}", err);
+        
+        // Flip a byte inside chunk 2: magic, header length, header, then
+        // frames of (u32 length + chunk + 16-byte tag)
+        let mut bytes = std::fs::read(&encrypted_file)?;
+        let header_len = u32::from_le_bytes(bytes[4..8].try_into()?) as usize;
+        let chunk_2 = 8 + header_len + 2 * (4 + 4096 + 16) + 4;
+        bytes[chunk_2 + 10] ^= 1;
+        std::fs::write(&encrypted_file, &bytes)?;
+        
+        let err = demo.verify_stream(&encrypted_file, Some(&digest)).unwrap_err();
+        assert!(err.to_string().contains("Chunk 2"), "unexpected error: {:#}", err);
+        
+        let _ = std::fs::remove_file(&temp_file);
+        let _ = std::fs::remove_file(&encrypted_file);
+        
+        Ok(())
+    }
+
+    #[test]
+    fn test_decrypt_prefix() -> Result<()> {
+        let demo = FileDemo::new()?;
+        let temp_file = std::env::temp_dir().join("decrypt_prefix_test.bin");
+        let content = testutil::random_bytes(4 * 4096 + 10, &mut testutil::seeded_rng(Some(5)));
+        std::fs::write(&temp_file, &content)?;
+        
+        let encrypted_file = std::env::temp_dir().join("decrypt_prefix_test.bin.sealstream");
+        demo.encrypt_file_streaming(&temp_file, &encrypted_file, ChunkingStrategy::Fixed(4096))?;
+        for n in [0, 1, 4096, 5000, content.len(), content.len() + 100] {
+            assert!(demo.decrypt_prefix(&encrypted_file, n)? == content[..n.min(content.len())], "prefix of {}", n);
+        }
+        
+        // Corrupt chunk 3: prefixes within chunks 0-1 never touch it
+        let mut bytes = std::fs::read(&encrypted_file)?;
+        let header_len = u32::from_le_bytes(bytes[4..8].try_into()?) as usize;
+        bytes[8 + header_len + 3 * (4 + 4096 + 16) + 20] ^= 1;
+        std::fs::write(&encrypted_file, &bytes)?;
+        assert!(demo.decrypt_prefix(&encrypted_file, 5000)? == content[..5000]);
+        assert!(demo.decrypt_prefix(&encrypted_file, 4 * 4096).is_err());
+        
+        // Whole-file containers are decrypted and truncated
+        let sealed_file = std::env::temp_dir().join("decrypt_prefix_test.bin.seal");
+        demo.encrypt_file(&temp_file, &sealed_file)?;
+        assert!(demo.decrypt_prefix(&sealed_file, 100)? == content[..100]);
+        
+        let _ = std::fs::remove_file(&temp_file);
+        let _ = std::fs::remove_file(&encrypted_file);
+        let _ = std::fs::remove_file(&sealed_file);
+        let _ = std::fs::remove_file(sealed_file.with_extension("key"));
+        
+        Ok(())
+    }
+
+    #[test]
+    fn test_decrypt_file_mmap() -> Result<()> {
+        let demo = FileDemo::new()?;
+        let temp_file = std::env::temp_dir().join("mmap_test.bin");
+        let content = testutil::random_bytes(8 * 1024 * 1024 + 77, &mut testutil::seeded_rng(Some(6)));
+        std::fs::write(&temp_file, &content)?;
+        
+        let encrypted_file = std::env::temp_dir().join("mmap_test.bin.sealstream");
+        let mapped_file = std::env::temp_dir().join("mmap_test.mapped.bin");
+        demo.encrypt_file_streaming(&temp_file, &encrypted_file, ChunkingStrategy::Fixed(64 * 1024))?;
+        
+        assert_eq!(demo.decrypt_file_mmap(&encrypted_file, &mapped_file)?, content.len() as u64);
+        let mut buffered = Vec::new();
+        demo.decrypt_to_writer(&encrypted_file, &mut buffered)?;
+        assert!(std::fs::read(&mapped_file)? == buffered);
+        assert!(buffered == content);
+        
+        // Empty files have an empty final chunk and nothing to map
+        std::fs::write(&temp_file, b"")?;
+        demo.encrypt_file_streaming(&temp_file, &encrypted_file, ChunkingStrategy::Fixed(4096))?;
+        assert_eq!(demo.decrypt_file_mmap(&encrypted_file, &mapped_file)?, 0);
+        assert!(std::fs::read(&mapped_file)?.is_empty());
+        
+        // A truncated stream leaves no output behind
+        let _ = std::fs::remove_file(&mapped_file);
+        demo.encrypt_file_streaming(&temp_file, &encrypted_file, ChunkingStrategy::Fixed(4096))?;
+        let bytes = std::fs::read(&encrypted_file)?;
+        std::fs::write(&encrypted_file, &bytes[..bytes.len() - 1])?;
+        assert!(demo.decrypt_file_mmap(&encrypted_file, &mapped_file).is_err());
+        assert!(!mapped_file.exists());
         
-        // Cleanup
         let _ = std::fs::remove_file(&temp_file);
         let _ = std::fs::remove_file(&encrypted_file);
+        
+        Ok(())
+    }
+
+    #[test]
+    fn test_decrypt_file_streaming_truncation() -> Result<()> {
+        let demo = FileDemo::new()?;
+        let temp_file = std::env::temp_dir().join("streaming_truncation_test.bin");
+        let encrypted_file = std::env::temp_dir().join("streaming_truncation_test.bin.sealstream");
+        let decrypted_file = std::env::temp_dir().join("streaming_truncation_test.decrypted.bin");
+        
+        // Three full chunks and a partial last one
+        let content = testutil::random_bytes(3 * 4096 + 123, &mut testutil::seeded_rng(Some(11)));
+        std::fs::write(&temp_file, &content)?;
+        demo.encrypt_file_streaming(&temp_file, &encrypted_file, ChunkingStrategy::Fixed(4096))?;
+        assert_eq!(demo.decrypt_file_streaming(&encrypted_file, &decrypted_file)?, content.len() as u64);
+        assert!(std::fs::read(&decrypted_file)? == content);
         let _ = std::fs::remove_file(&decrypted_file);
         
+        // Cut inside the magic, the header, a frame length, a frame, and
+        // cleanly after the third frame (dropping the last chunk)
+        let bytes = std::fs::read(&encrypted_file)?;
+        let frames = 8 + u32::from_le_bytes(bytes[4..8].try_into()?) as usize;
+        let frame = 4 + 4096 + 16;
+        for cut in [2, 20, frames, frames + 2, frames + frame + 100, frames + 3 * frame, bytes.len() - 1] {
+            std::fs::write(&encrypted_file, &bytes[..cut])?;
+            let err = format!("{:#}", demo.decrypt_file_streaming(&encrypted_file, &decrypted_file).unwrap_err());
+            assert!(err.contains("Truncated") || err.contains("too short"), "cut at {}: {}", cut, err);
+            assert!(!decrypted_file.exists(), "cut at {} left output behind", cut);
+        }
+        
+        let _ = std::fs::remove_file(&temp_file);
+        let _ = std::fs::remove_file(&encrypted_file);
+        
+        Ok(())
+    }
+
+    /// A file that fails once `limit` bytes have been read, like a crash.
+    struct InterruptedReader {
+        file: std::fs::File,
+        limit: u64,
+        read: u64,
+    }
+    
+    impl std::io::Read for InterruptedReader {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.read >= self.limit {
+                return Err(std::io::Error::other("interrupted"));
+            }
+            let allowed = buf.len().min((self.limit - self.read) as usize);
+            let n = self.file.read(&mut buf[..allowed])?;
+            self.read += n as u64;
+            Ok(n)
+        }
+    }
+    
+    impl std::io::Seek for InterruptedReader {
+        fn seek(&mut self, position: std::io::SeekFrom) -> std::io::Result<u64> {
+            self.file.seek(position)
+        }
+    }
+    
+    #[test]
+    fn test_interrupted_streaming_resumes() -> Result<()> {
+        let demo = FileDemo::new()?.with_checkpoints(Some(8));
+        let temp_file = std::env::temp_dir().join("resume_stream_test.bin");
+        let content = testutil::random_bytes(100 * 4096 + 77, &mut testutil::seeded_rng(Some(5)));
+        std::fs::write(&temp_file, &content)?;
+        let encrypted_file = std::env::temp_dir().join("resume_stream_test.bin.sealstream");
+        let checkpoint = std::env::temp_dir().join("resume_stream_test.bin.sealstream.checkpoint");
+        let partial = std::env::temp_dir().join("resume_stream_test.bin.sealstream.partial");
+        for path in [&encrypted_file, &checkpoint, &partial] {
+            let _ = std::fs::remove_file(path);
+        }
+        let chunking = ChunkingStrategy::Fixed(4096);
+        
+        // Dies while reading chunk 30, after the checkpoint at 24 chunks
+        let mut source = InterruptedReader { file: std::fs::File::open(&temp_file)?, limit: 30 * 4096 + 10, read: 0 };
+        assert!(demo.encrypt_file_resumable(&temp_file, &mut source, &encrypted_file, chunking, 8).is_err());
+        assert!(!encrypted_file.exists());
+        assert!(checkpoint.exists() && partial.exists());
+        
+        // The restart reads only what the checkpoint doesn't cover
+        let mut source = InterruptedReader { file: std::fs::File::open(&temp_file)?, limit: u64::MAX, read: 0 };
+        demo.encrypt_file_resumable(&temp_file, &mut source, &encrypted_file, chunking, 8)?;
+        assert_eq!(source.read, (content.len() - 24 * 4096) as u64);
+        assert!(!checkpoint.exists() && !partial.exists());
+        
+        let mut output = Vec::new();
+        demo.decrypt_to_writer(&encrypted_file, &mut output)?;
+        assert!(output == content, "resumed stream did not round trip");
+        
+        // A file that changed since the checkpoint isn't resumed
+        let mut source = InterruptedReader { file: std::fs::File::open(&temp_file)?, limit: 30 * 4096, read: 0 };
+        assert!(demo.encrypt_file_resumable(&temp_file, &mut source, &encrypted_file, chunking, 8).is_err());
+        std::fs::write(&temp_file, b"replaced")?;
+        let err = demo.encrypt_file_streaming(&temp_file, &encrypted_file, chunking).unwrap_err();
+        assert!(err.to_string().contains("changed since"), "{}", err);
+        
+        for path in [&temp_file, &encrypted_file, &checkpoint, &partial] {
+            let _ = std::fs::remove_file(path);
+        }
+        
+        Ok(())
+    }
+    
+    #[test]
+    fn test_parallel_streaming_matches_sequential() -> Result<()> {
+        let demo = FileDemo::new()?;
+        let temp_file = std::env::temp_dir().join("parallel_stream_test.bin");
+        let content = testutil::random_bytes(1024 * 1024 + 123, &mut testutil::seeded_rng(Some(2)));
+        std::fs::write(&temp_file, &content)?;
+        
+        let sequential = std::env::temp_dir().join("parallel_stream_test.seq.sealstream");
+        let parallel = std::env::temp_dir().join("parallel_stream_test.par.sealstream");
+        demo.encrypt_file_streaming_with_workers(&temp_file, &sequential, ChunkingStrategy::Fixed(16 * 1024), 1)?;
+        demo.encrypt_file_streaming_with_workers(&temp_file, &parallel, ChunkingStrategy::Fixed(16 * 1024), 4)?;
+        
+        for encrypted in [&sequential, &parallel] {
+            let mut output = Vec::new();
+            demo.decrypt_to_writer(encrypted, &mut output)?;
+            assert!(output == content, "{} did not round trip", encrypted.display());
+        }
+        
+        let _ = std::fs::remove_file(&temp_file);
+        let _ = std::fs::remove_file(&sequential);
+        let _ = std::fs::remove_file(&parallel);
+        
+        Ok(())
+    }
+
+    #[test]
+    fn test_content_defined_chunking_localizes_insert() -> Result<()> {
+        let content = testutil::random_bytes(2 * 1024 * 1024, &mut testutil::seeded_rng(Some(4)));
+        let mut edited = content.clone();
+        edited.insert(0, 0x42);
+        
+        let cdc = ChunkingStrategy::ContentDefined { min: 2 * 1024, avg: 8 * 1024, max: 32 * 1024 };
+        let before = streaming::chunk_digests(&mut content.as_slice(), cdc)?;
+        let after = streaming::chunk_digests(&mut edited.as_slice(), cdc)?;
+        let changed = after.iter().filter(|digest| !before.contains(digest)).count();
+        assert!(after.len() > 100, "only {} chunks", after.len());
+        assert!(changed <= 3, "{} of {} chunks changed", changed, after.len());
+        
+        // Fixed chunks all shift
+        let fixed = ChunkingStrategy::Fixed(8 * 1024);
+        let before = streaming::chunk_digests(&mut content.as_slice(), fixed)?;
+        let after = streaming::chunk_digests(&mut edited.as_slice(), fixed)?;
+        assert!(after.iter().all(|digest| !before.contains(digest)));
+        
+        // Sequential and parallel content-defined containers both round trip
+        let demo = FileDemo::new()?;
+        let temp_file = std::env::temp_dir().join("cdc_stream_test.bin");
+        let encrypted_file = std::env::temp_dir().join("cdc_stream_test.bin.sealstream");
+        std::fs::write(&temp_file, &edited)?;
+        for workers in [1, 4] {
+            demo.encrypt_file_streaming_with_workers(&temp_file, &encrypted_file, cdc, workers)?;
+            let mut output = Vec::new();
+            demo.decrypt_to_writer(&encrypted_file, &mut output)?;
+            assert!(output == edited, "{} workers did not round trip", workers);
+        }
+        
+        let _ = std::fs::remove_file(&temp_file);
+        let _ = std::fs::remove_file(&encrypted_file);
+        
+        Ok(())
+    }
+
+    #[test]
+    fn test_csv_field_encryption() -> Result<()> {
+        let demo = FileDemo::new()?;
+        let source = std::env::temp_dir().join("csv_fields_test.csv");
+        let encrypted = std::env::temp_dir().join("csv_fields_test.sealed.csv");
+        let decrypted = std::env::temp_dir().join("csv_fields_test.decrypted.csv");
+        let content = "id,secret,note\n1,alpha,\"a, b\"\n2,beta,c\n";
+        std::fs::write(&source, content)?;
+        
+        let sensitive = vec!["secret".to_string()];
+        demo.encrypt_csv(&source, &encrypted, "csv_fields_test", &sensitive)?;
+        
+        let sealed = std::fs::read_to_string(&encrypted)?;
+        assert!(!sealed.contains("alpha") && !sealed.contains("beta"));
+        assert!(sealed.contains("\"a, b\""));
+        
+        demo.decrypt_csv(&encrypted, &decrypted, "csv_fields_test", &sensitive)?;
+        assert_eq!(std::fs::read_to_string(&decrypted)?, content);
+        
+        // The same cell of another table gets its own identity, and a file
+        // only decrypts under the table it was sealed for
+        let other = std::env::temp_dir().join("csv_fields_test.other.csv");
+        demo.encrypt_csv(&source, &other, "other_table", &sensitive)?;
+        let cell_identity = |path: &Path| -> Result<Vec<u8>> {
+            use base64::{prelude::BASE64_STANDARD, Engine};
+            let mut reader = csv::Reader::from_path(path)?;
+            let cell = BASE64_STANDARD.decode(&reader.records().next().unwrap()?[1])?;
+            Ok(bcs::from_bytes::<crypto::EncryptedObject>(&cell)?.id)
+        };
+        assert_eq!(cell_identity(&encrypted)?, file_demo::csv_cell_identity("csv_fields_test", "secret", 0));
+        assert_eq!(cell_identity(&other)?, file_demo::csv_cell_identity("other_table", "secret", 0));
+        assert!(demo.decrypt_csv(&other, &decrypted, "csv_fields_test", &sensitive).is_err());
+        assert!(demo.encrypt_csv(&source, &other, "a:b", &sensitive).is_err());
+        let _ = std::fs::remove_file(&other);
+        
+        // Swapping two ciphertext cells must not silently decrypt
+        let mut rows: Vec<Vec<String>> = sealed
+            .lines()
+            .map(|line| line.split(',').map(str::to_string).collect())
+            .collect();
+        let first = rows[1][1].clone();
+        rows[1][1] = rows[2][1].clone();
+        rows[2][1] = first;
+        let swapped: Vec<String> = rows.iter().map(|row| row.join(",")).collect();
+        std::fs::write(&encrypted, swapped.join("\n"))?;
+        assert!(demo.decrypt_csv(&encrypted, &decrypted, "csv_fields_test", &sensitive).is_err());
+        
+        let _ = std::fs::remove_file(&source);
+        let _ = std::fs::remove_file(&encrypted);
+        let _ = std::fs::remove_file(&decrypted);
+        
+        Ok(())
+    }
+
+    /// Accepts `limit` bytes, then fails every write.
+    struct FailingWriter {
+        written: usize,
+        limit: usize,
+    }
+
+    impl std::io::Write for FailingWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            if self.written >= self.limit {
+                return Err(std::io::Error::new(std::io::ErrorKind::BrokenPipe, "player closed"));
+            }
+            let n = buf.len().min(self.limit - self.written);
+            self.written += n;
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_decrypt_to_writer() -> Result<()> {
+        let demo = FileDemo::new()?;
+        let temp_file = std::env::temp_dir().join("stream_writer_test.bin");
+        let content = testutil::random_bytes(10_000, &mut testutil::seeded_rng(Some(1)));
+        std::fs::write(&temp_file, &content)?;
+        
+        let encrypted_file = std::env::temp_dir().join("stream_writer_test.bin.sealstream");
+        demo.encrypt_file_streaming(&temp_file, &encrypted_file, ChunkingStrategy::Fixed(4096))?;
+        
+        let mut output = Vec::new();
+        let written = demo.decrypt_to_writer(&encrypted_file, &mut output)?;
+        assert_eq!(written, content.len() as u64);
+        assert_eq!(output, content);
+        
+        // A sink that breaks after the first chunk must surface as an error
+        let mut failing = FailingWriter { written: 0, limit: 5000 };
+        let err = demo.decrypt_to_writer(&encrypted_file, &mut failing).unwrap_err();
+        assert!(format!("{:#}", err).contains("player closed"));
+        
+        let _ = std::fs::remove_file(&temp_file);
+        let _ = std::fs::remove_file(&encrypted_file);
+        
         Ok(())
     }
 }
\ No newline at end of file
@@ -0,0 +1,113 @@
+//! Operation counters for `SealSession`, exported in the Prometheus text
+//! exposition format.
+//!
+//! Sessions report to the process-wide `Metrics::global()` unless their
+//! `metrics` field is replaced, so everything a process does shows up in one
+//! scrape. With the `net` feature, `serve` exposes them on `/metrics` over
+//! plain HTTP; batch runs can write them for node_exporter's textfile
+//! collector instead (`bench --metrics-file`).
+
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+
+#[derive(Default)]
+pub struct Metrics {
+    encryptions: AtomicU64,
+    decryptions: AtomicU64,
+    encrypt_failures: AtomicU64,
+    decrypt_failures: AtomicU64,
+    /// Plaintext bytes encrypted (zero for Plain mode, which has no data)
+    bytes_encrypted: AtomicU64,
+    bytes_decrypted: AtomicU64,
+}
+
+#[derive(Clone, Copy)]
+pub enum Operation {
+    Encrypt,
+    Decrypt,
+}
+
+impl Metrics {
+    pub fn global() -> Arc<Metrics> {
+        static GLOBAL: OnceLock<Arc<Metrics>> = OnceLock::new();
+        GLOBAL.get_or_init(Default::default).clone()
+    }
+
+    pub fn record_success(&self, operation: Operation, bytes: usize) {
+        let (count, total) = match operation {
+            Operation::Encrypt => (&self.encryptions, &self.bytes_encrypted),
+            Operation::Decrypt => (&self.decryptions, &self.bytes_decrypted),
+        };
+        count.fetch_add(1, Ordering::Relaxed);
+        total.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_failure(&self, operation: Operation) {
+        match operation {
+            Operation::Encrypt => &self.encrypt_failures,
+            Operation::Decrypt => &self.decrypt_failures,
+        }
+        .fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The counters in the Prometheus text exposition format (version 0.0.4).
+    pub fn prometheus_text(&self) -> String {
+        let families = [
+            ("seal_operations_total", "Successful Seal operations.", &self.encryptions, &self.decryptions),
+            ("seal_failures_total", "Failed Seal operations.", &self.encrypt_failures, &self.decrypt_failures),
+            ("seal_bytes_total", "Plaintext bytes processed.", &self.bytes_encrypted, &self.bytes_decrypted),
+        ];
+
+        let mut text = String::new();
+        for (name, help, encrypt, decrypt) in families {
+            let _ = writeln!(text, "# HELP {} {}", name, help);
+            let _ = writeln!(text, "# TYPE {} counter", name);
+            let _ = writeln!(text, "{}{{operation=\"encrypt\"}} {}", name, encrypt.load(Ordering::Relaxed));
+            let _ = writeln!(text, "{}{{operation=\"decrypt\"}} {}", name, decrypt.load(Ordering::Relaxed));
+        }
+        text
+    }
+}
+
+/// Serve `Metrics::global()` on `0.0.0.0:port` at `/metrics` from a
+/// background thread, for as long as the process runs.
+#[cfg(feature = "net")]
+pub fn serve(port: u16) -> anyhow::Result<()> {
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    let listener = TcpListener::bind(("0.0.0.0", port))?;
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let mut request_line = String::new();
+            let mut reader = BufReader::new(&stream);
+            if reader.read_line(&mut request_line).is_err() {
+                continue;
+            }
+            // Drain the headers so the client sees a clean close
+            let mut header = String::new();
+            while reader.read_line(&mut header).is_ok_and(|n| n > 2) {
+                header.clear();
+            }
+
+            let (status, body) = match request_line.split_whitespace().nth(1) {
+                Some("/metrics") => ("200 OK", Metrics::global().prometheus_text()),
+                _ => ("404 Not Found", "Not found, try /metrics\n".to_string()),
+            };
+            let _ = write!(
+                &stream,
+                "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+        }
+    });
+    Ok(())
+}
+
+#[cfg(not(feature = "net"))]
+pub fn serve(_port: u16) -> anyhow::Result<()> {
+    anyhow::bail!("Serving metrics needs the `net` feature: cargo run --features net -- --metrics-port <PORT> ...")
+}
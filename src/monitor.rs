@@ -0,0 +1,151 @@
+//! Scheduled fleet integrity check (`monitor`).
+//!
+//! Picks a random sample of the encrypted files under a directory and
+//! decrypts each with the current fleet, discarding the plaintext, so a cron
+//! job notices when a key rotation or a lost key file has stranded old
+//! ciphertexts. Files ending in `.seal` (containers, including legacy and
+//! `encrypt_directory` files) and `.sealstream` (streaming containers) are
+//! sampled; the directory manifest and sidecar files are not.
+
+use anyhow::Result;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+
+use crate::container::{self, SealContainer};
+use crate::file_demo::{self, DIRECTORY_MANIFEST};
+use crate::session::SealSession;
+use crate::streaming;
+
+/// Failures named in the summary line before it just counts the rest.
+const FAILURES_SHOWN: usize = 3;
+
+pub struct MonitorReport {
+    pub dir: PathBuf,
+    /// Encrypted files found under `dir`.
+    pub total: usize,
+    pub checked: usize,
+    pub failures: Vec<(PathBuf, String)>,
+    pub fleet: String,
+}
+
+impl MonitorReport {
+    /// One line for cron mail and log-based alerting.
+    pub fn summary(&self) -> String {
+        let scope = format!("{} encrypted files under {}, fleet {}", self.total, self.dir.display(), self.fleet);
+        if self.failures.is_empty() {
+            return format!("seal-monitor OK: all {} sampled files decrypt ({})", self.checked, scope);
+        }
+        let mut shown: Vec<String> = self
+            .failures
+            .iter()
+            .take(FAILURES_SHOWN)
+            .map(|(path, reason)| format!("{} ({})", path.display(), reason))
+            .collect();
+        if self.failures.len() > FAILURES_SHOWN {
+            shown.push(format!("and {} more", self.failures.len() - FAILURES_SHOWN));
+        }
+        format!(
+            "seal-monitor FAILED: {} of {} sampled files do not decrypt ({}): {}",
+            self.failures.len(),
+            self.checked,
+            scope,
+            shown.join("; ")
+        )
+    }
+}
+
+/// Decrypt up to `sample` randomly chosen encrypted files under `dir` with
+/// `session`. Fails only if there is nothing to sample; undecryptable files
+/// are reported in the result.
+pub fn check_sample(session: &SealSession, dir: &Path, sample: usize, rng: &mut impl Rng) -> Result<MonitorReport> {
+    let files: Vec<PathBuf> = file_demo::files_under(dir)?
+        .into_iter()
+        .map(|(path, _)| path)
+        .filter(|path| {
+            let sampled = path.extension().is_some_and(|ext| ext == "seal" || ext == "sealstream");
+            sampled && path.file_name().is_some_and(|name| name != DIRECTORY_MANIFEST)
+        })
+        .collect();
+    if files.is_empty() {
+        anyhow::bail!("No encrypted files (.seal, .sealstream) under {}", dir.display());
+    }
+
+    let mut report = MonitorReport {
+        dir: dir.to_path_buf(),
+        total: files.len(),
+        checked: 0,
+        failures: Vec::new(),
+        fleet: container::format_fingerprint(&session.fingerprint()),
+    };
+    for path in files.choose_multiple(rng, sample.max(1)) {
+        report.checked += 1;
+        if let Err(e) = check_file(session, path) {
+            report.failures.push((path.clone(), format!("{:#}", e)));
+        }
+    }
+    Ok(report)
+}
+
+fn check_file(session: &SealSession, path: &Path) -> Result<()> {
+    let mut reader = BufReader::new(std::fs::File::open(path)?);
+    let mut magic = [0u8; 4];
+    if reader.read_exact(&mut magic).is_ok() && &magic == streaming::STREAM_MAGIC {
+        let mut reader = BufReader::new(std::fs::File::open(path)?);
+        session.decrypt_stream(&mut reader, &mut std::io::sink())?;
+        return Ok(());
+    }
+    let container = SealContainer::from_bytes(&std::fs::read(path)?)?;
+    container.decrypt(|encrypted_object| session.decrypt(encrypted_object))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil;
+    use crypto::EncryptionInput;
+
+    #[test]
+    fn test_sample_reports_undecryptable_files() -> Result<()> {
+        let session = SealSession::generate(3, 2)?;
+        let dir = std::env::temp_dir().join("monitor_test");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("nested"))?;
+
+        for name in ["a.seal", "nested/b.seal"] {
+            let (encrypted_object, _) =
+                session.encrypt(name.as_bytes(), EncryptionInput::Aes256Gcm { data: b"payload".to_vec(), aad: None })?;
+            let container = SealContainer::for_fleet(encrypted_object, &session.key_servers, &session.public_keys);
+            std::fs::write(dir.join(name), container.to_bytes()?)?;
+        }
+        let mut stream = Vec::new();
+        session.encrypt_stream(b"c", &mut &b"streamed payload"[..], &mut stream, 4)?;
+        std::fs::write(dir.join("c.sealstream"), &stream)?;
+        std::fs::write(dir.join("a.seal.sig"), "not sampled")?;
+        std::fs::write(dir.join(DIRECTORY_MANIFEST), "not sampled either")?;
+
+        let mut rng = testutil::seeded_rng(Some(1));
+        let report = check_sample(&session, &dir, 100, &mut rng)?;
+        assert_eq!((report.total, report.checked), (3, 3));
+        let summary = report.summary();
+        assert!(summary.starts_with("seal-monitor OK: all 3 sampled files decrypt (3 encrypted files under"), "{}", summary);
+        assert_eq!(check_sample(&session, &dir, 2, &mut rng)?.checked, 2);
+
+        // A truncated stream and a rotated fleet both show up
+        std::fs::write(dir.join("c.sealstream"), &stream[..stream.len() - 5])?;
+        let report = check_sample(&session, &dir, 100, &mut rng)?;
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(report.failures[0].0, dir.join("c.sealstream"));
+        let summary = report.summary();
+        assert!(summary.starts_with("seal-monitor FAILED: 1 of 3 sampled files do not decrypt"), "{}", summary);
+        assert!(!summary.contains('\n'));
+        let rotated = check_sample(&SealSession::generate(3, 2)?, &dir, 100, &mut rng)?;
+        assert_eq!(rotated.failures.len(), 3);
+
+        assert!(check_sample(&session, &dir.join("nested/none"), 100, &mut rng).is_err());
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,226 @@
+//! Plain-ASCII rendering of the demo output.
+//!
+//! Output is decorated with emoji and box-drawing characters, which come out
+//! as mojibake on terminals without UTF-8 or an emoji font. With
+//! `--no-emoji`, or when the locale isn't UTF-8, every line goes through
+//! `render`, which swaps status markers for ASCII ones (`✅` becomes `[OK]`,
+//! `❌` becomes `[FAIL]`, `→` becomes `->`) and drops purely decorative emoji.
+//!
+//! Demo output is printed with `outln!` and `out!`, imported from here
+//! (`use crate::output::{out, outln};`), which format like `println!` and
+//! `print!` and hand the text to `emit`. Commands that write data to stdout
+//! send everything printed this way to stderr instead (`set_to_stderr`),
+//! and the TUI collects it for its output pane instead (`start_capture`).
+//! Plain `println!` is left alone for anything that must reach stdout as is.
+
+use std::borrow::Cow;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+static PLAIN: AtomicBool = AtomicBool::new(false);
+static TO_STDERR: AtomicBool = AtomicBool::new(false);
+static CAPTURED: Mutex<Option<String>> = Mutex::new(None);
+
+/// `println!` for demo output, see `emit`.
+macro_rules! outln {
+    () => {
+        $crate::output::emit("", true)
+    };
+    ($($arg:tt)*) => {
+        $crate::output::emit(&std::format!($($arg)*), true)
+    };
+}
+
+/// `print!` for demo output, see `emit`.
+macro_rules! out {
+    ($($arg:tt)*) => {
+        $crate::output::emit(&std::format!($($arg)*), false)
+    };
+}
+
+pub(crate) use out;
+pub(crate) use outln;
+
+/// Write `text` (plus a newline for `outln!`) rendered by `render`: to the
+/// capture if one is running, else to stderr or stdout (see `set_to_stderr`).
+pub fn emit(text: &str, newline: bool) {
+    if capture(text, newline) {
+        return;
+    }
+    let text = render(text);
+    match (to_stderr(), newline) {
+        (true, true) => eprintln!("{}", text),
+        (true, false) => eprint!("{}", text),
+        (false, true) => println!("{}", text),
+        (false, false) => print!("{}", text),
+    }
+}
+
+/// Switch ASCII rendering on or off for the rest of the process.
+pub fn set_plain(plain: bool) {
+    PLAIN.store(plain, Ordering::Relaxed);
+}
+
+pub fn plain() -> bool {
+    PLAIN.load(Ordering::Relaxed)
+}
+
+/// Print to stderr for the rest of the process, keeping stdout for data.
+pub fn set_to_stderr(to_stderr: bool) {
+    TO_STDERR.store(to_stderr, Ordering::Relaxed);
+}
+
+pub fn to_stderr() -> bool {
+    TO_STDERR.load(Ordering::Relaxed)
+}
+
+/// Collect everything printed from now on, from any thread, instead of
+/// writing it out, until `stop_capture`.
+#[cfg(any(feature = "tui", test))]
+pub fn start_capture() {
+    *CAPTURED.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(String::new());
+}
+
+/// Stop capturing and return what was printed since `start_capture`.
+#[cfg(any(feature = "tui", test))]
+pub fn stop_capture() -> String {
+    CAPTURED.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).take().unwrap_or_default()
+}
+
+/// What has been captured so far, without stopping.
+#[cfg(any(feature = "tui", test))]
+pub fn captured() -> String {
+    CAPTURED.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clone().unwrap_or_default()
+}
+
+/// Append `text` (rendered, plus a newline for `println!`) to the capture,
+/// if one is running. Returns whether it was captured.
+pub fn capture(text: &str, newline: bool) -> bool {
+    let mut captured = CAPTURED.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let Some(captured) = captured.as_mut() else {
+        return false;
+    };
+    captured.push_str(&render(text));
+    if newline {
+        captured.push('\n');
+    }
+    true
+}
+
+/// Whether the terminal can be expected to show emoji. On Unix this follows
+/// the locale: the first of `LC_ALL`, `LC_CTYPE` and `LANG` that is set
+/// decides, as it does for `setlocale`, and none at all means the ASCII-only
+/// C locale. Other platforms are assumed to cope.
+pub fn terminal_supports_emoji() -> bool {
+    !cfg!(unix) || locale_is_utf8(|name| std::env::var(name).ok())
+}
+
+fn locale_is_utf8(lookup: impl Fn(&str) -> Option<String>) -> bool {
+    ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .find_map(|name| lookup(name).filter(|value| !value.is_empty()))
+        .is_some_and(|locale| {
+            let locale = locale.to_ascii_lowercase();
+            locale.contains("utf-8") || locale.contains("utf8")
+        })
+}
+
+/// `text` as it should be printed: unchanged, or in plain ASCII with
+/// `--no-emoji`.
+pub fn render(text: &str) -> Cow<'_, str> {
+    if plain() {
+        Cow::Owned(to_ascii(text))
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+/// The ASCII stand-in for a status marker or drawing character.
+fn marker(ch: char) -> Option<&'static str> {
+    Some(match ch {
+        '✅' | '✔' | '🟢' => "[OK]",
+        '❌' | '🔴' => "[FAIL]",
+        '⚠' => "[WARN]",
+        'ℹ' => "[INFO]",
+        '⚪' => "[-]",
+        '→' => "->",
+        '←' => "<-",
+        '↑' | '⬆' => "^",
+        '↓' => "v",
+        '▶' => ">",
+        '➕' => "+",
+        '×' => "x",
+        '…' => "...",
+        '━' => "=",
+        '─' => "-",
+        '│' | '├' => "|",
+        '└' => "`",
+        _ => return None,
+    })
+}
+
+/// Emoji and pictographs with no ASCII meaning worth keeping.
+fn is_decoration(ch: char) -> bool {
+    matches!(ch as u32, 0x1F000..=0x1FAFF | 0x2300..=0x23FF | 0x2600..=0x27BF | 0x2B00..=0x2BFF)
+}
+
+fn to_ascii(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if let Some(marker) = marker(ch) {
+            out.push_str(marker);
+        } else if is_decoration(ch) {
+            // Don't leave a double space where the emoji was
+            if out.is_empty() || out.ends_with(char::is_whitespace) {
+                while chars.next_if(|&next| next == '\u{fe0f}' || next == '\u{20e3}').is_some() {}
+                chars.next_if_eq(&' ');
+            }
+        } else if ch != '\u{fe0f}' && ch != '\u{20e3}' {
+            // Variation selectors and keycaps only modify the emoji before them
+            out.push(ch);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ascii_rendering() {
+        assert_eq!(to_ascii("   ✅ Decrypted to: out.txt"), "   [OK] Decrypted to: out.txt");
+        assert_eq!(to_ascii("❌ Threshold not met"), "[FAIL] Threshold not met");
+        assert_eq!(to_ascii("   ⚠️  Share verification disabled"), "   [WARN]  Share verification disabled");
+        assert_eq!(to_ascii("\n🚀 === Basic Demo ==="), "\n=== Basic Demo ===");
+        assert_eq!(to_ascii("   🏷️  Fleet fingerprint: ab12"), "    Fleet fingerprint: ab12");
+        assert_eq!(to_ascii("━━━ FILE OPERATIONS ━━━"), "=== FILE OPERATIONS ===");
+        assert_eq!(to_ascii("│   ├── master_key → 0x01"), "|   |-- master_key -> 0x01");
+        assert_eq!(to_ascii("identity: café"), "identity: café");
+        assert!(to_ascii("🔒🔑📦 all decoration").is_ascii());
+
+        let locale = |vars: &'static [(&'static str, &'static str)]| {
+            locale_is_utf8(|name| vars.iter().find(|(var, _)| *var == name).map(|(_, value)| value.to_string()))
+        };
+        assert!(locale(&[("LANG", "en_US.UTF-8")]));
+        assert!(locale(&[("LC_ALL", "C.utf8"), ("LANG", "C")]));
+        assert!(!locale(&[("LC_ALL", "C"), ("LANG", "en_US.UTF-8")]));
+        assert!(locale(&[("LC_ALL", ""), ("LANG", "en_US.UTF-8")]));
+        assert!(!locale(&[("LANG", "en_US.ISO-8859-1")]));
+        assert!(!locale(&[]));
+    }
+
+    #[test]
+    fn test_capture_collects_printed_lines() {
+        start_capture();
+        outln!("✅ captured");
+        out!("partial ");
+        std::thread::spawn(|| outln!("from a worker")).join().unwrap();
+        assert!(captured().contains("captured\n"));
+        let output = stop_capture();
+        // Other tests may print meanwhile, so only look for these lines
+        assert!(output.contains("from a worker\n"), "{}", output);
+        assert!(output.contains("partial "), "{}", output);
+    }
+}
@@ -0,0 +1,192 @@
+//! Many small encrypted files in one indexed file (`pack`, `unpack`).
+//!
+//! Layout, with frames as in `streaming::write_frame`:
+//!
+//! ```text
+//! "SLPK" | frame(file)* | frame(bcs(PackEntry))* | u64 LE index offset | u64 LE entries | "SLPK"
+//! ```
+//!
+//! Files are copied byte for byte, so no keys are involved and nothing is
+//! decrypted or checked; the objects authenticate themselves when they are
+//! eventually decrypted. The trailing index maps each file's path to its
+//! frame, and since every file is framed the pack can still be walked if the
+//! index is lost. Unpacking reads one index entry and one file at a time.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path};
+
+use crate::atomic;
+use crate::file_demo;
+use crate::streaming;
+
+pub const PACK_MAGIC: &[u8; 4] = b"SLPK";
+
+const TRAILER_LEN: u64 = 8 + 8 + 4;
+/// Largest index entry `unpack` reads: a long path plus offset and length.
+const MAX_INDEX_ENTRY: u32 = 64 * 1024;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct PackEntry {
+    /// Path relative to the packed directory, `/`-separated.
+    path: String,
+    /// Where the file's frame starts.
+    offset: u64,
+    length: u32,
+}
+
+/// Pack every file under `dir` into `out`. Returns the number of files.
+pub fn pack(dir: &Path, out: &Path) -> Result<usize> {
+    let files = file_demo::files_under(dir)?;
+    let mut packed = 0;
+    atomic::write_atomically(out, |writer| {
+        let entries = files.iter().map(|(path, _)| {
+            let relative = path.strip_prefix(dir)?;
+            let components: Option<Vec<&str>> = relative.components().map(|component| component.as_os_str().to_str()).collect();
+            let name = components
+                .ok_or_else(|| anyhow::anyhow!("{} is not valid UTF-8", path.display()))?
+                .join("/");
+            Ok((name, fs::read(path)?))
+        });
+        packed = write_pack(entries, writer)?;
+        Ok(())
+    })?;
+    Ok(packed)
+}
+
+/// Write `(path, contents)` pairs as a pack, holding one file at a time.
+fn write_pack(entries: impl Iterator<Item = Result<(String, Vec<u8>)>>, writer: &mut impl Write) -> Result<usize> {
+    writer.write_all(PACK_MAGIC)?;
+    let mut offset = PACK_MAGIC.len() as u64;
+    let mut index = Vec::new();
+    for entry in entries {
+        let (path, contents) = entry?;
+        let length = u32::try_from(contents.len())
+            .map_err(|_| anyhow::anyhow!("{} is too large to pack ({} bytes)", path, contents.len()))?;
+        streaming::write_frame(writer, &contents)?;
+        index.push(PackEntry { path, offset, length });
+        offset += 4 + length as u64;
+    }
+
+    let index_offset = offset;
+    for entry in &index {
+        streaming::write_frame(writer, &bcs::to_bytes(entry)?)?;
+    }
+    writer.write_all(&index_offset.to_le_bytes())?;
+    writer.write_all(&(index.len() as u64).to_le_bytes())?;
+    writer.write_all(PACK_MAGIC)?;
+    writer.flush()?;
+    Ok(index.len())
+}
+
+/// Restore every file in the pack at `input` under `out`. Returns the number
+/// of files.
+pub fn unpack(input: &Path, out: &Path) -> Result<usize> {
+    let mut data = BufReader::new(File::open(input).with_context(|| format!("Failed to open {}", input.display()))?);
+    let (index_offset, count) = read_trailer(&mut data)?;
+
+    let mut index = BufReader::new(File::open(input)?);
+    index.seek(SeekFrom::Start(index_offset))?;
+    for n in 0..count {
+        let frame = streaming::read_frame(&mut index, MAX_INDEX_ENTRY)?
+            .ok_or_else(|| anyhow::anyhow!("Pack index ends after {} of {} entries", n, count))?;
+        let entry: PackEntry = bcs::from_bytes(&frame).context("Corrupt pack index")?;
+        let relative = Path::new(&entry.path);
+        if !relative.components().all(|component| matches!(component, Component::Normal(_))) {
+            anyhow::bail!("Pack path \"{}\" escapes the output directory", entry.path);
+        }
+        if entry.offset < PACK_MAGIC.len() as u64 || entry.offset + 4 + entry.length as u64 > index_offset {
+            anyhow::bail!("Pack entry \"{}\" points outside the packed files", entry.path);
+        }
+
+        data.seek(SeekFrom::Start(entry.offset))?;
+        let contents = streaming::read_frame(&mut data, entry.length)?
+            .filter(|contents| contents.len() == entry.length as usize)
+            .ok_or_else(|| anyhow::anyhow!("Pack entry \"{}\" doesn't match its frame", entry.path))?;
+
+        let output = out.join(relative);
+        if let Some(parent) = output.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        atomic::write_file(&output, &contents)?;
+    }
+    if index.stream_position()? != data.seek(SeekFrom::End(0))? - TRAILER_LEN {
+        anyhow::bail!("Pack index is longer than its {} entries", count);
+    }
+    Ok(count as usize)
+}
+
+/// Check both magics and return the index offset and entry count.
+fn read_trailer<R: Read + Seek>(reader: &mut R) -> Result<(u64, u64)> {
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic).context("File is too short to be a pack")?;
+    let len = reader.seek(SeekFrom::End(0))?;
+    if &magic != PACK_MAGIC || len < PACK_MAGIC.len() as u64 + TRAILER_LEN {
+        anyhow::bail!("Not a pack (bad magic)");
+    }
+
+    let mut trailer = [0u8; TRAILER_LEN as usize];
+    reader.seek(SeekFrom::End(-(TRAILER_LEN as i64)))?;
+    reader.read_exact(&mut trailer)?;
+    let (offset, rest) = trailer.split_at(8);
+    let (count, magic) = rest.split_at(8);
+    if magic != PACK_MAGIC {
+        anyhow::bail!("Truncated pack: trailer missing");
+    }
+    let index_offset = u64::from_le_bytes(offset.try_into()?);
+    if index_offset < PACK_MAGIC.len() as u64 || index_offset > len - TRAILER_LEN {
+        anyhow::bail!("Corrupt pack: index offset {} is outside the file", index_offset);
+    }
+    Ok((index_offset, u64::from_le_bytes(count.try_into()?)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil;
+
+    #[test]
+    fn test_pack_round_trip() -> Result<()> {
+        let base = std::env::temp_dir().join("pack_test");
+        let _ = fs::remove_dir_all(&base);
+        let (dir, pack_path, restored) = (base.join("in"), base.join("all.sealpack"), base.join("out"));
+        let mut rng = testutil::seeded_rng(Some(4));
+        for i in 0..50 {
+            let path = dir.join(format!("{}/{:03}.seal", i % 4, i));
+            fs::create_dir_all(path.parent().unwrap())?;
+            fs::write(&path, testutil::random_bytes(i * 7, &mut rng))?;
+        }
+
+        assert_eq!(pack(&dir, &pack_path)?, 50);
+        assert_eq!(unpack(&pack_path, &restored)?, 50);
+        let original = file_demo::files_under(&dir)?;
+        assert_eq!(original.len(), file_demo::files_under(&restored)?.len());
+        for (path, _) in &original {
+            assert_eq!(fs::read(path)?, fs::read(restored.join(path.strip_prefix(&dir)?))?);
+        }
+
+        // Damage to either end is caught before anything is written
+        let packed = fs::read(&pack_path)?;
+        fs::write(&pack_path, &packed[..packed.len() - 1])?;
+        assert!(unpack(&pack_path, &base.join("truncated")).is_err());
+        let mut moved = packed.clone();
+        let at = moved.len() - TRAILER_LEN as usize;
+        moved[at..at + 8].copy_from_slice(&(packed.len() as u64).to_le_bytes());
+        fs::write(&pack_path, &moved)?;
+        assert!(unpack(&pack_path, &base.join("moved")).is_err());
+        assert!(!base.join("truncated").exists() && !base.join("moved").exists());
+
+        // A crafted path can't climb out of the output directory
+        let mut crafted = Vec::new();
+        write_pack([Ok(("../escaped.seal".to_string(), b"x".to_vec()))].into_iter(), &mut crafted)?;
+        fs::write(&pack_path, &crafted)?;
+        let err = unpack(&pack_path, &base.join("crafted")).unwrap_err();
+        assert!(err.to_string().contains("escapes the output directory"));
+        assert!(!base.join("escaped.seal").exists());
+
+        fs::remove_dir_all(&base)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,125 @@
+//! Padding plaintext into size classes, so ciphertext length no longer
+//! gives away the exact plaintext length.
+//!
+//! The padded plaintext is `true length (u64 LE) | data | zeros`, rounded up
+//! to the bucket. It is encrypted as a whole, so the length is authenticated
+//! along with the data, and the scheme used is recorded in the AAD
+//! (`AadContext::padding`) for decryption to strip it.
+//!
+//! Only the size class leaks: every message up to `MIN_BUCKET` bytes looks
+//! the same, and larger ones reveal their length to within a factor of two
+//! (or a multiple of the bucket).
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+/// Smallest size class, so short messages all look alike.
+pub const MIN_BUCKET: usize = 1024;
+
+/// Largest bucket `--bucket-size` accepts; every file would be padded to at
+/// least this much.
+pub const MAX_BUCKET: usize = 1 << 30;
+
+/// Bytes of the length prefix.
+const LENGTH_PREFIX: usize = 8;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Padding {
+    /// Round up to the next power of two, at least `MIN_BUCKET`.
+    PowerOfTwo,
+    /// Round up to a multiple of this many bytes.
+    Multiple(usize),
+}
+
+impl Padding {
+    /// Padded length for `len` bytes of data, including the length prefix.
+    fn bucket_for(&self, len: usize) -> Result<usize> {
+        let too_large = || anyhow::anyhow!("{} bytes is too large to pad", len);
+        let needed = len.checked_add(LENGTH_PREFIX).ok_or_else(too_large)?;
+        match *self {
+            Padding::PowerOfTwo => needed
+                .max(MIN_BUCKET)
+                .checked_next_power_of_two()
+                .ok_or_else(too_large),
+            Padding::Multiple(0) => anyhow::bail!("Bucket size must be positive"),
+            Padding::Multiple(bucket) => needed.div_ceil(bucket).checked_mul(bucket).ok_or_else(too_large),
+        }
+    }
+
+    pub fn pad(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let bucket = self.bucket_for(data.len())?;
+        let mut padded = Vec::with_capacity(bucket);
+        padded.extend_from_slice(&(data.len() as u64).to_le_bytes());
+        padded.extend_from_slice(data);
+        padded.resize(bucket, 0);
+        Ok(padded)
+    }
+}
+
+/// For `--bucket-size`: a positive byte count up to `MAX_BUCKET`.
+pub fn parse_bucket_size(text: &str) -> Result<usize> {
+    let bucket: usize = text.trim().parse().map_err(|_| anyhow::anyhow!("Invalid bucket size \"{}\"", text))?;
+    if bucket == 0 || bucket > MAX_BUCKET {
+        anyhow::bail!("Bucket size must be between 1 and {} bytes, got {}", MAX_BUCKET, bucket);
+    }
+    Ok(bucket)
+}
+
+/// The data `Padding::pad` padded, whichever bucket it used.
+pub fn unpad(mut padded: Vec<u8>) -> Result<Vec<u8>> {
+    let prefix: [u8; LENGTH_PREFIX] = padded
+        .get(..LENGTH_PREFIX)
+        .and_then(|prefix| prefix.try_into().ok())
+        .ok_or_else(|| anyhow::anyhow!("Padded plaintext is shorter than its length prefix"))?;
+    let len = u64::from_le_bytes(prefix);
+    if len > (padded.len() - LENGTH_PREFIX) as u64 {
+        anyhow::bail!("Padding records {} bytes but only {} follow", len, padded.len() - LENGTH_PREFIX);
+    }
+    padded.truncate(LENGTH_PREFIX + len as usize);
+    padded.drain(..LENGTH_PREFIX);
+    Ok(padded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aad::AadContext;
+    use crate::session::SealSession;
+    use crypto::EncryptionInput;
+
+    #[test]
+    fn test_same_bucket_same_ciphertext_length() -> Result<()> {
+        let session = SealSession::generate(3, 2)?;
+        let context = AadContext { padding: Some(Padding::PowerOfTwo), ..Default::default() };
+        let encrypted_len = |message: &[u8]| -> Result<usize> {
+            let (encrypted_object, _) = session.encrypt(
+                b"file:note.txt",
+                EncryptionInput::Aes256Gcm { data: Padding::PowerOfTwo.pad(message)?, aad: context.to_aad()? },
+            )?;
+            assert_eq!(unpad(session.decrypt(&encrypted_object)?)?, message);
+            Ok(bcs::to_bytes(&encrypted_object)?.len())
+        };
+
+        assert_eq!(encrypted_len(&[b'a'; 10])?, encrypted_len(&[b'b'; 500])?);
+        assert_ne!(encrypted_len(&[b'a'; 10])?, encrypted_len(&[b'c'; 2000])?);
+
+        assert_eq!(Padding::PowerOfTwo.pad(&[7; 1017])?.len(), 2048);
+        assert_eq!(Padding::Multiple(100).pad(&[7; 92])?.len(), 100);
+        assert_eq!(unpad(Padding::Multiple(100).pad(&[7; 93])?)?, [7; 93]);
+        assert_eq!(unpad(Padding::PowerOfTwo.pad(b"")?)?, b"");
+        assert!(Padding::Multiple(0).pad(b"x").is_err());
+        assert!(Padding::Multiple(usize::MAX / 2 + 1).bucket_for(usize::MAX / 2).is_err());
+        assert!(Padding::PowerOfTwo.bucket_for(usize::MAX - 4).is_err());
+
+        assert_eq!(parse_bucket_size("4096")?, 4096);
+        for rejected in ["0", "-1", "lots", &(MAX_BUCKET + 1).to_string()] {
+            assert!(parse_bucket_size(rejected).is_err(), "{}", rejected);
+        }
+
+        let mut overlong = Padding::PowerOfTwo.pad(b"short")?;
+        overlong[..LENGTH_PREFIX].copy_from_slice(&5000u64.to_le_bytes());
+        assert!(unpad(overlong).is_err());
+        Ok(())
+    }
+}
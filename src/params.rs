@@ -0,0 +1,100 @@
+//! `seal-demo params`: the curve, element sizes and modes this build uses,
+//! for compliance records.
+//!
+//! Sizes come from fastcrypto's own constants and the dependency specs from
+//! the `Cargo.toml` the binary was built with, so the output can't drift
+//! from what actually runs. `Cargo.lock` isn't committed, so a `crypto`
+//! dependency without a pinned rev is reported as the branch it tracks.
+
+use crypto::EncryptionInput;
+use fastcrypto::groups::bls12381::{
+    G1_ELEMENT_BYTE_LENGTH, G2_ELEMENT_BYTE_LENGTH, GT_ELEMENT_BYTE_LENGTH, SCALAR_LENGTH,
+};
+
+use crate::output::outln;
+
+const CARGO_TOML: &str = include_str!("../Cargo.toml");
+
+/// Describe an encryption mode. The match is exhaustive so a new mode in
+/// `crypto` fails to compile here instead of going unreported.
+fn describe_mode(input: &EncryptionInput) -> (&'static str, &'static str) {
+    match input {
+        EncryptionInput::Aes256Gcm { .. } => ("Aes256Gcm", "AES-256-GCM, authenticated, optional AAD"),
+        EncryptionInput::Hmac256Ctr { .. } => ("Hmac256Ctr", "HMAC-SHA3-256 in counter mode with an HMAC tag, optional AAD"),
+        EncryptionInput::Plain => ("Plain", "no payload, the derived 32-byte key is returned to the caller"),
+    }
+}
+
+/// How `name` is declared under `[dependencies]`: its version, or its git
+/// repository and rev (or default branch).
+fn dependency_spec(manifest: &str, name: &str) -> Option<String> {
+    let manifest: toml::Table = manifest.parse().ok()?;
+    let spec = manifest.get("dependencies")?.get(name)?;
+    if let Some(version) = spec.as_str() {
+        return Some(version.to_string());
+    }
+
+    let field = |key: &str| spec.get(key).and_then(|value| value.as_str());
+    match (field("git"), field("version"), field("path")) {
+        (Some(git), _, _) => Some(format!(
+            "{} @ {}",
+            git,
+            field("rev").or(field("tag")).or(field("branch")).unwrap_or("default branch")
+        )),
+        (None, Some(version), _) => Some(version.to_string()),
+        (None, None, Some(path)) => Some(format!("path {}", path)),
+        (None, None, None) => None,
+    }
+}
+
+pub fn print_params() {
+    outln!("📐 Cryptographic parameters");
+    outln!("   Curve: BLS12-381");
+    outln!("   IBE scheme: Boneh-Franklin (threshold, one share per key server)");
+    outln!("   G1 element (user secret keys): {} bytes compressed", G1_ELEMENT_BYTE_LENGTH);
+    outln!("   G2 element (public keys): {} bytes compressed", G2_ELEMENT_BYTE_LENGTH);
+    outln!("   GT element: {} bytes", GT_ELEMENT_BYTE_LENGTH);
+    outln!("   Scalar (master keys): {} bytes", SCALAR_LENGTH);
+
+    outln!("\n🔐 Encryption modes");
+    let modes = [
+        EncryptionInput::Aes256Gcm { data: Vec::new(), aad: None },
+        EncryptionInput::Hmac256Ctr { data: Vec::new(), aad: None },
+        EncryptionInput::Plain,
+    ];
+    for mode in &modes {
+        let (name, description) = describe_mode(mode);
+        outln!("   {}: {}", name, description);
+    }
+
+    outln!("\n📦 Libraries");
+    outln!("   seal-demo {}", env!("CARGO_PKG_VERSION"));
+    for name in ["crypto", "fastcrypto"] {
+        match dependency_spec(CARGO_TOML, name) {
+            Some(spec) => outln!("   {}: {}", name, spec),
+            None => outln!("   {}: not declared in Cargo.toml", name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dependency_spec() {
+        let manifest = r#"
+            [dependencies]
+            anyhow = "1.0"
+            crypto = { git = "https://example.com/seal.git", package = "crypto" }
+            fastcrypto = { git = "https://example.com/fastcrypto", rev = "abc123", features = ["aes"] }
+            bcs = { version = "0.1", default-features = false }
+        "#;
+        assert_eq!(dependency_spec(manifest, "anyhow").as_deref(), Some("1.0"));
+        assert_eq!(dependency_spec(manifest, "crypto").as_deref(), Some("https://example.com/seal.git @ default branch"));
+        assert_eq!(dependency_spec(manifest, "fastcrypto").as_deref(), Some("https://example.com/fastcrypto @ abc123"));
+        assert_eq!(dependency_spec(manifest, "bcs").as_deref(), Some("0.1"));
+        assert_eq!(dependency_spec(manifest, "serde"), None);
+        assert!(dependency_spec(CARGO_TOML, "fastcrypto").is_some());
+    }
+}
@@ -0,0 +1,56 @@
+//! Access policies built from identities with AND and OR.
+//!
+//! Seal itself encrypts to one identity at a time; combinations are built
+//! from several `seal_encrypt` calls:
+//!
+//! - `And` nests: the data is encrypted under the first policy, the result
+//!   under the second, and so on, so every branch has to be opened in turn.
+//! - `Or` encrypts the same inner value once per branch, so any one branch
+//!   opens it. Each branch carries a full copy, so ciphertexts grow with the
+//!   number of alternatives.
+//!
+//! The result is self-describing (`PolicyCiphertext`), so decryption needs
+//! only the identities the caller can get keys for, not the policy.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Clone, Debug)]
+pub enum Policy {
+    Id(Vec<u8>),
+    And(Vec<Policy>),
+    Or(Vec<Policy>),
+}
+
+impl Policy {
+    pub fn id(identity: &str) -> Self {
+        Policy::Id(identity.as_bytes().to_vec())
+    }
+}
+
+impl fmt::Display for Policy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (branches, separator) = match self {
+            Policy::Id(identity) => return write!(f, "{}", String::from_utf8_lossy(identity)),
+            Policy::And(branches) => (branches, " AND "),
+            Policy::Or(branches) => (branches, " OR "),
+        };
+        write!(f, "(")?;
+        for (i, branch) in branches.iter().enumerate() {
+            if i > 0 {
+                write!(f, "{}", separator)?;
+            }
+            write!(f, "{}", branch)?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// One node of a policy-encrypted value. An `Id` node's plaintext is the
+/// BCS of the node it wraps.
+#[derive(Clone, Serialize, Deserialize)]
+pub enum PolicyCiphertext {
+    Data(Vec<u8>),
+    Id(Box<crypto::EncryptedObject>),
+    Or(Vec<PolicyCiphertext>),
+}
@@ -0,0 +1,126 @@
+//! Signed, timestamped envelopes for long-term archival.
+//!
+//! Unlike the container header, which only describes the fleet, an envelope
+//! records who produced an encrypted object, when, and with which build of
+//! the tool, under a BLS signature (see `signing`) covering all three:
+//!
+//! ```text
+//! SEALPROV | bcs(ProvenanceEnvelope)
+//! ```
+//!
+//! The signature is checked before anything else is trusted, including the
+//! timestamp and version that `open` reports.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use crypto::EncryptedObject;
+use fastcrypto::groups::bls12381::{G1Element, G2Element, Scalar};
+use fastcrypto::serde_helpers::ToFromByteArray;
+use serde::{Deserialize, Serialize};
+
+use crate::signing;
+
+pub const PROVENANCE_MAGIC: &[u8; 8] = b"SEALPROV";
+
+pub const TOOL_VERSION: &str = concat!("seal-demo ", env!("CARGO_PKG_VERSION"));
+
+#[derive(Serialize, Deserialize)]
+pub struct ProvenanceEnvelope {
+    /// Unix seconds at creation.
+    pub created_at: i64,
+    pub tool_version: String,
+    pub encrypted_object: EncryptedObject,
+    /// BLS signature (G1, compressed) over the fields above.
+    signature: Vec<u8>,
+}
+
+fn signed_bytes(created_at: i64, tool_version: &str, encrypted_object: &EncryptedObject) -> Result<Vec<u8>> {
+    Ok(bcs::to_bytes(&(created_at, tool_version, encrypted_object))?)
+}
+
+impl ProvenanceEnvelope {
+    pub fn create(encrypted_object: EncryptedObject, sign_key: &Scalar, now: DateTime<Utc>) -> Result<Self> {
+        let created_at = now.timestamp();
+        let signature = signing::sign(sign_key, &signed_bytes(created_at, TOOL_VERSION, &encrypted_object)?);
+        Ok(ProvenanceEnvelope {
+            created_at,
+            tool_version: TOOL_VERSION.to_string(),
+            encrypted_object,
+            signature: signature.to_byte_array().to_vec(),
+        })
+    }
+
+    /// Check the signature against the expected signer's public key.
+    pub fn verify(&self, public_key: &G2Element) -> Result<()> {
+        let array = self
+            .signature
+            .as_slice()
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("Envelope signature must be 48 bytes, got {}", self.signature.len()))?;
+        let signature =
+            G1Element::from_byte_array(&array).map_err(|e| anyhow::anyhow!("Invalid envelope signature: {}", e))?;
+        signing::verify(
+            &signature,
+            &signed_bytes(self.created_at, &self.tool_version, &self.encrypted_object)?,
+            public_key,
+        )
+        .context("Envelope signature is invalid: it was tampered with or signed by a different key")
+    }
+
+    /// Creation time, for display once `verify` has passed.
+    pub fn created_at(&self) -> Result<DateTime<Utc>> {
+        DateTime::from_timestamp(self.created_at, 0)
+            .ok_or_else(|| anyhow::anyhow!("Envelope timestamp {} is out of range", self.created_at))
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut bytes = PROVENANCE_MAGIC.to_vec();
+        bytes.extend_from_slice(&bcs::to_bytes(self)?);
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let body = bytes
+            .strip_prefix(PROVENANCE_MAGIC.as_slice())
+            .ok_or_else(|| anyhow::anyhow!("Not a provenance envelope (missing SEALPROV header)"))?;
+        bcs::from_bytes(body).context("Corrupt provenance envelope")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::SealSession;
+    use crypto::EncryptionInput;
+
+    #[test]
+    fn test_envelope_signature_covers_metadata() -> Result<()> {
+        let session = SealSession::generate(3, 2)?;
+        let (encrypted_object, _) = session.encrypt(
+            b"archive:2024",
+            EncryptionInput::Aes256Gcm { data: b"ledger".to_vec(), aad: None },
+        )?;
+        let (sign_key, public_key) = signing::generate_signing_key();
+        let now = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+
+        let envelope = ProvenanceEnvelope::from_bytes(
+            &ProvenanceEnvelope::create(encrypted_object, &sign_key, now)?.to_bytes()?,
+        )?;
+        envelope.verify(&public_key)?;
+        assert_eq!(envelope.created_at()?, now);
+        assert_eq!(envelope.tool_version, TOOL_VERSION);
+        assert_eq!(session.decrypt(&envelope.encrypted_object)?, b"ledger");
+
+        // Backdating, or claiming another tool version, breaks the signature
+        let mut backdated = ProvenanceEnvelope::from_bytes(&envelope.to_bytes()?)?;
+        backdated.created_at -= 86_400;
+        assert!(backdated.verify(&public_key).is_err());
+        let mut relabeled = ProvenanceEnvelope::from_bytes(&envelope.to_bytes()?)?;
+        relabeled.tool_version = "seal-demo 9.9.9".to_string();
+        assert!(relabeled.verify(&public_key).is_err());
+
+        let (_, other_key) = signing::generate_signing_key();
+        assert!(envelope.verify(&other_key).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,30 @@
+//! QR rendering for the `qr` command (`qr` feature).
+
+use anyhow::Result;
+use qrcode::render::unicode;
+use qrcode::QrCode;
+use std::path::Path;
+
+use crate::output::outln;
+
+/// Print `payload` as a QR code using half-block characters, light on dark
+/// so it scans from a dark terminal.
+pub fn print_terminal(payload: &str) -> Result<()> {
+    let code = QrCode::new(payload.as_bytes())?;
+    let rendered = code
+        .render::<unicode::Dense1x2>()
+        .dark_color(unicode::Dense1x2::Light)
+        .light_color(unicode::Dense1x2::Dark)
+        .build();
+    outln!("{}", rendered);
+    Ok(())
+}
+
+pub fn save_png(payload: &str, path: &Path) -> Result<()> {
+    let code = QrCode::new(payload.as_bytes())?;
+    code.render::<image::Luma<u8>>()
+        .min_dimensions(256, 256)
+        .build()
+        .save(path)?;
+    Ok(())
+}
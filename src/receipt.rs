@@ -0,0 +1,155 @@
+//! Signed receipts recording that an encrypted file was opened.
+//!
+//! `decrypt --receipt` writes one after a successful decryption: the SHA3-256
+//! of the encrypted file, its identity, the time, and the servers whose keys
+//! were used, under a BLS signature (see `signing`) by the opener's key:
+//!
+//! ```text
+//! SEALRCPT | bcs(DecryptionReceipt)
+//! ```
+//!
+//! A receipt proves that the holder of the signing key claims to have opened
+//! exactly that ciphertext; `verify-receipt` checks the signature and that
+//! the ciphertext on hand is the one it names.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use crypto::ObjectID;
+use fastcrypto::groups::bls12381::{G1Element, G2Element, Scalar};
+use fastcrypto::hash::{HashFunction, Sha3_256};
+use fastcrypto::serde_helpers::ToFromByteArray;
+use serde::{Deserialize, Serialize};
+
+use crate::signing;
+
+pub const RECEIPT_MAGIC: &[u8; 8] = b"SEALRCPT";
+
+/// Signed along with the fields, so a receipt signature can't be replayed
+/// as a detached signature or provenance envelope.
+const RECEIPT_DOMAIN: &str = "seal-demo/decryption-receipt/v1";
+
+#[derive(Serialize, Deserialize)]
+pub struct DecryptionReceipt {
+    /// SHA3-256 of the encrypted file as read.
+    pub ciphertext_hash: [u8; 32],
+    pub identity: Vec<u8>,
+    /// Unix seconds at decryption.
+    pub opened_at: i64,
+    /// Servers whose keys were used.
+    pub servers: Vec<ObjectID>,
+    /// BLS signature (G1, compressed) over the fields above.
+    signature: Vec<u8>,
+}
+
+pub fn ciphertext_hash(ciphertext: &[u8]) -> [u8; 32] {
+    Sha3_256::digest(ciphertext).digest
+}
+
+impl DecryptionReceipt {
+    pub fn create(
+        ciphertext: &[u8],
+        identity: Vec<u8>,
+        servers: Vec<ObjectID>,
+        sign_key: &Scalar,
+        now: DateTime<Utc>,
+    ) -> Result<Self> {
+        let mut receipt = DecryptionReceipt {
+            ciphertext_hash: ciphertext_hash(ciphertext),
+            identity,
+            opened_at: now.timestamp(),
+            servers,
+            signature: Vec::new(),
+        };
+        receipt.signature = signing::sign(sign_key, &receipt.signed_bytes()?).to_byte_array().to_vec();
+        Ok(receipt)
+    }
+
+    fn signed_bytes(&self) -> Result<Vec<u8>> {
+        Ok(bcs::to_bytes(&(RECEIPT_DOMAIN, &self.ciphertext_hash, &self.identity, self.opened_at, &self.servers))?)
+    }
+
+    /// Check the signature against the expected signer's public key.
+    pub fn verify(&self, public_key: &G2Element) -> Result<()> {
+        let array = self
+            .signature
+            .as_slice()
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("Receipt signature must be 48 bytes, got {}", self.signature.len()))?;
+        let signature =
+            G1Element::from_byte_array(&array).map_err(|e| anyhow::anyhow!("Invalid receipt signature: {}", e))?;
+        signing::verify(&signature, &self.signed_bytes()?, public_key)
+            .context("Receipt signature is invalid: it was tampered with or signed by a different key")
+    }
+
+    /// Check that `ciphertext` is the file the receipt was issued for.
+    pub fn check_ciphertext(&self, ciphertext: &[u8]) -> Result<()> {
+        if ciphertext_hash(ciphertext) != self.ciphertext_hash {
+            anyhow::bail!(
+                "Ciphertext hash {} doesn't match the receipt's {}",
+                hex::encode(ciphertext_hash(ciphertext)),
+                hex::encode(self.ciphertext_hash)
+            );
+        }
+        Ok(())
+    }
+
+    /// Decryption time, for display once `verify` has passed.
+    pub fn opened_at(&self) -> Result<DateTime<Utc>> {
+        DateTime::from_timestamp(self.opened_at, 0)
+            .ok_or_else(|| anyhow::anyhow!("Receipt timestamp {} is out of range", self.opened_at))
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut bytes = RECEIPT_MAGIC.to_vec();
+        bytes.extend_from_slice(&bcs::to_bytes(self)?);
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let body = bytes
+            .strip_prefix(RECEIPT_MAGIC.as_slice())
+            .ok_or_else(|| anyhow::anyhow!("Not a decryption receipt (missing SEALRCPT header)"))?;
+        bcs::from_bytes(body).context("Corrupt decryption receipt")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_receipt_binds_ciphertext_and_access() -> Result<()> {
+        let (sign_key, public_key) = signing::generate_signing_key();
+        let servers = vec![ObjectID::random(), ObjectID::random()];
+        let now = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let ciphertext = b"SEAL\x02 encrypted contract".to_vec();
+
+        let receipt = DecryptionReceipt::from_bytes(
+            &DecryptionReceipt::create(&ciphertext, b"file:contract.pdf".to_vec(), servers.clone(), &sign_key, now)?
+                .to_bytes()?,
+        )?;
+        receipt.verify(&public_key)?;
+        receipt.check_ciphertext(&ciphertext)?;
+        assert_eq!(receipt.opened_at()?, now);
+        assert_eq!(receipt.servers, servers);
+
+        // A different ciphertext isn't covered
+        assert!(receipt.check_ciphertext(b"SEAL\x02 another contract").is_err());
+
+        // Editing any recorded field breaks the signature
+        let mut later = DecryptionReceipt::from_bytes(&receipt.to_bytes()?)?;
+        later.opened_at += 3600;
+        assert!(later.verify(&public_key).is_err());
+        let mut fewer = DecryptionReceipt::from_bytes(&receipt.to_bytes()?)?;
+        fewer.servers.pop();
+        assert!(fewer.verify(&public_key).is_err());
+        let mut swapped = DecryptionReceipt::from_bytes(&receipt.to_bytes()?)?;
+        swapped.ciphertext_hash = ciphertext_hash(b"SEAL\x02 another contract");
+        assert!(swapped.verify(&public_key).is_err());
+
+        let (_, other_key) = signing::generate_signing_key();
+        assert!(receipt.verify(&other_key).is_err());
+        assert!(DecryptionReceipt::from_bytes(b"SEALPROV...").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,209 @@
+//! Every runnable demo by name, for the interactive menus and `seal-demo run`.
+
+use anyhow::Result;
+use chrono::{TimeDelta, Utc};
+
+use crate::basic_demo::BasicDemo;
+use crate::file_demo::FileDemo;
+use crate::key_management::KeyManagementDemo;
+use crate::threshold_demo::ThresholdDemo;
+
+/// Settings shared by all demos.
+pub struct DemoContext {
+    /// Number of key servers for demos that build a fleet.
+    pub servers: usize,
+}
+
+/// `Send + Sync` so the TUI can run a demo on a worker thread while it keeps drawing.
+pub type DemoFn = Box<dyn Fn(&DemoContext) -> Result<()> + Send + Sync>;
+
+pub struct DemoEntry {
+    /// Name for `seal-demo run <name>`.
+    pub name: &'static str,
+    /// Heading in the interactive menus; demos without one are only
+    /// reachable by name.
+    pub title: Option<&'static str>,
+    pub description: &'static str,
+    /// Whether `servers` changes anything, so the text menu knows to ask.
+    pub uses_servers: bool,
+    pub run: DemoFn,
+}
+
+fn menu_entry(
+    name: &'static str,
+    title: &'static str,
+    description: &'static str,
+    uses_servers: bool,
+    run: impl Fn(&DemoContext) -> Result<()> + Send + Sync + 'static,
+) -> DemoEntry {
+    DemoEntry {
+        title: Some(title),
+        ..entry(name, description, uses_servers, run)
+    }
+}
+
+fn entry(
+    name: &'static str,
+    description: &'static str,
+    uses_servers: bool,
+    run: impl Fn(&DemoContext) -> Result<()> + Send + Sync + 'static,
+) -> DemoEntry {
+    DemoEntry {
+        name,
+        title: None,
+        description,
+        uses_servers,
+        run: Box::new(run),
+    }
+}
+
+/// The entries shown in the interactive menus, in order.
+pub fn menu() -> Vec<DemoEntry> {
+    demos().into_iter().filter(|demo| demo.title.is_some()).collect()
+}
+
+fn threshold_demo(context: &DemoContext) -> Result<ThresholdDemo> {
+    if context.servers < 2 {
+        anyhow::bail!("Need at least 2 key servers for threshold encryption");
+    }
+    Ok(ThresholdDemo::new(context.servers))
+}
+
+fn file_demo(context: &DemoContext) -> Result<FileDemo> {
+    FileDemo::with_servers(context.servers, 2)
+}
+
+pub fn demos() -> Vec<DemoEntry> {
+    vec![
+        menu_entry("basic", "Basic Encryption", "AES-256-GCM, HMAC-256-CTR and Plain key derivation", false, |_| {
+            crate::run_basic_demo(false, false, false, false)
+        }),
+        entry("basic-aes", "Encrypt and decrypt with AES-256-GCM", false, |_| BasicDemo::new()?.run_aes_demo()),
+        entry("basic-hmac", "Encrypt and decrypt with HMAC-256-CTR", false, |_| BasicDemo::new()?.run_hmac_demo()),
+        entry("basic-plain", "Derive a symmetric key from an identity", false, |_| {
+            BasicDemo::new()?.run_plain_demo()
+        }),
+        entry("basic-auto", "Pick the mode from the CPU's AES support", false, |_| BasicDemo::new()?.run_auto_demo()),
+        entry("basic-randomized", "Same message twice, two different ciphertexts", false, |_| {
+            BasicDemo::new()?.demo_nondeterminism()
+        }),
+        entry("basic-overhead", "Ciphertext size per encryption mode", false, |_| {
+            BasicDemo::new()?.demo_mode_overheads()
+        }),
+        menu_entry("keys", "Key Management", "Generation, seed derivation, namespacing and verification", false, |_| {
+            crate::run_key_management_demo(false, false, false, false, false)
+        }),
+        entry("keys-generation", "Generate master key pairs", false, |_| {
+            KeyManagementDemo::new().demo_key_generation()
+        }),
+        entry("keys-seed", "Derive key pairs from a seed", false, |_| {
+            KeyManagementDemo::new().demo_seed_based_keys()
+        }),
+        entry("keys-namespace", "Package-scoped identities", false, |_| {
+            KeyManagementDemo::new().demo_identity_namespacing()
+        }),
+        entry("keys-verify", "Verify user secret keys", false, |_| {
+            KeyManagementDemo::new().demo_key_verification()
+        }),
+        entry("keys-shamir", "Classic Shamir sharing next to IBE threshold", false, |_| {
+            KeyManagementDemo::new().demo_shamir_comparison()
+        }),
+        menu_entry("threshold", "Threshold Encryption", "Multi-server T-out-of-N encryption and fault tolerance", true, |context| {
+            crate::run_threshold_demo(context.servers, None, false, false, false, false)
+        }),
+        entry("threshold-basic", "2-out-of-N encryption", true, |context| {
+            threshold_demo(context)?.demo_threshold_encryption(2)
+        }),
+        entry("threshold-rotation", "Replace a key server", true, |context| {
+            threshold_demo(context)?.demo_server_rotation()
+        }),
+        entry("threshold-staggered", "One server rotates its key on its own", true, |context| {
+            threshold_demo(context)?.demo_independent_rotation()
+        }),
+        entry("threshold-unanimous", "Every server needed to decrypt", true, |context| {
+            threshold_demo(context)?.demo_unanimous()
+        }),
+        entry("threshold-access", "Which server subsets can decrypt", true, |context| {
+            threshold_demo(context)?.demo_access_patterns()
+        }),
+        entry("threshold-nested", "Encryption nested across fleets", true, |context| {
+            threshold_demo(context)?.demo_nested_encryption()
+        }),
+        entry("threshold-policy", "AND/OR policies over identities", true, |context| {
+            threshold_demo(context)?.demo_policy_encryption()
+        }),
+        entry("threshold-time-lock", "Identities that unlock on a date", true, |context| {
+            threshold_demo(context)?.demo_time_lock(Utc::now() + TimeDelta::days(7))
+        }),
+        menu_entry("files", "File Operations", "File encryption, batch processing and metadata", true, |context| {
+            crate::run_file_demo((context.servers, 2), false, false, false, None, (None, None, None, Default::default(), false))
+        }),
+        entry("files-basic", "Encrypt and decrypt one file", true, |context| {
+            file_demo(context)?.demo_file_encryption()
+        }),
+        entry("files-batch", "Batch and parallel encryption", true, |context| {
+            file_demo(context)?.demo_batch_encryption(None)
+        }),
+        entry("files-metadata", "File metadata in the identity", true, |context| {
+            file_demo(context)?.demo_file_metadata()
+        }),
+        entry("files-rekey", "Rotate a file's symmetric key under the same identity", true, |context| {
+            file_demo(context)?.demo_symmetric_rekey()
+        }),
+        entry("files-streaming", "Chunked encryption of large files", true, |context| {
+            file_demo(context)?.demo_streaming()
+        }),
+        entry("files-cdc", "Content-defined chunks that survive insertions", true, |context| {
+            file_demo(context)?.demo_content_defined_chunking()
+        }),
+        entry("files-csv", "Encrypt sensitive CSV columns", true, |context| file_demo(context)?.demo_csv_records()),
+        entry("files-lines", "Per-line integrity for text files", true, |context| {
+            file_demo(context)?.demo_line_integrity()
+        }),
+        entry("files-audit-log", "Encrypted append-only log", true, |context| {
+            file_demo(context)?.demo_audit_log()
+        }),
+        entry("files-directory", "Hide a directory's structure behind an encrypted manifest", true, |context| {
+            file_demo(context)?.demo_directory_manifest()
+        }),
+        entry("tenants", "One fleet per tenant, served concurrently from one process", true, |context| {
+            crate::tenants::demo_tenants(context.servers)
+        }),
+        menu_entry("all", "Run All Demos", "Every demo above, in order", true, |context| {
+            crate::run_all_demos(context.servers, None, None)
+        }),
+    ]
+}
+
+pub fn find(name: &str) -> Result<DemoEntry> {
+    let mut demos = demos();
+    match demos.iter().position(|demo| demo.name == name) {
+        Some(index) => Ok(demos.swap_remove(index)),
+        None => anyhow::bail!(
+            "Unknown demo \"{}\"; available: {}",
+            name,
+            demos.iter().map(|demo| demo.name).collect::<Vec<_>>().join(", ")
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_demo_names() -> Result<()> {
+        let demos = demos();
+        for (i, demo) in demos.iter().enumerate() {
+            assert!(demos[..i].iter().all(|other| other.name != demo.name), "duplicate {}", demo.name);
+        }
+        assert_eq!(menu().iter().map(|demo| demo.name).collect::<Vec<_>>(), ["basic", "keys", "threshold", "files", "all"]);
+
+        assert_eq!(find("threshold-rotation")?.name, "threshold-rotation");
+        assert!(find("threshold-rotate").map(|demo| demo.name).unwrap_err().to_string().contains("threshold-rotation"));
+
+        (find("basic-aes")?.run)(&DemoContext { servers: 3 })?;
+        assert!((find("threshold")?.run)(&DemoContext { servers: 1 }).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,159 @@
+//! Operation counting and the summary table printed after `seal-demo all`.
+//!
+//! Each demo holds an `OpCounter` and calls `seal_encrypt`, `seal_decrypt`
+//! and `extract` on it instead of on `crypto`. The wrappers have the same
+//! parameters and only tally operations and payload bytes on that counter,
+//! which clones share, so work on the worker threads of batch encryption and
+//! quorum fetches is counted too. `measure` hands a fresh counter to one demo
+//! and turns what it tallied into a `DemoReport`, so demos running at the
+//! same time don't count each other's work. `seal_encrypt` also enforces the
+//! identity length limit (see `identity`), so it returns an `anyhow::Result`
+//! rather than a `FastCryptoResult`.
+//!
+//! They can't take an RNG either: `crypto::seal_encrypt` samples its key and
+//! shares internally. Seeded runs inject randomness at key generation
+//! instead (see `SealSession::generate_with_rng`).
+
+use anyhow::Result;
+use crypto::{EncryptedObject, EncryptionInput, IBEPublicKeys, IBEUserSecretKeys, ObjectID};
+use fastcrypto::error::FastCryptoResult;
+use fastcrypto::groups::bls12381::{G1Element, Scalar};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::identity;
+use crate::output::outln;
+
+/// Operations and payload bytes of one demo run, shared by its clones.
+#[derive(Clone, Default)]
+pub struct OpCounter(Arc<Counts>);
+
+#[derive(Default)]
+struct Counts {
+    ops: AtomicU64,
+    bytes: AtomicU64,
+}
+
+impl OpCounter {
+    fn record(&self, bytes: usize) {
+        self.0.ops.fetch_add(1, Ordering::Relaxed);
+        self.0.bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn ops(&self) -> u64 {
+        self.0.ops.load(Ordering::Relaxed)
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.0.bytes.load(Ordering::Relaxed)
+    }
+
+    pub fn seal_encrypt(
+        &self,
+        package_id: ObjectID,
+        id: Vec<u8>,
+        key_servers: Vec<ObjectID>,
+        public_keys: &IBEPublicKeys,
+        threshold: u8,
+        encryption_input: EncryptionInput,
+    ) -> Result<(EncryptedObject, [u8; 32])> {
+        identity::check_identity_len(&id)?;
+        let bytes = match &encryption_input {
+            EncryptionInput::Aes256Gcm { data, .. } | EncryptionInput::Hmac256Ctr { data, .. } => data.len(),
+            EncryptionInput::Plain => 0,
+        };
+        let result = crypto::seal_encrypt(package_id, id, key_servers, public_keys, threshold, encryption_input)?;
+        self.record(bytes);
+        Ok(result)
+    }
+
+    pub fn seal_decrypt(
+        &self,
+        encrypted_object: &EncryptedObject,
+        user_secret_keys: &IBEUserSecretKeys,
+        public_keys: Option<&IBEPublicKeys>,
+    ) -> FastCryptoResult<Vec<u8>> {
+        let plaintext = crypto::seal_decrypt(encrypted_object, user_secret_keys, public_keys)?;
+        self.record(plaintext.len());
+        Ok(plaintext)
+    }
+
+    pub fn extract(&self, master_key: &Scalar, id: &[u8]) -> G1Element {
+        self.record(0);
+        crypto::ibe::extract(master_key, id)
+    }
+}
+
+pub struct DemoReport {
+    pub name: String,
+    pub ops: u64,
+    pub bytes: u64,
+    pub elapsed: Duration,
+}
+
+/// Run one demo with a fresh counter and report what it counted.
+pub fn measure(name: &str, demo: impl FnOnce(&OpCounter) -> Result<()>) -> Result<DemoReport> {
+    let counter = OpCounter::default();
+    let start = Instant::now();
+
+    demo(&counter)?;
+
+    Ok(DemoReport {
+        name: name.to_string(),
+        ops: counter.ops(),
+        bytes: counter.bytes(),
+        elapsed: start.elapsed(),
+    })
+}
+
+pub fn print_summary(reports: &[DemoReport]) {
+    outln!("\n📊 === Summary ===");
+    outln!("   {:<24} {:>8} {:>12} {:>12}", "Demo", "Ops", "Bytes", "Elapsed");
+    for report in reports {
+        outln!(
+            "   {:<24} {:>8} {:>12} {:>12}",
+            report.name,
+            report.ops,
+            report.bytes,
+            format!("{:.2?}", report.elapsed)
+        );
+    }
+
+    let ops: u64 = reports.iter().map(|report| report.ops).sum();
+    let bytes: u64 = reports.iter().map(|report| report.bytes).sum();
+    let elapsed: Duration = reports.iter().map(|report| report.elapsed).sum();
+    outln!("   {:<24} {:>8} {:>12} {:>12}", "Total", ops, bytes, format!("{:.2?}", elapsed));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::basic_demo::BasicDemo;
+    use crypto::ibe::generate_key_pair;
+    use rand::thread_rng;
+
+    #[test]
+    fn test_measure_counts_operations() -> Result<()> {
+        let report = measure("AES", |counter| BasicDemo::new()?.with_counter(counter.clone()).run_aes_demo())?;
+        assert!(report.ops >= 2, "expected at least an encrypt and a decrypt, got {}", report.ops);
+        assert!(report.bytes > 0);
+
+        let idle = measure("Nothing", |_| Ok(()))?;
+        assert_eq!((idle.ops, idle.bytes), (0, 0));
+
+        // Extractions on scoped worker threads count toward the demo
+        let (master_key, _) = generate_key_pair(&mut thread_rng());
+        let parallel = measure("Parallel", |counter| {
+            std::thread::scope(|scope| {
+                for worker in 0..4u8 {
+                    let master_key = &master_key;
+                    scope.spawn(move || counter.extract(master_key, &[worker]));
+                }
+            });
+            Ok(())
+        })?;
+        assert_eq!((parallel.ops, parallel.bytes), (4, 0));
+        Ok(())
+    }
+}
@@ -0,0 +1,891 @@
+use anyhow::{Context, Result};
+use crypto::{
+    ibe::{generate_key_pair, extract, verify_user_secret_key, UserSecretKey},
+    seal_encrypt, seal_decrypt,
+    create_full_id, EncryptedObject, EncryptionInput, IBEPublicKeys, IBEUserSecretKeys, ObjectID
+};
+use fastcrypto::groups::bls12381::{G2Element, Scalar};
+use fastcrypto::serde_helpers::ToFromByteArray;
+use fastcrypto::traits::AllowedRng;
+use rand::{thread_rng, RngCore};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{Read, Write};
+use std::sync::Arc;
+
+use crate::container::{fleet_fingerprint, Fingerprint};
+use crate::full_id::FullIdScheme;
+use crate::identity;
+use crate::metrics::{Metrics, Operation};
+use crate::streaming::{self, ChunkCipher, ChunkingStrategy, StreamHeader};
+
+/// The public parameters of a key-server fleet, plus (optionally) the master
+/// keys needed to extract user secret keys locally.
+///
+/// A session is what another process needs to reproduce encryptions against
+/// the same fleet. It is deliberately separate from secret custody: master
+/// keys are only ever serialized when explicitly requested.
+#[derive(Clone)]
+pub struct SealSession {
+    pub package_id: ObjectID,
+    pub key_servers: Vec<ObjectID>,
+    pub public_keys: Vec<G2Element>,
+    pub threshold: u8,
+    pub master_keys: Option<Vec<Scalar>>,
+    /// Called as operations progress, see `SealEvent`. Never serialized.
+    pub on_event: Option<EventHook>,
+    /// Where operations are counted, `Metrics::global()` by default. Shared
+    /// by clones.
+    pub metrics: Arc<Metrics>,
+}
+
+pub type EventHook = Arc<dyn Fn(SealEvent) + Send + Sync>;
+
+/// What a `SealSession` is doing, for callers that want to observe
+/// operations without parsing its output.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SealEvent {
+    EncryptStarted { identity_len: usize },
+    /// `bytes` is the size of the serialized encrypted object.
+    EncryptFinished { bytes: usize },
+    DecryptStarted { threshold: u8 },
+    ServerKeyUsed { server: ObjectID },
+    /// Every key share checked out against the servers' public keys.
+    IntegrityVerified,
+    DecryptFinished { bytes: usize },
+}
+
+/// Returned (wrapped in `anyhow::Error`) by
+/// `SealSession::verify_key_from_server` for a server outside the fleet.
+#[derive(Debug, PartialEq, Eq)]
+pub struct UnknownServer {
+    pub server: ObjectID,
+}
+
+impl fmt::Display for UnknownServer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Server {} is not part of this session's fleet", self.server)
+    }
+}
+
+impl std::error::Error for UnknownServer {}
+
+#[derive(Serialize, Deserialize)]
+struct SessionFile {
+    package_id: ObjectID,
+    threshold: u8,
+    servers: Vec<ServerEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ServerEntry {
+    object_id: ObjectID,
+    public_key: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    master_key: Option<String>,
+}
+
+pub(crate) fn random_object_id(rng: &mut impl RngCore) -> ObjectID {
+    let mut bytes = [0u8; 32];
+    rng.fill_bytes(&mut bytes);
+    ObjectID::new(bytes)
+}
+
+impl SealSession {
+    /// Create a session backed by a freshly generated local fleet.
+    pub fn generate(num_servers: usize, threshold: u8) -> Result<Self> {
+        Self::generate_with_rng(num_servers, threshold, &mut thread_rng())
+    }
+
+    /// `generate`, drawing the package ID, server IDs and key pairs from
+    /// `rng`. A seeded RNG yields the same fleet every time.
+    ///
+    /// Randomness can only be injected where fleets are generated (here and
+    /// in the demos' `*_with_rng` constructors): `seal_encrypt` takes no RNG
+    /// and samples its key and shares internally, so ciphertexts differ
+    /// between runs even under a seeded fleet.
+    pub fn generate_with_rng(num_servers: usize, threshold: u8, rng: &mut impl AllowedRng) -> Result<Self> {
+        if threshold == 0 || threshold as usize > num_servers {
+            anyhow::bail!("Threshold {} is invalid for {} key servers", threshold, num_servers);
+        }
+
+        let mut key_servers = Vec::new();
+        let mut public_keys = Vec::new();
+        let mut master_keys = Vec::new();
+
+        for _ in 0..num_servers {
+            let (master_key, public_key) = generate_key_pair(rng);
+            key_servers.push(random_object_id(rng));
+            public_keys.push(public_key);
+            master_keys.push(master_key);
+        }
+
+        Ok(SealSession {
+            package_id: random_object_id(rng),
+            key_servers,
+            public_keys,
+            threshold,
+            master_keys: Some(master_keys),
+            on_event: None,
+            metrics: Metrics::global(),
+        })
+    }
+
+    /// Report progress to `hook`, see `SealEvent`.
+    pub fn with_event_hook(mut self, hook: impl Fn(SealEvent) + Send + Sync + 'static) -> Self {
+        self.on_event = Some(Arc::new(hook));
+        self
+    }
+
+    /// The operation counters in the Prometheus text exposition format.
+    pub fn metrics_prometheus(&self) -> String {
+        self.metrics.prometheus_text()
+    }
+
+    /// Count `result` as a success of `bytes` plaintext bytes or a failure.
+    fn record<T>(&self, operation: Operation, result: Result<T>, bytes: impl FnOnce(&T) -> usize) -> Result<T> {
+        match &result {
+            Ok(value) => self.metrics.record_success(operation, bytes(value)),
+            Err(_) => self.metrics.record_failure(operation),
+        }
+        result
+    }
+
+    /// `event` is only built when a hook is installed.
+    fn emit(&self, event: impl FnOnce() -> SealEvent) {
+        if let Some(hook) = &self.on_event {
+            hook(event());
+        }
+    }
+
+    /// Encrypt `input` to `identity` under this session's fleet and threshold.
+    pub fn encrypt(&self, identity: &[u8], input: EncryptionInput) -> Result<(EncryptedObject, [u8; 32])> {
+        let bytes = match &input {
+            EncryptionInput::Aes256Gcm { data, .. } | EncryptionInput::Hmac256Ctr { data, .. } => data.len(),
+            EncryptionInput::Plain => 0,
+        };
+        let result = self.encrypt_unrecorded(identity, input);
+        self.record(Operation::Encrypt, result, |_| bytes)
+    }
+
+    fn encrypt_unrecorded(&self, identity: &[u8], input: EncryptionInput) -> Result<(EncryptedObject, [u8; 32])> {
+        identity::check_identity_len(identity)?;
+        self.emit(|| SealEvent::EncryptStarted { identity_len: identity.len() });
+        let (encrypted_object, key) = seal_encrypt(
+            self.package_id,
+            identity.to_vec(),
+            self.key_servers.clone(),
+            &IBEPublicKeys::BonehFranklinBLS12381(self.public_keys.clone()),
+            self.threshold,
+            input,
+        )?;
+        self.emit(|| SealEvent::EncryptFinished {
+            bytes: bcs::serialized_size(&encrypted_object).unwrap_or_default(),
+        });
+        Ok((encrypted_object, key))
+    }
+
+    /// Encrypt everything `reader` yields to `identity` as a streaming
+    /// container (see `streaming`) on `writer`, in chunks of `chunk_size`.
+    /// At most two chunks are held in memory however long the input is.
+    /// Returns the plaintext length.
+    pub fn encrypt_stream<R: Read, W: Write>(
+        &self,
+        identity: &[u8],
+        reader: &mut R,
+        writer: &mut W,
+        chunk_size: usize,
+    ) -> Result<u64> {
+        let (encrypted_object, stream_key) = self.encrypt(identity, EncryptionInput::Plain)?;
+        streaming::encrypt_stream(reader, writer, encrypted_object, &stream_key, ChunkingStrategy::Fixed(chunk_size), 1)
+    }
+
+    /// Decrypt a streaming container from `reader` into `writer`, one chunk
+    /// at a time. On error `writer` may already hold a verified prefix (see
+    /// `streaming::decrypt_frames`).
+    pub fn decrypt_stream<R: Read, W: Write>(&self, reader: &mut R, writer: &mut W) -> Result<u64> {
+        streaming::decrypt_stream(reader, writer, |encrypted_object| self.decrypt(encrypted_object))
+    }
+
+    /// Move a streaming container from `reader` to `new_fleet`, split at
+    /// `new_threshold`, writing the result to `writer`.
+    ///
+    /// The header's Plain-mode object only wraps a key Seal derived itself,
+    /// so the key can't be carried over: a new one is derived for the same
+    /// package and identity under `new_fleet`, and each chunk is opened with
+    /// the old key and sealed with the new one as it goes past. About two
+    /// chunks are held in memory however long the stream is, and plaintext
+    /// never leaves it. `new_fleet` needs no master keys. On error `writer`
+    /// holds a partial stream to discard. Returns the plaintext length.
+    pub fn rewrap_streaming<R: Read, W: Write>(
+        &self,
+        reader: &mut R,
+        writer: &mut W,
+        new_fleet: &SealSession,
+        new_threshold: u8,
+    ) -> Result<u64> {
+        if new_threshold == 0 || new_threshold as usize > new_fleet.key_servers.len() {
+            anyhow::bail!("Threshold {} is invalid for {} key servers", new_threshold, new_fleet.key_servers.len());
+        }
+        let header = streaming::read_header(reader)?;
+        let old_key = self.decrypt(&header.encrypted_object)?;
+        let (encrypted_object, new_key) = seal_encrypt(
+            header.encrypted_object.package_id,
+            header.encrypted_object.id.clone(),
+            new_fleet.key_servers.clone(),
+            &IBEPublicKeys::BonehFranklinBLS12381(new_fleet.public_keys.clone()),
+            new_threshold,
+            EncryptionInput::Plain,
+        )?;
+
+        streaming::write_header(writer, &StreamHeader { chunk_size: header.chunk_size, encrypted_object })?;
+        streaming::reseal_frames(
+            reader,
+            writer,
+            &ChunkCipher::new(&old_key)?,
+            &ChunkCipher::new(&new_key)?,
+            header.chunk_size,
+        )
+    }
+
+    /// Decrypt using locally held master keys from the first `threshold` servers.
+    pub fn decrypt(&self, encrypted_object: &EncryptedObject) -> Result<Vec<u8>> {
+        self.decrypt_with_servers(encrypted_object, encrypted_object.threshold as usize)
+    }
+
+    /// Decrypt using locally held master keys from the first `num_servers` servers.
+    pub fn decrypt_with_servers(&self, encrypted_object: &EncryptedObject, num_servers: usize) -> Result<Vec<u8>> {
+        let public_keys = IBEPublicKeys::BonehFranklinBLS12381(self.public_keys.clone());
+        self.decrypt_shares(encrypted_object, num_servers, Some(&public_keys))
+    }
+
+    /// Decrypt without passing public keys to `seal_decrypt`, which skips
+    /// verifying the decrypted key shares.
+    ///
+    /// This accepts whatever shares the user secret keys produce: a wrong or
+    /// malicious key server can make AES/HMAC decryption fail with an opaque
+    /// error, and in Plain mode it silently yields a wrong key. Only use it to
+    /// diagnose decryption problems, never to work around a failed check.
+    pub fn decrypt_unverified(&self, encrypted_object: &EncryptedObject) -> Result<Vec<u8>> {
+        self.decrypt_shares(encrypted_object, encrypted_object.threshold as usize, None)
+    }
+
+    /// Decrypt an object sealed under an earlier full ID layout (see
+    /// `full_id`), extracting user secret keys for the full ID `scheme`
+    /// builds.
+    ///
+    /// `seal_decrypt` checks shares against the current layout only, so this
+    /// path is unverified in the same way as `decrypt_unverified`.
+    pub fn decrypt_legacy(&self, encrypted_object: &EncryptedObject, scheme: FullIdScheme) -> Result<Vec<u8>> {
+        let full_id = scheme.full_id(&encrypted_object.package_id, &encrypted_object.id);
+        let result =
+            self.decrypt_shares_unrecorded(encrypted_object, &full_id, encrypted_object.threshold as usize, None);
+        self.record(Operation::Decrypt, result, Vec::len)
+    }
+
+    fn decrypt_shares(
+        &self,
+        encrypted_object: &EncryptedObject,
+        num_servers: usize,
+        public_keys: Option<&IBEPublicKeys>,
+    ) -> Result<Vec<u8>> {
+        let full_id = create_full_id(&encrypted_object.package_id, &encrypted_object.id);
+        let result = self.decrypt_shares_unrecorded(encrypted_object, &full_id, num_servers, public_keys);
+        self.record(Operation::Decrypt, result, Vec::len)
+    }
+
+    fn decrypt_shares_unrecorded(
+        &self,
+        encrypted_object: &EncryptedObject,
+        full_id: &[u8],
+        num_servers: usize,
+        public_keys: Option<&IBEPublicKeys>,
+    ) -> Result<Vec<u8>> {
+        let master_keys = self
+            .master_keys
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Session has no master keys; it can only encrypt"))?;
+
+        self.emit(|| SealEvent::DecryptStarted { threshold: encrypted_object.threshold });
+        let user_secret_keys: HashMap<_, _> = self
+            .key_servers
+            .iter()
+            .zip(master_keys)
+            .take(num_servers)
+            .map(|(server, master_key)| {
+                self.emit(|| SealEvent::ServerKeyUsed { server: *server });
+                (*server, extract(master_key, full_id))
+            })
+            .collect();
+
+        let decrypted = seal_decrypt(
+            encrypted_object,
+            &IBEUserSecretKeys::BonehFranklinBLS12381(user_secret_keys),
+            public_keys,
+        )?;
+        if public_keys.is_some() {
+            self.emit(|| SealEvent::IntegrityVerified);
+        }
+        self.emit(|| SealEvent::DecryptFinished { bytes: decrypted.len() });
+        Ok(decrypted)
+    }
+
+    /// Check that `key` is the user secret key `server` issues for `identity`
+    /// under this session's package, against the public key the session has
+    /// for that server. Fails with `UnknownServer` if the session doesn't
+    /// list `server`.
+    pub fn verify_key_from_server(&self, key: &UserSecretKey, identity: &[u8], server: ObjectID) -> Result<()> {
+        let index = self.key_servers.iter().position(|id| *id == server).ok_or(UnknownServer { server })?;
+        let full_id = create_full_id(&self.package_id, identity);
+        verify_user_secret_key(key, &full_id, &self.public_keys[index])
+            .map_err(|_| anyhow::anyhow!("Key does not verify against server {} for this identity", server))
+    }
+
+    /// Short identifier of this session's fleet, as stored in containers.
+    pub fn fingerprint(&self) -> Fingerprint {
+        fleet_fingerprint(&self.key_servers, &self.public_keys)
+    }
+
+    /// Serialize the session to JSON. Master keys are omitted unless
+    /// `include_secrets` is set, in which case the output must be treated as
+    /// secret key material.
+    pub fn to_json(&self, include_secrets: bool) -> Result<String> {
+        Ok(serde_json::to_string_pretty(&self.to_file(include_secrets)?)?)
+    }
+
+    /// Serialize the session to TOML, with the same rules as [`SealSession::to_json`].
+    pub fn to_toml(&self, include_secrets: bool) -> Result<String> {
+        Ok(toml::to_string_pretty(&self.to_file(include_secrets)?)?)
+    }
+
+    /// Reconstruct a session from JSON produced by [`SealSession::to_json`].
+    pub fn from_json(json: &str) -> Result<Self> {
+        Self::from_file(serde_json::from_str(json).context("Invalid session JSON")?)
+    }
+
+    /// Reconstruct a session from TOML produced by [`SealSession::to_toml`].
+    pub fn from_toml(toml_str: &str) -> Result<Self> {
+        Self::from_file(toml::from_str(toml_str).context("Invalid session TOML")?)
+    }
+
+    /// One server's public parameters as compact JSON, in the same shape as
+    /// an entry of the session file's `servers` list (never with secrets).
+    pub fn server_json(&self, index: usize) -> Result<String> {
+        let (Some(object_id), Some(public_key)) = (self.key_servers.get(index), self.public_keys.get(index)) else {
+            anyhow::bail!("Server index {} out of range, the fleet has {} servers", index, self.key_servers.len());
+        };
+        Ok(serde_json::to_string(&ServerEntry {
+            object_id: *object_id,
+            public_key: encode_public_key(public_key),
+            master_key: None,
+        })?)
+    }
+
+    fn to_file(&self, include_secrets: bool) -> Result<SessionFile> {
+        if include_secrets {
+            if self.master_keys.is_none() {
+                anyhow::bail!("Session has no master keys to include");
+            }
+            tracing::warn!(
+                "Serializing MASTER KEYS for {} key servers - anyone holding this file can decrypt everything encrypted to this fleet",
+                self.key_servers.len()
+            );
+        }
+
+        let servers = self
+            .key_servers
+            .iter()
+            .zip(&self.public_keys)
+            .enumerate()
+            .map(|(i, (object_id, public_key))| ServerEntry {
+                object_id: *object_id,
+                public_key: encode_public_key(public_key),
+                master_key: match (&self.master_keys, include_secrets) {
+                    (Some(master_keys), true) => Some(encode_master_key(&master_keys[i])),
+                    _ => None,
+                },
+            })
+            .collect();
+
+        Ok(SessionFile {
+            package_id: self.package_id,
+            threshold: self.threshold,
+            servers,
+        })
+    }
+
+    fn from_file(file: SessionFile) -> Result<Self> {
+        if file.servers.is_empty() {
+            anyhow::bail!("Session lists no key servers");
+        }
+        if file.threshold == 0 || file.threshold as usize > file.servers.len() {
+            anyhow::bail!(
+                "Session threshold {} is invalid for {} key servers",
+                file.threshold,
+                file.servers.len()
+            );
+        }
+
+        let mut key_servers = Vec::new();
+        let mut public_keys = Vec::new();
+        let mut master_keys = Vec::new();
+
+        for server in &file.servers {
+            key_servers.push(server.object_id);
+            public_keys.push(
+                decode_public_key(&server.public_key)
+                    .with_context(|| format!("Bad public key for server {}", server.object_id))?,
+            );
+            if let Some(master_key) = &server.master_key {
+                master_keys.push(
+                    decode_master_key(master_key)
+                        .with_context(|| format!("Bad master key for server {}", server.object_id))?,
+                );
+            }
+        }
+
+        // Either every server carries its master key or none do.
+        let master_keys = match master_keys.len() {
+            0 => None,
+            n if n == key_servers.len() => Some(master_keys),
+            n => anyhow::bail!(
+                "Session has master keys for only {} of {} servers",
+                n,
+                key_servers.len()
+            ),
+        };
+
+        Ok(SealSession {
+            package_id: file.package_id,
+            key_servers,
+            public_keys,
+            threshold: file.threshold,
+            master_keys,
+            on_event: None,
+            metrics: Metrics::global(),
+        })
+    }
+}
+
+/// What Plain mode is, for warnings wherever it could be mistaken for encryption.
+pub const PLAIN_MODE_NOTE: &str =
+    "Plain mode only derives a 32-byte key and encrypts no data; use AES-256-GCM or HMAC-256-CTR to encrypt data";
+
+/// The `EncryptionInput` for a mode named `aes-256-gcm`, `hmac-256-ctr` or
+/// `plain`. `crypto::EncryptionInput::Plain` has no payload, so a caller
+/// choosing the mode separately from the data could drop the data without
+/// noticing; here data or AAD with Plain mode is an error instead.
+pub fn encryption_input(mode: &str, data: Option<Vec<u8>>, aad: Option<Vec<u8>>) -> Result<EncryptionInput> {
+    match (mode, data) {
+        ("aes-256-gcm", Some(data)) => Ok(EncryptionInput::Aes256Gcm { data, aad }),
+        ("hmac-256-ctr", Some(data)) => Ok(EncryptionInput::Hmac256Ctr { data, aad }),
+        ("aes-256-gcm" | "hmac-256-ctr", None) => anyhow::bail!("Mode {} needs data to encrypt", mode),
+        ("plain", None) if aad.is_none() => Ok(EncryptionInput::Plain),
+        ("plain", _) => anyhow::bail!("Refusing data for Plain mode: {}", PLAIN_MODE_NOTE),
+        (other, _) => anyhow::bail!("Unknown mode \"{}\", expected aes-256-gcm, hmac-256-ctr or plain", other),
+    }
+}
+
+pub fn encode_public_key(public_key: &G2Element) -> String {
+    hex::encode(public_key.to_byte_array())
+}
+
+pub fn decode_public_key(hex_str: &str) -> Result<G2Element> {
+    let bytes = hex::decode(hex_str.trim_start_matches("0x"))?;
+    let array = bytes
+        .try_into()
+        .map_err(|b: Vec<u8>| anyhow::anyhow!("Public key must be 96 bytes, got {}", b.len()))?;
+    G2Element::from_byte_array(&array).map_err(|e| anyhow::anyhow!("Invalid public key: {}", e))
+}
+
+/// Parse a user secret key as key servers return it: a compressed G1 point
+/// (48 bytes) in hex.
+pub fn decode_user_secret_key(hex_str: &str) -> Result<UserSecretKey> {
+    let bytes = hex::decode(hex_str.trim().trim_start_matches("0x")).context("User secret key must be hex")?;
+    let array = bytes
+        .try_into()
+        .map_err(|b: Vec<u8>| anyhow::anyhow!("User secret key must be 48 bytes, got {}", b.len()))?;
+    UserSecretKey::from_byte_array(&array).map_err(|e| anyhow::anyhow!("Invalid user secret key: {}", e))
+}
+
+/// Parse a package or object ID from hex (`0x` optional).
+pub fn decode_object_id(hex_str: &str) -> Result<ObjectID> {
+    let bytes = hex::decode(hex_str.trim().trim_start_matches("0x")).context("Object ID must be hex")?;
+    let array: [u8; 32] = bytes
+        .try_into()
+        .map_err(|b: Vec<u8>| anyhow::anyhow!("Object ID must be 32 bytes, got {}", b.len()))?;
+    Ok(ObjectID::new(array))
+}
+
+pub fn encode_master_key(master_key: &Scalar) -> String {
+    hex::encode(master_key.to_byte_array())
+}
+
+pub fn decode_master_key(hex_str: &str) -> Result<Scalar> {
+    let bytes = hex::decode(hex_str.trim_start_matches("0x"))?;
+    let array = bytes
+        .try_into()
+        .map_err(|b: Vec<u8>| anyhow::anyhow!("Master key must be 32 bytes, got {}", b.len()))?;
+    Scalar::from_byte_array(&array).map_err(|e| anyhow::anyhow!("Invalid master key: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::{any, prop, Just, ProptestConfig, Strategy, TestCaseError};
+    use proptest::{prop_oneof, proptest};
+    use std::cell::Cell;
+
+    #[test]
+    fn test_plain_mode_refuses_data() -> Result<()> {
+        assert!(matches!(encryption_input("plain", None, None)?, EncryptionInput::Plain));
+        assert!(matches!(
+            encryption_input("hmac-256-ctr", Some(b"x".to_vec()), None)?,
+            EncryptionInput::Hmac256Ctr { .. }
+        ));
+
+        let err = encryption_input("plain", Some(b"salary data".to_vec()), None).unwrap_err();
+        assert!(err.to_string().contains("AES-256-GCM"));
+        assert!(encryption_input("plain", None, Some(b"aad".to_vec())).is_err());
+        assert!(encryption_input("aes-256-gcm", None, None).is_err());
+        assert!(encryption_input("rot13", Some(Vec::new()), None).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_key_from_server() -> Result<()> {
+        let session = SealSession::generate(3, 2)?;
+        let master_keys = session.master_keys.clone().unwrap();
+        let identity = b"user:alice";
+        let key = extract(&master_keys[1], &create_full_id(&session.package_id, identity));
+
+        session.verify_key_from_server(&key, identity, session.key_servers[1])?;
+        let reparsed = decode_user_secret_key(&hex::encode(key.to_byte_array()))?;
+        session.verify_key_from_server(&reparsed, identity, session.key_servers[1])?;
+
+        // Wrong server or wrong identity fails, but not as an unknown server
+        let err = session.verify_key_from_server(&key, identity, session.key_servers[0]).unwrap_err();
+        assert!(err.downcast_ref::<UnknownServer>().is_none());
+        assert!(session.verify_key_from_server(&key, b"user:bob", session.key_servers[1]).is_err());
+
+        let stranger = ObjectID::random();
+        let err = session.verify_key_from_server(&key, identity, stranger).unwrap_err();
+        assert_eq!(err.downcast_ref::<UnknownServer>(), Some(&UnknownServer { server: stranger }));
+        assert!(decode_user_secret_key(&"ab".repeat(40)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_public_round_trip() -> Result<()> {
+        let session = SealSession::generate(3, 2)?;
+        let json = session.to_json(false)?;
+        assert!(!json.contains("master_key"));
+
+        let restored = SealSession::from_json(&json)?;
+        assert_eq!(restored.package_id, session.package_id);
+        assert_eq!(restored.key_servers, session.key_servers);
+        assert_eq!(restored.public_keys, session.public_keys);
+        assert_eq!(restored.threshold, session.threshold);
+        assert!(restored.master_keys.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_round_trip_with_secrets() -> Result<()> {
+        let session = SealSession::generate(3, 2)?;
+        let restored = SealSession::from_json(&session.to_json(true)?)?;
+        assert_eq!(restored.master_keys, session.master_keys);
+        Ok(())
+    }
+
+    #[test]
+    fn test_event_hook_sequence() -> Result<()> {
+        use std::sync::Mutex;
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let recorder = Arc::clone(&events);
+        let session = SealSession::generate(3, 2)?.with_event_hook(move |event| recorder.lock().unwrap().push(event));
+
+        let (encrypted_object, _) = session.encrypt(
+            b"user@example.com",
+            EncryptionInput::Aes256Gcm { data: b"observed".to_vec(), aad: None },
+        )?;
+        assert_eq!(session.decrypt(&encrypted_object)?, b"observed");
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                SealEvent::EncryptStarted { identity_len: 16 },
+                SealEvent::EncryptFinished { bytes: bcs::to_bytes(&encrypted_object)?.len() },
+                SealEvent::DecryptStarted { threshold: 2 },
+                SealEvent::ServerKeyUsed { server: session.key_servers[0] },
+                SealEvent::ServerKeyUsed { server: session.key_servers[1] },
+                SealEvent::IntegrityVerified,
+                SealEvent::DecryptFinished { bytes: 8 },
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_metrics_prometheus() -> Result<()> {
+        // Own counters, so tests running in parallel don't interfere
+        let session = SealSession { metrics: Arc::new(Metrics::default()), ..SealSession::generate(3, 2)? };
+        let (encrypted_object, _) = session.encrypt(
+            b"user@example.com",
+            EncryptionInput::Aes256Gcm { data: b"counted".to_vec(), aad: None },
+        )?;
+        session.decrypt(&encrypted_object)?;
+        session.decrypt(&encrypted_object)?;
+        assert!(session.encrypt(&vec![b'x'; identity::DEFAULT_MAX_IDENTITY_LEN + 1], EncryptionInput::Plain).is_err());
+
+        // Every sample is `name{labels} value` under a declared counter
+        let text = session.metrics_prometheus();
+        let mut samples = HashMap::new();
+        let mut counters = Vec::new();
+        for line in text.lines() {
+            if let Some(name) = line.strip_prefix("# TYPE ").and_then(|rest| rest.strip_suffix(" counter")) {
+                counters.push(name);
+            } else if !line.starts_with("# HELP ") {
+                let (series, value) = line.rsplit_once(' ').expect("sample without value");
+                let name = &series[..series.find('{').unwrap_or(series.len())];
+                assert!(counters.contains(&name), "{} has no TYPE line", name);
+                samples.insert(series, value.parse::<f64>()?);
+            }
+        }
+
+        assert_eq!(samples[r#"seal_operations_total{operation="encrypt"}"#], 1.0);
+        assert_eq!(samples[r#"seal_operations_total{operation="decrypt"}"#], 2.0);
+        assert_eq!(samples[r#"seal_failures_total{operation="encrypt"}"#], 1.0);
+        assert_eq!(samples[r#"seal_failures_total{operation="decrypt"}"#], 0.0);
+        assert_eq!(samples[r#"seal_bytes_total{operation="encrypt"}"#], 7.0);
+        assert_eq!(samples[r#"seal_bytes_total{operation="decrypt"}"#], 14.0);
+        Ok(())
+    }
+
+    /// Counts bytes taken from the pipe.
+    struct Pipe<'a> {
+        data: &'a [u8],
+        consumed: &'a Cell<u64>,
+    }
+    impl Read for Pipe<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = self.data.read(buf)?;
+            self.consumed.set(self.consumed.get() + n as u64);
+            Ok(n)
+        }
+    }
+    /// Checks on every write that little input is held back.
+    struct Sink<'a> {
+        out: Vec<u8>,
+        consumed: &'a Cell<u64>,
+        max_in_flight: u64,
+    }
+    impl Write for Sink<'_> {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.out.extend_from_slice(buf);
+            let in_flight = self.consumed.get().saturating_sub(self.out.len() as u64);
+            self.max_in_flight = self.max_in_flight.max(in_flight);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_stream_pipe_bounded_memory() -> Result<()> {
+        let session = SealSession::generate(3, 2)?;
+        let chunk_size = 64 * 1024;
+        let data = crate::testutil::random_bytes(5 * 1024 * 1024 + 321, &mut crate::testutil::seeded_rng(Some(9)));
+        let consumed = Cell::new(0);
+        let mut sink = Sink { out: Vec::new(), consumed: &consumed, max_in_flight: 0 };
+
+        let total =
+            session.encrypt_stream(b"pipe:backup.tar", &mut Pipe { data: &data, consumed: &consumed }, &mut sink, chunk_size)?;
+        assert_eq!(total, data.len() as u64);
+        // Input read ahead of output never exceeds the current and next chunk
+        assert!(sink.max_in_flight <= 2 * chunk_size as u64, "{} bytes in flight", sink.max_in_flight);
+
+        let mut decrypted = Vec::new();
+        assert_eq!(session.decrypt_stream(&mut sink.out.as_slice(), &mut decrypted)?, data.len() as u64);
+        assert!(decrypted == data, "stream did not round trip");
+
+        let mut truncated = &sink.out[..sink.out.len() - 100];
+        assert!(session.decrypt_stream(&mut truncated, &mut Vec::new()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_rewrap_streaming_between_fleets() -> Result<()> {
+        let old_fleet = SealSession::generate(3, 2)?;
+        let new_fleet = SealSession::generate(5, 2)?;
+        let chunk_size = 64 * 1024;
+        let data = crate::testutil::random_bytes(4 * 1024 * 1024 + 77, &mut crate::testutil::seeded_rng(Some(11)));
+        let mut sealed = Vec::new();
+        old_fleet.encrypt_stream(b"archive:2024.tar", &mut data.as_slice(), &mut sealed, chunk_size)?;
+
+        // The new fleet's public half is all the migration needs
+        let public_fleet = SealSession::from_json(&new_fleet.to_json(false)?)?;
+        let consumed = Cell::new(0);
+        let mut sink = Sink { out: Vec::new(), consumed: &consumed, max_in_flight: 0 };
+        let total =
+            old_fleet.rewrap_streaming(&mut Pipe { data: &sealed, consumed: &consumed }, &mut sink, &public_fleet, 3)?;
+        assert_eq!(total, data.len() as u64);
+        // Input read ahead of output never exceeds the current and next frame
+        assert!(sink.max_in_flight <= 2 * (chunk_size as u64 + 20), "{} bytes in flight", sink.max_in_flight);
+
+        let header = streaming::read_header(&mut sink.out.as_slice())?;
+        let servers: Vec<ObjectID> = header.encrypted_object.services.iter().map(|(id, _)| *id).collect();
+        assert_eq!(servers, new_fleet.key_servers);
+        assert_eq!(header.encrypted_object.threshold, 3);
+        assert_eq!(header.encrypted_object.id, b"archive:2024.tar");
+
+        let mut decrypted = Vec::new();
+        assert_eq!(new_fleet.decrypt_stream(&mut sink.out.as_slice(), &mut decrypted)?, data.len() as u64);
+        assert!(decrypted == data, "rewrapped stream did not round trip");
+        assert!(old_fleet.decrypt_stream(&mut sink.out.as_slice(), &mut Vec::new()).is_err());
+
+        // A truncated source is refused, not migrated short
+        let mut truncated = &sealed[..sealed.len() - 100];
+        assert!(old_fleet.rewrap_streaming(&mut truncated, &mut Vec::new(), &new_fleet, 3).is_err());
+        assert!(old_fleet.rewrap_streaming(&mut sealed.as_slice(), &mut Vec::new(), &new_fleet, 6).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_unverified_decryption() -> Result<()> {
+        let mut session = SealSession::generate(3, 2)?;
+        let (encrypted_object, _) = session.encrypt(
+            b"notes.txt",
+            EncryptionInput::Aes256Gcm {
+                data: b"meeting notes".to_vec(),
+                aad: None,
+            },
+        )?;
+        let (plain_object, key) = session.encrypt(b"notes.key", EncryptionInput::Plain)?;
+
+        // With honest servers both paths agree
+        assert_eq!(session.decrypt(&encrypted_object)?, session.decrypt_unverified(&encrypted_object)?);
+        assert_eq!(session.decrypt_unverified(&plain_object)?, key);
+
+        // Server 1 now hands out keys from the wrong master key: a bad share
+        let (wrong_master_key, _) = generate_key_pair(&mut thread_rng());
+        session.master_keys.as_mut().unwrap()[0] = wrong_master_key;
+
+        assert!(session.decrypt(&encrypted_object).is_err());
+        assert!(session.decrypt_unverified(&encrypted_object).is_err());
+        assert!(session.decrypt(&plain_object).is_err());
+        // Unverified Plain mode has nothing to authenticate against
+        if let Ok(garbage) = session.decrypt_unverified(&plain_object) {
+            assert_ne!(garbage, key);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_toml_round_trip() -> Result<()> {
+        let session = SealSession::generate(3, 2)?;
+        let restored = SealSession::from_toml(&session.to_toml(true)?)?;
+        assert_eq!(restored.key_servers, session.key_servers);
+        assert_eq!(restored.public_keys, session.public_keys);
+        assert_eq!(restored.master_keys, session.master_keys);
+        Ok(())
+    }
+
+    #[test]
+    fn test_server_json_matches_session_entries() -> Result<()> {
+        let session = SealSession::generate(3, 2)?;
+        let entry: ServerEntry = serde_json::from_str(&session.server_json(1)?)?;
+        assert_eq!(entry.object_id, session.key_servers[1]);
+        assert_eq!(decode_public_key(&entry.public_key)?, session.public_keys[1]);
+        assert!(entry.master_key.is_none());
+        assert!(session.server_json(3).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_object_id() -> Result<()> {
+        let id = ObjectID::random();
+        assert_eq!(decode_object_id(&id.to_string())?, id);
+        assert_eq!(decode_object_id(&hex::encode(id))?, id);
+
+        let err = decode_object_id("0xabcd").unwrap_err();
+        assert_eq!(err.to_string(), "Object ID must be 32 bytes, got 2");
+        assert!(decode_object_id(&"zz".repeat(32)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_seeded_generation_is_reproducible() -> Result<()> {
+        use crate::testutil::seeded_rng;
+
+        let session = SealSession::generate_with_rng(3, 2, &mut seeded_rng(Some(7)))?;
+        let again = SealSession::generate_with_rng(3, 2, &mut seeded_rng(Some(7)))?;
+        assert_eq!(again.package_id, session.package_id);
+        assert_eq!(again.key_servers, session.key_servers);
+        assert_eq!(again.public_keys, session.public_keys);
+        assert_eq!(again.master_keys, session.master_keys);
+        assert_eq!(again.fingerprint(), session.fingerprint());
+
+        let other = SealSession::generate_with_rng(3, 2, &mut seeded_rng(Some(8)))?;
+        assert_ne!(other.fingerprint(), session.fingerprint());
+
+        // seal_encrypt samples its own randomness, so ciphertexts still differ
+        let (first, _) = session.encrypt(b"doc", EncryptionInput::Plain)?;
+        let (second, _) = again.encrypt(b"doc", EncryptionInput::Plain)?;
+        assert_ne!(bcs::to_bytes(&first)?, bcs::to_bytes(&second)?);
+        Ok(())
+    }
+
+    /// Encrypt `data` to `identity` in every mode under a fresh fleet and
+    /// check each decrypts to what went in (the key, for Plain mode).
+    /// Identities over the length limit must be refused, and their hashed
+    /// form must work instead.
+    fn round_trip_every_mode(
+        data: &[u8],
+        identity: &[u8],
+        aad: Option<Vec<u8>>,
+        servers: usize,
+        threshold: u8,
+    ) -> Result<()> {
+        let session = SealSession::generate(servers, threshold)?;
+        let identity = if identity.len() > crate::identity::max_identity_len() {
+            let err = session.encrypt(identity, EncryptionInput::Plain).unwrap_err();
+            anyhow::ensure!(err.downcast_ref::<crate::identity::IdentityTooLong>().is_some(), "{:#}", err);
+            crate::identity::hash_identity(identity)
+        } else {
+            identity.to_vec()
+        };
+
+        for mode in ["aes-256-gcm", "hmac-256-ctr", "plain"] {
+            let plain = mode == "plain";
+            let input = encryption_input(mode, (!plain).then(|| data.to_vec()), aad.clone().filter(|_| !plain))?;
+            let (encrypted_object, key) = session.encrypt(&identity, input)?;
+            let expected = if plain { &key[..] } else { data };
+            anyhow::ensure!(session.decrypt(&encrypted_object)? == expected, "{} round trip changed the bytes", mode);
+        }
+        Ok(())
+    }
+
+    proptest! {
+        // Each case generates a fleet and up to 1 MiB of payload, so run
+        // fewer than proptest's default 256
+        #![proptest_config(ProptestConfig::with_cases(32))]
+
+        #[test]
+        fn prop_every_mode_round_trips(
+            data in prop_oneof![
+                prop::collection::vec(any::<u8>(), 0..=64),
+                prop::collection::vec(any::<u8>(), 0..=1 << 20),
+            ],
+            identity in prop::collection::vec(any::<u8>(), 0..=4096),
+            aad in prop::option::of(prop::collection::vec(any::<u8>(), 0..=64)),
+            (servers, threshold) in (1..=8usize).prop_flat_map(|servers| (Just(servers), 1..=servers as u8)),
+        ) {
+            round_trip_every_mode(&data, &identity, aad, servers, threshold)
+                .map_err(|e| TestCaseError::fail(format!("{:#}", e)))?;
+        }
+    }
+}
@@ -0,0 +1,140 @@
+//! Classic Shamir secret sharing over GF(256), byte by byte.
+//!
+//! Only used by `KeyManagementDemo::demo_shamir_comparison` to contrast a
+//! dealer-based split of a key with Seal's IBE threshold encryption. Each
+//! byte of the secret is the constant term of its own random polynomial of
+//! degree `threshold - 1`; share `i` holds every polynomial evaluated at `i`.
+
+use anyhow::Result;
+use rand::{CryptoRng, RngCore};
+
+#[derive(Clone, Debug)]
+pub struct Share {
+    /// Evaluation point, never zero (zero is where the secret lives).
+    pub index: u8,
+    pub data: Vec<u8>,
+}
+
+/// Multiplication in GF(256) with the AES polynomial x^8 + x^4 + x^3 + x + 1.
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0u8;
+    while b != 0 {
+        if b & 1 != 0 {
+            product ^= a;
+        }
+        let carry = a & 0x80 != 0;
+        a <<= 1;
+        if carry {
+            a ^= 0x1b;
+        }
+        b >>= 1;
+    }
+    product
+}
+
+/// a^254 = a^-1 for every non-zero a.
+fn gf_inv(a: u8) -> u8 {
+    let mut result = 1u8;
+    for _ in 0..254 {
+        result = gf_mul(result, a);
+    }
+    result
+}
+
+/// Split `secret` into `total` shares, any `threshold` of which recover it.
+pub fn split(secret: &[u8], threshold: u8, total: u8, rng: &mut (impl RngCore + CryptoRng)) -> Result<Vec<Share>> {
+    if threshold == 0 || threshold > total {
+        anyhow::bail!("Threshold must be between 1 and {}, got {}", total, threshold);
+    }
+
+    let mut shares: Vec<Share> = (1..=total)
+        .map(|index| Share {
+            index,
+            data: Vec::with_capacity(secret.len()),
+        })
+        .collect();
+
+    for &byte in secret {
+        let mut coefficients = vec![0u8; threshold as usize];
+        coefficients[0] = byte;
+        rng.fill_bytes(&mut coefficients[1..]);
+
+        for share in &mut shares {
+            // Horner's rule, highest coefficient first
+            let value = coefficients
+                .iter()
+                .rev()
+                .fold(0u8, |acc, &coefficient| gf_mul(acc, share.index) ^ coefficient);
+            share.data.push(value);
+        }
+    }
+
+    Ok(shares)
+}
+
+/// Interpolate the shares at zero. With fewer shares than the threshold this
+/// still returns bytes, just not the secret: Shamir can't tell the difference.
+pub fn combine(shares: &[Share]) -> Result<Vec<u8>> {
+    let Some(first) = shares.first() else {
+        anyhow::bail!("No shares to combine");
+    };
+    for (i, share) in shares.iter().enumerate() {
+        if share.index == 0 {
+            anyhow::bail!("Share index 0 is invalid");
+        }
+        if share.data.len() != first.data.len() {
+            anyhow::bail!("Shares have different lengths");
+        }
+        if shares[..i].iter().any(|other| other.index == share.index) {
+            anyhow::bail!("Duplicate share index {}", share.index);
+        }
+    }
+
+    // Lagrange basis at x = 0: prod_{j != i} x_j / (x_j - x_i), and - is ^
+    let weights: Vec<u8> = shares
+        .iter()
+        .map(|share| {
+            shares
+                .iter()
+                .filter(|other| other.index != share.index)
+                .fold(1u8, |acc, other| {
+                    gf_mul(acc, gf_mul(other.index, gf_inv(other.index ^ share.index)))
+                })
+        })
+        .collect();
+
+    Ok((0..first.data.len())
+        .map(|position| {
+            shares
+                .iter()
+                .zip(&weights)
+                .fold(0u8, |acc, (share, &weight)| acc ^ gf_mul(share.data[position], weight))
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::thread_rng;
+
+    #[test]
+    fn test_reconstruction_needs_threshold_shares() -> Result<()> {
+        let secret: Vec<u8> = (0..32).collect();
+        let shares = split(&secret, 3, 5, &mut thread_rng())?;
+        assert_eq!(shares.len(), 5);
+
+        // Any 3 of the 5 shares recover the secret
+        assert_eq!(combine(&shares[..3])?, secret);
+        assert_eq!(combine(&[shares[4].clone(), shares[1].clone(), shares[3].clone()])?, secret);
+        assert_eq!(combine(&shares)?, secret);
+
+        // 2 shares interpolate to something else
+        assert_ne!(combine(&shares[..2])?, secret);
+        assert_ne!(combine(&[shares[2].clone(), shares[4].clone()])?, secret);
+
+        assert!(split(&secret, 6, 5, &mut thread_rng()).is_err());
+        assert!(combine(&[shares[0].clone(), shares[0].clone()]).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,86 @@
+//! Best-effort shredding of the plaintext files the demos leave behind.
+//!
+//! With `--shred`, files are overwritten with random bytes and synced before
+//! they are unlinked, and the demo's temp directory moves to a RAM-backed
+//! filesystem (`/dev/shm`) when there is one, so plaintext never reaches a
+//! disk in the first place.
+//!
+//! Overwriting in place is only meaningful on a plain magnetic disk. SSDs
+//! and flash remap writes to fresh cells (wear levelling), copy-on-write
+//! and journaling filesystems (btrfs, ZFS, APFS, ext4 with `data=journal`)
+//! write the new bytes elsewhere, and snapshots or backups keep their own
+//! copies. In all of these the old plaintext can survive until the device
+//! reuses the blocks. tmpfs avoids the disk, except for pages that get
+//! swapped out. Full-disk encryption is the real defence.
+
+use rand::{thread_rng, RngCore};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Turn shredding on for the rest of the process (the global `--shred`).
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Overwrite `path` with random bytes, sync, then delete it.
+pub fn shred_file(path: &Path) -> io::Result<()> {
+    overwrite(path)?;
+    fs::remove_file(path)
+}
+
+fn overwrite(path: &Path) -> io::Result<()> {
+    let mut file = OpenOptions::new().write(true).open(path)?;
+    let mut remaining = file.metadata()?.len();
+    let mut buffer = vec![0u8; 64 * 1024];
+
+    file.seek(SeekFrom::Start(0))?;
+    while remaining > 0 {
+        let len = remaining.min(buffer.len() as u64) as usize;
+        thread_rng().fill_bytes(&mut buffer[..len]);
+        file.write_all(&buffer[..len])?;
+        remaining -= len as u64;
+    }
+    file.sync_all()
+}
+
+/// Where to keep plaintext temp files: a RAM-backed directory if there is a
+/// writable one, otherwise the system temp directory.
+pub fn temp_base() -> PathBuf {
+    let shm = Path::new("/dev/shm");
+    let writable = fs::metadata(shm).is_ok_and(|metadata| metadata.is_dir() && !metadata.permissions().readonly());
+    if writable {
+        shm.to_path_buf()
+    } else {
+        std::env::temp_dir()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shred_overwrites_before_removing() -> io::Result<()> {
+        let path = std::env::temp_dir().join("shred_test.txt");
+        let plaintext = b"account 4111-1111-1111-1111 pin 1234".repeat(3000);
+        fs::write(&path, &plaintext)?;
+
+        overwrite(&path)?;
+        let overwritten = fs::read(&path)?;
+        assert_eq!(overwritten.len(), plaintext.len());
+        assert!(!overwritten.windows(19).any(|window| window == b"4111-1111-1111-1111"));
+
+        fs::write(&path, &plaintext)?;
+        shred_file(&path)?;
+        assert!(!path.exists());
+        Ok(())
+    }
+}
@@ -0,0 +1,599 @@
+//! Chunked container format for files too large to encrypt in one piece.
+//!
+//! Layout:
+//!
+//! ```text
+//! "SLST" | u32 LE header length | bcs(StreamHeader) | frame*
+//! frame = u32 LE ciphertext length | AES-256-GCM(chunk)
+//! ```
+//!
+//! The header's `EncryptedObject` is a Plain-mode Seal encryption: it holds
+//! the threshold-wrapped key and no data. Each chunk is sealed with a key
+//! derived from it and a nonce built from the chunk index plus a "final chunk"
+//! flag, so reordered, dropped or truncated chunks fail authentication.
+//!
+//! Chunk boundaries are either every `chunk_size` bytes or content-defined
+//! (see `ChunkingStrategy`). Decryption doesn't need to know which: the
+//! header only records the largest chunk size, to bound frame lengths.
+
+use anyhow::{Context, Result};
+use crypto::{Ciphertext, EncryptedObject};
+use fastcrypto::aes::{Aes256Gcm, AesKey, AuthenticatedCipher, InitializationVector};
+use fastcrypto::hash::{HashFunction, Sha3_256};
+use fastcrypto::traits::ToFromBytes;
+use serde::{Deserialize, Serialize};
+use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
+use typenum::U12;
+
+pub const STREAM_MAGIC: &[u8; 4] = b"SLST";
+
+const MAX_HEADER_LEN: u32 = 1024 * 1024;
+const TAG_SIZE: usize = 16;
+const CHUNK_KEY_DOMAIN: &[u8] = b"seal-demo/stream-chunk-key/v1";
+
+/// Gear table for content-defined chunking: 256 fixed pseudo-random words
+/// (SplitMix64 from seed 0), so the same content always cuts the same way.
+const GEAR: [u64; 256] = {
+    let mut table = [0u64; 256];
+    let mut state = 0u64;
+    let mut i = 0;
+    while i < 256 {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+};
+
+/// Where chunk boundaries fall.
+///
+/// `Fixed` cuts every `size` bytes, so inserting a byte early in a file
+/// shifts every later chunk. `ContentDefined` cuts where a rolling (gear)
+/// hash of the last 64 bytes hits a pattern expected once every `avg`
+/// bytes, within `min..=max`; after an insertion the boundaries resync
+/// within a chunk or two and the rest of the chunks are unchanged.
+///
+/// Only the plaintext chunks (see `chunk_digests`) line up across versions
+/// of a file: each container has its own key and seals chunks by index, so
+/// ciphertexts never repeat.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChunkingStrategy {
+    Fixed(usize),
+    ContentDefined { min: usize, avg: usize, max: usize },
+}
+
+impl ChunkingStrategy {
+    pub fn max_chunk(&self) -> usize {
+        match *self {
+            ChunkingStrategy::Fixed(size) => size,
+            ChunkingStrategy::ContentDefined { max, .. } => max,
+        }
+    }
+
+    /// The same strategy with no chunk larger than `limit` bytes.
+    pub fn within(self, limit: usize) -> Self {
+        let limit = limit.max(1);
+        match self {
+            ChunkingStrategy::Fixed(size) => ChunkingStrategy::Fixed(size.min(limit)),
+            ChunkingStrategy::ContentDefined { min, avg, max } => {
+                let max = max.min(limit);
+                let avg = avg.min(max);
+                ChunkingStrategy::ContentDefined { min: min.min(avg), avg, max }
+            }
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        let max = self.max_chunk();
+        if max == 0 || max > u32::MAX as usize {
+            anyhow::bail!("Chunk size must be between 1 and {} bytes", u32::MAX);
+        }
+        if let ChunkingStrategy::ContentDefined { min, avg, max } = *self {
+            if min == 0 || min > avg || avg > max {
+                anyhow::bail!("Content-defined chunking needs 0 < min <= avg <= max, got {}/{}/{}", min, avg, max);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Length of the first chunk of `data` (which must hold `max` bytes unless
+/// the input ends sooner).
+fn content_defined_cut(data: &[u8], min: usize, avg: usize, max: usize) -> usize {
+    if data.len() <= min {
+        return data.len();
+    }
+    let bits = avg.next_power_of_two().trailing_zeros();
+    let mask = if bits == 0 { 0 } else { !0u64 << (64 - bits) };
+
+    let mut hash = 0u64;
+    let end = data.len().min(max);
+    for (i, byte) in data.iter().enumerate().take(end).skip(min) {
+        hash = (hash << 1).wrapping_add(GEAR[*byte as usize]);
+        if hash & mask == 0 {
+            return i + 1;
+        }
+    }
+    end
+}
+
+/// Splits a reader into chunks according to a `ChunkingStrategy`.
+pub struct Chunker {
+    strategy: ChunkingStrategy,
+    pending: Vec<u8>,
+}
+
+impl Chunker {
+    pub fn new(strategy: ChunkingStrategy) -> Self {
+        Chunker { strategy, pending: Vec::new() }
+    }
+
+    /// The next chunk, or an empty one at end of input.
+    pub fn next_chunk<R: Read>(&mut self, reader: &mut R) -> Result<Vec<u8>> {
+        match self.strategy {
+            ChunkingStrategy::Fixed(size) => read_chunk(reader, size),
+            ChunkingStrategy::ContentDefined { min, avg, max } => {
+                if self.pending.len() < max {
+                    reader
+                        .take((max - self.pending.len()) as u64)
+                        .read_to_end(&mut self.pending)?;
+                }
+                let cut = content_defined_cut(&self.pending, min, avg, max);
+                let rest = self.pending.split_off(cut);
+                Ok(std::mem::replace(&mut self.pending, rest))
+            }
+        }
+    }
+}
+
+/// SHA3-256 of every chunk `strategy` cuts `reader` into, the keys a dedup
+/// store would index chunks by. They reveal which chunks are equal, so they
+/// are as sensitive as the file's size and must not go in the container.
+pub fn chunk_digests<R: Read>(reader: &mut R, strategy: ChunkingStrategy) -> Result<Vec<[u8; 32]>> {
+    strategy.validate()?;
+    let mut chunker = Chunker::new(strategy);
+    let mut digests = Vec::new();
+    loop {
+        let chunk = chunker.next_chunk(reader)?;
+        if chunk.is_empty() {
+            return Ok(digests);
+        }
+        digests.push(Sha3_256::digest(&chunk).digest);
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct StreamHeader {
+    /// Largest chunk in the stream
+    pub chunk_size: u32,
+    pub encrypted_object: EncryptedObject,
+}
+
+/// Stateless chunk sealer shared by the encrypt and decrypt paths.
+pub struct ChunkCipher {
+    cipher: Aes256Gcm<U12>,
+}
+
+impl ChunkCipher {
+    /// Derive the chunk key from the symmetric key wrapped by Seal.
+    pub fn new(stream_key: &[u8]) -> Result<Self> {
+        let mut input = CHUNK_KEY_DOMAIN.to_vec();
+        input.extend_from_slice(stream_key);
+        let chunk_key = Sha3_256::digest(&input).digest;
+        let key = AesKey::from_bytes(&chunk_key).map_err(|e| anyhow::anyhow!("Invalid chunk key: {}", e))?;
+        Ok(ChunkCipher { cipher: Aes256Gcm::new(key) })
+    }
+
+    fn nonce(index: u64, is_last: bool) -> Result<InitializationVector<U12>> {
+        let mut nonce = [0u8; 12];
+        nonce[..8].copy_from_slice(&index.to_be_bytes());
+        nonce[11] = is_last as u8;
+        InitializationVector::from_bytes(&nonce).map_err(|e| anyhow::anyhow!("Invalid nonce: {}", e))
+    }
+
+    pub fn seal(&self, index: u64, is_last: bool, plaintext: &[u8]) -> Result<Vec<u8>> {
+        Ok(self.cipher.encrypt_authenticated(&Self::nonce(index, is_last)?, b"", plaintext))
+    }
+
+    pub fn open(&self, index: u64, is_last: bool, ciphertext: &[u8]) -> Result<Vec<u8>> {
+        self.cipher
+            .decrypt_authenticated(&Self::nonce(index, is_last)?, b"", ciphertext)
+            .map_err(|_| anyhow::anyhow!("Chunk {} failed authentication", index))
+    }
+}
+
+pub fn write_header<W: Write>(writer: &mut W, header: &StreamHeader) -> Result<()> {
+    let header_bytes = bcs::to_bytes(header)?;
+    writer.write_all(STREAM_MAGIC)?;
+    writer.write_all(&(header_bytes.len() as u32).to_le_bytes())?;
+    writer.write_all(&header_bytes)?;
+    Ok(())
+}
+
+pub fn read_header<R: Read>(reader: &mut R) -> Result<StreamHeader> {
+    let mut magic = [0u8; 4];
+    reader
+        .read_exact(&mut magic)
+        .context("File is too short to be a streaming container")?;
+    if &magic != STREAM_MAGIC {
+        anyhow::bail!("Not a streaming container (bad magic)");
+    }
+
+    let mut len_bytes = [0u8; 4];
+    reader.read_exact(&mut len_bytes).context("Truncated stream header")?;
+    let header_len = u32::from_le_bytes(len_bytes);
+    if header_len > MAX_HEADER_LEN {
+        anyhow::bail!("Stream header length {} is implausibly large", header_len);
+    }
+
+    let mut header_bytes = vec![0u8; header_len as usize];
+    reader.read_exact(&mut header_bytes).context("Truncated stream header")?;
+    let header: StreamHeader = bcs::from_bytes(&header_bytes).context("Corrupt stream header")?;
+    if header.chunk_size == 0 {
+        anyhow::bail!("Corrupt stream header: chunk size is zero");
+    }
+    Ok(header)
+}
+
+/// Read up to `chunk_size` bytes, stopping early only at end of input.
+pub fn read_chunk<R: Read>(reader: &mut R, chunk_size: usize) -> Result<Vec<u8>> {
+    let mut chunk = Vec::with_capacity(chunk_size);
+    reader.take(chunk_size as u64).read_to_end(&mut chunk)?;
+    Ok(chunk)
+}
+
+pub fn write_frame<W: Write>(writer: &mut W, ciphertext: &[u8]) -> Result<()> {
+    writer.write_all(&(ciphertext.len() as u32).to_le_bytes())?;
+    writer.write_all(ciphertext)?;
+    Ok(())
+}
+
+/// Read the next frame, or `None` at a clean end of stream.
+pub fn read_frame<R: Read>(reader: &mut R, chunk_size: u32) -> Result<Option<Vec<u8>>> {
+    let mut len_bytes = [0u8; 4];
+    let mut filled = 0;
+    while filled < len_bytes.len() {
+        match reader.read(&mut len_bytes[filled..]) {
+            Ok(0) if filled == 0 => return Ok(None),
+            Ok(0) => anyhow::bail!("Truncated stream: partial frame length"),
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    let frame_len = u32::from_le_bytes(len_bytes) as usize;
+    if frame_len > chunk_size as usize + TAG_SIZE {
+        anyhow::bail!("Corrupt stream: frame of {} bytes exceeds chunk size {}", frame_len, chunk_size);
+    }
+
+    let mut frame = vec![0u8; frame_len];
+    reader
+        .read_exact(&mut frame)
+        .context("Truncated stream: frame shorter than its declared length")?;
+    Ok(Some(frame))
+}
+
+/// Total plaintext length of the frames from the reader's position on, from
+/// the frame lengths alone (each frame is its chunk plus a tag), so nothing is
+/// decrypted. The reader is left where it started. Lengths are only trusted
+/// once the frames authenticate.
+pub fn plaintext_len<R: Read + Seek>(reader: &mut R, chunk_size: u32) -> Result<u64> {
+    let start = reader.stream_position()?;
+    let end = reader.seek(SeekFrom::End(0))?;
+    reader.seek(SeekFrom::Start(start))?;
+
+    let mut total = 0u64;
+    let mut position = start;
+    while position < end {
+        let mut len_bytes = [0u8; 4];
+        reader
+            .read_exact(&mut len_bytes)
+            .context("Truncated stream: partial frame length")?;
+        let frame_len = u32::from_le_bytes(len_bytes) as u64;
+        if frame_len < TAG_SIZE as u64 || frame_len > chunk_size as u64 + TAG_SIZE as u64 {
+            anyhow::bail!("Corrupt stream: frame of {} bytes for chunk size {}", frame_len, chunk_size);
+        }
+        position = reader.seek(SeekFrom::Current(frame_len as i64))?;
+        total += frame_len - TAG_SIZE as u64;
+    }
+    if position > end {
+        anyhow::bail!("Truncated stream: frame shorter than its declared length");
+    }
+
+    reader.seek(SeekFrom::Start(start))?;
+    Ok(total)
+}
+
+/// Encrypt everything from `reader` as frames, holding at most two chunks in
+/// memory. Always emits at least one (final) frame, even for empty input.
+pub fn encrypt_frames<R: Read, W: Write>(
+    reader: &mut R,
+    writer: &mut W,
+    cipher: &ChunkCipher,
+    chunking: ChunkingStrategy,
+) -> Result<u64> {
+    let mut chunker = Chunker::new(chunking);
+    let mut index = 0u64;
+    let mut total = 0u64;
+    let mut current = chunker.next_chunk(reader)?;
+
+    loop {
+        let next = if current.is_empty() {
+            Vec::new()
+        } else {
+            chunker.next_chunk(reader)?
+        };
+        let is_last = next.is_empty();
+
+        write_frame(writer, &cipher.seal(index, is_last, &current)?)?;
+        total += current.len() as u64;
+
+        if is_last {
+            return Ok(total);
+        }
+        current = next;
+        index += 1;
+    }
+}
+
+/// How far a checkpointed encryption got: the first `chunks` frames are
+/// durably in the output, which is `output_len` bytes long up to them.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct Checkpoint {
+    /// Frames written; the next chunk is sealed with this index.
+    pub chunks: u64,
+    /// Plaintext bytes those frames cover.
+    pub plaintext_offset: u64,
+    /// Output length after those frames, header included.
+    pub output_len: u64,
+}
+
+/// `encrypt_frames` picking up at `from`, with `reader` positioned at
+/// `from.plaintext_offset` and `writer` at `from.output_len`.
+///
+/// `checkpoint` gets the progress after every `every` frames, but never after
+/// the final one, which completes the stream. It must make the frames written
+/// so far durable before recording that progress. Chunks are sealed
+/// sequentially. Returns the plaintext length of the whole stream.
+pub fn encrypt_frames_resumable<R: Read, W: Write>(
+    reader: &mut R,
+    writer: &mut W,
+    cipher: &ChunkCipher,
+    chunking: ChunkingStrategy,
+    from: Checkpoint,
+    every: u64,
+    mut checkpoint: impl FnMut(&mut W, Checkpoint) -> Result<()>,
+) -> Result<u64> {
+    // Chunks are cut from their own start, so cuts after a resume match
+    // those of an uninterrupted run
+    let mut chunker = Chunker::new(chunking);
+    let mut progress = from;
+    let mut current = chunker.next_chunk(reader)?;
+
+    loop {
+        let next = if current.is_empty() {
+            Vec::new()
+        } else {
+            chunker.next_chunk(reader)?
+        };
+        let is_last = next.is_empty();
+
+        let frame = cipher.seal(progress.chunks, is_last, &current)?;
+        write_frame(writer, &frame)?;
+        progress.chunks += 1;
+        progress.plaintext_offset += current.len() as u64;
+        progress.output_len += (4 + frame.len()) as u64;
+
+        if is_last {
+            writer.flush()?;
+            return Ok(progress.plaintext_offset);
+        }
+        if progress.chunks.is_multiple_of(every.max(1)) {
+            checkpoint(writer, progress)?;
+        }
+        current = next;
+    }
+}
+
+/// Parallel variant of `encrypt_frames`: up to `workers` chunks are read,
+/// sealed on one thread each, and written in index order before the next
+/// batch is read. Memory stays around `2 × max chunk × workers` (plaintext
+/// and ciphertext of one batch), and the output is interchangeable with the
+/// sequential path.
+pub fn encrypt_frames_parallel<R: Read, W: Write>(
+    reader: &mut R,
+    writer: &mut W,
+    cipher: &ChunkCipher,
+    chunking: ChunkingStrategy,
+    workers: usize,
+) -> Result<u64> {
+    let mut chunker = Chunker::new(chunking);
+    let mut index = 0u64;
+    let mut total = 0u64;
+    let mut next = chunker.next_chunk(reader)?;
+    let mut done = false;
+
+    while !done {
+        let mut batch = Vec::with_capacity(workers);
+        while batch.len() < workers && !done {
+            let current = std::mem::take(&mut next);
+            if !current.is_empty() {
+                next = chunker.next_chunk(reader)?;
+            }
+            done = next.is_empty();
+            batch.push((index, done, current));
+            index += 1;
+        }
+
+        let sealed = std::thread::scope(|scope| {
+            let handles: Vec<_> = batch
+                .iter()
+                .map(|(index, is_last, chunk)| scope.spawn(move || cipher.seal(*index, *is_last, chunk)))
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().expect("chunk encryption thread panicked"))
+                .collect::<Result<Vec<_>>>()
+        })?;
+
+        for (ciphertext, (_, _, chunk)) in sealed.iter().zip(&batch) {
+            write_frame(writer, ciphertext)?;
+            total += chunk.len() as u64;
+        }
+    }
+
+    Ok(total)
+}
+
+/// Decrypt frames from `reader` into `writer`, returning plaintext bytes
+/// written. Output is produced chunk by chunk, so on error the writer may
+/// already hold a verified prefix: callers must discard it.
+pub fn decrypt_frames<R: Read, W: Write>(
+    reader: &mut R,
+    writer: &mut W,
+    cipher: &ChunkCipher,
+    chunk_size: u32,
+) -> Result<u64> {
+    let mut index = 0u64;
+    let mut total = 0u64;
+    let mut frame = read_frame(reader, chunk_size)?
+        .ok_or_else(|| anyhow::anyhow!("Truncated stream: no chunks after header"))?;
+
+    loop {
+        let next = read_frame(reader, chunk_size)?;
+        let is_last = next.is_none();
+
+        let plaintext = cipher.open(index, is_last, &frame).map_err(|e| {
+            if is_last {
+                anyhow::anyhow!("Truncated or corrupt stream: chunk {} is not a valid final chunk", index)
+            } else {
+                e
+            }
+        })?;
+        writer
+            .write_all(&plaintext)
+            .with_context(|| format!("Failed writing decrypted chunk {}", index))?;
+        total += plaintext.len() as u64;
+
+        match next {
+            Some(next_frame) => frame = next_frame,
+            None => break,
+        }
+        index += 1;
+    }
+
+    writer.flush()?;
+    Ok(total)
+}
+
+/// Move frames from `reader` to `writer` from one chunk key to another,
+/// keeping every chunk's boundaries, index and final flag. Only the chunk
+/// being resealed is ever in plaintext, and only in memory. Returns the
+/// plaintext bytes resealed; on error `writer` holds a partial stream.
+pub fn reseal_frames<R: Read, W: Write>(
+    reader: &mut R,
+    writer: &mut W,
+    old: &ChunkCipher,
+    new: &ChunkCipher,
+    chunk_size: u32,
+) -> Result<u64> {
+    let mut index = 0u64;
+    let mut total = 0u64;
+    let mut frame = read_frame(reader, chunk_size)?
+        .ok_or_else(|| anyhow::anyhow!("Truncated stream: no chunks after header"))?;
+
+    loop {
+        let next = read_frame(reader, chunk_size)?;
+        let is_last = next.is_none();
+
+        let plaintext = old.open(index, is_last, &frame).map_err(|e| {
+            if is_last {
+                anyhow::anyhow!("Truncated or corrupt stream: chunk {} is not a valid final chunk", index)
+            } else {
+                e
+            }
+        })?;
+        write_frame(writer, &new.seal(index, is_last, &plaintext)?)?;
+        total += plaintext.len() as u64;
+
+        match next {
+            Some(next_frame) => frame = next_frame,
+            None => break,
+        }
+        index += 1;
+    }
+
+    writer.flush()?;
+    Ok(total)
+}
+
+/// Decrypt just enough frames to return the first `n` plaintext bytes (all
+/// of them if the stream is shorter). Only those chunks are authenticated:
+/// corruption or truncation after them goes unnoticed.
+pub fn decrypt_prefix_frames<R: Read>(reader: &mut R, cipher: &ChunkCipher, chunk_size: u32, n: usize) -> Result<Vec<u8>> {
+    let mut prefix = Vec::with_capacity(n.min(chunk_size as usize));
+    let mut index = 0u64;
+    let mut frame = read_frame(reader, chunk_size)?
+        .ok_or_else(|| anyhow::anyhow!("Truncated stream: no chunks after header"))?;
+
+    while prefix.len() < n {
+        // Whether this is the final chunk is part of its nonce
+        let next = read_frame(reader, chunk_size)?;
+        prefix.extend(cipher.open(index, next.is_none(), &frame)?);
+        match next {
+            Some(next_frame) => frame = next_frame,
+            None => break,
+        }
+        index += 1;
+    }
+
+    prefix.truncate(n);
+    Ok(prefix)
+}
+
+/// Write a complete streaming container: header, then every chunk of `reader`,
+/// sealed on up to `workers` threads. `encrypted_object` must be the
+/// Plain-mode object that wraps `stream_key`.
+pub fn encrypt_stream<R: Read, W: Write>(
+    reader: &mut R,
+    writer: &mut W,
+    encrypted_object: EncryptedObject,
+    stream_key: &[u8],
+    chunking: ChunkingStrategy,
+    workers: usize,
+) -> Result<u64> {
+    chunking.validate()?;
+    if !matches!(encrypted_object.ciphertext, Ciphertext::Plain) {
+        anyhow::bail!("Streaming header must hold a Plain-mode object");
+    }
+
+    let header = StreamHeader {
+        chunk_size: chunking.max_chunk() as u32,
+        encrypted_object,
+    };
+    write_header(writer, &header)?;
+    let cipher = ChunkCipher::new(stream_key)?;
+    let total = if workers > 1 {
+        encrypt_frames_parallel(reader, writer, &cipher, chunking, workers)?
+    } else {
+        encrypt_frames(reader, writer, &cipher, chunking)?
+    };
+    writer.flush()?;
+    Ok(total)
+}
+
+/// Read a streaming container, recover its key with `unwrap_key` and decrypt
+/// every chunk into `writer`. See `decrypt_frames` for partial-output caveats.
+pub fn decrypt_stream<R: Read, W: Write>(
+    reader: &mut R,
+    writer: &mut W,
+    unwrap_key: impl FnOnce(&EncryptedObject) -> Result<Vec<u8>>,
+) -> Result<u64> {
+    let header = read_header(reader)?;
+    let stream_key = unwrap_key(&header.encrypted_object)?;
+    decrypt_frames(reader, writer, &ChunkCipher::new(&stream_key)?, header.chunk_size)
+}
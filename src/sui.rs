@@ -0,0 +1,215 @@
+//! Import a key-server fleet from a Sui full node (`sui` feature).
+//!
+//! Key servers are `<package>::key_server::KeyServer` objects. There is no
+//! RPC that lists objects by type, so discovery goes through the
+//! transactions that called `key_server::create_and_transfer_v1` of the
+//! package and collects the `KeyServer` objects they created. The public key
+//! sits in the object's fields or, for upgradable servers, in its version 1
+//! dynamic field.
+//!
+//! Master keys stay with the servers; the resulting session can only encrypt.
+
+use anyhow::{Context, Result};
+use crypto::ObjectID;
+use fastcrypto::groups::bls12381::G2Element;
+use serde_json::{json, Value};
+
+use crate::metrics::Metrics;
+use crate::session::{self, SealSession};
+
+/// `key_type` of a BLS12-381 Boneh-Franklin key server, the only kind the
+/// demo can encrypt to.
+const KEY_TYPE_BONEH_FRANKLIN_BLS12381: u64 = 0;
+/// Transactions fetched per `suix_queryTransactionBlocks` page.
+const PAGE_SIZE: u64 = 50;
+
+/// A key server as registered on chain.
+pub struct OnChainServer {
+    pub object_id: ObjectID,
+    pub name: String,
+    pub url: String,
+    pub public_key: G2Element,
+}
+
+pub struct ImportedFleet {
+    pub servers: Vec<OnChainServer>,
+}
+
+struct RpcClient<'a> {
+    url: &'a str,
+}
+
+impl RpcClient<'_> {
+    fn call(&self, method: &str, params: Value) -> Result<Value> {
+        let request = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+        let response: Value = ureq::post(self.url)
+            .send_json(request)
+            .with_context(|| format!("{} request to {} failed", method, self.url))?
+            .into_json()
+            .with_context(|| format!("{} returned invalid JSON", method))?;
+
+        if let Some(error) = response.get("error") {
+            anyhow::bail!("{} failed: {}", method, error);
+        }
+        response
+            .get("result")
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("{} returned no result", method))
+    }
+}
+
+/// Discover and validate every key server registered under `package`.
+pub fn fetch_fleet(rpc_url: &str, package: ObjectID) -> Result<ImportedFleet> {
+    let client = RpcClient { url: rpc_url };
+    let mut server_ids = Vec::new();
+    let mut cursor = Value::Null;
+
+    loop {
+        let page = client.call(
+            "suix_queryTransactionBlocks",
+            json!([
+                {
+                    "filter": { "MoveFunction": { "package": package.to_string(), "module": "key_server", "function": "create_and_transfer_v1" } },
+                    "options": { "showObjectChanges": true }
+                },
+                cursor,
+                PAGE_SIZE,
+                false
+            ]),
+        )?;
+        for transaction in page["data"].as_array().into_iter().flatten() {
+            for id in created_key_servers(transaction) {
+                if !server_ids.contains(&id) {
+                    server_ids.push(id);
+                }
+            }
+        }
+        if page["hasNextPage"].as_bool() != Some(true) {
+            break;
+        }
+        cursor = page["nextCursor"].clone();
+    }
+
+    if server_ids.is_empty() {
+        anyhow::bail!("No key servers are registered under package {}", package);
+    }
+
+    let mut servers = Vec::new();
+    for object_id in server_ids {
+        let object = client.call("sui_getObject", json!([object_id, { "showContent": true }]))?;
+        let mut fields = object["data"]["content"]["fields"].clone();
+        if fields.get("pk").is_none() {
+            let versioned = client.call(
+                "suix_getDynamicFieldObject",
+                json!([object_id, { "type": "u64", "value": "1" }]),
+            )?;
+            fields = versioned["data"]["content"]["fields"]["value"]["fields"].clone();
+        }
+        servers.push(parse_key_server(&object_id, &fields).with_context(|| format!("Key server {} is invalid", object_id))?);
+    }
+
+    Ok(ImportedFleet { servers })
+}
+
+/// IDs of the `KeyServer` objects a transaction created.
+fn created_key_servers(transaction: &Value) -> Vec<String> {
+    transaction["objectChanges"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|change| change["type"] == "created")
+        .filter(|change| change["objectType"].as_str().is_some_and(|t| t.ends_with("::key_server::KeyServer")))
+        .filter_map(|change| change["objectId"].as_str().map(str::to_string))
+        .collect()
+}
+
+/// A key server from its Move fields (`name`, `url`, `key_type`, `pk`),
+/// rejecting key types other than BLS12-381 Boneh-Franklin and public keys
+/// that aren't valid G2 points.
+fn parse_key_server(object_id: &str, fields: &Value) -> Result<OnChainServer> {
+    let key_type = match &fields["key_type"] {
+        Value::Number(n) => n.as_u64(),
+        Value::String(s) => s.parse().ok(),
+        _ => None,
+    };
+    if key_type != Some(KEY_TYPE_BONEH_FRANKLIN_BLS12381) {
+        anyhow::bail!("Unsupported key type {}", fields["key_type"]);
+    }
+
+    let pk: Vec<u8> = fields["pk"]
+        .as_array()
+        .ok_or_else(|| anyhow::anyhow!("Missing public key"))?
+        .iter()
+        .map(|byte| byte.as_u64().and_then(|byte| u8::try_from(byte).ok()))
+        .collect::<Option<_>>()
+        .ok_or_else(|| anyhow::anyhow!("Public key is not a byte vector"))?;
+
+    Ok(OnChainServer {
+        object_id: session::decode_object_id(object_id)?,
+        name: fields["name"].as_str().unwrap_or_default().to_string(),
+        url: fields["url"].as_str().unwrap_or_default().to_string(),
+        public_key: session::decode_public_key(&hex::encode(pk))?,
+    })
+}
+
+impl ImportedFleet {
+    /// A public-only session for this fleet. Seal keeps no threshold on
+    /// chain (each encryption picks its own), so without `threshold` a
+    /// majority of the servers is required.
+    pub fn into_session(self, package_id: ObjectID, threshold: Option<u8>) -> Result<SealSession> {
+        let count = self.servers.len();
+        let threshold = threshold.unwrap_or((count / 2 + 1) as u8);
+        if threshold == 0 || threshold as usize > count {
+            anyhow::bail!("Threshold {} is invalid for {} key servers", threshold, count);
+        }
+        Ok(SealSession {
+            package_id,
+            key_servers: self.servers.iter().map(|server| server.object_id).collect(),
+            public_keys: self.servers.iter().map(|server| server.public_key).collect(),
+            threshold,
+            master_keys: None,
+            on_event: None,
+            metrics: Metrics::global(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fastcrypto::serde_helpers::ToFromByteArray;
+
+    #[test]
+    fn test_parse_key_server() -> Result<()> {
+        let (_, public_key) = crypto::ibe::generate_key_pair(&mut rand::thread_rng());
+        let object_id = format!("0x{}", "ab".repeat(32));
+        let mut fields = json!({
+            "name": "mysten-testnet-1",
+            "url": "https://seal-key-server-testnet-1.mystenlabs.com",
+            "key_type": 0,
+            "pk": public_key.to_byte_array().to_vec(),
+        });
+
+        let server = parse_key_server(&object_id, &fields)?;
+        assert_eq!(server.public_key, public_key);
+        assert_eq!(server.name, "mysten-testnet-1");
+
+        let transaction = json!({ "objectChanges": [
+            { "type": "created", "objectType": "0x2::coin::Coin<0x2::sui::SUI>", "objectId": "0x1" },
+            { "type": "created", "objectType": "0xabc::key_server::KeyServer", "objectId": object_id },
+        ]});
+        assert_eq!(created_key_servers(&transaction), vec![object_id.clone()]);
+
+        fields["key_type"] = json!(1);
+        assert!(parse_key_server(&object_id, &fields).is_err());
+        fields["key_type"] = json!("0");
+        fields["pk"] = json!(vec![1u8; 96]);
+        assert!(parse_key_server(&object_id, &fields).is_err());
+
+        let fleet = ImportedFleet { servers: vec![server] };
+        let session = fleet.into_session(ObjectID::random(), None)?;
+        assert_eq!(session.threshold, 1);
+        assert!(session.master_keys.is_none());
+        Ok(())
+    }
+}
@@ -0,0 +1,45 @@
+//! Random payloads and files for demos, benches and tests.
+//!
+//! Pass a seed to `seeded_rng` to make a run reproducible; without one the
+//! generator is seeded from the OS.
+
+use anyhow::Result;
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
+use std::path::{Path, PathBuf};
+
+pub fn seeded_rng(seed: Option<u64>) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    }
+}
+
+pub fn random_bytes<R: RngCore>(len: usize, rng: &mut R) -> Vec<u8> {
+    let mut bytes = vec![0u8; len];
+    rng.fill_bytes(&mut bytes);
+    bytes
+}
+
+/// Write `len` random bytes to a new `random-<hex>.bin` file in `dir`.
+pub fn random_file<R: RngCore>(dir: &Path, len: usize, rng: &mut R) -> Result<PathBuf> {
+    let path = dir.join(format!("random-{:016x}.bin", rng.next_u64()));
+    std::fs::write(&path, random_bytes(len, rng))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seed_is_reproducible() -> Result<()> {
+        assert_eq!(random_bytes(64, &mut seeded_rng(Some(7))), random_bytes(64, &mut seeded_rng(Some(7))));
+        assert_ne!(random_bytes(64, &mut seeded_rng(Some(7))), random_bytes(64, &mut seeded_rng(Some(8))));
+
+        let path = random_file(&std::env::temp_dir(), 1000, &mut seeded_rng(None))?;
+        assert_eq!(std::fs::read(&path)?.len(), 1000);
+        let _ = std::fs::remove_file(&path);
+        Ok(())
+    }
+}
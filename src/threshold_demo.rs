@@ -1,17 +1,68 @@
 use anyhow::Result;
+use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
 use crypto::{
-    ibe::{generate_key_pair, extract},
-    seal_encrypt, seal_decrypt,
+    ibe::{generate_key_pair, verify_user_secret_key},
     create_full_id, EncryptionInput, IBEPublicKeys, IBEUserSecretKeys, ObjectID
 };
-use fastcrypto::groups::bls12381::{G2Element, Scalar};
+use fastcrypto::groups::bls12381::{G1Element, G2Element, Scalar};
+use fastcrypto::traits::AllowedRng;
 use rand::thread_rng;
 use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::output::outln;
+use crate::policy::{Policy, PolicyCiphertext};
+use crate::report::OpCounter;
+use crate::session::random_object_id;
+
+/// Payload and decryptions per threshold in `bench_by_threshold`.
+const BENCH_PAYLOAD_BYTES: usize = 1024;
+const BENCH_ITERATIONS: usize = 20;
+/// Decryptions per threshold and fetch strategy in `latency_by_threshold`.
+const LATENCY_ITERATIONS: usize = 5;
 
 pub struct ThresholdDemo {
     pub package_id: ObjectID,
     pub identity: Vec<u8>,
     pub key_servers: Vec<KeyServer>,
+    /// Threshold for `run_all_demos`; `None` tries 2 and 3.
+    pub threshold: Option<u8>,
+    /// Tallies operations, including those of parallel key fetches.
+    pub counter: OpCounter,
+}
+
+/// Decrypt latency at one threshold, from `bench_by_threshold`.
+pub struct ThresholdTiming {
+    pub threshold: u8,
+    pub p50: Duration,
+    pub mean: Duration,
+}
+
+/// How a client collects user secret keys from the servers it needs.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum KeyFetch {
+    /// One request after another: latency grows with the threshold.
+    Serial,
+    /// All requests at once: latency is set by the slowest server.
+    Parallel,
+}
+
+impl KeyFetch {
+    /// For `latency-sim --fetch`.
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "serial" => Ok(KeyFetch::Serial),
+            "parallel" => Ok(KeyFetch::Parallel),
+            other => anyhow::bail!("Unknown fetch strategy \"{}\", expected serial or parallel", other),
+        }
+    }
+}
+
+/// End-to-end decrypt latency at one threshold, from `latency_by_threshold`.
+pub struct LatencyTiming {
+    pub threshold: u8,
+    pub fetch: KeyFetch,
+    pub mean: Duration,
 }
 
 #[derive(Clone)]
@@ -23,10 +74,10 @@ pub struct KeyServer {
 }
 
 impl KeyServer {
-    fn new(name: String) -> Self {
-        let (master_key, public_key) = generate_key_pair(&mut thread_rng());
+    fn new(name: String, rng: &mut impl AllowedRng) -> Self {
+        let (master_key, public_key) = generate_key_pair(rng);
         KeyServer {
-            object_id: ObjectID::random(),
+            object_id: random_object_id(rng),
             master_key,
             public_key,
             name,
@@ -36,25 +87,43 @@ impl KeyServer {
 
 impl ThresholdDemo {
     pub fn new(num_servers: usize) -> Self {
-        println!("🏗️  Setting up threshold encryption with {} key servers...", num_servers);
+        Self::new_with_rng(num_servers, &mut thread_rng())
+    }
+    
+    /// `new`, drawing the package ID, server IDs and key pairs from `rng`.
+    pub fn new_with_rng(num_servers: usize, rng: &mut impl AllowedRng) -> Self {
+        outln!("🏗️  Setting up threshold encryption with {} key servers...", num_servers);
         
         let mut key_servers = Vec::new();
         
         for i in 0..num_servers {
-            let server = KeyServer::new(format!("KeyServer-{}", i + 1));
-            println!("   📡 Created {}: {}", server.name, server.object_id);
+            let server = KeyServer::new(format!("KeyServer-{}", i + 1), rng);
+            outln!("   📡 Created {}: {}", server.name, server.object_id);
             key_servers.push(server);
         }
         
         ThresholdDemo {
-            package_id: ObjectID::random(),
+            package_id: random_object_id(rng),
             identity: b"sensitive-document@company.com".to_vec(),
             key_servers,
+            threshold: None,
+            counter: OpCounter::default(),
         }
     }
     
+    pub fn with_threshold(mut self, threshold: Option<u8>) -> Self {
+        self.threshold = threshold;
+        self
+    }
+    
+    /// Tally on `counter`, so `report::measure` sees this demo's work.
+    pub fn with_counter(mut self, counter: OpCounter) -> Self {
+        self.counter = counter;
+        self
+    }
+    
     pub fn demo_threshold_encryption(&self, threshold: u8) -> Result<()> {
-        println!("\n🚀 === Threshold Encryption Demo ({}/{}) ===", threshold, self.key_servers.len());
+        outln!("\n🚀 === Threshold Encryption Demo ({}/{}) ===", threshold, self.key_servers.len());
         
         let message = format!("Secret message requiring {}-out-of-{} key servers for decryption.", threshold, self.key_servers.len());
         
@@ -62,13 +131,13 @@ impl ThresholdDemo {
         let object_ids: Vec<ObjectID> = self.key_servers.iter().map(|s| s.object_id).collect();
         let public_keys: Vec<G2Element> = self.key_servers.iter().map(|s| s.public_key).collect();
         
-        println!("🔒 Encrypting with threshold {}/{}...", threshold, self.key_servers.len());
-        println!("   📝 Message: \"{}\"", message);
-        println!("   🆔 Identity: \"{}\"", String::from_utf8_lossy(&self.identity));
-        println!("   📡 Available servers: {}", self.key_servers.len());
+        outln!("🔒 Encrypting with threshold {}/{}...", threshold, self.key_servers.len());
+        outln!("   📝 Message: \"{}\"", message);
+        outln!("   🆔 Identity: \"{}\"", String::from_utf8_lossy(&self.identity));
+        outln!("   📡 Available servers: {}", self.key_servers.len());
         
         // Encrypt the message
-        let (encrypted_object, _) = seal_encrypt(
+        let (encrypted_object, _) = self.counter.seal_encrypt(
             self.package_id,
             self.identity.clone(),
             object_ids,
@@ -80,26 +149,26 @@ impl ThresholdDemo {
             },
         )?;
         
-        println!("   ✅ Encryption successful!");
+        outln!("   ✅ Encryption successful!");
         
         // Test decryption with exactly threshold number of servers
-        println!("\n🔓 Testing decryption with minimum servers ({}):", threshold);
+        outln!("\n🔓 Testing decryption with minimum servers ({}):", threshold);
         self.test_decryption_with_servers(&encrypted_object, &public_keys, threshold as usize, true)?;
         
         // Test decryption with more than threshold servers (if possible)
         if self.key_servers.len() > threshold as usize {
             let extra_servers = std::cmp::min(self.key_servers.len(), threshold as usize + 1);
-            println!("\n🔓 Testing decryption with extra servers ({}):", extra_servers);
+            outln!("\n🔓 Testing decryption with extra servers ({}):", extra_servers);
             self.test_decryption_with_servers(&encrypted_object, &public_keys, extra_servers, true)?;
         }
         
         // Test failure with insufficient servers
         if threshold > 1 {
-            println!("\n❌ Testing decryption failure with insufficient servers ({}):", threshold - 1);
+            outln!("\n❌ Testing decryption failure with insufficient servers ({}):", threshold - 1);
             self.test_decryption_with_servers(&encrypted_object, &public_keys, (threshold - 1) as usize, false)?;
         }
         
-        println!("   ✅ Threshold encryption demo completed!");
+        outln!("   ✅ Threshold encryption demo completed!");
         Ok(())
     }
     
@@ -117,12 +186,12 @@ impl ThresholdDemo {
         let selected_servers = &self.key_servers[..num_servers];
         
         for server in selected_servers {
-            let secret_key = extract(&server.master_key, &full_id);
+            let secret_key = self.counter.extract(&server.master_key, &full_id);
             user_secret_keys.insert(server.object_id, secret_key);
-            println!("   🔑 Using secret key from {}", server.name);
+            outln!("   🔑 Using secret key from {}", server.name);
         }
         
-        let decrypt_result = seal_decrypt(
+        let decrypt_result = self.counter.seal_decrypt(
             encrypted_object,
             &IBEUserSecretKeys::BonehFranklinBLS12381(user_secret_keys),
             Some(&IBEPublicKeys::BonehFranklinBLS12381(public_keys.to_vec())),
@@ -131,10 +200,10 @@ impl ThresholdDemo {
         match (decrypt_result, should_succeed) {
             (Ok(decrypted), true) => {
                 let decrypted_message = String::from_utf8_lossy(&decrypted);
-                println!("   ✅ Decryption successful: \"{}\"", decrypted_message);
+                outln!("   ✅ Decryption successful: \"{}\"", decrypted_message);
             }
             (Err(e), false) => {
-                println!("   ✅ Decryption correctly failed: {}", e);
+                outln!("   ✅ Decryption correctly failed: {}", e);
             }
             (Ok(_), false) => {
                 anyhow::bail!("❌ Decryption unexpectedly succeeded when it should have failed");
@@ -148,10 +217,10 @@ impl ThresholdDemo {
     }
     
     pub fn demo_server_rotation(&self) -> Result<()> {
-        println!("\n🚀 === Key Server Rotation Demo ===");
+        outln!("\n🚀 === Key Server Rotation Demo ===");
         
         if self.key_servers.len() < 3 {
-            println!("   ⚠️  Need at least 3 servers for rotation demo");
+            outln!("   ⚠️  Need at least 3 servers for rotation demo");
             return Ok(());
         }
         
@@ -162,8 +231,8 @@ impl ThresholdDemo {
         let object_ids: Vec<ObjectID> = self.key_servers.iter().map(|s| s.object_id).collect();
         let public_keys: Vec<G2Element> = self.key_servers.iter().map(|s| s.public_key).collect();
         
-        println!("🔒 Encrypting document with all servers...");
-        let (encrypted_object, _) = seal_encrypt(
+        outln!("🔒 Encrypting document with all servers...");
+        let (encrypted_object, _) = self.counter.seal_encrypt(
             self.package_id,
             self.identity.clone(),
             object_ids,
@@ -182,25 +251,25 @@ impl ThresholdDemo {
             vec![1, 2],  // Second and third
         ];
         
-        println!("\n🔄 Testing different server combinations:");
+        outln!("\n🔄 Testing different server combinations:");
         
         for (i, combination) in server_combinations.iter().enumerate() {
             let server_names: Vec<String> = combination.iter()
                 .map(|&idx| self.key_servers[idx].name.clone())
                 .collect();
             
-            println!("\n   Combination {}: {} + {}", i + 1, server_names[0], server_names[1]);
+            outln!("\n   Combination {}: {} + {}", i + 1, server_names[0], server_names[1]);
             
             let full_id = create_full_id(&self.package_id, &self.identity);
             let mut user_secret_keys = HashMap::new();
             
             for &server_idx in combination {
                 let server = &self.key_servers[server_idx];
-                let secret_key = extract(&server.master_key, &full_id);
+                let secret_key = self.counter.extract(&server.master_key, &full_id);
                 user_secret_keys.insert(server.object_id, secret_key);
             }
             
-            let decrypt_result = seal_decrypt(
+            let decrypt_result = self.counter.seal_decrypt(
                 &encrypted_object,
                 &IBEUserSecretKeys::BonehFranklinBLS12381(user_secret_keys),
                 Some(&IBEPublicKeys::BonehFranklinBLS12381(public_keys.clone())),
@@ -209,27 +278,110 @@ impl ThresholdDemo {
             match decrypt_result {
                 Ok(decrypted) => {
                     if decrypted == message {
-                        println!("     ✅ Decryption successful with this combination");
+                        outln!("     ✅ Decryption successful with this combination");
                     } else {
-                        println!("     ❌ Decryption produced wrong result");
+                        outln!("     ❌ Decryption produced wrong result");
                     }
                 }
                 Err(e) => {
-                    println!("     ❌ Decryption failed: {}", e);
+                    outln!("     ❌ Decryption failed: {}", e);
                 }
             }
         }
         
-        println!("\n💡 This demonstrates how threshold encryption provides resilience:");
-        println!("   - Any {} servers out of {} can decrypt the document", threshold, self.key_servers.len());
-        println!("   - System continues working even if some servers are offline");
-        println!("   - No single server can decrypt alone (requires cooperation)");
+        outln!("\n💡 This demonstrates how threshold encryption provides resilience:");
+        outln!("   - Any {} servers out of {} can decrypt the document", threshold, self.key_servers.len());
+        outln!("   - System continues working even if some servers are offline");
+        outln!("   - No single server can decrypt alone (requires cooperation)");
+        
+        Ok(())
+    }
+    
+    /// A server rotates its key on its own schedule after a document was
+    /// sealed. Its share was encrypted to the old public key, so the new
+    /// secret key can't open it and any quorum including that server fails,
+    /// while the other servers' keys still validate. Checking each key
+    /// against the public key the document was sealed to shows which servers
+    /// to leave out of the quorum.
+    pub fn demo_independent_rotation(&self) -> Result<()> {
+        outln!("\n🚀 === Independent Key Rotation Demo ===");
+        
+        if self.key_servers.len() < 3 {
+            outln!("   ⚠️  Need at least 3 servers for independent rotation demo");
+            return Ok(());
+        }
+        
+        let threshold = 2u8;
+        let message = b"Contract sealed before the rotation";
+        let object_ids: Vec<ObjectID> = self.key_servers.iter().map(|s| s.object_id).collect();
+        let sealed_public_keys: Vec<G2Element> = self.key_servers.iter().map(|s| s.public_key).collect();
+        
+        outln!("🔒 Sealing a document to all {} servers ({} needed)...", self.key_servers.len(), threshold);
+        let (encrypted_object, _) = self.counter.seal_encrypt(
+            self.package_id,
+            self.identity.clone(),
+            object_ids,
+            &IBEPublicKeys::BonehFranklinBLS12381(sealed_public_keys.clone()),
+            threshold,
+            EncryptionInput::Aes256Gcm {
+                data: message.to_vec(),
+                aad: None,
+            },
+        )?;
+        
+        // The first server rotates; the rest keep their keys
+        let mut fleet = self.key_servers.clone();
+        let (master_key, public_key) = generate_key_pair(&mut thread_rng());
+        fleet[0].master_key = master_key;
+        fleet[0].public_key = public_key;
+        outln!("\n🔄 {} rotated its key; the other servers did not", fleet[0].name);
+        
+        let full_id = create_full_id(&self.package_id, &self.identity);
+        let user_secret_keys: Vec<G1Element> =
+            fleet.iter().map(|server| self.counter.extract(&server.master_key, &full_id)).collect();
+        let decrypt = |quorum: &[usize]| {
+            let keys = quorum.iter().map(|&i| (fleet[i].object_id, user_secret_keys[i])).collect();
+            self.counter.seal_decrypt(
+                &encrypted_object,
+                &IBEUserSecretKeys::BonehFranklinBLS12381(keys),
+                Some(&IBEPublicKeys::BonehFranklinBLS12381(sealed_public_keys.clone())),
+            )
+        };
+        
+        outln!("\n🔓 Decrypting with {} + {}:", fleet[0].name, fleet[1].name);
+        match decrypt(&[0, 1]) {
+            Ok(_) => anyhow::bail!("❌ A rotated key unexpectedly opened a share sealed to its old key"),
+            Err(e) => outln!("   ✅ Decryption correctly failed: {}", e),
+        }
+        
+        outln!("\n🔍 Checking each server's key against the one the document was sealed to:");
+        let mut quorum = Vec::new();
+        for (i, server) in fleet.iter().enumerate() {
+            if verify_user_secret_key(&user_secret_keys[i], &full_id, &sealed_public_keys[i]).is_ok() {
+                outln!("   ✅ {} still validates", server.name);
+                quorum.push(i);
+            } else {
+                outln!("   ❌ {} has rotated since; excluding it", server.name);
+            }
+        }
+        
+        quorum.truncate(threshold as usize);
+        let names: Vec<&str> = quorum.iter().map(|&i| fleet[i].name.as_str()).collect();
+        if decrypt(&quorum)? != message {
+            anyhow::bail!("❌ Decryption produced wrong result");
+        }
+        outln!("   ✅ Decrypted with {} once the rotated server was left out", names.join(" + "));
+        
+        outln!("\n💡 During a staggered rotation:");
+        outln!("   - Documents sealed before a server rotated need a quorum of servers that have not rotated since");
+        outln!("   - Each rotation costs one server of slack until old documents are re-encrypted");
+        outln!("   - Keep the public keys a document was sealed to, to tell stale servers apart");
         
         Ok(())
     }
     
     pub fn demo_access_patterns(&self) -> Result<()> {
-        println!("\n🚀 === Access Pattern Demo ===");
+        outln!("\n🚀 === Access Pattern Demo ===");
         
         let threshold = 2u8;
         
@@ -243,15 +395,15 @@ impl ThresholdDemo {
         let object_ids: Vec<ObjectID> = self.key_servers.iter().map(|s| s.object_id).collect();
         let public_keys: Vec<G2Element> = self.key_servers.iter().map(|s| s.public_key).collect();
         
-        println!("🗂️  Encrypting different document types:");
+        outln!("🗂️  Encrypting different document types:");
         
         for (filename, content) in &documents {
             let document_identity = format!("document:{}", filename).into_bytes();
             
-            println!("\n   📄 Document: {}", filename);
-            println!("      Identity: \"{}\"", String::from_utf8_lossy(&document_identity));
+            outln!("\n   📄 Document: {}", filename);
+            outln!("      Identity: \"{}\"", String::from_utf8_lossy(&document_identity));
             
-            let (encrypted_object, _) = seal_encrypt(
+            let (encrypted_object, _) = self.counter.seal_encrypt(
                 self.package_id,
                 document_identity.clone(),
                 object_ids.clone(),
@@ -264,7 +416,7 @@ impl ThresholdDemo {
             )?;
             
             // Simulate access attempt
-            println!("      🔓 Simulating authorized access...");
+            outln!("      🔓 Simulating authorized access...");
             
             let full_id = create_full_id(&self.package_id, &document_identity);
             let mut user_secret_keys = HashMap::new();
@@ -272,40 +424,605 @@ impl ThresholdDemo {
             // Use first `threshold` servers
             for i in 0..(threshold as usize) {
                 let server = &self.key_servers[i];
-                let secret_key = extract(&server.master_key, &full_id);
+                let secret_key = self.counter.extract(&server.master_key, &full_id);
                 user_secret_keys.insert(server.object_id, secret_key);
             }
             
-            let decrypted = seal_decrypt(
+            let decrypted = self.counter.seal_decrypt(
                 &encrypted_object,
                 &IBEUserSecretKeys::BonehFranklinBLS12381(user_secret_keys),
                 Some(&IBEPublicKeys::BonehFranklinBLS12381(public_keys.clone())),
             )?;
             
             if decrypted == *content {
-                println!("      ✅ Access granted - content verified");
+                outln!("      ✅ Access granted - content verified");
             } else {
-                println!("      ❌ Access failed - content mismatch");
+                outln!("      ❌ Access failed - content mismatch");
+            }
+        }
+        
+        outln!("\n💡 Each document has a unique identity, creating separate access controls");
+        outln!("   - Same key servers, same threshold, but different encryption keys");
+        outln!("   - Fine-grained access control per document/resource");
+        
+        Ok(())
+    }
+    
+    fn server(&self, object_id: &ObjectID) -> Result<&KeyServer> {
+        self.key_servers
+            .iter()
+            .find(|server| server.object_id == *object_id)
+            .ok_or_else(|| anyhow::anyhow!("Unknown key server {}", object_id))
+    }
+    
+    /// Encrypt `data` under several independent quorums.
+    ///
+    /// Each layer is `(servers, threshold, identity)` and is applied over the
+    /// BCS-serialized object of the previous one, so the first layer ends up
+    /// innermost. Every layer's quorum is needed to recover the data.
+    pub fn encrypt_nested(&self, data: &[u8], layers: &[(Vec<ObjectID>, u8, Vec<u8>)]) -> Result<Vec<u8>> {
+        if layers.is_empty() {
+            anyhow::bail!("Nested encryption needs at least one layer");
+        }
+        
+        let mut ciphertext = data.to_vec();
+        for (servers, threshold, identity) in layers {
+            let public_keys = servers
+                .iter()
+                .map(|object_id| Ok(self.server(object_id)?.public_key))
+                .collect::<Result<Vec<G2Element>>>()?;
+            
+            let (encrypted_object, _) = self.counter.seal_encrypt(
+                self.package_id,
+                identity.clone(),
+                servers.clone(),
+                &IBEPublicKeys::BonehFranklinBLS12381(public_keys),
+                *threshold,
+                EncryptionInput::Aes256Gcm {
+                    data: ciphertext,
+                    aad: None,
+                },
+            )?;
+            ciphertext = bcs::to_bytes(&encrypted_object)?;
+        }
+        
+        Ok(ciphertext)
+    }
+    
+    /// Peel `num_layers` layers of `encrypt_nested`, outermost first, using
+    /// keys only from the `available` servers.
+    pub fn decrypt_nested(&self, ciphertext: &[u8], num_layers: usize, available: &[ObjectID]) -> Result<Vec<u8>> {
+        let mut data = ciphertext.to_vec();
+        for layer in (0..num_layers).rev() {
+            let encrypted_object: crypto::EncryptedObject = bcs::from_bytes(&data)?;
+            let full_id = create_full_id(&encrypted_object.package_id, &encrypted_object.id);
+            
+            let mut user_secret_keys = HashMap::new();
+            let mut public_keys = Vec::new();
+            for (object_id, _) in &encrypted_object.services {
+                let server = self.server(object_id)?;
+                public_keys.push(server.public_key);
+                if available.contains(object_id) {
+                    user_secret_keys.insert(*object_id, self.counter.extract(&server.master_key, &full_id));
+                }
+            }
+            
+            data = self.counter.seal_decrypt(
+                &encrypted_object,
+                &IBEUserSecretKeys::BonehFranklinBLS12381(user_secret_keys),
+                Some(&IBEPublicKeys::BonehFranklinBLS12381(public_keys)),
+            )
+            .map_err(|e| anyhow::anyhow!("Layer {} (\"{}\") could not be opened: {}", layer + 1, String::from_utf8_lossy(&encrypted_object.id), e))?;
+        }
+        
+        Ok(data)
+    }
+    
+    pub fn demo_nested_encryption(&self) -> Result<()> {
+        outln!("\n🚀 === Nested Encryption Demo ===");
+        
+        // Split the fleet into two compartments
+        let ids: Vec<ObjectID> = self.key_servers.iter().map(|s| s.object_id).collect();
+        let (hr_servers, legal_servers) = ids.split_at(ids.len().div_ceil(2));
+        let layers = vec![
+            (hr_servers.to_vec(), hr_servers.len().min(2) as u8, b"compartment:hr".to_vec()),
+            (legal_servers.to_vec(), legal_servers.len().min(2) as u8, b"compartment:legal".to_vec()),
+        ];
+        
+        let message = b"Settlement terms for the pending employment dispute";
+        outln!("🔒 Wrapping document for HR ({} servers) then Legal ({} servers)", hr_servers.len(), legal_servers.len());
+        let ciphertext = self.encrypt_nested(message, &layers)?;
+        outln!("   📏 Nested ciphertext: {} bytes", ciphertext.len());
+        
+        outln!("\n🔓 Both compartments cooperate:");
+        if self.decrypt_nested(&ciphertext, layers.len(), &ids)? == message {
+            outln!("   ✅ Both layers peeled - content verified");
+        } else {
+            anyhow::bail!("❌ Nested decryption produced the wrong content");
+        }
+        
+        for (name, available) in [("HR only", hr_servers), ("Legal only", legal_servers)] {
+            outln!("\n❌ {}:", name);
+            match self.decrypt_nested(&ciphertext, layers.len(), available) {
+                Ok(_) => anyhow::bail!("❌ One compartment alone decrypted the document"),
+                Err(e) => outln!("   ✅ Decryption correctly failed: {}", e),
+            }
+        }
+        
+        outln!("\n💡 Each layer has its own quorum, identity and threshold:");
+        outln!("   - Neither compartment can read the document alone");
+        outln!("   - The outer layer hides even the inner identity from non-members");
+        
+        Ok(())
+    }
+    
+    /// Encrypt `data` so that only holders of keys satisfying `policy` can
+    /// read it (see `policy`). Every identity is encrypted to the whole fleet
+    /// with the demo's threshold (2 unless set).
+    pub fn encrypt_policy(&self, data: &[u8], policy: &Policy) -> Result<Vec<u8>> {
+        let sealed = self.seal_policy(policy, PolicyCiphertext::Data(data.to_vec()))?;
+        Ok(bcs::to_bytes(&sealed)?)
+    }
+    
+    fn seal_policy(&self, policy: &Policy, inner: PolicyCiphertext) -> Result<PolicyCiphertext> {
+        match policy {
+            Policy::Id(identity) => {
+                let (encrypted_object, _) = self.counter.seal_encrypt(
+                    self.package_id,
+                    identity.clone(),
+                    self.key_servers.iter().map(|s| s.object_id).collect(),
+                    &IBEPublicKeys::BonehFranklinBLS12381(self.key_servers.iter().map(|s| s.public_key).collect()),
+                    self.threshold.unwrap_or(2).min(self.key_servers.len() as u8),
+                    EncryptionInput::Aes256Gcm {
+                        data: bcs::to_bytes(&inner)?,
+                        aad: None,
+                    },
+                )?;
+                Ok(PolicyCiphertext::Id(Box::new(encrypted_object)))
+            }
+            Policy::And(branches) | Policy::Or(branches) if branches.is_empty() => {
+                anyhow::bail!("Empty combination in policy {}", policy)
+            }
+            Policy::And(branches) => branches.iter().try_fold(inner, |inner, branch| self.seal_policy(branch, inner)),
+            Policy::Or(branches) => Ok(PolicyCiphertext::Or(
+                branches
+                    .iter()
+                    .map(|branch| self.seal_policy(branch, inner.clone()))
+                    .collect::<Result<_>>()?,
+            )),
+        }
+    }
+    
+    /// Decrypt a value from `encrypt_policy`, holding keys for the `held`
+    /// identities only. Of an `Or`, the first branch that opens wins.
+    pub fn decrypt_policy(&self, ciphertext: &[u8], held: &[&[u8]]) -> Result<Vec<u8>> {
+        self.open_policy(bcs::from_bytes(ciphertext)?, held)
+    }
+    
+    fn open_policy(&self, sealed: PolicyCiphertext, held: &[&[u8]]) -> Result<Vec<u8>> {
+        match sealed {
+            PolicyCiphertext::Data(data) => Ok(data),
+            PolicyCiphertext::Id(encrypted_object) => {
+                self.open_policy(bcs::from_bytes(&self.open_policy_layer(&encrypted_object, held)?)?, held)
+            }
+            PolicyCiphertext::Or(branches) => {
+                let mut errors = Vec::new();
+                for branch in branches {
+                    match self.open_policy(branch, held) {
+                        Ok(data) => return Ok(data),
+                        Err(e) => errors.push(e.to_string()),
+                    }
+                }
+                anyhow::bail!("No alternative could be opened: {}", errors.join("; "))
+            }
+        }
+    }
+    
+    fn open_policy_layer(&self, encrypted_object: &crypto::EncryptedObject, held: &[&[u8]]) -> Result<Vec<u8>> {
+        if !held.contains(&encrypted_object.id.as_slice()) {
+            anyhow::bail!("Missing key for \"{}\"", String::from_utf8_lossy(&encrypted_object.id));
+        }
+        let full_id = create_full_id(&encrypted_object.package_id, &encrypted_object.id);
+        let mut user_secret_keys = HashMap::new();
+        for (object_id, _) in encrypted_object.services.iter().take(encrypted_object.threshold as usize) {
+            user_secret_keys.insert(*object_id, self.counter.extract(&self.server(object_id)?.master_key, &full_id));
+        }
+        let public_keys: Vec<G2Element> = encrypted_object
+            .services
+            .iter()
+            .map(|(object_id, _)| Ok(self.server(object_id)?.public_key))
+            .collect::<Result<_>>()?;
+        
+        Ok(self.counter.seal_decrypt(
+            encrypted_object,
+            &IBEUserSecretKeys::BonehFranklinBLS12381(user_secret_keys),
+            Some(&IBEPublicKeys::BonehFranklinBLS12381(public_keys)),
+        )?)
+    }
+    
+    pub fn demo_policy_encryption(&self) -> Result<()> {
+        outln!("\n🚀 === Policy Encryption Demo ===");
+        
+        let policy = Policy::Or(vec![
+            Policy::And(vec![Policy::id("role:admin"), Policy::id("dept:finance")]),
+            Policy::id("role:auditor"),
+        ]);
+        let message = b"FY2024 budget: headcount +12, travel -30%";
+        outln!("🔒 Encrypting to {}", policy);
+        let ciphertext = self.encrypt_policy(message, &policy)?;
+        outln!("   📏 Policy ciphertext: {} bytes", ciphertext.len());
+        
+        // Who holds what, and whether the policy should let them in
+        let holders: [(&str, &[&[u8]], bool); 4] = [
+            ("Finance admin", &[b"role:admin", b"dept:finance"], true),
+            ("Auditor", &[b"role:auditor"], true),
+            ("Admin outside finance", &[b"role:admin", b"dept:sales"], false),
+            ("Finance analyst", &[b"dept:finance"], false),
+        ];
+        for (name, held, allowed) in holders {
+            outln!("\n👤 {}:", name);
+            match (self.decrypt_policy(&ciphertext, held), allowed) {
+                (Ok(data), true) if data == message => outln!("   ✅ Access granted - content verified"),
+                (Ok(_), true) => anyhow::bail!("❌ Policy decryption produced the wrong content"),
+                (Ok(_), false) => anyhow::bail!("❌ {} decrypted without satisfying the policy", name),
+                (Err(e), false) => outln!("   🚫 Access denied: {}", e),
+                (Err(e), true) => anyhow::bail!("❌ {} satisfies the policy but was denied: {}", name, e),
             }
         }
         
-        println!("\n💡 Each document has a unique identity, creating separate access controls");
-        println!("   - Same key servers, same threshold, but different encryption keys");
-        println!("   - Fine-grained access control per document/resource");
+        outln!("\n💡 AND nests one encryption inside another; OR encrypts a copy per branch.");
         
         Ok(())
     }
     
+    /// `time:<YYYY-MM-DD>:<name>`: an identity whose keys servers only issue
+    /// from the given (UTC) date on.
+    pub fn time_lock_identity(unlock: DateTime<Utc>, name: &str) -> Vec<u8> {
+        format!("time:{}:{}", unlock.format("%Y-%m-%d"), name).into_bytes()
+    }
+    
+    /// A key server's extraction with the time-lock policy applied, judged
+    /// against `now` rather than the system clock so the demo can move time.
+    fn extract_time_locked(&self, server: &KeyServer, identity: &[u8], now: DateTime<Utc>) -> Result<G1Element> {
+        if let Some(rest) = identity.strip_prefix(b"time:") {
+            let date = std::str::from_utf8(rest)
+                .ok()
+                .and_then(|rest| rest.split(':').next())
+                .and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok())
+                .ok_or_else(|| anyhow::anyhow!("{} refuses: malformed time-lock identity", server.name))?;
+            if now.date_naive() < date {
+                anyhow::bail!("{} refuses: identity unlocks on {}, it is {}", server.name, date, now.date_naive());
+            }
+        }
+        Ok(self.counter.extract(&server.master_key, &create_full_id(&self.package_id, identity)))
+    }
+    
+    /// Decrypt with the first `threshold` servers as if the clock read `now`.
+    pub fn decrypt_time_locked(&self, encrypted_object: &crypto::EncryptedObject, now: DateTime<Utc>) -> Result<Vec<u8>> {
+        let mut user_secret_keys = HashMap::new();
+        for server in self.key_servers.iter().take(encrypted_object.threshold as usize) {
+            user_secret_keys.insert(server.object_id, self.extract_time_locked(server, &encrypted_object.id, now)?);
+        }
+        let public_keys: Vec<G2Element> = self.key_servers.iter().map(|s| s.public_key).collect();
+        
+        Ok(self.counter.seal_decrypt(
+            encrypted_object,
+            &IBEUserSecretKeys::BonehFranklinBLS12381(user_secret_keys),
+            Some(&IBEPublicKeys::BonehFranklinBLS12381(public_keys)),
+        )?)
+    }
+    
+    pub fn demo_time_lock(&self, unlock: DateTime<Utc>) -> Result<()> {
+        outln!("\n🚀 === Time-Lock Demo ===");
+        
+        let identity = Self::time_lock_identity(unlock, "press-release");
+        let message = b"Embargoed: Q3 results beat expectations";
+        let object_ids: Vec<ObjectID> = self.key_servers.iter().map(|s| s.object_id).collect();
+        let public_keys: Vec<G2Element> = self.key_servers.iter().map(|s| s.public_key).collect();
+        
+        outln!("🔒 Encrypting to \"{}\"", String::from_utf8_lossy(&identity));
+        outln!("   ⏰ Servers will only issue keys from {}", unlock.format("%Y-%m-%d"));
+        let (encrypted_object, _) = self.counter.seal_encrypt(
+            self.package_id,
+            identity,
+            object_ids,
+            &IBEPublicKeys::BonehFranklinBLS12381(public_keys),
+            2.min(self.key_servers.len() as u8),
+            EncryptionInput::Aes256Gcm {
+                data: message.to_vec(),
+                aad: None,
+            },
+        )?;
+        
+        let before = unlock - TimeDelta::days(1);
+        outln!("\n🕐 Clock set to {}:", before.format("%Y-%m-%d"));
+        match self.decrypt_time_locked(&encrypted_object, before) {
+            Ok(_) => anyhow::bail!("❌ Decrypted before the unlock date"),
+            Err(e) => outln!("   ✅ Decryption correctly failed: {}", e),
+        }
+        
+        outln!("\n🕐 Clock set to {}:", unlock.format("%Y-%m-%d"));
+        if self.decrypt_time_locked(&encrypted_object, unlock)? == message {
+            outln!("   ✅ Unlocked - content verified");
+        } else {
+            anyhow::bail!("❌ Time-locked decryption produced the wrong content");
+        }
+        
+        outln!("\n💡 Encryption needs nothing from the servers, so the document can be");
+        outln!("   published now; the time lives in the identity and servers enforce it.");
+        
+        Ok(())
+    }
+    
+    /// Encrypt `data` to `identity` with the threshold set to the fleet size,
+    /// so decrypting it takes a key from every server.
+    pub fn encrypt_unanimous(&self, data: &[u8], identity: &[u8]) -> Result<crypto::EncryptedObject> {
+        let threshold = u8::try_from(self.key_servers.len())
+            .map_err(|_| anyhow::anyhow!("{} key servers is too many for a unanimous threshold", self.key_servers.len()))?;
+        
+        let (encrypted_object, _) = self.counter.seal_encrypt(
+            self.package_id,
+            identity.to_vec(),
+            self.key_servers.iter().map(|s| s.object_id).collect(),
+            &IBEPublicKeys::BonehFranklinBLS12381(self.key_servers.iter().map(|s| s.public_key).collect()),
+            threshold,
+            EncryptionInput::Aes256Gcm {
+                data: data.to_vec(),
+                aad: None,
+            },
+        )?;
+        Ok(encrypted_object)
+    }
+    
+    /// Decrypt an object from `encrypt_unanimous` with keys from the
+    /// `available` servers. Refuses objects that don't need every server, and
+    /// names the servers without a key rather than leaving `seal_decrypt` to
+    /// report a short quorum.
+    pub fn decrypt_unanimous(&self, encrypted_object: &crypto::EncryptedObject, available: &[ObjectID]) -> Result<Vec<u8>> {
+        let num_servers = encrypted_object.services.len();
+        if encrypted_object.threshold as usize != num_servers {
+            anyhow::bail!(
+                "Object needs {} of {} servers, not all of them; it wasn't sealed unanimously",
+                encrypted_object.threshold,
+                num_servers
+            );
+        }
+        
+        let mut missing = Vec::new();
+        for (object_id, _) in &encrypted_object.services {
+            if !available.contains(object_id) {
+                missing.push(format!("{} ({})", self.server(object_id)?.name, object_id));
+            }
+        }
+        if !missing.is_empty() {
+            anyhow::bail!(
+                "Decryption needs a key from all {} servers; missing {}: {}",
+                num_servers,
+                missing.len(),
+                missing.join(", ")
+            );
+        }
+        
+        let full_id = create_full_id(&encrypted_object.package_id, &encrypted_object.id);
+        let mut user_secret_keys = HashMap::new();
+        let mut public_keys = Vec::new();
+        for (object_id, _) in &encrypted_object.services {
+            let server = self.server(object_id)?;
+            user_secret_keys.insert(*object_id, self.counter.extract(&server.master_key, &full_id));
+            public_keys.push(server.public_key);
+        }
+        
+        Ok(self.counter.seal_decrypt(
+            encrypted_object,
+            &IBEUserSecretKeys::BonehFranklinBLS12381(user_secret_keys),
+            Some(&IBEPublicKeys::BonehFranklinBLS12381(public_keys)),
+        )?)
+    }
+    
+    pub fn demo_unanimous(&self) -> Result<()> {
+        outln!("\n🚀 === Unanimous Decryption Demo ===");
+        
+        let message = b"Board resolution: authorize the acquisition";
+        let ids: Vec<ObjectID> = self.key_servers.iter().map(|s| s.object_id).collect();
+        outln!("🔒 Encrypting to all {} servers, every one of them required...", ids.len());
+        let encrypted_object = self.encrypt_unanimous(message, &self.identity)?;
+        
+        outln!("\n🔓 Every server contributes a key:");
+        if self.decrypt_unanimous(&encrypted_object, &ids)? == message {
+            outln!("   ✅ Decrypted - content verified");
+        } else {
+            anyhow::bail!("❌ Unanimous decryption produced the wrong content");
+        }
+        
+        for (i, server) in self.key_servers.iter().enumerate() {
+            let mut available = ids.clone();
+            available.remove(i);
+            outln!("\n❌ Without {}:", server.name);
+            match self.decrypt_unanimous(&encrypted_object, &available) {
+                Ok(_) => anyhow::bail!("❌ Decrypted without {}", server.name),
+                Err(e) => outln!("   ✅ Decryption correctly failed: {}", e),
+            }
+        }
+        
+        outln!("\n💡 A unanimous threshold is the strictest setting, and the most fragile:");
+        outln!("   - Any one server can veto access to the document");
+        outln!("   - Losing any one server's key loses the document for good");
+        
+        Ok(())
+    }
+    
+    /// Time decryption at every threshold from 1 to `max_threshold`. Each
+    /// threshold encrypts the payload once and extracts its keys once, so
+    /// only `seal_decrypt` is timed: one pairing and one share check per
+    /// server, plus combining the shares.
+    pub fn bench_by_threshold(&self, max_threshold: u8) -> Result<Vec<ThresholdTiming>> {
+        if max_threshold == 0 || max_threshold as usize > self.key_servers.len() {
+            anyhow::bail!("Threshold {} is invalid for {} key servers", max_threshold, self.key_servers.len());
+        }
+        
+        outln!("\n🚀 === Decrypt Latency by Threshold ===");
+        outln!("   📏 Payload: {} bytes, {} decryptions per threshold", BENCH_PAYLOAD_BYTES, BENCH_ITERATIONS);
+        
+        let object_ids: Vec<ObjectID> = self.key_servers.iter().map(|s| s.object_id).collect();
+        let public_keys = IBEPublicKeys::BonehFranklinBLS12381(self.key_servers.iter().map(|s| s.public_key).collect());
+        let full_id = create_full_id(&self.package_id, &self.identity);
+        let payload = vec![0x5a; BENCH_PAYLOAD_BYTES];
+        let mut timings = Vec::new();
+        
+        for threshold in 1..=max_threshold {
+            let (encrypted_object, _) = self.counter.seal_encrypt(
+                self.package_id,
+                self.identity.clone(),
+                object_ids.clone(),
+                &public_keys,
+                threshold,
+                EncryptionInput::Aes256Gcm {
+                    data: payload.clone(),
+                    aad: None,
+                },
+            )?;
+            let user_secret_keys = IBEUserSecretKeys::BonehFranklinBLS12381(
+                self.key_servers
+                    .iter()
+                    .take(threshold as usize)
+                    .map(|server| (server.object_id, self.counter.extract(&server.master_key, &full_id)))
+                    .collect(),
+            );
+            
+            let mut times = Vec::with_capacity(BENCH_ITERATIONS);
+            for _ in 0..BENCH_ITERATIONS {
+                let start = Instant::now();
+                let decrypted =
+                    self.counter.seal_decrypt(&encrypted_object, &user_secret_keys, Some(&public_keys))?;
+                times.push(start.elapsed());
+                if decrypted != payload {
+                    anyhow::bail!("❌ Round trip mismatch at threshold {}", threshold);
+                }
+            }
+            times.sort();
+            
+            timings.push(ThresholdTiming {
+                threshold,
+                p50: times[times.len() / 2],
+                mean: times.iter().sum::<Duration>() / times.len() as u32,
+            });
+        }
+        
+        outln!("\n📊 Decrypt latency:");
+        outln!("   {:>9}  {:>12}  {:>12}  {:>9}", "threshold", "p50", "mean", "vs 1-of-N");
+        let baseline = timings[0].p50.as_secs_f64();
+        for timing in &timings {
+            outln!(
+                "   {:>9}  {:>12.2?}  {:>12.2?}  {:>8.2}x",
+                format!("{}/{}", timing.threshold, self.key_servers.len()),
+                timing.p50,
+                timing.mean,
+                timing.p50.as_secs_f64() / baseline
+            );
+        }
+        
+        Ok(timings)
+    }
+    
+    /// Time decryption end to end, key fetching included, at every threshold
+    /// from 1 to `max_threshold`, with each server answering `server_delay`
+    /// late as if over the network. Keys are extracted locally behind the
+    /// delay; each strategy in `fetches` is timed separately, so serial
+    /// fetching shows the delay adding up per server while parallel fetching
+    /// pays it about once.
+    pub fn latency_by_threshold(
+        &self,
+        max_threshold: u8,
+        server_delay: Duration,
+        fetches: &[KeyFetch],
+    ) -> Result<Vec<LatencyTiming>> {
+        if max_threshold == 0 || max_threshold as usize > self.key_servers.len() {
+            anyhow::bail!("Threshold {} is invalid for {} key servers", max_threshold, self.key_servers.len());
+        }
+        if fetches.is_empty() {
+            anyhow::bail!("No fetch strategy to time");
+        }
+        
+        outln!("\n🚀 === Simulated Network Latency by Threshold ===");
+        outln!("   🌐 Delay per key server: {:?}", server_delay);
+        outln!("   📏 Payload: {} bytes, {} decryptions per row", BENCH_PAYLOAD_BYTES, LATENCY_ITERATIONS);
+        
+        let object_ids: Vec<ObjectID> = self.key_servers.iter().map(|s| s.object_id).collect();
+        let public_keys = IBEPublicKeys::BonehFranklinBLS12381(self.key_servers.iter().map(|s| s.public_key).collect());
+        let full_id = create_full_id(&self.package_id, &self.identity);
+        let payload = vec![0x5a; BENCH_PAYLOAD_BYTES];
+        let mut timings = Vec::new();
+        
+        for threshold in 1..=max_threshold {
+            let (encrypted_object, _) = self.counter.seal_encrypt(
+                self.package_id,
+                self.identity.clone(),
+                object_ids.clone(),
+                &public_keys,
+                threshold,
+                EncryptionInput::Aes256Gcm {
+                    data: payload.clone(),
+                    aad: None,
+                },
+            )?;
+            let servers = &self.key_servers[..threshold as usize];
+            
+            for &fetch in fetches {
+                let mut total = Duration::ZERO;
+                for _ in 0..LATENCY_ITERATIONS {
+                    let start = Instant::now();
+                    let user_secret_keys = IBEUserSecretKeys::BonehFranklinBLS12381(
+                        fetch_keys(servers, &full_id, server_delay, fetch, &self.counter),
+                    );
+                    let decrypted =
+                        self.counter.seal_decrypt(&encrypted_object, &user_secret_keys, Some(&public_keys))?;
+                    total += start.elapsed();
+                    if decrypted != payload {
+                        anyhow::bail!("❌ Round trip mismatch at threshold {}", threshold);
+                    }
+                }
+                timings.push(LatencyTiming {
+                    threshold,
+                    fetch,
+                    mean: total / LATENCY_ITERATIONS as u32,
+                });
+            }
+        }
+        
+        outln!("\n📊 Mean decrypt latency including key fetches:");
+        let rows: Vec<&[LatencyTiming]> = timings.chunks(fetches.len()).collect();
+        let header: Vec<String> =
+            rows[0].iter().map(|timing| format!("{:>12}", format!("{:?}", timing.fetch).to_lowercase())).collect();
+        outln!("   {:>9}  {}", "threshold", header.join("  "));
+        for row in rows {
+            let cells: Vec<String> = row.iter().map(|timing| format!("{:>12.2?}", timing.mean)).collect();
+            outln!("   {:>9}  {}", format!("{}/{}", row[0].threshold, self.key_servers.len()), cells.join("  "));
+        }
+        outln!("\n💡 Serial fetching costs about threshold x delay; parallel about one delay however many servers it needs.");
+        
+        Ok(timings)
+    }
+    
     pub fn run_all_demos(&self) -> Result<()> {
-        // Test different threshold values
-        for threshold in 2..=std::cmp::min(3, self.key_servers.len() as u8) {
-            self.demo_threshold_encryption(threshold)?;
+        // Test different threshold values, unless one was chosen
+        match self.threshold {
+            Some(threshold) => self.demo_threshold_encryption(threshold)?,
+            None => {
+                for threshold in 2..=std::cmp::min(3, self.key_servers.len() as u8) {
+                    self.demo_threshold_encryption(threshold)?;
+                }
+            }
         }
         
         self.demo_server_rotation()?;
+        self.demo_independent_rotation()?;
         self.demo_access_patterns()?;
+        self.demo_nested_encryption()?;
+        self.demo_policy_encryption()?;
+        self.demo_time_lock(Utc::now() + TimeDelta::days(7))?;
+        self.demo_unanimous()?;
         
-        println!("\n🎉 All threshold encryption demos completed successfully!");
+        outln!("\n🎉 All threshold encryption demos completed successfully!");
         Ok(())
     }
 }
@@ -314,4 +1031,27 @@ impl Default for ThresholdDemo {
     fn default() -> Self {
         Self::new(3) // Default to 3 key servers
     }
-}
\ No newline at end of file
+}
+
+/// User secret keys for `full_id` from each of `servers`, every answer
+/// arriving `delay` after it was asked for. Extractions are tallied on
+/// `counter`.
+fn fetch_keys(
+    servers: &[KeyServer],
+    full_id: &[u8],
+    delay: Duration,
+    fetch: KeyFetch,
+    counter: &OpCounter,
+) -> HashMap<ObjectID, G1Element> {
+    let fetch_one = |server: &KeyServer| {
+        std::thread::sleep(delay);
+        (server.object_id, counter.extract(&server.master_key, full_id))
+    };
+    match fetch {
+        KeyFetch::Serial => servers.iter().map(fetch_one).collect(),
+        KeyFetch::Parallel => std::thread::scope(|scope| {
+            let handles: Vec<_> = servers.iter().map(|server| scope.spawn(move || fetch_one(server))).collect();
+            handles.into_iter().map(|handle| handle.join().expect("Key fetch thread panicked")).collect()
+        }),
+    }
+}
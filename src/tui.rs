@@ -0,0 +1,229 @@
+//! Arrow-key driven replacement for the text menu in interactive mode.
+//!
+//! The selected demo runs on a worker thread while the TUI keeps drawing.
+//! Everything it prints is captured (see `output::start_capture`) and shown
+//! live in the output pane, which follows the end of the output unless
+//! scrolled back. The history pane keeps a line per run.
+
+use anyhow::Result;
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEventKind},
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::Line,
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap},
+    DefaultTerminal, Frame,
+};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::output::{self, outln};
+use crate::registry::{self, DemoContext, DemoEntry};
+
+/// How often the output pane is redrawn while a demo runs.
+const REFRESH: Duration = Duration::from_millis(100);
+/// Lines moved by Page Up and Page Down.
+const PAGE: usize = 10;
+
+struct Running {
+    name: &'static str,
+    start: Instant,
+    handle: JoinHandle<Result<()>>,
+}
+
+struct App {
+    menu: Arc<Vec<DemoEntry>>,
+    menu_state: ListState,
+    servers: usize,
+    log: Vec<String>,
+    status: String,
+    /// Output of the running or most recent demo.
+    output: String,
+    /// Lines scrolled back from the end of `output`.
+    scroll_back: usize,
+    running: Option<Running>,
+}
+
+impl App {
+    fn new() -> Self {
+        let mut menu_state = ListState::default();
+        menu_state.select(Some(0));
+        App {
+            menu: Arc::new(registry::menu()),
+            menu_state,
+            servers: 3,
+            log: Vec::new(),
+            status: "Ready".to_string(),
+            output: String::new(),
+            scroll_back: 0,
+            running: None,
+        }
+    }
+
+    fn selected(&self) -> usize {
+        self.menu_state.selected().unwrap_or(0)
+    }
+
+    fn move_selection(&mut self, delta: isize) {
+        let len = self.menu.len() as isize;
+        let next = (self.selected() as isize + delta).rem_euclid(len);
+        self.menu_state.select(Some(next as usize));
+    }
+
+    fn scroll(&mut self, delta: isize) {
+        let max = self.output.lines().count();
+        self.scroll_back = self.scroll_back.saturating_add_signed(delta).min(max);
+    }
+}
+
+pub fn run() -> Result<()> {
+    let mut terminal = ratatui::init();
+    let mut app = App::new();
+
+    let result = event_loop(&mut terminal, &mut app);
+    ratatui::restore();
+    // A demo still running when the user quits keeps printing into the void
+    output::stop_capture();
+    result?;
+
+    outln!("👋 Thanks for trying the Seal Rust demo!");
+    Ok(())
+}
+
+fn event_loop(terminal: &mut DefaultTerminal, app: &mut App) -> Result<()> {
+    loop {
+        if app.running.is_some() {
+            poll_running(app);
+        }
+        terminal.draw(|frame| draw(frame, app))?;
+
+        if !event::poll(REFRESH)? {
+            continue;
+        }
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => app.move_selection(-1),
+            KeyCode::Down | KeyCode::Char('j') => app.move_selection(1),
+            KeyCode::Left => app.servers = (app.servers - 1).max(2),
+            KeyCode::Right => app.servers = (app.servers + 1).min(16),
+            KeyCode::PageUp => app.scroll(PAGE as isize),
+            KeyCode::PageDown => app.scroll(-(PAGE as isize)),
+            KeyCode::End => app.scroll_back = 0,
+            KeyCode::Enter if app.running.is_none() => start_selected(app),
+            KeyCode::Esc | KeyCode::Char('q') => return Ok(()),
+            _ => {}
+        }
+    }
+}
+
+/// Start the selected demo on a worker thread, capturing its output.
+fn start_selected(app: &mut App) {
+    let index = app.selected();
+    let demo = &app.menu[index];
+    let name = demo.title.unwrap_or(demo.name);
+    let (menu, context) = (Arc::clone(&app.menu), DemoContext { servers: app.servers });
+
+    output::start_capture();
+    app.output.clear();
+    app.scroll_back = 0;
+    app.status = format!("⏳ Running {}...", name);
+    app.running = Some(Running {
+        name,
+        start: Instant::now(),
+        handle: std::thread::spawn(move || (menu[index].run)(&context)),
+    });
+}
+
+/// Refresh the output pane, and record the outcome once the demo is done.
+fn poll_running(app: &mut App) {
+    app.output = output::captured();
+    if !app.running.as_ref().is_some_and(|running| running.handle.is_finished()) {
+        return;
+    }
+    let Some(Running { name, start, handle }) = app.running.take() else {
+        return;
+    };
+    let elapsed = start.elapsed();
+    let result = handle.join().unwrap_or_else(|_| Err(anyhow::anyhow!("the demo panicked")));
+    app.output = output::stop_capture();
+
+    let entry = match &result {
+        Ok(()) => format!("✅ {} finished in {:.2?}", name, elapsed),
+        Err(e) => format!("❌ {} failed after {:.2?}: {}", name, elapsed, e),
+    };
+    app.status = entry.clone();
+    app.log.push(entry);
+}
+
+fn draw(frame: &mut Frame, app: &mut App) {
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(8), Constraint::Length(3)])
+        .split(frame.area());
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(40), Constraint::Percentage(60)])
+        .split(rows[0]);
+    let panes = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(5), Constraint::Length(6)])
+        .split(columns[1]);
+
+    let items: Vec<ListItem> = app
+        .menu
+        .iter()
+        .map(|demo| {
+            ListItem::new(vec![
+                Line::from(demo.title.unwrap_or(demo.name)),
+                Line::styled(format!("  {}", demo.description), Style::default().fg(Color::DarkGray)),
+            ])
+        })
+        .collect();
+    let menu = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(" 🎮 Seal Demos "))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+        .highlight_symbol("▶ ");
+    frame.render_stateful_widget(menu, columns[0], &mut app.menu_state);
+
+    draw_output(frame, app, panes[0]);
+
+    let log_lines: Vec<Line> = if app.log.is_empty() {
+        vec![Line::from("No demos run yet. Select one and press Enter.")]
+    } else {
+        app.log.iter().rev().map(|entry| Line::from(entry.as_str())).collect()
+    };
+    let history = Paragraph::new(log_lines)
+        .block(Block::default().borders(Borders::ALL).title(" History "))
+        .wrap(Wrap { trim: false });
+    frame.render_widget(history, panes[1]);
+
+    let status = Paragraph::new(format!(
+        "{} │ servers: {} (←/→) │ ↑/↓ select, Enter run, PgUp/PgDn/End scroll, q quit",
+        app.status, app.servers
+    ))
+    .block(Block::default().borders(Borders::ALL));
+    frame.render_widget(status, rows[1]);
+}
+
+/// The demo's output, scrolled so the last lines show unless the user has
+/// scrolled back. Lines are not wrapped, so one output line is one row.
+fn draw_output(frame: &mut Frame, app: &App, area: Rect) {
+    let lines: Vec<Line> = app.output.lines().map(Line::from).collect();
+    let height = area.height.saturating_sub(2) as usize;
+    let top = lines.len().saturating_sub(height + app.scroll_back);
+    let title = match app.scroll_back {
+        0 => " Output ".to_string(),
+        back => format!(" Output ({} lines back, End to follow) ", back),
+    };
+    let output = Paragraph::new(lines)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .scroll((top.min(u16::MAX as usize) as u16, 0));
+    frame.render_widget(output, area);
+}